@@ -0,0 +1,111 @@
+//! Automatic color decisions of `anstream`, with the "anstyle" feature.
+//!
+//! `anstream::AutoStream` passes ANSI escape codes through to streams supporting
+//! colors and strips them from the others. Altio streams don't support colors by
+//! default, like pipes, so tools using `anstream` write plain text when embedded,
+//! no matter which terminal runs the tests. `Altio::color_support()` changes that,
+//! and `Altio::color_choice()` overrides it.
+//!
+//! ```rust
+//! use altio::Altio;
+//! use std::io::Write;
+//!
+//! let io = Altio::default();
+//! write!( io.auto_out(), "\x1b[1mbold\x1b[0m" ).unwrap();
+//! assert_eq!( io.recv(), "bold" );
+//!
+//! io.color_support( true, false );
+//! write!( io.auto_out(), "\x1b[1mbold\x1b[0m" ).unwrap();
+//! assert_eq!( io.recv(), "\x1b[1mbold\x1b[0m" );
+//! ```
+
+use crate::{Altio, Altout, ColorChoice, Stream};
+
+use ::anstream::AutoStream;
+
+use std::{
+    io::{self, Write},
+    sync::atomic::Ordering,
+};
+
+/// An `anstream::AutoStream` writing into an altio output or error stream.
+pub type AltAutoStream = AutoStream<Box<dyn Write + Send>>;
+
+/// The raw stream of an `AltAutoStream`.
+#[derive( Debug )]
+struct AltWriter {
+    io     : Altio,
+    stream : Stream,
+}
+
+impl AltWriter {
+    fn altout( &self ) -> &Altout {
+        match self.stream {
+            Stream::Err => &self.io.0.2,
+            _ => &self.io.0.1,
+        }
+    }
+}
+
+impl Write for AltWriter {
+    fn write( &mut self, buf: &[u8] ) -> io::Result<usize> {
+        self.altout().latency.apply();
+        let mut lock = self.altout().lock();
+        lock.altout.check()?;
+        lock.write_bytes( buf );
+        lock.output().end_message();
+        Ok( buf.len() )
+    }
+
+    fn flush( &mut self ) -> io::Result<()> { self.altout().lock().flush() }
+}
+
+impl Altio {
+    /// Sets whether the output and error streams support colors, i.e. keep ANSI escape
+    /// codes written by `auto_out()` and `auto_err()` if the color choice is
+    /// `ColorChoice::Auto`. Both don't by default.
+    pub fn color_support( &self, out: bool, err: bool ) {
+        self.0.1.color.store( out, Ordering::Relaxed );
+        self.0.2.color.store( err, Ordering::Relaxed );
+    }
+
+    /// Corresponding to `anstream::stdout()`.
+    pub fn auto_out( &self ) -> AltAutoStream { self.auto_stream( Stream::Out )}
+
+    /// Corresponding to `anstream::stderr()`.
+    pub fn auto_err( &self ) -> AltAutoStream { self.auto_stream( Stream::Err )}
+
+    fn auto_stream( &self, stream: Stream ) -> AltAutoStream {
+        let writer = AltWriter{ io: self.clone(), stream };
+        let color = match self.color_choice() {
+            ColorChoice::Auto => writer.altout().color.load( Ordering::Relaxed ),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        let choice = if color { ::anstream::ColorChoice::AlwaysAnsi } else { ::anstream::ColorChoice::Never };
+        AutoStream::new( Box::new( writer ), choice )
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use crate::{Altio, ColorChoice};
+    use std::io::Write;
+
+    #[test]
+    fn color_support() {
+        let io = Altio::default();
+        io.color_support( false, true );
+        writeln!( io.auto_out(), "\x1b[31mred\x1b[0m" ).unwrap();
+        writeln!( io.auto_err(), "\x1b[31mred\x1b[0m" ).unwrap();
+        assert_eq!( io.recv(), "red\n" );
+        assert_eq!( io.recv_err(), "\x1b[31mred\x1b[0m\n" );
+
+        io.env().set_color_choice( ColorChoice::Always );
+        writeln!( io.auto_out(), "\x1b[31mred\x1b[0m" ).unwrap();
+        assert_eq!( io.recv(), "\x1b[31mred\x1b[0m\n" );
+        io.env().set_color_choice( ColorChoice::Never );
+        writeln!( io.auto_err(), "\x1b[31mred\x1b[0m" ).unwrap();
+        assert_eq!( io.recv_err(), "red\n" );
+    }
+}
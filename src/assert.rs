@@ -0,0 +1,435 @@
+//! Assertions on embedded tools.
+//!
+//! The macros `assert_next_line!`, `assert_output_eq!`, `assert_output_contains!` and
+//! `assert_err_empty!` check a running session, panicking with the text pending in its
+//! streams on failure. Mismatched text is shown as a line diff, colored according to
+//! `Altenv::color_choice()` of the process, with carriage returns shown as `␍`, and
+//! trailing spaces and tabs as `·` and `→`.
+//! With the "predicates" feature, `AltOutput::assert()` checks the results of a tool
+//! run to completion, in the style of `assert_cmd`. `install_panic_dump()` leaves a
+//! trace of the session when a test fails otherwise, e.g. by `expect`.
+//!
+//! ```rust
+//! use altio::{Altio, assert_err_empty, assert_next_line, assert_output_contains, echo};
+//! use std::time::Duration;
+//!
+//! let io = Altio::default();
+//! echo!( io.out(), "Compiling\nFinished in 1s" );
+//! assert_next_line!( io, "Compiling" );
+//! assert_output_contains!( io, "Finished", Duration::from_secs(1) );
+//! assert_err_empty!( io );
+//! ```
+
+use crate::{AltOutput, Altenv, Altio, Altout, ColorChoice, Stream, diff, into_string, try_lock};
+
+#[cfg( feature = "predicates" )]
+use predicates::Predicate;
+
+use std::{
+    fmt::{self, Display},
+    io::IsTerminal,
+    sync::Arc,
+    time::Duration,
+};
+
+/// How long `assert_next_line!` waits for a line unless told otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs( 10 );
+
+/// How many lines of the output history `install_panic_dump()` shows.
+pub const PANIC_DUMP_LINES: usize = 20;
+
+/// Asserts that the next line received from the output stream of a session is the
+/// expected one, ignoring the trailing newline, waiting at most `DEFAULT_TIMEOUT` or
+/// the given timeout.
+#[macro_export]
+macro_rules! assert_next_line {
+    ( $io:expr, $expected:expr ) => {
+        $crate::assert::next_line( &$io, $expected, $crate::assert::DEFAULT_TIMEOUT )
+    };
+    ( $io:expr, $expected:expr, $timeout:expr ) => {
+        $crate::assert::next_line( &$io, $expected, $timeout )
+    };
+}
+
+/// Asserts that the text received from the output stream of a session is the expected
+/// one, waiting at most `DEFAULT_TIMEOUT` or the given timeout for it to be complete.
+#[macro_export]
+macro_rules! assert_output_eq {
+    ( $io:expr, $expected:expr ) => {
+        $crate::assert::output_eq( &$io, $expected, $crate::assert::DEFAULT_TIMEOUT )
+    };
+    ( $io:expr, $expected:expr, $timeout:expr ) => {
+        $crate::assert::output_eq( &$io, $expected, $timeout )
+    };
+}
+
+/// Asserts that the output stream of a session receives text containing a substring
+/// within a timeout, consuming the text received.
+#[macro_export]
+macro_rules! assert_output_contains {
+    ( $io:expr, $substr:expr, $timeout:expr ) => {
+        $crate::assert::output_contains( &$io, $substr, $timeout )
+    };
+}
+
+/// Asserts that nothing is pending in the error stream of a session.
+#[macro_export]
+macro_rules! assert_err_empty {
+    ( $io:expr ) => {
+        $crate::assert::err_empty( &$io )
+    };
+}
+
+/// Receives the next line from the output stream of `io` within `timeout`, panicking
+/// unless it is `expected`, ignoring the trailing newline. See `assert_next_line!`.
+#[track_caller]
+pub fn next_line( io: &Altio, expected: &str, timeout: Duration ) -> String {
+    match io.recv_line_timeout( timeout ) {
+        Some( line ) if line.strip_suffix( '\n' ).unwrap_or( &line ) == expected.strip_suffix( '\n' ).unwrap_or( expected ) => line,
+        Some( line ) => panic!( "expected next line `{expected}`, received `{}`\n{}\n{}", line.trim_end_matches( '\n' ), Pending( io ),
+            diff::unified( expected.strip_suffix( '\n' ).unwrap_or( expected ), line.strip_suffix( '\n' ).unwrap_or( &line ), colored() )),
+        None => panic!( "expected next line `{expected}`, received none in {timeout:?}\n{}", Pending( io )),
+    }
+}
+
+/// Receives text from the output stream of `io` until it is as long as `expected` or
+/// `timeout` elapses, panicking with a diff unless it is `expected`. Returns the text
+/// received. See `assert_output_eq!`.
+#[track_caller]
+pub fn output_eq( io: &Altio, expected: &str, timeout: Duration ) -> String {
+    let clock = &io.0.1.clock;
+    let deadline = clock.now() + timeout;
+    let mut received = String::new();
+    let mut expired = false;
+    while received.len() < expected.len() {
+        let remaining = deadline.saturating_duration_since( clock.now() );
+        match ( !expired ).then( || io.recv_timeout( remaining )).flatten() {
+            Some( text ) => received.push_str( &text ),
+            None => break,
+        }
+        expired = remaining.is_zero();
+    }
+    if received != expected {
+        panic!( "output differs from the expected in {timeout:?}:\n{}{}", diff::unified( expected, &received, colored() ), Pending( io ));
+    }
+    received
+}
+
+/// Receives text from the output stream of `io` until it contains `substr`, panicking
+/// if it doesn't within `timeout`. Returns the text received. See `assert_output_contains!`.
+#[track_caller]
+pub fn output_contains( io: &Altio, substr: &str, timeout: Duration ) -> String {
+    let clock = &io.0.1.clock;
+    let deadline = clock.now() + timeout;
+    let mut received = String::new();
+    let mut expired = false;
+    while !received.contains( substr ) {
+        let remaining = deadline.saturating_duration_since( clock.now() );
+        match ( !expired ).then( || io.recv_timeout( remaining )).flatten() {
+            Some( text ) => received.push_str( &text ),
+            None => panic!( "expected output containing `{substr}` in {timeout:?}, received: ```\n{received}```\n{}", Pending( io )),
+        }
+        expired = remaining.is_zero();
+    }
+    received
+}
+
+/// Panics with the text pending in the error stream of `io` if any, consuming it.
+/// See `assert_err_empty!`.
+#[track_caller]
+pub fn err_empty( io: &Altio ) {
+    if let Some( text ) = io.try_recv_err().filter( |text| !text.is_empty() ) {
+        panic!( "expected no error, received: ```\n{text}```\n{}", Pending( io ));
+    }
+}
+
+/// Whether to color diffs, according to the color choice of the process, coloring if
+/// stderr is a terminal by default.
+fn colored() -> bool {
+    match Altenv::new().color_choice() {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stderr().is_terminal(),
+    }
+}
+
+/// Shows the session recorded so far, if recording, and the text pending in the output
+/// and error streams of a session.
+struct Pending<'a>( &'a Altio );
+
+impl Display for Pending<'_> {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        let session = self.0.format_session();
+        if !session.is_empty() {
+            writeln!( f, "session: ```\n{session}```" )?;
+        }
+        let out = self.0.pending( Stream::Out );
+        let err = self.0.pending( Stream::Err );
+        writeln!( f, "pending stdout: ```\n{}```", String::from_utf8_lossy( &out ))?;
+        write!( f, "pending stderr: ```\n{}```", String::from_utf8_lossy( &err ))
+    }
+}
+
+/// Dumps the state of the session `io` into stderr whenever the current thread panics,
+/// e.g. a test failing: the session recorded so far if recording, the text pending in
+/// the output and error streams, the last `PANIC_DUMP_LINES` lines of the output
+/// history, which is kept from now on, and the input not read by the tool yet.
+///
+/// The panic hook installed before is called first. Meant for tests, since the hook is
+/// global to the process. Panics of other threads, or after `io` is dropped, are left
+/// to the previous hook alone.
+///
+/// ```rust,should_panic
+/// use altio::{Altio, echo, install_panic_dump};
+///
+/// let io = Altio::default();
+/// install_panic_dump( &io );
+/// echo!( io.out(), "Error: file not found" );
+/// assert!( io.recv_line().starts_with( "Done" )); // dumps the history of the session
+/// ```
+pub fn install_panic_dump( io: &Altio ) {
+    io.keep_history();
+    let session = Arc::downgrade( &io.0 );
+    let thread = std::thread::current().id();
+    let previous = std::panic::take_hook();
+    std::panic::set_hook( Box::new( move |info| {
+        previous( info );
+        if std::thread::current().id() == thread {
+            if let Some( session ) = session.upgrade() {
+                eprintln!( "{}", Dump( &Altio( session )));
+            }
+        }
+    }));
+}
+
+/// Shows the state of a session without blocking, since the panicking thread may hold
+/// the locks of its streams.
+struct Dump<'a>( &'a Altio );
+
+impl Display for Dump<'_> {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        const LOCKED: &str = "<locked by the panicking thread>";
+        let session = &self.0.0;
+        let ( altin, out, err ) = ( &session.0, &session.1, &session.2 );
+        let pending = |altout: &Altout| try_lock( &altout.output )
+            .map_or_else( || LOCKED.to_owned(), |mut output| into_string( output.copy_all() ));
+        let history = try_lock( &out.output ).map_or_else( || LOCKED.to_owned(), |output| {
+            let history = output.history.as_ref().map( |history| into_string( history.to_bytes() )).unwrap_or_default();
+            let lines = history.split_inclusive( '\n' ).collect::<Vec<_>>();
+            lines[ lines.len().saturating_sub( PANIC_DUMP_LINES ).. ].concat()
+        });
+        let input = try_lock( &altin.input )
+            .map_or_else( || LOCKED.to_owned(), |mut input| into_string( input.copy_all() ));
+        let session = self.0.format_session();
+
+        writeln!( f, "altio session dump:" )?;
+        if !session.is_empty() {
+            writeln!( f, "session: ```\n{session}```" )?;
+        }
+        writeln!( f, "pending stdout: ```\n{}```", pending( out ))?;
+        writeln!( f, "pending stderr: ```\n{}```", pending( err ))?;
+        writeln!( f, "last {PANIC_DUMP_LINES} lines of stdout history: ```\n{history}```" )?;
+        write!( f, "input not read: ```\n{input}```" )
+    }
+}
+
+#[cfg( feature = "predicates" )]
+impl AltOutput {
+    /// Starts asserting on this output, with the "predicates" feature.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo, run_to_completion};
+    /// use predicates::prelude::*;
+    ///
+    /// run_to_completion( |io: Altio, _| echo!( io.out(), "Hello" ), [""; 0], "" )
+    ///     .assert()
+    ///     .success()
+    ///     .stdout( predicate::str::starts_with( "Hello" ))
+    ///     .stderr( predicate::str::is_empty() );
+    /// ```
+    pub fn assert( self ) -> Assert { Assert{ output: self }}
+}
+
+impl Display for AltOutput {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        writeln!( f, "status: {}", self.status )?;
+        writeln!( f, "stdout: ```\n{}```", self.stdout )?;
+        write!( f, "stderr: ```\n{}```", self.stderr )
+    }
+}
+
+/// Assertions on an `AltOutput`, corresponding to `assert_cmd::assert::Assert`.
+///
+/// Every method panics with the whole output on failure, or returns the `Assert`
+/// for chaining.
+#[cfg( feature = "predicates" )]
+#[derive( Debug )]
+pub struct Assert {
+    output: AltOutput,
+}
+
+#[cfg( feature = "predicates" )]
+impl Assert {
+    /// Returns the output being asserted.
+    pub fn get_output( &self ) -> &AltOutput { &self.output }
+
+    /// Asserts that the tool exited with code 0.
+    #[track_caller]
+    pub fn success( self ) -> Self {
+        if self.output.status != 0 {
+            panic!( "expected success\n{}", self.output );
+        }
+        self
+    }
+
+    /// Asserts that the tool exited with a non-zero code, including panics.
+    #[track_caller]
+    pub fn failure( self ) -> Self {
+        if self.output.status == 0 {
+            panic!( "expected failure\n{}", self.output );
+        }
+        self
+    }
+
+    /// Asserts that the tool exited with `code`.
+    #[track_caller]
+    pub fn code( self, code: i32 ) -> Self {
+        if self.output.status != code {
+            panic!( "expected code {code}\n{}", self.output );
+        }
+        self
+    }
+
+    /// Asserts that the text written into the output stream satisfies `predicate`.
+    #[track_caller]
+    pub fn stdout( self, predicate: impl Predicate<str> ) -> Self {
+        if !predicate.eval( &self.output.stdout ) {
+            panic!( "stdout doesn't satisfy `{predicate}`\n{}", self.output );
+        }
+        self
+    }
+
+    /// Asserts that the text written into the error stream satisfies `predicate`.
+    #[track_caller]
+    pub fn stderr( self, predicate: impl Predicate<str> ) -> Self {
+        if !predicate.eval( &self.output.stderr ) {
+            panic!( "stderr doesn't satisfy `{predicate}`\n{}", self.output );
+        }
+        self
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use crate::{Altio, echo};
+    use std::time::Duration;
+
+    #[test]
+    fn session() {
+        let io = Altio::default();
+        echo!( io.out(), "one\ntwo\nthree" );
+        assert_eq!( assert_next_line!( io, "one" ), "one\n" );
+        assert_eq!( assert_output_contains!( io, "three", Duration::from_secs(1) ), "two\nthree\n" );
+        assert_err_empty!( io );
+    }
+
+    #[test]
+    #[should_panic( expected = "received `two`\npending stdout: ```\nthree\n```" )]
+    fn next_line() {
+        let io = Altio::default();
+        echo!( io.out(), "two\nthree" );
+        assert_next_line!( io, "one" );
+    }
+
+    #[test]
+    fn output_eq() {
+        let io = Altio::default();
+        echo!( io.out(), "one\ntwo" );
+        assert_eq!( assert_output_eq!( io, "one\ntwo\n" ), "one\ntwo\n" );
+        echo!( -n, io.out(), "abc" );
+        assert_eq!( assert_output_eq!( io, "abc", Duration::ZERO ), "abc" );
+        echo!( -n, io.out(), "def" );
+        assert_eq!( assert_output_contains!( io, "e", Duration::ZERO ), "def" );
+    }
+
+    #[test]
+    #[should_panic( expected = "output differs from the expected in 1ms:\n--- expected\n+++ received\n one\n" )]
+    fn output_diff() {
+        let io = Altio::default();
+        echo!( io.out(), "one\ntwo \r" );
+        assert_output_eq!( io, "one\ntwo\n", Duration::from_millis(1) );
+    }
+
+    #[test]
+    #[should_panic( expected = "session: ```\nout: two\n>>> 2\nout: three\n```\npending stdout: ```\n```" )]
+    fn recorded_session() {
+        let io = Altio::default();
+        io.start_recording();
+        echo!( io.out(), "two" );
+        io.send_line( "2" );
+        echo!( io.out(), "three" );
+        assert_output_contains!( io, "four", Duration::from_millis(1) );
+    }
+
+    #[test]
+    fn panic_dump() {
+        let io = Altio::default();
+        let tool = io.clone();
+        std::thread::spawn( move || {
+            super::install_panic_dump( &tool );
+            for n in 0..25 {
+                echo!( tool.out(), "line {n}" );
+            }
+            tool.recv_lines( 24 );
+            panic!( "tool failed" );
+        }).join().unwrap_err();
+
+        io.send_line( "unread" );
+        echo!( io.err(), "oops" );
+        assert_eq!( super::Dump( &io ).to_string(), format!( "altio session dump:
+pending stdout: ```
+line 24
+```
+pending stderr: ```
+oops
+```
+last 20 lines of stdout history: ```
+{}```
+input not read: ```
+unread
+```", ( 5..25 ).map( |n| format!( "line {n}\n" )).collect::<String>() ));
+    }
+
+    #[test]
+    #[should_panic( expected = "expected no error, received: ```\noops\n```" )]
+    fn err_empty() {
+        let io = Altio::default();
+        echo!( io.err(), "oops" );
+        assert_err_empty!( io );
+    }
+}
+
+#[cfg( all( test, feature = "predicates" ))]
+mod predicates_tests {
+    use crate::{Altio, echo, run_to_completion};
+    use predicates::prelude::*;
+
+    #[test]
+    fn assert() {
+        run_to_completion( |io: Altio, _| { echo!( io.err(), "oops" ); 2 }, [""; 0], "" )
+            .assert()
+            .failure()
+            .code( 2 )
+            .stdout( predicate::str::is_empty() )
+            .stderr( predicate::str::contains( "oops" ));
+    }
+
+    #[test]
+    #[should_panic( expected = "stdout doesn't satisfy" )]
+    fn assert_stdout() {
+        run_to_completion( |io: Altio, _| echo!( io.out(), "Hello" ), [""; 0], "" )
+            .assert()
+            .success()
+            .stdout( predicate::str::contains( "Bye" ));
+    }
+}
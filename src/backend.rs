@@ -0,0 +1,224 @@
+//! Pluggable transports of the bytes of altio streams, see `AltioBuilder::backend()`.
+//!
+//! By default, the text flowing through a stream is stored in memory until received
+//! or read. A backend can connect each stream to a transport outside of it, such as
+//! OS pipes or another process, while the send/recv API stays the same:
+//!
+//! - the bytes read from the *source* of a stream are written into it, as if sent by
+//!   the driver into the input stream, or written by the tool into the output and
+//!   error streams. The stream is closed at the end of the source.
+//! - the bytes written into a stream with a *sink* are forwarded to the sink instead
+//!   of being stored.
+//!
+//! ```rust
+//! use altio::{Altio, Stream, backend::{AltBackend, Port}};
+//!
+//! /// A tool whose output was produced elsewhere.
+//! #[derive( Debug )]
+//! struct Replayed( &'static str );
+//!
+//! impl AltBackend for Replayed {
+//!     fn open( &mut self, stream: Stream ) -> Port {
+//!         match stream {
+//!             Stream::Out => Port{ source: Some( Box::new( self.0.as_bytes() )), sink: None },
+//!             _ => Port::default(),
+//!         }
+//!     }
+//! }
+//!
+//! let io = Altio::builder().backend( Replayed( "Hello\n" )).build();
+//! assert_eq!( io.recv_line(), "Hello\n" );
+//! ```
+
+use crate::{Altio, Stream};
+
+#[cfg( all( feature = "shm", unix ))]
+mod shm;
+#[cfg( all( feature = "shm", unix ))]
+pub use shm::SharedMemory;
+
+#[cfg( feature = "os-pipe" )]
+use os_pipe::{PipeReader, PipeWriter};
+
+use std::{
+    fmt::{self, Debug},
+    io::{BufRead, BufReader, ErrorKind, Read, Write},
+    process::Stdio,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+/// The transport of one stream.
+#[derive( Default )]
+pub struct Port {
+    /// Bytes written into the stream by a background thread until end-of-file.
+    pub source : Option<Box<dyn Read + Send>>,
+    /// Receives the bytes written into the stream instead of storing them.
+    pub sink   : Option<Box<dyn Write + Send>>,
+}
+
+impl Debug for Port {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        f.debug_struct( "Port" )
+            .field( "source", &self.source.is_some() )
+            .field( "sink", &self.sink.is_some() )
+            .finish()
+    }
+}
+
+/// The transports of the streams of an `Altio`.
+pub trait AltBackend: Send + Debug {
+    /// Opens the transport of `stream`, called once per stream when the `Altio` is
+    /// built.
+    fn open( &mut self, stream: Stream ) -> Port;
+
+    /// Returns the handles of stdin, stdout and stderr for processes spawned by the
+    /// tool, connected to the streams, see `Altio::inheritable_stdio()`. Unsupported
+    /// by default.
+    fn stdio( &self ) -> std::io::Result<( Stdio, Stdio, Stdio )> {
+        Err( ErrorKind::Unsupported.into() )
+    }
+}
+
+/// Stores all text in memory, the default backend.
+#[derive( Clone, Copy, Debug, Default )]
+pub struct Memory;
+
+impl AltBackend for Memory {
+    fn open( &mut self, _stream: Stream ) -> Port { Port::default() }
+}
+
+/// Backs the streams by OS pipes, with the "os-pipe" feature, so that the tool can
+/// hand real file descriptors of its stdio to C libraries or write them directly, and
+/// the driver still uses the send/recv API.
+///
+/// The ends of the pipes for the tool are returned by `stdin()`, `stdout()` and
+/// `stderr()`, and those for its child processes by `Altio::inheritable_stdio()`. The
+/// pipes are closed when the `Altio` is dropped.
+///
+/// ```rust
+/// use altio::{Altio, backend::OsPipes};
+/// use std::io::{BufRead, BufReader, Write};
+///
+/// let pipes = OsPipes::new().unwrap();
+/// let ( stdin, mut stdout ) = ( pipes.stdin().unwrap(), pipes.stdout().unwrap() );
+/// let io = Altio::builder().backend( pipes ).build();
+///
+/// io.send_line( "ping" );
+/// let mut line = String::new();
+/// BufReader::new( stdin ).read_line( &mut line ).unwrap();
+/// writeln!( stdout, "{}", line.trim_end().replace( "ping", "pong" )).unwrap();
+/// assert_eq!( io.recv_line(), "pong\n" );
+/// ```
+#[cfg( feature = "os-pipe" )]
+#[derive( Debug )]
+pub struct OsPipes {
+    stdin  : ( Option<PipeWriter>, PipeReader ),
+    stdout : ( Option<PipeReader>, PipeWriter ),
+    stderr : ( Option<PipeReader>, PipeWriter ),
+}
+
+#[cfg( feature = "os-pipe" )]
+impl OsPipes {
+    /// Creates the pipes of the three streams.
+    pub fn new() -> std::io::Result<Self> {
+        let ( stdin_reader, stdin_writer ) = os_pipe::pipe()?;
+        let ( stdout_reader, stdout_writer ) = os_pipe::pipe()?;
+        let ( stderr_reader, stderr_writer ) = os_pipe::pipe()?;
+        Ok( OsPipes {
+            stdin  : ( Some( stdin_writer ), stdin_reader ),
+            stdout : ( Some( stdout_reader ), stdout_writer ),
+            stderr : ( Some( stderr_reader ), stderr_writer ),
+        })
+    }
+
+    /// Returns the reading end of the input pipe for the tool.
+    pub fn stdin( &self ) -> std::io::Result<PipeReader> { self.stdin.1.try_clone() }
+
+    /// Returns the writing end of the output pipe for the tool.
+    pub fn stdout( &self ) -> std::io::Result<PipeWriter> { self.stdout.1.try_clone() }
+
+    /// Returns the writing end of the error pipe for the tool.
+    pub fn stderr( &self ) -> std::io::Result<PipeWriter> { self.stderr.1.try_clone() }
+}
+
+#[cfg( feature = "os-pipe" )]
+impl AltBackend for OsPipes {
+    fn open( &mut self, stream: Stream ) -> Port {
+        match stream {
+            Stream::In  => Port{ source: None, sink: self.stdin.0.take().map( |writer| Box::new( writer ) as _ )},
+            Stream::Out => Port{ source: self.stdout.0.take().map( |reader| Box::new( reader ) as _ ), sink: None },
+            Stream::Err => Port{ source: self.stderr.0.take().map( |reader| Box::new( reader ) as _ ), sink: None },
+        }
+    }
+
+    fn stdio( &self ) -> std::io::Result<( Stdio, Stdio, Stdio )> {
+        Ok(( self.stdin()?.into(), self.stdout()?.into(), self.stderr()?.into() ))
+    }
+}
+
+/// Writes the bytes read from `source` into `stream` of `io` in a background thread,
+/// closing the stream at end-of-file or on error. The thread doesn't keep `io` alive.
+pub(crate) fn pump( io: &Altio, stream: Stream, mut source: Box<dyn Read + Send> ) {
+    let io = Arc::downgrade( &io.0 );
+    thread::spawn( move || {
+        let mut chunk = vec![ 0; 8192 ];
+        loop {
+            let read = match source.read( &mut chunk ) {
+                Err( err ) if err.kind() == ErrorKind::Interrupted => continue,
+                Ok( read ) => read,
+                Err(_) => 0,
+            };
+            let Some( io ) = io.upgrade().map( Altio ) else { return };
+            match ( stream, read ) {
+                ( Stream::In, 0 ) => io.0.0.close(),
+                ( Stream::In, _ ) => io.0.0.send_raw( &chunk[ ..read ]),
+                ( _, 0 ) => io.altout( stream ).close(),
+                ( _, _ ) => {
+                    let mut lock = io.altout( stream ).lock();
+                    lock.write_bytes( &chunk[ ..read ]);
+                    lock.output().end_message();
+                }
+            }
+            if read == 0 {
+                return;
+            }
+        }
+    });
+}
+
+/// Writes the lines read from `source` into the output or error `stream` of `io` in a
+/// background thread, each prefixed with `tag` and kept whole among the text written
+/// by the tool. The stream is left open at end-of-file. The thread doesn't keep `io`
+/// alive.
+pub(crate) fn adopt( io: &Altio, stream: Stream, source: impl Read + Send + 'static, tag: String ) {
+    let io = Arc::downgrade( &io.0 );
+    thread::spawn( move || {
+        let mut source = BufReader::new( source );
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match source.read_until( b'\n', &mut line ) {
+                Err( err ) if err.kind() == ErrorKind::Interrupted => continue,
+                Ok( 0 ) | Err(_) => return,
+                Ok(_) => (),
+            }
+            let Some( io ) = io.upgrade().map( Altio ) else { return };
+            let mut lock = io.altout( stream ).lock();
+            lock.write_bytes( tag.as_bytes() );
+            lock.write_bytes( &line );
+            lock.output().end_message();
+        }
+    });
+}
+
+/// The backend of an `Altio`, kept as long as the `Altio` lives.
+#[derive( Debug, Default )]
+pub(crate) struct Backend( pub(crate) Mutex<Option<Box<dyn AltBackend>>> );
+
+/// The sink of a stream.
+pub(crate) struct Sink( pub(crate) Box<dyn Write + Send> );
+
+impl Debug for Sink {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result { f.write_str( "Sink" )}
+}
@@ -0,0 +1,289 @@
+//! A backend sharing the streams with another process through ring buffers in a
+//! memory-backed file.
+//!
+//! Layout of the file, all numbers in little endian:
+//!
+//! - header: magic, version, capacity of the rings and the attached flag.
+//!
+//! - three rings for stdin, stdout and stderr, each of which has a control block of
+//!   the total count of bytes written, the total count of bytes read, the closed flag
+//!   of the writer and the closed flag of the reader, followed by the data.
+
+use crate::{POLL_INTERVAL, Stream};
+
+use super::{AltBackend, Port};
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Read, Write},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+const MAGIC      : u64 = u64::from_le_bytes( *b"ALTIOSHM" );
+const VERSION    : u64 = 1;
+
+const HEADER     : u64 = 64;
+const CONTROL    : u64 = 64;
+
+const MAGIC_AT   : u64 = 0;
+const VERSION_AT : u64 = 8;
+const CAPACITY   : u64 = 16;
+const ATTACHED   : u64 = 24;
+
+const WRITTEN    : u64 = 0;
+const READ       : u64 = 8;
+const W_CLOSED   : u64 = 16;
+const R_CLOSED   : u64 = 24;
+
+/// Backs the streams by ring buffers in a shared memory file, with the "shm" feature,
+/// so that a driver process can attach to a tool embedded in another process, both
+/// using the same send/recv API.
+///
+/// The tool side creates the file with `create()`, and the driver side attaches to it
+/// with `attach()`. The file is removed when the `Altio` of the tool side is dropped,
+/// and the streams of the other side are closed then.
+///
+/// The file should be placed in a memory-backed file system such as `/dev/shm`.
+///
+/// ```rust
+/// use altio::{Altio, backend::SharedMemory};
+/// use std::{io::Write, time::Duration};
+///
+/// let path = std::env::temp_dir().join( format!( "altio-doc-{}", std::process::id() ));
+///
+/// // in the worker process
+/// let tool = Altio::builder().backend( SharedMemory::create( &path, 4096 ).unwrap() ).build();
+///
+/// // in the orchestrator process
+/// let driver = Altio::builder()
+///     .backend( SharedMemory::attach( &path, Duration::from_secs( 1 )).unwrap() )
+///     .build();
+///
+/// driver.send_line( "ping" );
+/// let mut line = String::new();
+/// tool.input().read_line( &mut line ).unwrap();
+/// writeln!( tool.out(), "{}", line.trim_end().replace( "ping", "pong" )).unwrap();
+/// assert_eq!( driver.recv_line(), "pong\n" );
+/// ```
+#[derive( Debug )]
+pub struct SharedMemory {
+    file     : Arc<File>,
+    capacity : u64,
+    creator  : Option<PathBuf>,
+}
+
+impl SharedMemory {
+    /// Creates the shared memory file at `path` for the tool side, with rings of
+    /// `capacity` bytes.
+    pub fn create( path: impl AsRef<Path>, capacity: usize ) -> io::Result<Self> {
+        let capacity = capacity.max( 1 ) as u64;
+        let path = path.as_ref();
+        let file = OpenOptions::new().read( true ).write( true ).create_new( true ).open( path )?;
+        file.set_len( HEADER + 3 * ( CONTROL + capacity ))?;
+        put( &file, CAPACITY, capacity )?;
+        put( &file, VERSION_AT, VERSION )?;
+        put( &file, MAGIC_AT, MAGIC )?;
+        Ok( SharedMemory{ file: Arc::new( file ), capacity, creator: Some( path.to_owned() )})
+    }
+
+    /// Attaches to the shared memory file at `path` for the driver side, waiting at
+    /// most `timeout` for the tool side to create it.
+    pub fn attach( path: impl AsRef<Path>, timeout: Duration ) -> io::Result<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match OpenOptions::new().read( true ).write( true ).open( path.as_ref() ) {
+                Ok( file ) => if get( &file, MAGIC_AT ).ok() == Some( MAGIC ) {
+                    if get( &file, VERSION_AT )? != VERSION {
+                        return Err( io::Error::new( ErrorKind::InvalidData, "unsupported version of altio shared memory" ));
+                    }
+                    let capacity = get( &file, CAPACITY )?;
+                    put( &file, ATTACHED, 1 )?;
+                    return Ok( SharedMemory{ file: Arc::new( file ), capacity, creator: None });
+                },
+                Err( err ) if err.kind() != ErrorKind::NotFound => return Err( err ),
+                Err(_) => (),
+            }
+            if Instant::now() >= deadline {
+                return Err( ErrorKind::TimedOut.into() );
+            }
+            thread::sleep( POLL_INTERVAL );
+        }
+    }
+
+    /// Checks if the driver side has attached to the shared memory.
+    pub fn is_attached( &self ) -> bool {
+        get( &self.file, ATTACHED ).map( |flag| flag != 0 ).unwrap_or( false )
+    }
+
+    /// Waits at most `timeout` for the driver side to attach, returning `true` if it
+    /// has attached.
+    pub fn wait_attached( &self, timeout: Duration ) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.is_attached() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep( POLL_INTERVAL );
+        }
+        true
+    }
+
+    fn ring( &self, stream: Stream ) -> Ring {
+        let index = match stream { Stream::In => 0, Stream::Out => 1, Stream::Err => 2 };
+        Ring {
+            file     : self.file.clone(),
+            base     : HEADER + index * ( CONTROL + self.capacity ),
+            capacity : self.capacity,
+        }
+    }
+}
+
+impl AltBackend for SharedMemory {
+    fn open( &mut self, stream: Stream ) -> Port {
+        let ring = self.ring( stream );
+        match ( self.creator.is_some(), stream ) {
+            ( true, Stream::In ) | ( false, Stream::Out | Stream::Err ) =>
+                Port{ source: Some( Box::new( Reader( ring ))), sink: None },
+            _ => Port{ source: None, sink: Some( Box::new( Writer( ring )))},
+        }
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop( &mut self ) {
+        if let Some( path ) = self.creator.take() {
+            fs::remove_file( path ).ok();
+        }
+    }
+}
+
+fn get( file: &File, offset: u64 ) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    file.read_exact_at( &mut bytes, offset )?;
+    Ok( u64::from_le_bytes( bytes ))
+}
+
+fn put( file: &File, offset: u64, value: u64 ) -> io::Result<()> {
+    file.write_all_at( &value.to_le_bytes(), offset )
+}
+
+/// One ring buffer in the shared memory, with a single writer and a single reader.
+struct Ring {
+    file     : Arc<File>,
+    base     : u64,
+    capacity : u64,
+}
+
+impl Ring {
+    fn get( &self, field: u64 ) -> io::Result<u64> { get( &self.file, self.base + field )}
+
+    fn put( &self, field: u64, value: u64 ) -> io::Result<()> { put( &self.file, self.base + field, value )}
+
+    fn data( &self, pos: u64 ) -> u64 { self.base + CONTROL + pos % self.capacity }
+
+    /// The length of the contiguous region starting at `pos`, at most `len`.
+    fn span( &self, pos: u64, len: u64 ) -> u64 { len.min( self.capacity - pos % self.capacity )}
+}
+
+/// The writing end of a ring, closing it on drop.
+struct Writer( Ring );
+
+impl Write for Writer {
+    fn write( &mut self, buf: &[u8] ) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok( 0 );
+        }
+        loop {
+            let written = self.0.get( WRITTEN )?;
+            let free = self.0.capacity - ( written - self.0.get( READ )? );
+            if free == 0 {
+                if self.0.get( R_CLOSED )? != 0 {
+                    return Err( ErrorKind::BrokenPipe.into() );
+                }
+                thread::sleep( POLL_INTERVAL );
+                continue;
+            }
+            let len = self.0.span( written, free.min( buf.len() as u64 ));
+            self.0.file.write_all_at( &buf[ ..len as usize ], self.0.data( written ))?;
+            self.0.put( WRITTEN, written + len )?;
+            return Ok( len as usize );
+        }
+    }
+
+    fn flush( &mut self ) -> io::Result<()> { Ok(()) }
+}
+
+impl Drop for Writer {
+    fn drop( &mut self ) { self.0.put( W_CLOSED, 1 ).ok(); }
+}
+
+/// The reading end of a ring, blocking until some bytes are available or the writer
+/// has closed.
+struct Reader( Ring );
+
+impl Read for Reader {
+    fn read( &mut self, buf: &mut [u8] ) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok( 0 );
+        }
+        loop {
+            let closed = self.0.get( W_CLOSED )? != 0;
+            let read = self.0.get( READ )?;
+            let available = self.0.get( WRITTEN )? - read;
+            if available == 0 {
+                if closed {
+                    return Ok( 0 );
+                }
+                thread::sleep( POLL_INTERVAL );
+                continue;
+            }
+            let len = self.0.span( read, available.min( buf.len() as u64 ));
+            self.0.file.read_exact_at( &mut buf[ ..len as usize ], self.0.data( read ))?;
+            self.0.put( READ, read + len )?;
+            return Ok( len as usize );
+        }
+    }
+}
+
+impl Drop for Reader {
+    fn drop( &mut self ) { self.0.put( R_CLOSED, 1 ).ok(); }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::Altio;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join( format!( "altio-shm-test-{}", std::process::id() ));
+        let backend = SharedMemory::create( &path, 16 ).unwrap();
+        assert!( !backend.is_attached() );
+        let tool = Altio::builder().backend( backend ).build();
+        let driver = Altio::builder()
+            .backend( SharedMemory::attach( &path, Duration::from_secs( 1 )).unwrap() )
+            .build();
+
+        let long = "0123456789".repeat( 5 );
+        driver.send_line( &long );
+        let mut line = String::new();
+        tool.input().read_line( &mut line ).unwrap();
+        assert_eq!( line.trim_end(), long );
+
+        writeln!( tool.out(), "out" ).unwrap();
+        writeln!( tool.err(), "err" ).unwrap();
+        assert_eq!( driver.recv_line(), "out\n" );
+        assert_eq!( driver.recv_err_line(), "err\n" );
+
+        drop( tool );
+        assert!( !path.exists() );
+        while !driver.0.1.closed.load( Ordering::Relaxed ) {
+            thread::sleep( POLL_INTERVAL );
+        }
+    }
+}
@@ -0,0 +1,683 @@
+//! Chunked storage for the text flowing through altio streams.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display, Formatter},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Appends shorter than this are packed into the last chunk, longer ones get a chunk
+/// of their own.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A FIFO of bytes stored as a list of chunks.
+///
+/// Appending never copies previously stored data, and consuming from the front only
+/// advances an offset into the first chunk, so both stay cheap no matter how much text
+/// is pending. The contents are joined lazily, when received or displayed.
+///
+/// Optionally, data beyond a memory threshold is spilled into a temporary file and
+/// loaded back when the front of the buffer reaches it.
+#[derive( Debug, Default )]
+pub struct Buffer {
+    chunks  : VecDeque<Vec<u8>>,
+    head    : usize,
+    len     : usize,
+    scanned : usize, // no newline in the first `scanned` bytes
+    taken   : u64,   // bytes taken so far minus bytes pushed to the front, wrapping
+    spill   : Option<Spill>,
+    error   : Option<io::Error>, // of reading back spilled data, which is lost
+}
+
+/// The temporary file holding data spilled out of memory, oldest first.
+#[derive( Debug )]
+struct Spill {
+    file      : File,
+    path      : PathBuf,
+    threshold : usize,
+    read_pos  : u64,
+    write_pos : u64,
+    chunks    : VecDeque<usize>, // sizes of spilled chunks
+    len       : usize,
+}
+
+impl Spill {
+    fn create( threshold: usize ) -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new( 0 );
+
+        let path = std::env::temp_dir().join( format!( "altio-{}-{}.spill",
+            std::process::id(), COUNT.fetch_add( 1, Ordering::Relaxed )));
+        let file = OpenOptions::new().read( true ).write( true ).create_new( true ).open( &path )?;
+        Ok( Spill{ file, path, threshold, read_pos: 0, write_pos: 0, chunks: VecDeque::new(), len: 0 })
+    }
+
+    fn write( &mut self, bytes: &[u8] ) -> io::Result<()> {
+        self.file.seek( SeekFrom::Start( self.write_pos ))?;
+        self.file.write_all( bytes )?;
+        self.write_pos += bytes.len() as u64;
+        self.chunks.push_back( bytes.len() );
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn read_chunk( &mut self ) -> io::Result<Option<Vec<u8>>> {
+        match self.chunks.front() {
+            None => Ok( None ),
+            Some( &size ) => {
+                let mut chunk = vec![ 0; size ];
+                self.file.seek( SeekFrom::Start( self.read_pos ))?;
+                self.file.read_exact( &mut chunk )?;
+                self.chunks.pop_front();
+                self.read_pos += size as u64;
+                self.len -= size;
+                if self.len == 0 {
+                    self.file.set_len( 0 )?;
+                    self.read_pos = 0;
+                    self.write_pos = 0;
+                }
+                Ok( Some( chunk ))
+            }
+        }
+    }
+
+    /// Reads the spilled bytes after the first `offset`, leaving them in the file.
+    fn read_from( &self, offset: usize ) -> io::Result<Vec<u8>> {
+        let mut file = &self.file;
+        let mut bytes = vec![ 0; self.len.saturating_sub( offset )];
+        file.seek( SeekFrom::Start( self.read_pos + offset as u64 ))?;
+        file.read_exact( &mut bytes )?;
+        Ok( bytes )
+    }
+}
+
+impl Drop for Spill {
+    fn drop( &mut self ) {
+        let _ = std::fs::remove_file( &self.path );
+    }
+}
+
+impl Buffer {
+    /// Returns the amount of pending bytes.
+    pub fn len( &self ) -> usize { self.len }
+
+    /// Returns true if nothing is pending.
+    pub fn is_empty( &self ) -> bool { self.len == 0 }
+
+    /// Keeps at most `threshold` bytes in memory, spilling the rest into a temporary
+    /// file which is removed when the buffer is dropped.
+    pub(crate) fn spill_to_disk( &mut self, threshold: usize ) -> io::Result<()> {
+        match self.spill.as_mut() {
+            Some( spill ) => spill.threshold = threshold,
+            None => self.spill = Some( Spill::create( threshold )? ),
+        }
+        Ok(())
+    }
+
+    fn spilled( &self ) -> usize {
+        self.spill.as_ref().map_or( 0, |spill| spill.len )
+    }
+
+    pub(crate) fn push( &mut self, bytes: &[u8] ) {
+        if bytes.is_empty() {
+            return;
+        }
+        let in_memory = self.len - self.spilled();
+        if let Some( spill ) = self.spill.as_mut() {
+            if spill.len != 0 || in_memory + bytes.len() > spill.threshold {
+                if spill.write( bytes ).is_ok() {
+                    self.len += bytes.len();
+                    return;
+                }
+                // Keeps the order of data if the file is not writable any more.
+                self.load( self.len );
+            }
+        }
+        self.len += bytes.len();
+        self.push_in_memory( bytes );
+    }
+
+    /// Prepends `bytes`, which become the first pending ones.
+    pub(crate) fn push_front( &mut self, bytes: &[u8] ) {
+        if bytes.is_empty() {
+            return;
+        }
+        if self.head != 0 {
+            let head = std::mem::take( &mut self.head );
+            self.chunks[0].drain( ..head );
+        }
+        self.chunks.push_front( bytes.to_vec() );
+        self.len += bytes.len();
+        self.scanned = 0;
+        self.taken = self.taken.wrapping_sub( bytes.len() as u64 );
+    }
+
+    /// Inserts `bytes` after the first `offset` pending bytes.
+    pub(crate) fn insert( &mut self, offset: usize, bytes: &[u8] ) {
+        let offset = offset.min( self.len );
+        if offset == 0 {
+            self.push_front( bytes );
+            self.taken = self.taken.wrapping_add( bytes.len() as u64 );
+            return;
+        }
+        if bytes.is_empty() {
+            return;
+        }
+        self.load( offset );
+        let offset = offset.min( self.len );
+        let mut start = 0;
+        for i in 0..self.chunks.len() {
+            let head = if i == 0 { self.head } else { 0 };
+            let end = start + self.chunks[i].len() - head;
+            if offset <= end {
+                let tail = self.chunks[i].split_off( head + offset - start );
+                let mut at = i+1;
+                for chunk in [ bytes.to_vec(), tail ] {
+                    if !chunk.is_empty() {
+                        self.chunks.insert( at, chunk );
+                        at += 1;
+                    }
+                }
+                break;
+            }
+            start = end;
+        }
+        self.len += bytes.len();
+        self.scanned = self.scanned.min( offset );
+    }
+
+    fn push_in_memory( &mut self, bytes: &[u8] ) {
+        if let Some( last ) = self.chunks.back_mut() {
+            if last.len() + bytes.len() <= last.capacity() {
+                last.extend_from_slice( bytes );
+                return;
+            }
+        }
+        if bytes.len() >= CHUNK_SIZE {
+            self.chunks.push_back( bytes.to_vec() );
+        } else {
+            let mut chunk = Vec::with_capacity( CHUNK_SIZE );
+            chunk.extend_from_slice( bytes );
+            self.chunks.push_back( chunk );
+        }
+    }
+
+    /// Moves spilled data back into memory until at least `n` bytes are there.
+    fn load( &mut self, n: usize ) {
+        while self.len - self.spilled() < n && self.load_chunk() {}
+    }
+
+    fn load_chunk( &mut self ) -> bool {
+        match self.spill.as_mut().map( Spill::read_chunk ) {
+            Some( Ok( Some( chunk ))) => {
+                self.push_in_memory( &chunk );
+                true
+            }
+            Some( Err( err )) => {
+                self.lose_spilled( err );
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops the spilled data which can't be read back, keeping `err` to be reported,
+    /// and keeps the data pushed from now on in memory.
+    fn lose_spilled( &mut self, err: io::Error ) {
+        if let Some( spill ) = self.spill.take() {
+            self.len -= spill.len;
+        }
+        self.scanned = self.scanned.min( self.len );
+        self.error = Some( err );
+    }
+
+    /// Takes the error of reading back spilled data, which has been lost.
+    pub(crate) fn take_error( &mut self ) -> Option<io::Error> { self.error.take() }
+
+    /// Keeps `err` of reading back spilled data to be reported, without losing it.
+    pub(crate) fn set_error( &mut self, err: io::Error ) { self.error = Some( err ); }
+
+    /// Makes the spilled data unreadable, to test handling the errors.
+    #[cfg( test )]
+    pub(crate) fn break_spill( &mut self ) {
+        let spill = self.spill.as_mut().expect( "spilling" );
+        spill.file = OpenOptions::new().write( true ).open( &spill.path ).unwrap();
+    }
+
+    /// Iterates over the in-memory bytes, chunk by chunk.
+    pub(crate) fn slices( &self ) -> impl Iterator<Item=&[u8]> {
+        let head = self.head;
+        self.chunks
+            .iter()
+            .enumerate()
+            .map( move |(i, chunk)| if i == 0 { &chunk[ head.. ] } else { &chunk[..] })
+    }
+
+    /// Returns the bytes at the front which are contiguous in memory, empty if none.
+    pub(crate) fn front( &mut self ) -> &[u8] {
+        self.load( 1 );
+        self.slices().next().unwrap_or_default()
+    }
+
+    /// Returns the offset of the first `byte` at or after `from`.
+    pub(crate) fn find( &mut self, mut from: usize, byte: u8 ) -> Option<usize> {
+        loop {
+            let mut start = 0;
+            for slice in self.slices() {
+                let end = start + slice.len();
+                if from < end {
+                    let skip = from.saturating_sub( start );
+                    if let Some( off ) = position( &slice[ skip.. ], byte ) {
+                        return Some( start + skip + off );
+                    }
+                }
+                start = end;
+            }
+            from = from.max( start );
+            if !self.load_chunk() {
+                return None;
+            }
+        }
+    }
+
+    /// Returns the offset of the first newline.
+    ///
+    /// Bytes already known to contain no newline are not searched again.
+    pub(crate) fn find_newline( &mut self ) -> Option<usize> {
+        let found = self.find( self.scanned, b'\n' );
+        self.scanned = found.unwrap_or( self.len );
+        found
+    }
+
+    /// Returns true if the pending bytes end with `suffix`, reading the spilled ones
+    /// without loading them back into memory.
+    pub(crate) fn ends_with( &mut self, suffix: &[u8] ) -> bool {
+        let Some( start ) = self.len.checked_sub( suffix.len() ) else { return false };
+        let in_memory = self.len - self.spilled();
+        let ( head, tail ) = suffix.split_at( in_memory.saturating_sub( start ));
+        if let Some( spill ) = self.spill.as_ref().filter( |_| !tail.is_empty() ) {
+            match spill.read_from( start.max( in_memory ) - in_memory ) {
+                Ok( bytes ) if bytes != tail => return false,
+                Ok( _ ) => (),
+                Err( err ) => {
+                    self.lose_spilled( err );
+                    return self.ends_with( suffix );
+                }
+            }
+        }
+        head.iter().enumerate().all( |( i, &byte )| self.byte_at( start+i ) == Some( byte ))
+    }
+
+    /// Returns the in-memory byte at offset `index`.
+    fn byte_at( &self, mut index: usize ) -> Option<u8> {
+        for slice in self.slices() {
+            if index < slice.len() {
+                return Some( slice[ index ]);
+            }
+            index -= slice.len();
+        }
+        None
+    }
+
+    /// Returns the largest offset not greater than `n` which does not split a UTF-8
+    /// encoded char, or the end of the first char if that offset would be zero.
+    pub(crate) fn char_boundary( &mut self, n: usize ) -> usize {
+        let n = n.min( self.len );
+        self.load( n.max(4) + 1 );
+        let is_boundary = |i| self.byte_at( i ).is_none_or( |b| ( b as i8 ) >= -0x40 );
+        if n == 0 {
+            return 0;
+        }
+        let mut offset = n;
+        while offset > 0 && !is_boundary( offset ) {
+            offset -= 1;
+        }
+        if offset == 0 {
+            offset = 1;
+            while !is_boundary( offset ) {
+                offset += 1;
+            }
+        }
+        offset
+    }
+
+    /// Returns the offset after the first `n` UTF-8 encoded chars, or `None` if fewer
+    /// are complete.
+    pub(crate) fn char_offset( &mut self, n: usize ) -> Option<usize> {
+        self.load( n.saturating_mul(4) );
+        let mut offset = 0;
+        for _ in 0..n {
+            let lead = self.byte_at( offset )?;
+            offset += ( lead.leading_ones() as usize ).clamp( 1, 4 );
+        }
+        ( offset <= self.len ).then_some( offset )
+    }
+
+    /// Copies the first `n` bytes, leaving them in the buffer.
+    pub(crate) fn copy( &mut self, n: usize ) -> Vec<u8> {
+        self.load( n );
+        let mut bytes = Vec::with_capacity( n );
+        for slice in self.slices() {
+            let want = n - bytes.len();
+            if want == 0 {
+                break;
+            }
+            bytes.extend_from_slice( &slice[ ..want.min( slice.len() )]);
+        }
+        bytes
+    }
+
+    /// Copies the bytes after the first `from`, leaving them in the buffer, and reading
+    /// the spilled ones without loading them back into memory.
+    pub(crate) fn copy_from( &mut self, from: usize ) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity( self.len.saturating_sub( from ));
+        let mut start = 0;
+        for slice in self.slices() {
+            let end = start + slice.len();
+            if from < end {
+                bytes.extend_from_slice( &slice[ from.saturating_sub( start ).. ]);
+            }
+            start = end;
+        }
+        if let Some( spill ) = self.spill.as_ref().filter( |spill| spill.len != 0 ) {
+            match spill.read_from( from.saturating_sub( start )) {
+                Ok( spilled ) => bytes.extend( spilled ),
+                Err( err ) => self.lose_spilled( err ),
+            }
+        }
+        bytes
+    }
+
+    /// Removes the first `n` bytes and returns them.
+    pub(crate) fn take( &mut self, n: usize ) -> Vec<u8> {
+        self.load( n.min( self.len ));
+        let n = n.min( self.len );
+        let mut bytes = Vec::new();
+        while bytes.len() < n {
+            let want = n - bytes.len();
+            let avail = self.chunks[0].len() - self.head;
+            if want >= avail {
+                let chunk = self.chunks.pop_front().unwrap();
+                if bytes.is_empty() && self.head == 0 {
+                    bytes = chunk;
+                } else {
+                    bytes.extend_from_slice( &chunk[ self.head.. ]);
+                }
+                self.head = 0;
+            } else {
+                bytes.extend_from_slice( &self.chunks[0][ self.head..self.head+want ]);
+                self.head += want;
+            }
+        }
+        self.len -= n;
+        self.scanned = self.scanned.saturating_sub( n );
+        self.taken = self.taken.wrapping_add( n as u64 );
+        bytes
+    }
+
+    /// Returns the position of the first pending byte in the stream, counting bytes
+    /// pushed to the front backwards. Positions wrap, compare them by difference.
+    pub(crate) fn taken( &self ) -> u64 { self.taken }
+
+    /// Removes everything and returns it.
+    pub(crate) fn take_all( &mut self ) -> Vec<u8> {
+        self.take( self.len )
+    }
+
+    /// Copies everything, leaving it in the buffer.
+    #[cfg( feature = "altio" )]
+    pub(crate) fn copy_all( &mut self ) -> Vec<u8> {
+        self.copy( self.len )
+    }
+
+    /// Joins all pending bytes as text, leaving them in the buffer, or fails with
+    /// the error of reading back spilled data and the text in memory.
+    pub(crate) fn joined( &self ) -> Result<String, ( io::Error, String )> {
+        let mut bytes = self.slices().collect::<Vec<_>>().concat();
+        if let Some( spill ) = self.spill.as_ref().filter( |spill| spill.len != 0 ) {
+            match spill.read_from( 0 ) {
+                Ok( spilled ) => bytes.extend( spilled ),
+                Err( err ) => return Err(( err, into_string( bytes ))),
+            }
+        }
+        Ok( into_string( bytes ))
+    }
+
+    /// Replaces all pending bytes with `bytes`.
+    pub(crate) fn replace_all( &mut self, bytes: &[u8] ) {
+        self.take_all();
+        self.push( bytes );
+    }
+
+    /// Removes the first `n` bytes as text.
+    pub(crate) fn take_string( &mut self, n: usize ) -> String {
+        into_string( self.take( n ))
+    }
+
+    /// Copies the first `n` bytes as text, leaving them in the buffer.
+    pub(crate) fn copy_string( &mut self, n: usize ) -> String {
+        into_string( self.copy( n ))
+    }
+}
+
+impl Display for Buffer {
+    fn fmt( &self, f: &mut Formatter<'_> ) -> fmt::Result {
+        for slice in self.slices() {
+            f.write_str( &String::from_utf8_lossy( slice ))?;
+        }
+        if let Some( spill ) = self.spill.as_ref().filter( |spill| spill.len != 0 ) {
+            let bytes = spill.read_from( 0 ).map_err( |_| fmt::Error )?;
+            f.write_str( &String::from_utf8_lossy( &bytes ))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the index of the first `byte` in `haystack`.
+#[cfg( feature = "memchr" )]
+#[inline]
+pub(crate) fn position( haystack: &[u8], byte: u8 ) -> Option<usize> {
+    memchr::memchr( byte, haystack )
+}
+
+/// Returns the index of the first `byte` in `haystack`.
+#[cfg( not( feature = "memchr" ))]
+#[inline]
+pub(crate) fn position( haystack: &[u8], byte: u8 ) -> Option<usize> {
+    haystack.iter().position( |&b| b == byte )
+}
+
+/// Converts bytes into a `String`, replacing invalid UTF-8 sequences.
+pub(crate) fn into_string( bytes: Vec<u8> ) -> String {
+    String::from_utf8( bytes )
+        .unwrap_or_else( |err| String::from_utf8_lossy( err.as_bytes() ).into_owned() )
+}
+
+/// Returns the offset in `bytes` matching `offset` in the text converted from them by
+/// `into_string()`, where each invalid UTF-8 sequence became one U+FFFD.
+pub(crate) fn raw_offset( bytes: &[u8], mut offset: usize ) -> usize {
+    let mut raw = 0;
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid().len();
+        if offset <= valid {
+            return raw + offset;
+        }
+        offset = ( offset - valid ).saturating_sub( char::REPLACEMENT_CHARACTER.len_utf8() );
+        raw += valid + chunk.invalid().len();
+    }
+    raw
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Buffer, CHUNK_SIZE, into_string, raw_offset};
+
+    #[test]
+    fn unreadable_spill() {
+        let mut buf = Buffer::default();
+        buf.spill_to_disk( 4 ).unwrap();
+        buf.push( b"abc\n" );
+        buf.push( b"def\n" );
+        buf.break_spill();
+
+        assert_eq!( buf.joined().unwrap_err().1, "abc\n" );
+        assert_eq!( buf.len(), 8 );
+        assert_eq!( buf.take_string( 8 ), "abc\n" );
+        assert!( buf.is_empty() );
+        assert!( buf.take_error().is_some() );
+        assert!( buf.take_error().is_none() );
+
+        buf.push( b"ghi\n" );
+        assert_eq!( buf.take_string( 4 ), "ghi\n" );
+    }
+
+    #[test]
+    fn push_and_take_across_chunks() {
+        let mut buf = Buffer::default();
+        let line = "x".repeat( CHUNK_SIZE - 1 ) + "\n";
+        for _ in 0..3 {
+            buf.push( line.as_bytes() );
+        }
+        assert_eq!( buf.len(), 3 * CHUNK_SIZE );
+        assert_eq!( buf.find( 0, b'\n' ), Some( CHUNK_SIZE - 1 ));
+        assert_eq!( buf.find( CHUNK_SIZE, b'\n' ), Some( 2 * CHUNK_SIZE - 1 ));
+
+        assert_eq!( buf.take_string( 3 ), "xxx" );
+        assert_eq!( buf.take_string( CHUNK_SIZE - 3 ), line[3..] );
+        assert_eq!( buf.copy_string( CHUNK_SIZE ), line );
+        assert_eq!( buf.take_all().len(), 2 * CHUNK_SIZE );
+        assert!( buf.is_empty() );
+        assert_eq!( buf.find( 0, b'\n' ), None );
+    }
+
+    #[test]
+    fn newline_scan_resumes() {
+        let mut buf = Buffer::default();
+        buf.push( b"abc" );
+        assert_eq!( buf.find_newline(), None );
+        assert_eq!( buf.scanned, 3 );
+
+        buf.push( b"de\nf" );
+        assert_eq!( buf.find_newline(), Some( 5 ));
+        assert_eq!( buf.find_newline(), Some( 5 ));
+
+        buf.take( 6 );
+        assert_eq!( buf.scanned, 0 );
+        assert_eq!( buf.find_newline(), None );
+        buf.push( b"\n" );
+        assert_eq!( buf.find_newline(), Some( 1 ));
+    }
+
+    #[test]
+    fn push_front() {
+        let mut buf = Buffer::default();
+        buf.push( b"abc\n" );
+        assert_eq!( buf.find_newline(), Some( 3 ));
+        assert_eq!( buf.take( 1 ), b"a" );
+        buf.push_front( b"x\n" );
+        assert_eq!( buf.find_newline(), Some( 1 ));
+        assert_eq!( buf.take_all(), b"x\nbc\n" );
+    }
+
+    #[test]
+    fn insert() {
+        let mut buf = Buffer::default();
+        buf.push( b"abc" );
+        buf.push( &[ b'd'; CHUNK_SIZE ]);
+        buf.take( 1 );
+        buf.insert( 1, b"1" );
+        buf.insert( 0, b"0" );
+        buf.insert( 3 + CHUNK_SIZE + 2, b"\n" );
+        assert_eq!( buf.take( 5 ), b"0b1cd" );
+        assert_eq!( buf.taken(), 6 );
+        assert_eq!( buf.find_newline(), Some( CHUNK_SIZE-1 ));
+    }
+
+    #[test]
+    fn ends_with() {
+        let mut buf = Buffer::default();
+        buf.push( b"Pass" );
+        buf.push( b"word: " );
+        assert!( buf.ends_with( b"d: " ));
+        assert!( buf.ends_with( b"Password: " ));
+        assert!( !buf.ends_with( b"> " ));
+        assert!( !buf.ends_with( b"Old Password: " ));
+
+        let mut buf = Buffer::default();
+        buf.spill_to_disk( 4 ).unwrap();
+        buf.push( b"Pass" );
+        buf.push( b"word: " );
+        assert!( buf.ends_with( b": " ));
+        assert!( buf.ends_with( b"Password: " ));
+        assert!( !buf.ends_with( b"Passwd: " ));
+        assert_eq!( buf.spilled(), 6 );
+        assert_eq!( buf.copy_from( 2 ), b"ssword: " );
+        assert_eq!( buf.copy_from( 7 ), b"d: " );
+        assert_eq!( buf.spilled(), 6 );
+    }
+
+    #[test]
+    fn char_offset() {
+        let mut buf = Buffer::default();
+        buf.push( "a\u{4e2d}".as_bytes() );
+        buf.push( &"\u{6587}".as_bytes()[..1] );
+        assert_eq!( buf.char_offset( 0 ), Some( 0 ));
+        assert_eq!( buf.char_offset( 2 ), Some( 4 ));
+        assert_eq!( buf.char_offset( 3 ), None );
+        buf.push( &"\u{6587}".as_bytes()[1..] );
+        assert_eq!( buf.char_offset( 3 ), Some( 7 ));
+    }
+
+    #[test]
+    fn char_boundary() {
+        let mut buf = Buffer::default();
+        buf.push( "a".as_bytes() );
+        buf.push( "\u{4e2d}\u{6587}".as_bytes() );
+        assert_eq!( buf.char_boundary( 0 ), 0 );
+        assert_eq!( buf.char_boundary( 1 ), 1 );
+        assert_eq!( buf.char_boundary( 3 ), 1 );
+        assert_eq!( buf.char_boundary( 4 ), 4 );
+        assert_eq!( buf.char_boundary( 100 ), 7 );
+
+        buf.take( 1 );
+        assert_eq!( buf.char_boundary( 2 ), 3 );
+    }
+
+    #[test]
+    fn raw_offsets() {
+        let bytes = b"\xff\xffa\xe4\xb8b";
+        let text = into_string( bytes.to_vec() );
+        assert_eq!( text, "\u{fffd}\u{fffd}a\u{fffd}b" );
+        let offsets = text.char_indices().map( |( offset, _ )| raw_offset( bytes, offset )).collect::<Vec<_>>();
+        assert_eq!( offsets, [ 0, 1, 2, 3, 5 ]);
+        assert_eq!(( raw_offset( bytes, text.len() - 1 ), raw_offset( bytes, text.len() )), ( 5, 6 ));
+    }
+
+    #[test]
+    fn spill_to_disk() {
+        let mut buf = Buffer::default();
+        buf.spill_to_disk( 8 ).unwrap();
+        let path = buf.spill.as_ref().unwrap().path.clone();
+
+        buf.push( b"abc\n" );
+        buf.push( b"defg" );
+        buf.push( b"hi\njk" );
+        buf.push( b"l\n" );
+        assert_eq!( buf.len(), 15 );
+        assert_eq!( buf.spilled(), 7 );
+        assert_eq!( buf.to_string(), "abc\ndefghi\njkl\n" );
+
+        assert_eq!( buf.take_string( 4 ), "abc\n" );
+        let offset = buf.find_newline().unwrap();
+        assert_eq!( buf.take_string( offset+1 ), "defghi\n" );
+        assert_eq!( buf.spilled(), 2 );
+        buf.push( b"mn" );
+        assert_eq!( buf.take_string( 100 ), "jkl\nmn" );
+        assert_eq!( buf.spilled(), 0 );
+
+        drop( buf );
+        assert!( !path.exists() );
+    }
+}
@@ -0,0 +1,88 @@
+//! Emulation of the buffering of the standard output of real programs.
+
+use std::borrow::Cow;
+
+/// When text written into an output stream becomes visible to receivers.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub enum Buffering {
+    /// Instantly, like a `Stderr`.
+    #[default]
+    Unbuffered,
+    /// At newlines, like a `Stdout` connected to a terminal.
+    Line,
+    /// When more than the capacity is written, like a `Stdout` connected to a pipe
+    /// in languages other than Rust, e.g. C.
+    Full( usize ),
+}
+
+/// Text written into an output stream but not flushed yet.
+#[derive( Debug )]
+pub(crate) struct WriteBuffer {
+    mode    : Buffering,
+    pending : Vec<u8>,
+}
+
+impl WriteBuffer {
+    pub(crate) fn new( mode: Buffering ) -> Self {
+        WriteBuffer{ mode, pending: Vec::new() }
+    }
+
+    /// Buffers written `bytes`, returning those flushed.
+    pub(crate) fn write<'a>( &mut self, bytes: &'a [u8] ) -> Cow<'a, [u8]> {
+        match self.mode {
+            Buffering::Unbuffered => Cow::Borrowed( bytes ),
+            Buffering::Line => match bytes.iter().rposition( |&byte| byte == b'\n' ) {
+                Some( offset ) => {
+                    let mut flushed = std::mem::take( &mut self.pending );
+                    flushed.extend_from_slice( &bytes[ ..=offset ]);
+                    self.pending.extend_from_slice( &bytes[ offset+1.. ]);
+                    Cow::Owned( flushed )
+                }
+                None => {
+                    self.pending.extend_from_slice( bytes );
+                    Cow::Borrowed( &[] )
+                }
+            },
+            Buffering::Full( capacity ) => {
+                let mut flushed = Vec::new();
+                if self.pending.len() + bytes.len() > capacity {
+                    flushed = std::mem::take( &mut self.pending );
+                }
+                if bytes.len() >= capacity {
+                    flushed.extend_from_slice( bytes );
+                } else {
+                    self.pending.extend_from_slice( bytes );
+                }
+                Cow::Owned( flushed )
+            }
+        }
+    }
+
+    /// Returns all buffered bytes.
+    pub(crate) fn flush( &mut self ) -> Vec<u8> {
+        std::mem::take( &mut self.pending )
+    }
+
+    /// Returns the amount of buffered bytes.
+    pub(crate) fn held( &self ) -> usize { self.pending.len() }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Buffering, WriteBuffer};
+
+    #[test]
+    fn buffering() {
+        let mut line = WriteBuffer::new( Buffering::Line );
+        assert_eq!( &*line.write( b"a" ), b"" );
+        assert_eq!( &*line.write( b"b\nc\nd" ), b"ab\nc\n" );
+        assert_eq!( line.flush(), b"d" );
+
+        let mut full = WriteBuffer::new( Buffering::Full( 4 ));
+        assert_eq!( &*full.write( b"ab" ), b"" );
+        assert_eq!( &*full.write( b"c\n" ), b"" );
+        assert_eq!( &*full.write( b"d" ), b"abc\n" );
+        assert_eq!( &*full.write( b"efgh" ), b"defgh" );
+        assert_eq!( full.held(), 0 );
+    }
+}
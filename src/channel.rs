@@ -0,0 +1,269 @@
+//! Typed channels beside the text streams, for drivers which need structured data,
+//! e.g. GUIs embedding tools.
+
+#[cfg( feature = "altio" )]
+use crate::{Altio, Prompt, Ready, Stream, into_string, prompt::CONTROL, wait_for};
+
+#[cfg( feature = "altio" )]
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Mutex, PoisonError, atomic::{AtomicBool, Ordering}},
+    time::Duration,
+};
+
+/// Progress reported by a tool with `Altio::progress()`.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub struct ProgressEvent {
+    /// The amount of work done.
+    pub done    : u64,
+    /// The total amount of work.
+    pub total   : u64,
+    /// What is being done.
+    pub message : String,
+}
+
+impl ProgressEvent {
+    /// Returns the textual progress line for human users, e.g.
+    /// `"indexing: 3/10 (30%)\n"`.
+    pub fn render( &self ) -> String {
+        let ProgressEvent{ done, total, message } = self;
+        match done.saturating_mul( 100 ).checked_div( *total ) {
+            Some( percent ) => format!( "{message}: {done}/{total} ({percent}%)\n" ),
+            None => format!( "{message}: {done}/{total}\n" ),
+        }
+    }
+}
+
+/// The channels of a session.
+#[cfg( feature = "altio" )]
+#[derive( Debug, Default )]
+pub(crate) struct Channels {
+    pub(crate) progress : Mutex<VecDeque<ProgressEvent>>,
+    pub(crate) status   : Mutex<Status>,
+    pub(crate) ready    : Ready,
+    pub(crate) checks   : Checks,
+    pub(crate) finished : AtomicBool, // the checks have run by `Altio::finish()`
+}
+
+/// The checks of a session when its last handle is dropped, set by `AltioBuilder`.
+#[cfg( feature = "altio" )]
+#[derive( Clone, Copy, Debug, Default )]
+pub(crate) struct Checks {
+    pub(crate) input      : bool, // all input sent has been read
+    pub(crate) strict_err : bool, // nothing has been written into the error stream
+    pub(crate) output     : bool, // all output has been received
+}
+
+/// The key-value status of a session, set by `Altio::set_status()`.
+#[cfg( feature = "altio" )]
+#[derive( Debug, Default )]
+pub(crate) struct Status {
+    pub(crate) values  : BTreeMap<String, String>,
+    pub(crate) version : u64, // bumped whenever a value changes
+}
+
+/// Receives the changes of the key-value status of a session, created by
+/// `Altio::watch_status()`, e.g. for dashboards supervising many sessions.
+///
+/// ```rust
+/// use altio::Altio;
+///
+/// let io = Altio::default();
+/// io.set_status( "phase", "scanning" );
+/// let mut watcher = io.watch_status();
+/// io.set_status( "phase", "indexing" );
+/// io.set_status( "files", "42" );
+/// assert_eq!( watcher.recv(), [( "files".to_owned(), "42".to_owned() ), ( "phase".to_owned(), "indexing".to_owned() )]);
+/// assert_eq!( io.status_value( "phase" ).as_deref(), Some( "indexing" ));
+/// ```
+#[cfg( feature = "altio" )]
+#[derive( Clone, Debug )]
+pub struct StatusWatcher {
+    io      : Altio,
+    seen    : BTreeMap<String, String>,
+    version : u64,
+}
+
+#[cfg( feature = "altio" )]
+impl StatusWatcher {
+    pub(crate) fn new( io: Altio ) -> Self {
+        let ( seen, version ) = {
+            let status = io.0.5.status.lock().unwrap_or_else( PoisonError::into_inner );
+            ( status.values.clone(), status.version )
+        };
+        StatusWatcher{ io, seen, version }
+    }
+
+    /// Receives the keys and values changed since the last receiving, in the order of
+    /// keys. Values changed several times in between are received once, with the
+    /// latest value.
+    ///
+    /// This function will always block the current thread until some value changes.
+    pub fn recv( &mut self ) -> Vec<( String, String )> {
+        self.wait_until( None ).expect( "waiting without deadline" )
+    }
+
+    /// Receives the changes like `recv()`, blocking at most `timeout` measured by the
+    /// clock of the `Altio`.
+    pub fn recv_timeout( &mut self, timeout: Duration ) -> Option<Vec<( String, String )>> {
+        self.wait_until( Some( timeout ))
+    }
+
+    /// Tries to receive the changes like `recv()`, without blocking.
+    pub fn try_recv( &mut self ) -> Option<Vec<( String, String )>> {
+        self.wait_until( Some( Duration::ZERO ))
+    }
+
+    fn wait_until( &mut self, timeout: Option<Duration> ) -> Option<Vec<( String, String )>> {
+        let io = self.io.clone();
+        let ( channels, err ) = ( &io.0.5, &io.0.2 );
+        let deadline = timeout.map( |timeout| err.clock.now() + timeout );
+        wait_for( &channels.status, &channels.ready, Stream::Err, &err.hook, &*err.clock, deadline, |status| {
+            if status.version == self.version {
+                return None;
+            }
+            self.version = status.version;
+            let changes = status.values.iter()
+                .filter( |&( key, value )| self.seen.get( key ) != Some( value ))
+                .map( |( key, value )| ( key.clone(), value.clone() ))
+                .collect::<Vec<_>>();
+            self.seen.clone_from( &status.values );
+            ( !changes.is_empty() ).then_some( changes )
+        })
+    }
+}
+
+/// Something happened in a session, received by `Altio::events()` in the order it
+/// happened.
+#[cfg( feature = "altio" )]
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub enum IoEvent {
+    /// Text written into the output stream, by one write or several in a row.
+    Out( String ),
+    /// Text written into the error stream, by one write or several in a row.
+    Err( String ),
+    /// A prompt emitted by `Altio::emit_prompt()`.
+    Prompt( Prompt ),
+    /// Progress reported by `Altio::progress()`.
+    Progress( ProgressEvent ),
+    /// The tool has read some bytes of the input.
+    InputConsumed {
+        /// The amount of bytes read.
+        bytes : usize,
+    },
+    /// The tool spawned by `AltCommand` has returned the exit code.
+    Exit( i32 ),
+}
+
+#[cfg( feature = "altio" )]
+impl IoEvent {
+    /// Returns the event of writing `bytes` into `stream`, which is a prompt if they
+    /// are its control line.
+    pub(crate) fn written( stream: Stream, bytes: &[u8] ) -> Self {
+        let text = into_string( bytes.to_vec() );
+        if text.starts_with( CONTROL ) {
+            if let Some( prompt ) = Prompt::from_control_line( &text ) {
+                return IoEvent::Prompt( prompt );
+            }
+        }
+        match stream {
+            Stream::Err => IoEvent::Err( text ),
+            _ => IoEvent::Out( text ),
+        }
+    }
+}
+
+/// The events of a session, shared by its streams, recorded only after
+/// `Altio::record_events()` so that sessions not receiving them don't grow.
+#[cfg( feature = "altio" )]
+#[derive( Debug, Default )]
+pub(crate) struct EventLog {
+    enabled : AtomicBool,
+    events  : Mutex<VecDeque<IoEvent>>,
+    ready   : Ready,
+}
+
+#[cfg( feature = "altio" )]
+impl EventLog {
+    pub(crate) fn enable( &self ) { self.enabled.store( true, Ordering::Relaxed ); }
+
+    /// Records the event returned by `event`, if enabled, joining the text written
+    /// into the same stream with the text of the last event not received yet.
+    pub(crate) fn record( &self, event: impl FnOnce() -> IoEvent ) {
+        if self.enabled.load( Ordering::Relaxed ) {
+            let mut events = self.events.lock().unwrap_or_else( PoisonError::into_inner );
+            match ( events.back_mut(), event() ) {
+                ( Some( IoEvent::Out( text )), IoEvent::Out( more )) |
+                ( Some( IoEvent::Err( text )), IoEvent::Err( more )) => text.push_str( &more ),
+                ( _, event ) => events.push_back( event ),
+            }
+            drop( events );
+            self.ready.notify_all();
+        }
+    }
+}
+
+/// Iterates over the events of a session in the order they happened, created by
+/// `Altio::events()` or `Altio::try_events()`. Ends after the `Exit` event.
+///
+/// ```rust
+/// use altio::{AltCommand, Altio, IoEvent, echo};
+///
+/// let child = AltCommand::new( |io: Altio, _| {
+///     echo!( io.out(), "working" );
+///     io.progress( 1, 1, "done" );
+///     echo!( io.err(), "warning" );
+///     3
+/// }).record_events().spawn();
+///
+/// let events = child.io().events().collect::<Vec<_>>();
+/// assert!( matches!( &events[..], [
+///     IoEvent::Out( out ), IoEvent::Progress(_), IoEvent::Err( err ), IoEvent::Exit(3),
+/// ] if out == "working\n" && err == "warning\n" ));
+/// ```
+#[cfg( feature = "altio" )]
+pub struct Events<'a> {
+    io       : &'a Altio,
+    blocking : bool,
+    exited   : bool,
+}
+
+#[cfg( feature = "altio" )]
+impl<'a> Events<'a> {
+    pub(crate) fn new( io: &'a Altio, blocking: bool ) -> Self {
+        io.0.0.events.enable();
+        Events{ io, blocking, exited: false }
+    }
+}
+
+#[cfg( feature = "altio" )]
+impl Iterator for Events<'_> {
+    type Item = IoEvent;
+
+    fn next( &mut self ) -> Option<IoEvent> {
+        if self.exited {
+            return None;
+        }
+        let ( log, err ) = ( &self.io.0.0.events, &self.io.0.2 );
+        let event = if self.blocking {
+            wait_for( &log.events, &log.ready, Stream::Out, &err.hook, &*err.clock, None, |events| events.pop_front() )
+        } else {
+            log.events.lock().unwrap_or_else( PoisonError::into_inner ).pop_front()
+        };
+        self.exited = matches!( event, Some( IoEvent::Exit(_) ));
+        event
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::ProgressEvent;
+
+    #[test]
+    fn render_progress() {
+        let event = ProgressEvent{ done: 3, total: 10, message: "indexing".to_owned() };
+        assert_eq!( event.render(), "indexing: 3/10 (30%)\n" );
+        let event = ProgressEvent{ done: 3, total: 0, message: "scanning".to_owned() };
+        assert_eq!( event.render(), "scanning: 3/0\n" );
+    }
+}
@@ -0,0 +1,111 @@
+//! Source of time for timeouts, which can be replaced for advancing time manually
+//! in tests.
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Source of time for timeouts, see `AltioBuilder::clock()`.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now( &self ) -> Instant;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now( &self ) -> Instant { (**self).now() }
+}
+
+/// The clock of the operating system.
+#[derive( Clone, Copy, Debug, Default )]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now( &self ) -> Instant { Instant::now() }
+}
+
+/// A clock which stands still until advanced manually.
+///
+/// Blocking receives with timeouts notice the advanced time within a millisecond.
+#[derive( Debug )]
+pub struct ManualClock {
+    start   : Instant,
+    elapsed : Mutex<Duration>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock{ start: Instant::now(), elapsed: Mutex::default() }
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock standing still at the current time.
+    pub fn new() -> Self { ManualClock::default() }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance( &self, duration: Duration ) {
+        *self.elapsed.lock().unwrap_or_else( PoisonError::into_inner ) += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now( &self ) -> Instant {
+        self.start + *self.elapsed.lock().unwrap_or_else( PoisonError::into_inner )
+    }
+}
+
+/// The clock of a `tokio` runtime, which `tokio::time::pause()` and `advance()` control
+/// in tests. Used by default for an `Altio` built within a runtime. With the "tokio"
+/// feature.
+///
+/// Blocking receives with timeouts never time out while the clock is paused, unless
+/// it is advanced by another thread.
+#[cfg( feature = "tokio" )]
+#[derive( Clone, Debug )]
+pub struct TokioClock( tokio::runtime::Handle );
+
+#[cfg( feature = "tokio" )]
+impl TokioClock {
+    /// Creates the clock of the runtime of `handle`.
+    pub fn new( handle: tokio::runtime::Handle ) -> Self { TokioClock( handle )}
+
+    /// Returns the clock of the runtime the current thread is running in, if any.
+    pub fn current() -> Option<Self> {
+        tokio::runtime::Handle::try_current().ok().map( TokioClock )
+    }
+}
+
+#[cfg( feature = "tokio" )]
+impl Clock for TokioClock {
+    fn now( &self ) -> Instant {
+        let _runtime = self.0.enter();
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock() {
+        let clock = ManualClock::new();
+        let now = clock.now();
+        std::thread::sleep( Duration::from_millis( 2 ));
+        assert_eq!( clock.now(), now );
+        clock.advance( Duration::from_secs( 60 ));
+        assert_eq!( clock.now() - now, Duration::from_secs( 60 ));
+    }
+
+    #[cfg( feature = "tokio" )]
+    #[tokio::test( start_paused = true )]
+    async fn tokio_clock() {
+        let clock = TokioClock::current().unwrap();
+        let now = clock.now();
+        tokio::time::advance( Duration::from_secs( 60 )).await;
+        let later = std::thread::spawn( move || clock.now() ).join().unwrap();
+        assert_eq!( later - now, Duration::from_secs( 60 ));
+    }
+}
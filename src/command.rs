@@ -0,0 +1,400 @@
+//! Running embedded tools on threads, in the style of `std::process::Command`.
+
+use crate::{Altin, Altio, Altout, IoEvent, log};
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt::{self, Debug},
+    io::Result,
+    panic::{AssertUnwindSafe, catch_unwind},
+    thread::JoinHandle,
+};
+
+/// The exit code of a tool which panicked, the same as of a Rust program.
+const PANICKED: i32 = 101;
+
+/// Return values of entry functions which can be converted to exit codes, in the
+/// same way as `std::process::Termination` does for `main()`.
+pub trait IntoExitCode {
+    /// Returns the exit code, zero for success.
+    fn into_exit_code( self ) -> i32;
+}
+
+impl IntoExitCode for () {
+    fn into_exit_code( self ) -> i32 { 0 }
+}
+
+impl IntoExitCode for i32 {
+    fn into_exit_code( self ) -> i32 { self }
+}
+
+impl<T: IntoExitCode, E> IntoExitCode for std::result::Result<T, E> {
+    fn into_exit_code( self ) -> i32 {
+        match self {
+            Ok( value ) => value.into_exit_code(),
+            Err( _ ) => 1,
+        }
+    }
+}
+
+type Entry = Box<dyn FnOnce( Altio, Vec<OsString> ) -> i32 + Send>;
+
+/// A builder for running an embedded tool on a thread with its own `Altio`,
+/// corresponding to `std::process::Command`.
+///
+/// The entry function is called with the `Altio` and the arguments, beginning with
+/// the program name given by `arg0()`. Environment variables are visible to the tool
+/// via `Altio::env()`.
+///
+/// ```rust
+/// use altio::{AltCommand, Altio, echo};
+/// use std::ffi::OsString;
+///
+/// fn run( io: Altio, args: Vec<OsString> ) {
+///     let greeting = io.env().var( "GREETING" ).unwrap();
+///     for name in io.input().lines() {
+///         echo!( io.out(), "{greeting}, {}{}", name.trim_end(), args[1].to_str().unwrap() );
+///     }
+/// }
+///
+/// let child = AltCommand::new( run )
+///     .arg( "!" )
+///     .env( "GREETING", "Hello" )
+///     .stdin_lines([ "Alice", "Bob" ])
+///     .spawn();
+/// assert_eq!( child.io().recv_lines(2), "Hello, Alice!\nHello, Bob!\n" );
+/// ```
+pub struct AltCommand {
+    entry     : Entry,
+    arg0      : OsString,
+    args      : Vec<OsString>,
+    envs      : Vec<( OsString, Option<OsString> )>,
+    env_clear : bool,
+    lines     : Vec<String>,
+    events    : bool,
+}
+
+impl Debug for AltCommand {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        f.debug_struct( "AltCommand" )
+            .field( "arg0", &self.arg0 )
+            .field( "args", &self.args )
+            .field( "envs", &self.envs )
+            .field( "env_clear", &self.env_clear )
+            .field( "lines", &self.lines )
+            .finish_non_exhaustive()
+    }
+}
+
+impl AltCommand {
+    /// Creates a command running `entry`, with the program name "tool", no arguments,
+    /// the environment of the current process and no input.
+    pub fn new<R: IntoExitCode>( entry: impl FnOnce( Altio, Vec<OsString> ) -> R + Send + 'static ) -> Self {
+        AltCommand {
+            entry     : Box::new( move |io, args| entry( io, args ).into_exit_code() ),
+            arg0      : OsString::from( "tool" ),
+            args      : Vec::new(),
+            envs      : Vec::new(),
+            env_clear : false,
+            lines     : Vec::new(),
+            events    : false,
+        }
+    }
+
+    /// Sets the program name, i.e. the first argument passed to the entry function.
+    pub fn arg0( mut self, arg0: impl AsRef<OsStr> ) -> Self {
+        self.arg0 = arg0.as_ref().to_owned();
+        self
+    }
+
+    /// Adds an argument.
+    pub fn arg( mut self, arg: impl AsRef<OsStr> ) -> Self {
+        self.args.push( arg.as_ref().to_owned() );
+        self
+    }
+
+    /// Adds multiple arguments.
+    pub fn args<I,S>( mut self, args: I ) -> Self
+        where I: IntoIterator<Item=S>
+            , S: AsRef<OsStr>
+    {
+        self.args.extend( args.into_iter().map( |arg| arg.as_ref().to_owned() ));
+        self
+    }
+
+    /// Sets an environment variable.
+    pub fn env( mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr> ) -> Self {
+        self.envs.push(( key.as_ref().to_owned(), Some( value.as_ref().to_owned() )));
+        self
+    }
+
+    /// Sets multiple environment variables.
+    pub fn envs<I,K,V>( mut self, vars: I ) -> Self
+        where I: IntoIterator<Item=(K,V)>
+            , K: AsRef<OsStr>
+            , V: AsRef<OsStr>
+    {
+        for ( key, value ) in vars {
+            self = self.env( key, value );
+        }
+        self
+    }
+
+    /// Removes an environment variable.
+    pub fn env_remove( mut self, key: impl AsRef<OsStr> ) -> Self {
+        self.envs.push(( key.as_ref().to_owned(), None ));
+        self
+    }
+
+    /// Clears the environment, including variables inherited from the process.
+    pub fn env_clear( mut self ) -> Self {
+        self.envs.clear();
+        self.env_clear = true;
+        self
+    }
+
+    /// Sends lines to the input stream before the tool starts.
+    pub fn stdin_lines<I,S>( mut self, lines: I ) -> Self
+        where I: IntoIterator<Item=S>
+            , S: Into<String>
+    {
+        self.lines.extend( lines.into_iter().map( Into::into ));
+        self
+    }
+
+    /// Records the events of the session from the beginning, see `Altio::events()`.
+    pub fn record_events( mut self ) -> Self {
+        self.events = true;
+        self
+    }
+
+    /// Runs the tool on a new thread named after the program, returning a handle to it.
+    pub fn spawn( self ) -> AltChild {
+        let io = Altio::default();
+        if self.events {
+            io.record_events();
+        }
+        if self.env_clear {
+            io.env().clear();
+        }
+        for ( key, value ) in &self.envs {
+            match value {
+                Some( value ) => io.env().set_var( key, value ),
+                None => io.env().remove_var( key ),
+            }
+        }
+        for line in &self.lines {
+            io.send_line( line );
+        }
+
+        let mut args = Vec::with_capacity( self.args.len() + 1 );
+        args.push( self.arg0.clone() );
+        args.extend( self.args );
+
+        let entry = self.entry;
+        let tool_io = io.clone();
+        let handle = std::thread::Builder::new()
+            .name( self.arg0.to_string_lossy().into_owned() )
+            .spawn( move || {
+                let code = catch_unwind( AssertUnwindSafe( || entry( tool_io.clone(), args ))).unwrap_or( PANICKED );
+                for altout in [ &tool_io.0.1, &tool_io.0.2 ] {
+                    altout.lock().output().flush();
+                    altout.close();
+                }
+                tool_io.0.0.events.record( || IoEvent::Exit( code ));
+                tool_io.0.0.mirror.log( log::Event::Exit( code ));
+                code
+            })
+            .expect( "altio failed to spawn a thread" );
+
+        AltChild{ io, handle: Some( handle ), status: None }
+    }
+}
+
+/// Everything collected from a finished tool, corresponding to
+/// `std::process::Output`.
+#[derive( Clone, Debug, Default, PartialEq, Eq )]
+pub struct AltOutput {
+    /// The exit code of the tool, 101 if it panicked.
+    pub status : i32,
+    /// The text written into the output stream and not received yet.
+    pub stdout : String,
+    /// The text written into the error stream and not received yet.
+    pub stderr : String,
+}
+
+/// A handle to an embedded tool running on a thread, corresponding to
+/// `std::process::Child`.
+#[derive( Debug )]
+pub struct AltChild {
+    io     : Altio,
+    handle : Option<JoinHandle<i32>>,
+    status : Option<i32>,
+}
+
+impl AltChild {
+    /// Returns the `Altio` shared with the tool, for sending and receiving.
+    pub fn io( &self ) -> &Altio { &self.io }
+
+    /// Returns the input stream of the tool, for sending.
+    pub fn stdin( &self ) -> &Altin { &self.io.0.0 }
+
+    /// Returns the output stream of the tool, for receiving.
+    pub fn stdout( &self ) -> &Altout { &self.io.0.1 }
+
+    /// Returns the error stream of the tool, for receiving.
+    pub fn stderr( &self ) -> &Altout { &self.io.0.2 }
+
+    /// Returns true if the tool has returned or panicked.
+    pub fn is_finished( &self ) -> bool {
+        self.handle.as_ref().is_none_or( JoinHandle::is_finished )
+    }
+
+    /// Asks the tool to stop: from now on its reads and fallible writes fail with
+    /// `ErrorKind::BrokenPipe`, and blocked reads wake up.
+    ///
+    /// Threads cannot be killed, so a tool ignoring these errors keeps running.
+    pub fn kill( &mut self ) -> Result<()> {
+        self.io.kill();
+        Ok(())
+    }
+
+    /// Waits for the tool to finish, returning its exit code.
+    pub fn wait( &mut self ) -> Result<i32> {
+        if let Some( handle ) = self.handle.take() {
+            self.status = Some( handle.join().unwrap_or( PANICKED ));
+        }
+        Ok( self.status.unwrap_or( PANICKED ))
+    }
+
+    /// Returns the exit code if the tool has finished, without blocking.
+    pub fn try_wait( &mut self ) -> Result<Option<i32>> {
+        if self.is_finished() {
+            self.wait().map( Some )
+        } else {
+            Ok( None )
+        }
+    }
+
+    /// Waits for the tool to finish, collecting its exit code and all the text not
+    /// received from its output and error streams.
+    pub fn wait_with_output( mut self ) -> Result<AltOutput> {
+        let status = self.wait()?;
+        Ok( AltOutput {
+            status,
+            stdout : self.io.try_recv().unwrap_or_default(),
+            stderr : self.io.try_recv_err().unwrap_or_default(),
+        })
+    }
+}
+
+/// Runs `entry` to completion on the current thread, with the program name "tool"
+/// followed by `args`, and `input` sent into the closed input stream, returning the
+/// exit code and everything written into output and error streams.
+///
+/// Suitable for non-interactive tools. Reads beyond `input` hit end-of-file.
+///
+/// ```rust
+/// use altio::{Altio, echo, run_to_completion};
+///
+/// let output = run_to_completion( |io: Altio, args| {
+///     let mut text = String::new();
+///     io.input().read_to_string( &mut text )?;
+///     echo!( io.out(), "{} {}", args.len(), text.lines().count() );
+///     Ok::<_,std::io::Error>(())
+/// }, [ "-c" ], "a\nb\nc" );
+/// assert_eq!( output.stdout, "2 3\n" );
+/// assert_eq!( output.status, 0 );
+/// ```
+pub fn run_to_completion<R,I,S>( entry: impl FnOnce( Altio, Vec<OsString> ) -> R, args: I, input: &str )
+    -> AltOutput
+    where R: IntoExitCode
+        , I: IntoIterator<Item=S>
+        , S: AsRef<OsStr>
+{
+    let io = Altio::default();
+    io.send( input );
+    io.close_input();
+
+    let mut argv = vec![ OsString::from( "tool" )];
+    argv.extend( args.into_iter().map( |arg| arg.as_ref().to_owned() ));
+
+    let tool_io = io.clone();
+    let status = catch_unwind( AssertUnwindSafe( move || entry( tool_io, argv ).into_exit_code() ))
+        .unwrap_or( PANICKED );
+    io.0.1.lock().output().flush();
+    io.0.2.lock().output().flush();
+
+    AltOutput {
+        status,
+        stdout : io.try_recv().unwrap_or_default(),
+        stderr : io.try_recv_err().unwrap_or_default(),
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{AltCommand, AltOutput, run_to_completion};
+    use crate::{Altio, IoEvent, echo};
+    use std::{ffi::OsString, io::ErrorKind};
+
+    #[test]
+    fn wait_with_output() {
+        let output = AltCommand::new( |io: Altio, _| {
+            echo!( io.out(), "out" );
+            echo!( io.err(), "err" );
+            7
+        }).spawn().wait_with_output().unwrap();
+        assert_eq!( output, AltOutput{ status: 7, stdout: "out\n".to_owned(), stderr: "err\n".to_owned() });
+
+        let mut child = AltCommand::new( |_: Altio, _: Vec<OsString>| -> i32 { panic!( "oops" )}).spawn();
+        assert_eq!( child.wait().unwrap(), 101 );
+        assert_eq!( child.try_wait().unwrap(), Some( 101 ));
+    }
+
+    #[test]
+    fn run_to_completion_until_eof() {
+        let output = run_to_completion( |io: Altio, _| {
+            let mut buf = String::new();
+            while io.input().read_line( &mut buf ).unwrap() != 0 {
+                echo!( io.out(), "{:?}", buf );
+                buf.clear();
+            }
+            echo!( io.err(), "eof" );
+            3
+        }, [""; 0], "a\nb" );
+        assert_eq!( output, AltOutput{ status: 3, stdout: "\"a\\n\"\n\"b\"\n".to_owned(), stderr: "eof\n".to_owned() });
+    }
+
+    #[test]
+    fn panicked() {
+        let mut child = AltCommand::new( |io: Altio, _| -> i32 {
+            echo!( -n, io.out(), "partial" );
+            panic!( "the tool panics" );
+        }).record_events().spawn();
+        assert_eq!( child.wait().unwrap(), 101 );
+        assert_eq!( child.io().recv(), "partial" );
+        assert_eq!( child.io().events().last(), Some( IoEvent::Exit( 101 )));
+    }
+
+    #[test]
+    fn kill() {
+        let mut child = AltCommand::new( |io: Altio, _| {
+            let mut buf = String::new();
+            loop {
+                if let Err( err ) = io.input().read_line( &mut buf ) {
+                    assert_eq!( err.kind(), ErrorKind::BrokenPipe );
+                    return 1;
+                }
+                echo!( -n, io.out(), "{buf}" );
+                buf.clear();
+            }
+        }).spawn();
+
+        child.stdin().send_line( "a" );
+        assert_eq!( child.stdout().recv_line(), "a\n" );
+        assert_eq!( child.try_wait().unwrap(), None );
+        child.kill().unwrap();
+        assert_eq!( child.wait().unwrap(), 1 );
+    }
+}
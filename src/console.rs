@@ -0,0 +1,90 @@
+//! Emulation of the Windows console, so that tools deployed on Windows can be tested
+//! on other platforms.
+
+use std::borrow::Cow;
+
+/// How text crosses the console between a tool and its user.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub enum Console {
+    /// Unchanged, like a Unix terminal.
+    #[default]
+    Unix,
+    /// Newlines are written and read as `"\r\n"`, like a Windows console.
+    Windows,
+    /// Like `Windows`, and the text sent into the input stream is round-tripped
+    /// through the OEM codepage 437, chars not in it being read as `'?'`.
+    WindowsOem,
+}
+
+/// The chars of the codepage 437 from 0x80 to 0xFF.
+const CP437: &str = "\
+    ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒ\
+    áíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐\
+    └┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀\
+    αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}";
+
+/// Translates the text crossing a console, remembering whether the last byte was a
+/// carriage return, so that a `"\r\n"` split between two writes is kept as it is.
+#[derive( Debug, Default )]
+pub(crate) struct Translator {
+    pub(crate) console : Console,
+    cr                 : bool,
+}
+
+impl Translator {
+    /// Translates the bytes written by the tool.
+    pub(crate) fn write<'a>( &mut self, bytes: &'a [u8] ) -> Cow<'a, [u8]> {
+        if self.console == Console::Unix || bytes.is_empty() {
+            return Cow::Borrowed( bytes );
+        }
+        let mut translated = Vec::with_capacity( bytes.len() );
+        for &byte in bytes {
+            if byte == b'\n' && !self.cr {
+                translated.push( b'\r' );
+            }
+            translated.push( byte );
+            self.cr = byte == b'\r';
+        }
+        Cow::Owned( translated )
+    }
+
+    /// Translates the text sent to the tool.
+    pub(crate) fn send<'a>( &mut self, text: &'a str ) -> Cow<'a, str> {
+        let text = match self.console {
+            Console::Unix => return Cow::Borrowed( text ),
+            Console::Windows => Cow::Borrowed( text ),
+            Console::WindowsOem => text.chars()
+                .map( |ch| if ch.is_ascii() || CP437.contains( ch ) { ch } else { '?' })
+                .collect(),
+        };
+        let translated = match self.write( text.as_bytes() ) {
+            Cow::Owned( bytes ) => Some( bytes ),
+            Cow::Borrowed(_) => None,
+        };
+        match translated {
+            Some( bytes ) => Cow::Owned( String::from_utf8( bytes ).expect( "inserting ASCII into UTF-8" )),
+            None => text,
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codepage() {
+        assert_eq!( CP437.chars().count(), 128 );
+    }
+
+    #[test]
+    fn translate() {
+        let mut translator = Translator{ console: Console::Windows, cr: false };
+        assert_eq!( translator.write( b"a\nb\r\nc\r" ), &b"a\r\nb\r\nc\r"[..] );
+        assert_eq!( translator.write( b"\nd\n" ), &b"\nd\r\n"[..] );
+        assert_eq!( translator.send( "\u{4e2d}\n" ), "\u{4e2d}\r\n" );
+
+        let mut translator = Translator{ console: Console::WindowsOem, cr: false };
+        assert_eq!( translator.send( "café \u{4e2d}½\n" ), "café ?½\r\n" );
+    }
+}
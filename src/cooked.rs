@@ -0,0 +1,136 @@
+//! Control characters in the input stream: canonical line editing like a terminal in
+//! cooked mode, and the delivery of the ones left in read lines, as well as the
+//! delivery of invalid UTF-8.
+
+use crate::into_string;
+
+use std::io::{Error, ErrorKind, Result};
+
+/// Erases the previous char.
+const BACKSPACE: char = '\x08';
+/// Erases the previous char, sent by the backspace key of most terminals.
+const DELETE: char = '\x7f';
+/// Erases the whole line, i.e. Ctrl-U.
+const KILL: char = '\x15';
+/// Erases the previous word, i.e. Ctrl-W.
+const WERASE: char = '\x17';
+
+/// The line being edited, invisible to reads until a newline is sent.
+#[derive( Debug, Default )]
+pub(crate) struct LineEditor {
+    line: String,
+}
+
+impl LineEditor {
+    /// Edits the line with `text`, returning the lines completed by newlines.
+    pub(crate) fn feed( &mut self, text: &str ) -> String {
+        let mut completed = String::new();
+        for ch in text.chars() {
+            match ch {
+                BACKSPACE | DELETE => { self.line.pop(); }
+                KILL => self.line.clear(),
+                WERASE => {
+                    let word = self.line.trim_end().trim_end_matches( |ch: char| !ch.is_whitespace() );
+                    self.line.truncate( word.len() );
+                }
+                '\n' => {
+                    completed.push_str( &self.line );
+                    completed.push( '\n' );
+                    self.line.clear();
+                }
+                _ => self.line.push( ch ),
+            }
+        }
+        completed
+    }
+
+    /// Returns the incomplete line, e.g. on end-of-file.
+    pub(crate) fn finish( &mut self ) -> String {
+        std::mem::take( &mut self.line )
+    }
+}
+
+/// How `read_line()` delivers control characters other than tab, carriage return and
+/// newline, e.g. garbage sent by `Altio::send_raw()`.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub enum ControlChars {
+    /// Delivers them as they are, like reading from a pipe.
+    #[default]
+    Verbatim,
+    /// Removes them from the line.
+    Strip,
+    /// Fails the read with `ErrorKind::InvalidData`, the line being consumed.
+    Error,
+}
+
+impl ControlChars {
+    /// Applies this policy to a line being read.
+    pub(crate) fn apply( self, line: String ) -> Result<String> {
+        let is_raw = |ch: char| ch.is_control() && !matches!( ch, '\t' | '\r' | '\n' );
+        match self {
+            ControlChars::Verbatim => Ok( line ),
+            ControlChars::Strip => Ok( line.chars().filter( |&ch| !is_raw( ch )).collect() ),
+            ControlChars::Error => match line.find( is_raw ) {
+                None => Ok( line ),
+                Some( offset ) => Err( Error::new( ErrorKind::InvalidData,
+                    format!( "control character {:?} in input line {line:?}", &line[ offset.. ].chars().next().unwrap() ))),
+            },
+        }
+    }
+}
+
+/// How the string-level reads, e.g. `read_line()` and `read_to_string()`, deliver
+/// input which is not valid UTF-8, e.g. sent by `Altio::send_invalid_utf8()`.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub enum InvalidUtf8 {
+    /// Fails the read with `ErrorKind::InvalidData`, the input being consumed and
+    /// nothing appended, like `std::io::Stdin`.
+    #[default]
+    Error,
+    /// Replaces invalid sequences with `U+FFFD`, like `String::from_utf8_lossy()`.
+    Lossy,
+}
+
+impl InvalidUtf8 {
+    /// Applies this policy to the bytes being read as text.
+    pub(crate) fn decode( self, bytes: Vec<u8> ) -> Result<String> {
+        match self {
+            InvalidUtf8::Error => String::from_utf8( bytes )
+                .map_err( |_| Error::new( ErrorKind::InvalidData, "stream did not contain valid UTF-8" )),
+            InvalidUtf8::Lossy => Ok( into_string( bytes )),
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{ControlChars, InvalidUtf8, LineEditor};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn edit() {
+        let mut editor = LineEditor::default();
+        assert_eq!( editor.feed( "helo\x08\x08llo\nwor" ), "hello\n" );
+        assert_eq!( editor.feed( "ld\x7f\x7fk\n" ), "work\n" );
+        assert_eq!( editor.feed( "rm -rf /\x15ls -l\x17-a\n" ), "ls -a\n" );
+        assert_eq!( editor.feed( "\x08\x08ab" ), "" );
+        assert_eq!( editor.finish(), "ab" );
+    }
+
+    #[test]
+    fn control_chars() {
+        let line = || "a\x1b[0m\tb\x00\r\n".to_owned();
+        assert_eq!( ControlChars::Verbatim.apply( line() ).unwrap(), line() );
+        assert_eq!( ControlChars::Strip.apply( line() ).unwrap(), "a[0m\tb\r\n" );
+        assert_eq!( ControlChars::Error.apply( line() ).unwrap_err().kind(), ErrorKind::InvalidData );
+        assert_eq!( ControlChars::Error.apply( "a\tb\r\n".to_owned() ).unwrap(), "a\tb\r\n" );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let bytes = || b"a\xffb\n".to_vec();
+        assert_eq!( InvalidUtf8::Error.decode( bytes() ).unwrap_err().kind(), ErrorKind::InvalidData );
+        assert_eq!( InvalidUtf8::Lossy.decode( bytes() ).unwrap(), "a\u{fffd}b\n" );
+        assert_eq!( InvalidUtf8::Error.decode( "\u{4e2d}".into() ).unwrap(), "\u{4e2d}" );
+    }
+}
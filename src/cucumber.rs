@@ -0,0 +1,181 @@
+//! Reusable `cucumber` steps for interactive sessions, with the "cucumber" feature.
+//!
+//! Tools are registered by name with `register_tool()`, then the steps of `AltWorld`
+//! drive them:
+//!
+//! - `Given I run "<command line>"`, starting a registered tool.
+//! - `When I send "<line>"`.
+//! - `When I close the input`.
+//! - `Then I should see "<text>"`, waiting at most 5 seconds.
+//! - `Then I should see "<text>" within <secs> seconds`.
+//! - `Then I should see "<text>" on stderr`.
+//! - `Then the tool should exit with code <code>`.
+//!
+//! The "should see" steps consume the received text up to the end of the match,
+//! so the following steps only see text received later.
+//!
+//! ```rust,no_run
+//! use altio::{Altio, cucumber::{AltWorld, register_tool}};
+//! use cucumber::World;
+//! use std::ffi::OsString;
+//!
+//! fn the_tool( io: Altio, args: Vec<OsString> ) -> i32 { 0 }
+//!
+//! # async fn run() {
+//! register_tool( "the-tool", the_tool );
+//! AltWorld::run( "tests/features" ).await;
+//! # }
+//! ```
+
+use crate::{AltChild, AltCommand, Altio, IntoExitCode};
+
+use ::cucumber::{World, given, then, when};
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+type Entry = Arc<dyn Fn( Altio, Vec<OsString> ) -> i32 + Send + Sync>;
+
+/// The default timeout of "should see" steps.
+const TIMEOUT: Duration = Duration::from_secs( 5 );
+
+static TOOLS: Mutex<BTreeMap<String, Entry>> = Mutex::new( BTreeMap::new() );
+
+/// Makes `entry` runnable by the step `Given I run "<name> <args>"`.
+pub fn register_tool<R: IntoExitCode>( name: &str, entry: impl Fn( Altio, Vec<OsString> ) -> R + Send + Sync + 'static ) {
+    TOOLS.lock().unwrap_or_else( PoisonError::into_inner ).insert( name.to_owned(), Arc::new( move |io, args| entry( io, args ).into_exit_code() ));
+}
+
+/// A `cucumber` world running one embedded tool per scenario.
+#[derive( Debug, Default, World )]
+pub struct AltWorld {
+    child  : Option<AltChild>,
+    stdout : String,
+    stderr : String,
+}
+
+impl AltWorld {
+    /// Returns the running tool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no tool has been run in this scenario.
+    pub fn child( &mut self ) -> &mut AltChild {
+        self.child.as_mut().expect( "no tool is running, missing `Given I run \"...\"`" )
+    }
+
+    /// Waits until `text` is received from the output or error stream, consuming the
+    /// received text up to the end of it.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the received text if `text` is not received within `timeout`.
+    #[track_caller]
+    pub fn expect( &mut self, text: &str, timeout: Duration, err: bool ) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let seen = if err { &mut self.stderr } else { &mut self.stdout };
+            if let Some( offset ) = seen.find( text ) {
+                seen.drain( ..offset + text.len() );
+                return;
+            }
+            let timeout = deadline.saturating_duration_since( Instant::now() );
+            let io = self.child().io();
+            let received = if err { io.recv_err_timeout( timeout )} else { io.recv_timeout( timeout )};
+            match received {
+                Some( received ) => if err { self.stderr += &received } else { self.stdout += &received },
+                None => panic!( "{text:?} not seen within {timeout:?}, received: {:?}",
+                    if err { &self.stderr } else { &self.stdout }),
+            }
+        }
+    }
+}
+
+#[given( expr = "I run {string}" )]
+fn run( world: &mut AltWorld, command: String ) {
+    let mut args = command.split_whitespace();
+    let name = args.next().expect( "empty command" );
+    let entry = TOOLS.lock().unwrap_or_else( PoisonError::into_inner )
+        .get( name )
+        .unwrap_or_else( || panic!( "tool `{name}` is not registered" ))
+        .clone();
+    world.child = Some( AltCommand::new( move |io, args| entry( io, args ))
+        .arg0( name )
+        .args( args )
+        .spawn() );
+}
+
+#[when( expr = "I send {string}" )]
+fn send( world: &mut AltWorld, line: String ) {
+    world.child().io().send_line( &line );
+}
+
+#[when( "I close the input" )]
+fn close_input( world: &mut AltWorld ) {
+    world.child().io().close_input();
+}
+
+#[then( expr = "I should see {string}" )]
+fn should_see( world: &mut AltWorld, text: String ) {
+    world.expect( &text, TIMEOUT, false );
+}
+
+#[then( expr = "I should see {string} within {float} seconds" )]
+fn should_see_within( world: &mut AltWorld, text: String, secs: f64 ) {
+    world.expect( &text, Duration::from_secs_f64( secs ), false );
+}
+
+#[then( expr = "I should see {string} on stderr" )]
+fn should_see_on_stderr( world: &mut AltWorld, text: String ) {
+    world.expect( &text, TIMEOUT, true );
+}
+
+#[then( expr = "the tool should exit with code {int}" )]
+fn should_exit_with( world: &mut AltWorld, code: i32 ) {
+    assert_eq!( world.child().wait().unwrap(), code );
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{AltWorld, register_tool};
+    use crate::{Altio, echo};
+    use ::cucumber::{World, cli::{Empty, Opts}, writer::Stats};
+
+    #[test]
+    fn steps() {
+        register_tool( "greet", |io: Altio, args: Vec<std::ffi::OsString>| {
+            echo!( io.err(), "started with {} args", args.len() - 1 );
+            let mut name = String::new();
+            while io.input().read_line( &mut name ).unwrap() != 0 {
+                echo!( io.out(), "Hello, {}!", name.trim_end() );
+                name.clear();
+            }
+            3
+        });
+
+        let dir = std::env::temp_dir().join( format!( "altio-cucumber-{}", std::process::id() ));
+        std::fs::create_dir_all( &dir ).unwrap();
+        std::fs::write( dir.join( "greet.feature" ), r#"
+Feature: Greeting
+  Scenario: Greet people
+    Given I run "greet -v"
+    Then I should see "started with 1 args" on stderr
+    When I send "Alice"
+    Then I should see "Hello, Alice!"
+    When I send "Bob"
+    And I close the input
+    Then I should see "Hello, Bob!" within 1 seconds
+    And the tool should exit with code 3
+"# ).unwrap();
+
+        // Arguments of the test binary are not for cucumber.
+        let cucumber = AltWorld::cucumber().with_cli( Opts::<_, _, _, Empty>::default() ).fail_on_skipped();
+        let writer = futures::executor::block_on( cucumber.run( &dir ));
+        std::fs::remove_dir_all( &dir ).unwrap();
+        assert!( !writer.execution_has_failed() );
+    }
+}
@@ -0,0 +1,126 @@
+//! Readers over the retained output history, each at its own pace.
+
+use crate::{Altio, Stream, history::History, into_string, wait_for};
+
+use std::time::Duration;
+
+/// Reads an output stream from its retained history without draining it, so that
+/// several readers, e.g. a logger, a UI and the expect engine, can read the same
+/// stream at their own pace, independent of each other and of `recv()`.
+///
+/// Created by `Altio::cursor()` or `Altio::cursor_err()`, starting at the beginning of
+/// the history.
+///
+/// ```rust
+/// use altio::{Altio, echo};
+///
+/// let io = Altio::default();
+/// let mut logger = io.cursor();
+/// let mut ui = io.cursor();
+/// echo!( io.out(), "one\ntwo" );
+/// assert_eq!( logger.read_line(), "one\n" );
+/// assert_eq!( ui.try_read().as_deref(), Some( "one\ntwo\n" ));
+/// assert_eq!( logger.read_line(), "two\n" );
+/// assert_eq!( io.recv(), "one\ntwo\n" );
+/// ```
+#[derive( Clone, Debug )]
+pub struct OutputCursor {
+    io     : Altio,
+    stream : Stream,
+    pos    : usize,
+}
+
+impl OutputCursor {
+    pub(crate) fn new( io: Altio, stream: Stream ) -> Self {
+        io.altout( stream ).keep_history();
+        OutputCursor{ io, stream, pos: 0 }
+    }
+
+    /// Returns the amount of bytes read by this cursor, including those dropped from
+    /// the history by its retention before being read.
+    pub fn position( &self ) -> usize { self.pos }
+
+    /// Reads all the text written since the last read, without blocking.
+    pub fn try_read( &mut self ) -> Option<String> {
+        self.wait_until( Some( Duration::ZERO ), false )
+    }
+
+    /// Reads all the text written since the last read, blocking until some is written.
+    pub fn read( &mut self ) -> String {
+        self.wait_until( None, false ).expect( "waiting without deadline" )
+    }
+
+    /// Reads one line of text, without blocking.
+    pub fn try_read_line( &mut self ) -> Option<String> {
+        self.wait_until( Some( Duration::ZERO ), true )
+    }
+
+    /// Reads one line of text, blocking until it is written.
+    pub fn read_line( &mut self ) -> String {
+        self.wait_until( None, true ).expect( "waiting without deadline" )
+    }
+
+    /// Reads one line of text, blocking at most `timeout` measured by the clock of the
+    /// `Altio`.
+    pub fn read_line_timeout( &mut self, timeout: Duration ) -> Option<String> {
+        self.wait_until( Some( timeout ), true )
+    }
+
+    fn wait_until( &mut self, timeout: Option<Duration>, line: bool ) -> Option<String> {
+        let io = self.io.clone();
+        let altout = io.altout( self.stream );
+        let deadline = timeout.map( |timeout| altout.clock.now() + timeout );
+        wait_for( &altout.output, &altout.ready, self.stream, &altout.hook, &*altout.clock, deadline, |output| {
+            output.history.as_ref().and_then( |history| self.take( history, line ))
+        })
+    }
+
+    /// Takes the next line, or all complete chars if not `line`, from `history`.
+    fn take( &mut self, history: &History, line: bool ) -> Option<String> {
+        self.pos = self.pos.max( history.truncated() );
+        let bytes = history.bytes_from( self.pos );
+        let len = if line {
+            bytes.iter().position( |&byte| byte == b'\n' )? + 1
+        } else {
+            match std::str::from_utf8( &bytes ) {
+                Err( err ) if err.error_len().is_none() => err.valid_up_to(),
+                _ => bytes.len(),
+            }
+        };
+        if len == 0 {
+            return None;
+        }
+        self.pos += len;
+        Some( into_string( bytes[ ..len ].to_vec() ))
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use crate::{Altio, echo};
+    use std::io::Write;
+
+    #[test]
+    fn independent_cursors() {
+        let io = Altio::default();
+        echo!( -n, io.err(), "lost" );
+        let mut first = io.cursor_err();
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                io.err().write_all( b"\xe4\xb8" ).unwrap();
+                std::thread::sleep( std::time::Duration::from_millis( 10 ));
+                io.err().write_all( b"\xad\n" ).unwrap();
+            })
+        };
+        assert_eq!( first.read_line(), "\u{4e2d}\n" );
+        tool.join().unwrap();
+
+        let mut second = io.cursor_err();
+        assert_eq!( second.try_read().as_deref(), Some( "\u{4e2d}\n" ));
+        assert_eq!( first.try_read_line(), None );
+        assert_eq!( first.position(), 4 );
+        assert_eq!( io.recv_err(), "lost\u{4e2d}\n" );
+        assert_eq!( second.try_read(), None );
+    }
+}
@@ -0,0 +1,126 @@
+//! Line diffs of the expected and received text in assertion failures, in the style
+//! of `diff -u`, with carriage returns and trailing whitespace made visible.
+
+/// The amount of unchanged lines shown around the changed ones.
+const CONTEXT: usize = 3;
+
+/// The most cells of the table of common subsequences computed for the lines which
+/// differ, about 32 MiB, above which they are listed as all removed then all added.
+const MAX_CELLS: usize = 1 << 22;
+
+/// Returns the diff of `expected` and `actual`, colored with ANSI escape codes if
+/// `colored`: removed lines red and added lines green.
+pub(crate) fn unified( expected: &str, actual: &str, colored: bool ) -> String {
+    let old = expected.split_inclusive( '\n' ).collect::<Vec<_>>();
+    let new = actual.split_inclusive( '\n' ).collect::<Vec<_>>();
+    let prefix = old.iter().zip( &new ).take_while( |( a, b )| a == b ).count();
+    let suffix = old[ prefix.. ].iter().rev().zip( new[ prefix.. ].iter().rev() ).take_while( |( a, b )| a == b ).count();
+
+    let mut ops = Vec::with_capacity( old.len() + new.len() );
+    ops.extend( old[ ..prefix ].iter().map( |&line| ( ' ', line )));
+    changes( &old[ prefix..old.len()-suffix ], &new[ prefix..new.len()-suffix ], &mut ops );
+    ops.extend( old[ old.len()-suffix.. ].iter().map( |&line| ( ' ', line )));
+
+    let mut near_change = vec![ false; ops.len() ];
+    for ( k, _ ) in ops.iter().enumerate().filter( |( _, &( tag, _ ))| tag != ' ' ) {
+        near_change[ k.saturating_sub( CONTEXT )..( k + CONTEXT + 1 ).min( ops.len() )].fill( true );
+    }
+
+    let mut diff = String::from( "--- expected\n+++ received\n" );
+    let mut skipped = false;
+    for ( k, &( tag, line )) in ops.iter().enumerate() {
+        if !near_change[k] {
+            if !skipped {
+                diff.push_str( "...\n" );
+                skipped = true;
+            }
+            continue;
+        }
+        skipped = false;
+        let color = match tag {
+            '-' if colored => "\x1b[31m",
+            '+' if colored => "\x1b[32m",
+            _ => "",
+        };
+        diff.push_str( color );
+        diff.push( tag );
+        visible( line, &mut diff );
+        if !color.is_empty() {
+            diff.push_str( "\x1b[0m" );
+        }
+        diff.push( '\n' );
+        if !line.ends_with( '\n' ) {
+            diff.push_str( "\\ No newline at end of text\n" );
+        }
+    }
+    diff
+}
+
+/// Appends the lines of the diff of `old` and `new` to `ops`, each tagged with ` `, `-`
+/// or `+`, following a longest common subsequence unless the table of them would be
+/// too large.
+fn changes<'a>( old: &[&'a str], new: &[&'a str], ops: &mut Vec<( char, &'a str )> ) {
+    if ( old.len()+1 ).saturating_mul( new.len()+1 ) > MAX_CELLS {
+        ops.extend( old.iter().map( |&line| ( '-', line )));
+        ops.extend( new.iter().map( |&line| ( '+', line )));
+        return;
+    }
+
+    // the lengths of the longest common subsequences of `old[i..]` and `new[j..]`
+    let mut lcs = vec![ vec![ 0_usize; new.len()+1 ]; old.len()+1 ];
+    for i in ( 0..old.len() ).rev() {
+        for j in ( 0..new.len() ).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i+1][j+1] + 1 } else { lcs[i+1][j].max( lcs[i][j+1] )};
+        }
+    }
+
+    let ( mut i, mut j ) = ( 0, 0 );
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(( ' ', old[i] ));
+            ( i, j ) = ( i+1, j+1 );
+        } else if i < old.len() && ( j == new.len() || lcs[i+1][j] >= lcs[i][j+1] ) {
+            ops.push(( '-', old[i] ));
+            i += 1;
+        } else {
+            ops.push(( '+', new[j] ));
+            j += 1;
+        }
+    }
+}
+
+/// Appends `line` without its newline, showing carriage returns as `␍`, and trailing
+/// spaces and tabs as `·` and `→`.
+fn visible( line: &str, diff: &mut String ) {
+    let text = line.strip_suffix( '\n' ).unwrap_or( line );
+    let trimmed = text.trim_end_matches( [' ', '\t', '\r'] );
+    diff.push_str( &trimmed.replace( '\r', "␍" ));
+    diff.extend( text[ trimmed.len().. ].chars().map( |ch| match ch {
+        ' '  => '·',
+        '\t' => '→',
+        _    => '␍',
+    }));
+}
+
+#[cfg( test )]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn diff() {
+        assert_eq!( unified( "a\nb\nc\n", "a\nB \nc\t \r\nd", false ),
+            "--- expected\n+++ received\n a\n-b\n-c\n+B·\n+c→·␍\n+d\n\\ No newline at end of text\n" );
+        assert_eq!( unified( "same\n", "same\n", false ), "--- expected\n+++ received\n...\n" );
+
+        let lines = ( 0..10 ).map( |n| format!( "{n}\n" )).collect::<String>();
+        assert_eq!( unified( &lines, &lines.replace( "5", "five" ), true ),
+            "--- expected\n+++ received\n...\n 2\n 3\n 4\n\x1b[31m-5\x1b[0m\n\x1b[32m+five\x1b[0m\n 6\n 7\n 8\n...\n" );
+
+        let old = ( 0..3000 ).map( |n| format!( "{n}\n" )).collect::<String>();
+        let new = format!( "first\n{}last\n", ( 0..3000 ).map( |n| format!( "{}\n", n+1 )).collect::<String>() );
+        let diff = unified( &old, &new, false );
+        assert!( diff.starts_with( "--- expected\n+++ received\n-0\n-1\n" ));
+        assert!( diff.contains( "\n-2999\n+first\n+1\n" ) && diff.ends_with( "\n+3000\n+last\n" ));
+        assert_eq!( diff.lines().count(), 2 + 3000 + 3002 );
+    }
+}
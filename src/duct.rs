@@ -0,0 +1,124 @@
+//! Composing embedded tools with external commands in `duct` pipelines, with the
+//! "duct" feature.
+//!
+//! An embedded tool takes part in a pipeline through its `Altio`: the output it
+//! writes can be the stdin of an expression, and the stdout of an expression can be
+//! the input it reads, e.g. "embedded formatter | external grep | embedded pager":
+//!
+//! ```rust
+//! # #[cfg( unix )] {
+//! use altio::{AltCommand, Altio, duct::AltExpression, echo};
+//!
+//! let formatter = AltCommand::new( |io: Altio, _| {
+//!     for word in [ "apple", "banana", "avocado" ] {
+//!         echo!( io.out(), "{word}" );
+//!     }
+//! }).spawn();
+//!
+//! let pager = AltCommand::new( |io: Altio, _| {
+//!     for no in 1.. {
+//!         let mut line = String::new();
+//!         if io.input().read_line( &mut line )? == 0 {
+//!             break;
+//!         }
+//!         echo!( -n, io.out(), "{no}: {line}" );
+//!     }
+//!     Ok::<_,std::io::Error>(())
+//! }).spawn();
+//!
+//! duct::cmd!( "grep", "^a" )
+//!     .stdin_from_altio( formatter.io() ).unwrap()
+//!     .stdout_to_altio( pager.io() ).unwrap()
+//!     .run().unwrap();
+//!
+//! assert_eq!( pager.wait_with_output().unwrap().stdout, "1: apple\n2: avocado\n" );
+//! # }
+//! ```
+
+use crate::{Altio, Stream, backend};
+
+use ::duct::Expression;
+
+use std::{
+    io::{Result, Write},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+/// How long the thread feeding the stdin of an expression waits for output before
+/// checking whether the `Altio` is still alive, which it doesn't keep alive.
+const WAIT: Duration = Duration::from_millis( 100 );
+
+/// Connects `duct` expressions to the streams of embedded tools.
+pub trait AltExpression: Sized {
+    /// Feeds the bytes written into the output stream of `io` to the stdin of the
+    /// expression, until the tool finishes, i.e. the stream is closed, or the last
+    /// handle of `io` is dropped.
+    fn stdin_from_altio( &self, io: &Altio ) -> Result<Self>;
+
+    /// Sends the stdout of the expression into the input stream of `io`, closing it at
+    /// the end. The end is reached after the expression has run and been dropped.
+    fn stdout_to_altio( &self, io: &Altio ) -> Result<Self>;
+}
+
+impl AltExpression for Expression {
+    fn stdin_from_altio( &self, io: &Altio ) -> Result<Self> {
+        let ( reader, mut writer ) = os_pipe::pipe()?;
+        let io = Arc::downgrade( &io.0 );
+        thread::spawn( move || loop {
+            let Some( io ) = io.upgrade() else { return };
+            let received = io.1.recv_bytes_for( WAIT );
+            drop( io );
+            match received {
+                Some( bytes ) if bytes.is_empty() || writer.write_all( &bytes ).is_err() => return,
+                _ => (),
+            }
+        });
+        Ok( self.stdin_file( reader ))
+    }
+
+    fn stdout_to_altio( &self, io: &Altio ) -> Result<Self> {
+        let ( reader, writer ) = os_pipe::pipe()?;
+        backend::pump( io, Stream::In, Box::new( reader ));
+        Ok( self.stdout_file( writer ))
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::{AltCommand, echo};
+
+    #[cfg( unix )]
+    #[test]
+    fn pipeline() {
+        let producer = AltCommand::new( |io: Altio, _| {
+            echo!( io.out(), "one\ntwo" );
+        }).spawn();
+        let consumer = Altio::default();
+        ::duct::cmd!( "tr", "a-z", "A-Z" )
+            .stdin_from_altio( producer.io() ).unwrap()
+            .stdout_to_altio( &consumer ).unwrap()
+            .run().unwrap();
+        let mut text = String::new();
+        consumer.input().read_line( &mut text ).unwrap();
+        consumer.input().read_line( &mut text ).unwrap();
+        assert_eq!( text, "ONE\nTWO\n" );
+    }
+
+    #[cfg( unix )]
+    #[test]
+    fn binary() {
+        let producer = Altio::default();
+        let expression = ::duct::cmd!( "cat" ).stdin_from_altio( &producer ).unwrap();
+        producer.out().write_all( b"\xff\x00bin" ).unwrap();
+        producer.0.1.close();
+        let consumer = Altio::default();
+        expression.stdout_to_altio( &consumer ).unwrap().run().unwrap();
+        assert_eq!( std::sync::Arc::strong_count( &producer.0 ), 1 );
+        let mut bytes = Vec::new();
+        consumer.input().read_to_end( &mut bytes ).unwrap();
+        assert_eq!( bytes, b"\xff\x00bin" );
+    }
+}
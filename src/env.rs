@@ -0,0 +1,137 @@
+//! Environment variables of a simulated program.
+
+use std::{
+    collections::BTreeMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+    sync::{
+        PoisonError, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Corresponding to `std::env` variables, as an overlay of the environment of the
+/// current process.
+///
+/// Variables set or removed in an `Altenv` shadow the ones of the process, without
+/// touching the process environment which is shared by all threads.
+#[derive( Debug, Default )]
+pub struct Altenv {
+    vars    : RwLock<BTreeMap<OsString, Option<OsString>>>,
+    cleared : AtomicBool,
+}
+
+impl Altenv {
+    /// Creates an overlay which shadows nothing.
+    pub const fn new() -> Self {
+        Altenv{ vars: RwLock::new( BTreeMap::new() ), cleared: AtomicBool::new( false )}
+    }
+
+    /// Corresponding to `std::env::var_os()`.
+    pub fn var_os( &self, key: impl AsRef<OsStr> ) -> Option<OsString> {
+        let key = key.as_ref();
+        match self.vars.read().unwrap_or_else( PoisonError::into_inner ).get( key ) {
+            Some( value ) => value.clone(),
+            None if self.cleared.load( Ordering::Relaxed ) => None,
+            None => std::env::var_os( key ),
+        }
+    }
+
+    /// Corresponding to `std::env::var()`.
+    pub fn var( &self, key: impl AsRef<OsStr> ) -> Result<String, VarError> {
+        self.var_os( key )
+            .ok_or( VarError::NotPresent )?
+            .into_string()
+            .map_err( VarError::NotUnicode )
+    }
+
+    /// Corresponding to `std::env::set_var()`, affecting this overlay only.
+    pub fn set_var( &self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr> ) {
+        self.vars.write().unwrap_or_else( PoisonError::into_inner ).insert( key.as_ref().to_owned(), Some( value.as_ref().to_owned() ));
+    }
+
+    /// Corresponding to `std::env::remove_var()`, affecting this overlay only.
+    pub fn remove_var( &self, key: impl AsRef<OsStr> ) {
+        self.vars.write().unwrap_or_else( PoisonError::into_inner ).insert( key.as_ref().to_owned(), None );
+    }
+
+    /// Hides all variables of the process, and removes all variables set.
+    pub fn clear( &self ) {
+        self.vars.write().unwrap_or_else( PoisonError::into_inner ).clear();
+        self.cleared.store( true, Ordering::Relaxed );
+    }
+
+    /// Returns the color choice of the user, following the conventions of `NO_COLOR`,
+    /// `CLICOLOR_FORCE` and `CLICOLOR`, in order of precedence.
+    pub fn color_choice( &self ) -> ColorChoice {
+        let set = |key: &str| self.var_os( key ).filter( |value| !value.is_empty() );
+        if set( "NO_COLOR" ).is_some() {
+            ColorChoice::Never
+        } else if set( "CLICOLOR_FORCE" ).is_some_and( |value| value != "0" ) {
+            ColorChoice::Always
+        } else if set( "CLICOLOR" ).is_some_and( |value| value == "0" ) {
+            ColorChoice::Never
+        } else {
+            ColorChoice::Auto
+        }
+    }
+
+    /// Sets the variables read by `color_choice()` to make it return `choice`,
+    /// affecting this overlay only.
+    pub fn set_color_choice( &self, choice: ColorChoice ) {
+        for key in [ "NO_COLOR", "CLICOLOR_FORCE", "CLICOLOR" ] {
+            self.remove_var( key );
+        }
+        match choice {
+            ColorChoice::Auto => (),
+            ColorChoice::Always => self.set_var( "CLICOLOR_FORCE", "1" ),
+            ColorChoice::Never => self.set_var( "NO_COLOR", "1" ),
+        }
+    }
+}
+
+/// Whether a tool should write colored text.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq, Hash )]
+pub enum ColorChoice {
+    /// Colors if the stream supports them, e.g. a terminal.
+    #[default]
+    Auto,
+    /// Colors anyway.
+    Always,
+    /// Plain text anyway.
+    Never,
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Altenv, ColorChoice};
+
+    #[test]
+    fn overlay() {
+        let env = Altenv::new();
+        assert_eq!( env.var_os( "PATH" ), std::env::var_os( "PATH" ));
+        env.set_var( "ALTIO_ENV_TEST", "1" );
+        assert_eq!( env.var( "ALTIO_ENV_TEST" ).as_deref(), Ok( "1" ));
+        assert_eq!( std::env::var_os( "ALTIO_ENV_TEST" ), None );
+        env.remove_var( "PATH" );
+        assert_eq!( env.var_os( "PATH" ), None );
+        env.clear();
+        assert_eq!( env.var_os( "ALTIO_ENV_TEST" ), None );
+        assert_eq!( env.var_os( "HOME" ), None );
+    }
+
+    #[test]
+    fn color_choice() {
+        let env = Altenv::new();
+        env.set_color_choice( ColorChoice::Auto );
+        assert_eq!( env.color_choice(), ColorChoice::Auto );
+        env.set_var( "CLICOLOR", "0" );
+        assert_eq!( env.color_choice(), ColorChoice::Never );
+        env.set_var( "CLICOLOR_FORCE", "1" );
+        assert_eq!( env.color_choice(), ColorChoice::Always );
+        env.set_var( "NO_COLOR", "" );
+        assert_eq!( env.color_choice(), ColorChoice::Always );
+        env.set_color_choice( ColorChoice::Never );
+        assert_eq!( env.color_choice(), ColorChoice::Never );
+    }
+}
@@ -0,0 +1,313 @@
+//! Importing classic expect(1) scripts, easing the migration of expect-based test
+//! suites onto embedded tools.
+//!
+//! A useful subset of Tcl expect is supported, one command per line:
+//!
+//! - `expect "text"`, `expect -exact "text"`, `expect -nocase "text"` ignoring case,
+//!   `expect -gl "glob"`, `expect -re "regex"` (with the "regex" feature) and
+//!   `expect eof`
+//! - `send "text\r"`, where carriage returns become newlines as a terminal does
+//! - `set timeout seconds`, with -1 for no timeout
+//! - `match_max bytes`, the lookbehind of the expectations, see `StreamMatcher`
+//! - `sleep seconds`
+//! - comments, and `spawn`, `interact`, `exit` which are ignored
+//!
+//! ```rust
+//! use altio::{Altio, echo, expect::ExpectScript};
+//!
+//! let script = ExpectScript::parse( r#"
+//!     set timeout 5
+//!     spawn ./greet
+//!     expect "Name: "
+//!     send "Alice\r"
+//!     expect "Hello, Alice!"
+//! "# ).unwrap();
+//!
+//! let io = Altio::default();
+//! let tool = {
+//!     let io = io.clone();
+//!     std::thread::spawn( move || {
+//!         echo!( -n, io.out(), "Name: " );
+//!         let mut name = String::new();
+//!         io.input().read_line( &mut name ).unwrap();
+//!         echo!( io.out(), "Hello, {}!", name.trim_end() );
+//!     })
+//! };
+//! script.run( &io ).unwrap();
+//! tool.join().unwrap();
+//! ```
+
+use crate::{Altio, MatchKind, Pattern, StreamMatcher, source::Script};
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    thread,
+    time::Duration,
+};
+
+/// The timeout of expect commands unless set.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs( 10 );
+
+/// A command of an expect script.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub enum Step {
+    /// Waits for output containing `pattern`, matched according to `kind`.
+    Expect {
+        /// The text, glob or regular expression.
+        pattern : String,
+        /// How `pattern` matches the output.
+        kind    : MatchKind,
+        /// How long to wait, forever if `None`.
+        timeout : Option<Duration>,
+    },
+    /// Waits for the tool to close its output stream.
+    Eof {
+        /// How long to wait, forever if `None`.
+        timeout : Option<Duration>,
+    },
+    /// Sends text to the input stream.
+    Send( String ),
+    /// Pauses the script.
+    Sleep( Duration ),
+}
+
+/// A parsed expect script.
+#[derive( Clone, Debug, Default, PartialEq, Eq )]
+pub struct ExpectScript {
+    /// The commands in order.
+    pub steps     : Vec<Step>,
+    /// The most bytes of output kept while waiting for a match, unbounded if `None`.
+    pub match_max : Option<usize>,
+}
+
+impl ExpectScript {
+    /// Parses the supported subset of an expect script, failing with `InvalidData`
+    /// on other commands.
+    pub fn parse( script: &str ) -> Result<Self> {
+        let mut steps = Vec::new();
+        let mut timeout = Some( DEFAULT_TIMEOUT );
+        let mut match_max = None;
+        for ( number, line ) in script.lines().enumerate() {
+            let invalid = |msg: &str| Error::new( ErrorKind::InvalidData, format!( "line {}: {msg}: {}", number+1, line.trim() ));
+            let words = words( line ).ok_or_else( || invalid( "unbalanced quotes or braces" ))?;
+            let words = words.iter().map( String::as_str ).collect::<Vec<_>>();
+            match words.as_slice() {
+                [] => (),
+                [ comment, .. ] if comment.starts_with( '#' ) => (),
+                [ "spawn", .. ] | [ "interact", .. ] | [ "exit", .. ] => (),
+                [ "set", "timeout", seconds ] => timeout = match seconds.parse::<i64>() {
+                    Ok( -1 ) => None,
+                    Ok( seconds ) if seconds >= 0 => Some( Duration::from_secs( seconds as u64 )),
+                    _ => return Err( invalid( "invalid timeout" )),
+                },
+                [ "match_max", bytes ] => match_max = Some( bytes.parse().map_err( |_| invalid( "invalid bytes" ))? ),
+                [ "sleep", seconds ] => {
+                    let seconds = seconds.parse::<f64>().ok().filter( |seconds| *seconds >= 0.0 ).ok_or_else( || invalid( "invalid seconds" ))?;
+                    steps.push( Step::Sleep( Duration::from_secs_f64( seconds )));
+                }
+                [ "expect", "eof" ] => steps.push( Step::Eof{ timeout }),
+                [ "expect", flag @ .., pattern ] if flag.len() <= 1 => {
+                    let kind = match flag {
+                        [] | [ "-exact" ] => MatchKind::Exact,
+                        [ "-nocase" ] => MatchKind::CaseInsensitive,
+                        [ "-gl" ] => MatchKind::Glob,
+                        [ "-re" ] => MatchKind::Regex,
+                        _ => return Err( invalid( "unsupported command" )),
+                    };
+                    steps.push( Step::Expect{ pattern: pattern.to_string(), kind, timeout });
+                }
+                [ "send", text ] => steps.push( Step::Send( text.replace( '\r', "\n" ))),
+                _ => return Err( invalid( "unsupported command" )),
+            }
+        }
+        Ok( ExpectScript{ steps, match_max })
+    }
+
+    /// Runs the script against the tool embedded in `io`, failing with `TimedOut` if
+    /// an expectation is not met in time. Text received before a match is discarded,
+    /// text after it is kept for the next expectation.
+    pub fn run( &self, io: &Altio ) -> Result<()> {
+        let mut received = String::new();
+        for step in &self.steps {
+            match step {
+                Step::Expect{ pattern, kind, timeout } => {
+                    let mut matcher = StreamMatcher::new( vec![ Pattern::new( pattern, *kind )? ]);
+                    if let Some( bytes ) = self.match_max {
+                        matcher = matcher.lookbehind( bytes );
+                    }
+                    let deadline = timeout.map( |timeout| io.0.1.clock.now() + timeout );
+                    let mut text = std::mem::take( &mut received );
+                    let mut expired = false;
+                    while matcher.push( &text ).is_none() {
+                        text = match deadline {
+                            Some( deadline ) => {
+                                let remaining = deadline.saturating_duration_since( io.0.1.clock.now() );
+                                let text = ( !expired ).then( || io.recv_timeout( remaining )).flatten();
+                                expired = remaining.is_zero();
+                                text.ok_or_else( || timed_out( &format!( "expect {pattern:?}" ), matcher.pending() ))?
+                            }
+                            None => io.recv(),
+                        };
+                    }
+                    received = matcher.pending().to_owned();
+                }
+                Step::Eof{ timeout } => {
+                    let deadline = timeout.map( |timeout| io.0.1.clock.now() + timeout );
+                    if io.0.1.recv_eof_until( deadline ).is_none() {
+                        received.extend( io.try_recv() );
+                        return Err( timed_out( "expect eof", &received ));
+                    }
+                    received.clear();
+                }
+                Step::Send( text ) => io.send( text ),
+                Step::Sleep( duration ) => thread::sleep( *duration ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the text sent by the script as a source of input lines, for tools which
+    /// don't need to be waited for.
+    pub fn input_script( &self ) -> Script {
+        let sent = self.steps.iter().filter_map( |step| match step {
+            Step::Send( text ) => Some( text.as_str() ),
+            _ => None,
+        }).collect::<String>();
+        Script::new( sent.lines() )
+    }
+
+    /// Registers each expectation followed by a send as an automatic reply by
+    /// `Altio::auto_respond_max()`, answering once. With the "regex" feature.
+    #[cfg( feature = "regex" )]
+    pub fn auto_respond( &self, io: &Altio ) -> std::result::Result<(), regex::Error> {
+        for pair in self.steps.windows(2) {
+            if let [ Step::Expect{ pattern, kind, .. }, Step::Send( text )] = pair {
+                let pattern = crate::pattern::to_regex( pattern, *kind );
+                io.auto_respond_max( &pattern, text.strip_suffix( '\n' ).unwrap_or( text ), 1 )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn timed_out( step: &str, received: &str ) -> Error {
+    Error::new( ErrorKind::TimedOut, format!( "{step} timed out, received: {received:?}" ))
+}
+
+/// Splits a line into Tcl words: bare, "quoted" with backslash escapes, or {braced}
+/// verbatim. Returns `None` if quotes or braces are unbalanced.
+fn words( line: &str ) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some( &ch ) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut word = String::new();
+        match ch {
+            '"' => {
+                chars.next();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => word.push( match chars.next()? {
+                            'r' => '\r',
+                            'n' => '\n',
+                            't' => '\t',
+                            ch => ch,
+                        }),
+                        ch => word.push( ch ),
+                    }
+                }
+            }
+            '{' => {
+                chars.next();
+                let mut depth = 1;
+                loop {
+                    let ch = chars.next()?;
+                    match ch {
+                        '{' => depth += 1,
+                        '}' if depth == 1 => break,
+                        '}' => depth -= 1,
+                        _ => (),
+                    }
+                    word.push( ch );
+                }
+            }
+            _ => while let Some( ch ) = chars.next_if( |ch| !ch.is_whitespace() ) {
+                word.push( ch );
+            },
+        }
+        words.push( word );
+    }
+    Some( words )
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{ExpectScript, Step, words};
+    use crate::{Altio, ManualClock, MatchKind, echo, source::{Next, Source}};
+    use std::{io::ErrorKind, sync::Arc, time::Duration};
+
+    #[test]
+    fn parse() {
+        assert_eq!( words( r#"send "a \"b\"\r" {c {d}} e"# ).unwrap(), [ "send", "a \"b\"\r", "c {d}", "e" ]);
+        assert!( words( r#"send "a"# ).is_none() );
+
+        let script = ExpectScript::parse( "#!/usr/bin/expect\nset timeout -1\nexpect -re {[0-9]+}\nsend \"y\\r\"\nsleep 0.5\nexpect eof" ).unwrap();
+        assert_eq!( script.steps, [
+            Step::Expect{ pattern: "[0-9]+".to_owned(), kind: MatchKind::Regex, timeout: None },
+            Step::Send( "y\n".to_owned() ),
+            Step::Sleep( Duration::from_millis( 500 )),
+            Step::Eof{ timeout: None },
+        ]);
+        assert!( ExpectScript::parse( "puts hello" ).is_err() );
+        assert!( ExpectScript::parse( "expect -ex a" ).is_err() );
+        let script = ExpectScript::parse( "match_max 100\nexpect -gl {Port ?*:}\nexpect -nocase done" ).unwrap();
+        assert_eq!( script.match_max, Some( 100 ));
+        assert_eq!( script.steps, [
+            Step::Expect{ pattern: "Port ?*:".to_owned(), kind: MatchKind::Glob, timeout: Some( super::DEFAULT_TIMEOUT )},
+            Step::Expect{ pattern: "done".to_owned(), kind: MatchKind::CaseInsensitive, timeout: Some( super::DEFAULT_TIMEOUT )},
+        ]);
+    }
+
+    #[test]
+    fn run() {
+        let script = ExpectScript::parse( "set timeout 1\nexpect \"Name: \"\nsend \"Bob\\r\"\nexpect Hello" ).unwrap();
+        let io = Altio::default();
+        echo!( -n, io.out(), "Name: " );
+        script.run( &io ).unwrap_err();
+        assert_eq!( io.input().lines().next().as_deref(), Some( "Bob\n" ));
+        echo!( -n, io.out(), "Name: Hello" );
+        script.run( &io ).unwrap();
+        assert_eq!( script.input_script().next_line(), Next::Line( "Bob".to_owned() ));
+    }
+
+    #[test]
+    fn deadline() {
+        let script = ExpectScript::parse( "set timeout 0\nexpect Hello" ).unwrap();
+        let io = Altio::default();
+        echo!( io.out(), "Hello" );
+        script.run( &io ).unwrap();
+
+        let clock = Arc::new( ManualClock::new() );
+        let io = Altio::builder().clock( clock.clone() ).build();
+        let script = ExpectScript::parse( "set timeout 60\nexpect eof" ).unwrap();
+        echo!( io.out(), "bye" );
+        let driver = {
+            let io = io.clone();
+            std::thread::spawn( move || script.run( &io ))
+        };
+        std::thread::sleep( Duration::from_millis( 10 ));
+        assert!( !driver.is_finished() );
+        clock.advance( Duration::from_secs( 60 ));
+        let err = driver.join().unwrap().unwrap_err();
+        assert_eq!(( err.kind(), err.to_string().as_str() ), ( ErrorKind::TimedOut, "expect eof timed out, received: \"bye\\n\"" ));
+
+        echo!( io.out(), "rest" );
+        io.0.1.close();
+        ExpectScript::parse( "expect eof" ).unwrap().run( &io ).unwrap();
+        assert_eq!( io.try_recv(), None );
+    }
+}
@@ -0,0 +1,165 @@
+//! Driving embedded tools with `expectrl`, with the "expectrl" feature.
+//!
+//! An `AltSession` is an `expectrl::Session` whose process is an `AltChild` and
+//! whose stream is the `Altio` of it, so the `expect`/`send` combinators, regex
+//! needles and logging of `expectrl` work without a PTY.
+//!
+//! ```rust
+//! use altio::{AltCommand, Altio, echo, expectrl::session};
+//! use expectrl::Expect;
+//!
+//! let child = AltCommand::new( |io: Altio, _| {
+//!     echo!( -n, io.out(), "name? " );
+//!     let mut name = String::new();
+//!     io.input().read_line( &mut name )?;
+//!     echo!( io.out(), "Hello, {}", name.trim_end() );
+//!     Ok::<_,std::io::Error>(())
+//! }).spawn();
+//!
+//! let mut session = session( child ).unwrap();
+//! session.expect( "name? " ).unwrap();
+//! session.send_line( "altio" ).unwrap();
+//! session.expect( "Hello, altio" ).unwrap();
+//! session.expect( expectrl::Eof ).unwrap();
+//! ```
+
+use crate::{AltChild, Altio, Altout, Output, Stream, wait_for};
+
+use ::expectrl::{
+    Session,
+    process::{Healthcheck, NonBlocking},
+};
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    sync::atomic::Ordering,
+};
+
+/// A `expectrl::Session` driving an embedded tool.
+pub type AltSession = Session<AltChild, AltStream>;
+
+/// Creates an `expectrl` session driving `child`.
+pub fn session( child: AltChild ) -> io::Result<AltSession> {
+    let stream = AltStream{ io: child.io().clone(), blocking: true };
+    Session::new( child, stream )
+}
+
+impl Healthcheck for AltChild {
+    /// Whether the tool has finished.
+    type Status = bool;
+
+    fn get_status( &self ) -> io::Result<bool> { Ok( self.is_finished() )}
+
+    fn is_alive( &self ) -> io::Result<bool> { Ok( !self.is_finished() )}
+}
+
+/// The streams of an embedded tool as a byte stream: writes are sent to the input
+/// stream, and reads receive from the output stream, then the error stream, like a
+/// terminal showing both. Reads hit end-of-file after the tool finishes.
+#[derive( Debug )]
+pub struct AltStream {
+    io       : Altio,
+    blocking : bool,
+}
+
+impl AltStream {
+    fn streams( &self ) -> [&Altout; 2] { [ &self.io.0.1, &self.io.0.2 ]}
+
+    /// Receives from the output stream locked as `output`, then the error stream.
+    fn try_read( &self, output: &mut Output, buf: &mut [u8] ) -> Option<usize> {
+        let [ out, err ] = self.streams();
+        // Keeps the output stream locked while trying the error stream, so that the
+        // error stream is only read while no output is pending, even if the tool
+        // writes both in between. Text pending in both is not ordered by writing.
+        let bytes = out.try_recv_bytes( out.changing( output ), buf.len() )
+            .or_else( || err.try_recv_bytes( &mut err.output(), buf.len() ))?;
+        buf[ ..bytes.len() ].copy_from_slice( &bytes );
+        Some( bytes.len() )
+    }
+
+    fn is_closed( &self ) -> bool {
+        self.streams().into_iter().all( |altout| altout.closed.load( Ordering::Relaxed ))
+    }
+}
+
+impl Read for AltStream {
+    /// Blocks on the output stream like `Altio::recv()`, noticing writes into the
+    /// error stream within a millisecond, unless not blocking.
+    fn read( &mut self, buf: &mut [u8] ) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok( 0 );
+        }
+        let out = &self.io.0.1;
+        let deadline = ( !self.blocking ).then( || out.clock.now() );
+        wait_for( &out.output, &out.ready, Stream::Out, &out.hook, &*out.clock, deadline, |output| {
+            // Checks closing first, or the text written before closing could be missed.
+            let closed = self.is_closed();
+            self.try_read( output, buf ).or( closed.then_some( 0 ))
+        }).ok_or_else( || ErrorKind::WouldBlock.into() )
+    }
+}
+
+impl Write for AltStream {
+    /// Sends the bytes as they are, see `Altio::send_raw()`.
+    fn write( &mut self, buf: &[u8] ) -> io::Result<usize> {
+        self.io.send_raw( buf );
+        Ok( buf.len() )
+    }
+
+    fn flush( &mut self ) -> io::Result<()> { Ok(()) }
+}
+
+impl NonBlocking for AltStream {
+    fn set_blocking( &mut self, on: bool ) -> io::Result<()> {
+        self.blocking = on;
+        Ok(())
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{AltStream, session};
+    use crate::{AltCommand, Altio, echo};
+    use ::expectrl::{Eof, Expect, Regex};
+    use std::io::{ErrorKind, Read, Write};
+
+    #[test]
+    fn stream() {
+        let io = Altio::default();
+        let mut stream = AltStream{ io: io.clone(), blocking: false };
+        let bytes = "é\n".as_bytes();
+        stream.write_all( &bytes[ ..1 ]).unwrap();
+        stream.write_all( &bytes[ 1.. ]).unwrap();
+        assert_eq!( io.input().lines().next().as_deref(), Some( "é\n" ));
+
+        let mut buf = [0; 8];
+        assert_eq!( stream.read( &mut buf ).unwrap_err().kind(), ErrorKind::WouldBlock );
+        echo!( io.err(), "e" );
+        assert_eq!( stream.read( &mut buf ).unwrap(), 2 );
+        stream.blocking = true;
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || echo!( -n, io.out(), "o" ))
+        };
+        assert_eq!(( stream.read( &mut buf ).unwrap(), buf[0] ), ( 1, b'o' ));
+        tool.join().unwrap();
+    }
+
+    #[test]
+    fn expect() {
+        let child = AltCommand::new( |io: Altio, _| {
+            for n in 1..=3 {
+                echo!( io.out(), "item #{n}" );
+            }
+            echo!( io.err(), "warning" );
+        }).spawn();
+
+        let mut session = session( child ).unwrap();
+        session.expect( "#2" ).unwrap();
+        let captures = session.expect( Regex( r"#(\d)\n" )).unwrap();
+        assert_eq!( captures.get( 1 ), Some( b"3".as_slice() ));
+        session.expect( "warning" ).unwrap();
+        session.expect( Eof ).unwrap();
+        assert_eq!( session.get_process_mut().wait().unwrap(), 0 );
+    }
+}
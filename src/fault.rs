@@ -0,0 +1,132 @@
+//! Simulated io errors and latency, for testing error handling paths of tools and
+//! timing dependent code of drivers.
+
+use crate::rng::Rng;
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// When an injected error occurs, see `Altio::inject_error()`.
+#[derive( Clone, Copy, Debug, PartialEq, Eq )]
+pub enum When {
+    /// The next operation fails, then the stream works as usual.
+    Next,
+    /// Every nth operation fails, until the error is cleared.
+    Every( usize ),
+}
+
+#[derive( Debug )]
+struct Fault {
+    kind  : ErrorKind,
+    when  : When,
+    count : usize,
+}
+
+/// The error injected into a stream, if any.
+#[derive( Debug, Default )]
+pub(crate) struct Injector( Mutex<Option<Fault>> );
+
+impl Injector {
+    pub(crate) fn inject( &self, kind: ErrorKind, when: When ) {
+        *self.0.lock().unwrap_or_else( PoisonError::into_inner ) = Some( Fault{ kind, when, count: 0 });
+    }
+
+    pub(crate) fn clear( &self ) {
+        *self.0.lock().unwrap_or_else( PoisonError::into_inner ) = None;
+    }
+
+    /// Counts an operation, returning the injected error if it should fail.
+    pub(crate) fn check( &self ) -> Result<()> {
+        let mut fault = self.0.lock().unwrap_or_else( PoisonError::into_inner );
+        let kind = match fault.as_mut() {
+            None => return Ok(()),
+            Some( fault ) => {
+                fault.count += 1;
+                match fault.when {
+                    When::Every( n ) if fault.count % n.max(1) != 0 => return Ok(()),
+                    _ => fault.kind,
+                }
+            }
+        };
+        if let Some( Fault{ when: When::Next, .. }) = *fault {
+            *fault = None;
+        }
+        Err( Error::new( kind, "error injected by altio" ))
+    }
+}
+
+/// Artificial delay of io operations, see `Altio::inject_latency()`.
+#[derive( Clone, Copy, Debug, PartialEq, Eq )]
+pub enum Delay {
+    /// Delays every operation for the same duration.
+    Fixed( Duration ),
+    /// Delays every operation for a random duration between `min` and `max`.
+    Jitter{ min: Duration, max: Duration },
+}
+
+/// The delay injected into a stream, if any, jittered by a generator of `seed` if
+/// given, see `AltioBuilder::latency_seed()`.
+#[derive( Debug, Default )]
+pub(crate) struct Latency {
+    delay : Mutex<Option<(Delay, Rng)>>,
+    seed  : Option<u64>,
+}
+
+impl Latency {
+    pub(crate) fn seeded( seed: Option<u64> ) -> Self {
+        Latency{ delay: Mutex::default(), seed }
+    }
+
+    pub(crate) fn inject( &self, delay: Delay ) {
+        let rng = self.seed.map_or_else( Rng::from_time, Rng::seeded );
+        *self.delay.lock().unwrap_or_else( PoisonError::into_inner ) = Some(( delay, rng ));
+    }
+
+    pub(crate) fn clear( &self ) {
+        *self.delay.lock().unwrap_or_else( PoisonError::into_inner ) = None;
+    }
+
+    pub(crate) fn is_injected( &self ) -> bool {
+        self.delay.lock().unwrap_or_else( PoisonError::into_inner ).is_some()
+    }
+
+    /// Returns the next delay to sleep for, if any.
+    fn next( &self ) -> Option<Duration> {
+        match self.delay.lock().unwrap_or_else( PoisonError::into_inner ).as_mut()? {
+            ( Delay::Fixed( duration ), _ ) => Some( *duration ),
+            ( Delay::Jitter{ min, max }, rng ) => {
+                let range = max.saturating_sub( *min ).as_nanos().min( u64::MAX as u128 ) as u64;
+                Some( *min + Duration::from_nanos( rng.up_to( range )))
+            }
+        }
+    }
+
+    /// Sleeps for the injected delay, if any. Callers holding a stream lock should
+    /// release it first, see `is_injected()`.
+    pub(crate) fn apply( &self ) {
+        if let Some( duration ) = self.next() {
+            std::thread::sleep( duration );
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_jitter() {
+        let jitter = Delay::Jitter{ min: Duration::from_millis( 1 ), max: Duration::from_millis( 100 )};
+        let delays = || {
+            let latency = Latency::seeded( Some( 42 ));
+            latency.inject( jitter );
+            ( 0..8 ).map( |_| latency.next().unwrap() ).collect::<Vec<_>>()
+        };
+        let first = delays();
+        assert_eq!( first, delays() );
+        assert!( first.iter().all( |delay| ( Duration::from_millis( 1 )..=Duration::from_millis( 100 )).contains( delay )));
+    }
+}
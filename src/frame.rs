@@ -0,0 +1,87 @@
+//! Boundaries of the messages written into an output stream, one per write call.
+
+use std::collections::VecDeque;
+
+/// Counts bytes written into an output stream, and where the messages end.
+#[derive( Debug )]
+pub(crate) struct Frames {
+    written : u64,
+    ends    : VecDeque<u64>,
+}
+
+impl Frames {
+    /// Starts framing a stream having `pending` bytes not received yet, which make
+    /// a message of their own.
+    pub(crate) fn new( pending: usize ) -> Self {
+        let written = pending as u64;
+        Frames{ written, ends: ( pending != 0 ).then_some( written ).into_iter().collect() }
+    }
+
+    pub(crate) fn wrote( &mut self, len: usize ) { self.written += len as u64; }
+
+    /// Ends the current message, unless it is empty.
+    pub(crate) fn end( &mut self ) {
+        if self.ends.back().copied().unwrap_or_default() < self.written {
+            self.ends.push_back( self.written );
+        }
+    }
+
+    /// Makes the `pending` bytes not received yet, replaced with `edited` bytes, one
+    /// message, given the amount of bytes `held` back from receivers.
+    pub(crate) fn edited( &mut self, pending: usize, edited: usize, held: usize ) {
+        let received = self.written.saturating_sub(( held + pending ) as u64 );
+        let ( pending, edited ) = ( pending as u64, edited as u64 );
+        self.ends.retain( |&end| end <= received || end > received + pending );
+        for end in self.ends.iter_mut().filter( |end| **end > received ) {
+            *end = *end - pending + edited;
+        }
+        if edited != 0 {
+            let at = self.ends.partition_point( |&end| end <= received );
+            self.ends.insert( at, received + edited );
+        }
+        self.written = self.written - pending + edited;
+    }
+
+    /// Returns the length of the first message not received yet if it is visible as
+    /// a whole, given the amount of bytes `pending` to be received and those `held`
+    /// back from receivers.
+    pub(crate) fn next( &mut self, pending: usize, held: usize ) -> Option<usize> {
+        let visible = self.written.saturating_sub( held as u64 );
+        let received = visible.saturating_sub( pending as u64 );
+        while self.ends.front().is_some_and( |&end| end <= received ) {
+            self.ends.pop_front();
+        }
+        let end = *self.ends.front()?;
+        ( end <= visible ).then( || ( end - received ) as usize )
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::Frames;
+
+    #[test]
+    fn frames() {
+        let mut frames = Frames::new( 2 );
+        frames.wrote( 3 );
+        frames.end();
+        frames.end();
+        frames.wrote( 4 );
+        assert_eq!( frames.next( 9, 0 ), Some( 2 ));
+        assert_eq!( frames.next( 3, 4 ), Some( 3 ));
+        assert_eq!( frames.next( 0, 4 ), None );
+        frames.end();
+        assert_eq!( frames.next( 4, 0 ), Some( 4 ));
+        assert_eq!( frames.next( 0, 0 ), None );
+
+        frames.wrote( 2 );
+        frames.end();
+        frames.wrote( 3 );
+        frames.end();
+        frames.wrote( 1 );
+        frames.edited( 5, 7, 1 );
+        frames.end();
+        assert_eq!( frames.next( 7, 1 ), Some( 7 ));
+        assert_eq!( frames.next( 1, 0 ), Some( 1 ));
+    }
+}
@@ -0,0 +1,41 @@
+//! Hex and ASCII dumps of raw bytes, showing what text conversions would mangle,
+//! e.g. stray carriage returns, byte order marks or NULs.
+
+use std::fmt::Write;
+
+/// Returns the canonical hex+ASCII dump of `bytes`, 16 per line, whose offsets start
+/// from `offset`.
+pub(crate) fn dump( bytes: &[u8], offset: u64 ) -> String {
+    let mut dump = String::new();
+    for ( i, row ) in bytes.chunks( 16 ).enumerate() {
+        let _ = write!( dump, "{:08x} ", offset + i as u64 * 16 );
+        for col in 0..16 {
+            if col == 8 {
+                dump.push( ' ' );
+            }
+            match row.get( col ) {
+                Some( byte ) => { let _ = write!( dump, " {byte:02x}" ); }
+                None => dump.push_str( "   " ),
+            }
+        }
+        dump.push_str( "  |" );
+        dump.extend( row.iter().map( |&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }));
+        dump.push_str( "|\n" );
+    }
+    dump
+}
+
+#[cfg( test )]
+mod tests {
+    use super::dump;
+
+    #[test]
+    fn hex_dump() {
+        assert_eq!( dump( b"", 0 ), "" );
+        assert_eq!( dump( b"\xef\xbb\xbfok\r\n\0", 0x10 ),
+            "00000010  ef bb bf 6f 6b 0d 0a 00                           |...ok...|\n" );
+        assert_eq!( dump( b"0123456789abcdefg", 0 ),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             00000010  67                                                |g|\n" );
+    }
+}
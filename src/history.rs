@@ -0,0 +1,268 @@
+//! Retained copy of everything written into an output stream.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Text is retained in blocks of this size, the unit of compression.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Limits of the retained history, beyond which the oldest text is dropped, e.g. for
+/// long-running embedded daemons. No limit by default.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub struct Retention {
+    /// The maximum amount of retained bytes.
+    pub max_bytes : Option<usize>,
+    /// The maximum amount of retained complete lines, not counting the line being
+    /// written.
+    pub max_lines : Option<usize>,
+    /// The maximum time text is retained after being written.
+    pub max_age   : Option<Duration>,
+}
+
+/// Everything written into a stream, regardless of being received or not, within
+/// the limits of its `Retention`.
+///
+/// With the "zstd" feature, full blocks are compressed and only decompressed when
+/// queried, so long sessions cost a fraction of their text size.
+#[derive( Debug, Default )]
+pub(crate) struct History {
+    blocks    : VecDeque<Block>,
+    tail      : Vec<u8>,
+    head      : usize, // the amount of bytes dropped from the first block
+    truncated : usize,
+    retention : Retention,
+    newlines  : VecDeque<usize>, // the offsets of retained newlines, with `max_lines`
+    writes    : VecDeque<( Instant, usize )>, // the times and ends of writes, with `max_age`
+}
+
+#[derive( Debug )]
+struct Block {
+    data       : Vec<u8>,
+    len        : usize,
+    #[cfg( feature = "zstd" )]
+    compressed : bool,
+}
+
+impl Block {
+    /// Compresses `text`, or keeps it as is if compression fails.
+    #[cfg( feature = "zstd" )]
+    fn seal( text: Vec<u8> ) -> Self {
+        let len = text.len();
+        match zstd::bulk::compress( &text, 0 ) {
+            Ok( data ) => Block{ data, len, compressed: true },
+            Err( _ ) => Block{ data: text, len, compressed: false },
+        }
+    }
+
+    #[cfg( not( feature = "zstd" ))]
+    fn seal( text: Vec<u8> ) -> Self {
+        Block{ len: text.len(), data: text }
+    }
+
+    /// Decompresses the text, which is replaced with as many '?' if that fails, so
+    /// that offsets into the history still hold.
+    #[cfg( feature = "zstd" )]
+    fn text( &self ) -> Vec<u8> {
+        if !self.compressed {
+            return self.data.clone();
+        }
+        zstd::bulk::decompress( &self.data, self.len ).unwrap_or_else( |_| vec![ b'?'; self.len ])
+    }
+
+    #[cfg( not( feature = "zstd" ))]
+    fn text( &self ) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+impl History {
+    pub(crate) fn push( &mut self, bytes: &[u8], now: Instant ) {
+        let start = self.end();
+        self.tail.extend_from_slice( bytes );
+        if self.tail.len() >= BLOCK_SIZE {
+            let text = std::mem::replace( &mut self.tail, Vec::with_capacity( BLOCK_SIZE ));
+            self.blocks.push_back( Block::seal( text ));
+        }
+        if self.retention.max_lines.is_some() {
+            self.newlines.extend( bytes.iter().enumerate().filter( |( _, &byte )| byte == b'\n' ).map( |( i, _ )| start + i ));
+        }
+        if self.retention.max_age.is_some() {
+            self.writes.push_back(( now, self.end() ));
+        }
+        self.trim( now );
+    }
+
+    /// Applies `retention` from now on, to the text retained so far as well, which is
+    /// regarded as written `now`.
+    pub(crate) fn retain( &mut self, retention: Retention, now: Instant ) {
+        self.retention = retention;
+        self.newlines.clear();
+        if retention.max_lines.is_some() {
+            let start = self.truncated;
+            self.newlines.extend( self.to_bytes().iter().enumerate().filter( |( _, &byte )| byte == b'\n' ).map( |( i, _ )| start + i ));
+        }
+        self.writes.clear();
+        if retention.max_age.is_some() {
+            self.writes.push_back(( now, self.end() ));
+        }
+        self.trim( now );
+    }
+
+    /// Drops the oldest text beyond the limits of the retention.
+    pub(crate) fn trim( &mut self, now: Instant ) {
+        let Retention{ max_bytes, max_lines, max_age } = self.retention;
+        let mut to = self.truncated;
+        if let Some( max_bytes ) = max_bytes {
+            to = to.max( self.end().saturating_sub( max_bytes ));
+        }
+        if let Some( max_lines ) = max_lines {
+            while self.newlines.len() > max_lines {
+                to = to.max( self.newlines.pop_front().unwrap_or_default() + 1 );
+            }
+        }
+        if let Some( max_age ) = max_age {
+            while let Some( &( at, end )) = self.writes.front() {
+                if now.saturating_duration_since( at ) <= max_age {
+                    break;
+                }
+                to = to.max( end );
+                self.writes.pop_front();
+            }
+        }
+        while self.newlines.front().is_some_and( |&offset| offset < to ) {
+            self.newlines.pop_front();
+        }
+        while self.writes.front().is_some_and( |&( _, end )| end <= to ) {
+            self.writes.pop_front();
+        }
+        self.drop_to( to );
+    }
+
+    /// Drops the text before the offset `to`, counted since the history started.
+    fn drop_to( &mut self, to: usize ) {
+        let mut n = to.saturating_sub( self.truncated );
+        self.truncated += n;
+        while n > 0 {
+            match self.blocks.front() {
+                Some( block ) if self.head + n >= block.len => {
+                    n -= block.len - self.head;
+                    self.head = 0;
+                    self.blocks.pop_front();
+                }
+                Some(_) => {
+                    self.head += n;
+                    n = 0;
+                }
+                None => {
+                    self.tail.drain( ..n );
+                    n = 0;
+                }
+            }
+        }
+    }
+
+    /// Returns the amount of retained bytes.
+    pub(crate) fn len( &self ) -> usize {
+        self.blocks.iter().map( |block| block.len ).sum::<usize>() - self.head + self.tail.len()
+    }
+
+    /// Returns the amount of dropped bytes, i.e. the offset of the first retained one
+    /// counted since the history started.
+    pub(crate) fn truncated( &self ) -> usize { self.truncated }
+
+    /// Returns the offset after the last retained byte, counted since the history
+    /// started.
+    fn end( &self ) -> usize { self.truncated + self.len() }
+
+    /// Returns the amount of memory used for storing the retained bytes.
+    #[cfg( test )]
+    pub(crate) fn stored_len( &self ) -> usize {
+        self.blocks.iter().map( |block| block.data.len() ).sum::<usize>() + self.tail.len()
+    }
+
+    /// Returns all retained bytes, decompressing them if necessary.
+    pub(crate) fn to_bytes( &self ) -> Vec<u8> {
+        self.bytes_from( self.truncated )
+    }
+
+    /// Returns the retained bytes from the offset counted since the history started
+    /// on, decompressing only the blocks containing them.
+    pub(crate) fn bytes_from( &self, offset: usize ) -> Vec<u8> {
+        let mut offset = offset.saturating_sub( self.truncated ) + self.head;
+        let mut bytes = Vec::new();
+        for block in &self.blocks {
+            if offset >= block.len {
+                offset -= block.len;
+            } else {
+                bytes.extend_from_slice( &block.text()[ offset.. ]);
+                offset = 0;
+            }
+        }
+        bytes.extend_from_slice( &self.tail[ offset.min( self.tail.len() ).. ]);
+        bytes
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retain_blocks() {
+        let mut history = History::default();
+        let line = "0123456789abcdef".repeat( 64 ) + "\n";
+        for _ in 0..100 {
+            history.push( line.as_bytes(), Instant::now() );
+        }
+        assert_eq!( history.len(), 100 * line.len() );
+        assert_eq!( history.blocks.len(), 100 * line.len() / BLOCK_SIZE );
+        assert_eq!( history.to_bytes(), line.repeat( 100 ).into_bytes() );
+        assert_eq!( history.bytes_from( 99 * line.len() ), line.as_bytes() );
+        assert_eq!( history.bytes_from( 100 * line.len() ), b"" );
+
+        if cfg!( feature = "zstd" ) {
+            assert!( history.stored_len() < history.len() / 2 );
+        } else {
+            assert_eq!( history.stored_len(), history.len() );
+        }
+    }
+
+    #[cfg( feature = "zstd" )]
+    #[test]
+    fn corrupt_block() {
+        let mut history = History::default();
+        history.push( &[ b'a'; BLOCK_SIZE ], Instant::now() );
+        history.blocks[0].data.truncate( 4 );
+        assert_eq!( history.to_bytes(), vec![ b'?'; BLOCK_SIZE ]);
+    }
+
+    #[test]
+    fn retention() {
+        let mut history = History::default();
+        let now = Instant::now();
+        let line = "0123456789abcdef".repeat( 64 ) + "\n";
+        for _ in 0..100 {
+            history.push( line.as_bytes(), now );
+        }
+        history.retain( Retention{ max_bytes: Some( BLOCK_SIZE + 10 ), ..Retention::default() }, now );
+        assert_eq!( history.len(), BLOCK_SIZE + 10 );
+        assert_eq!( history.truncated(), 100 * line.len() - BLOCK_SIZE - 10 );
+        assert_eq!( history.to_bytes(), line.repeat( 100 ).as_bytes()[ history.truncated().. ]);
+
+        history.retain( Retention{ max_lines: Some(1), ..Retention::default() }, now );
+        history.push( b"partial", now );
+        assert_eq!( history.to_bytes(), ( line.clone() + "partial" ).into_bytes() );
+        history.push( b"\nnext", now );
+        assert_eq!( history.to_bytes(), b"partial\nnext" );
+
+        history.retain( Retention{ max_age: Some( Duration::from_secs(1) ), ..Retention::default() }, now );
+        history.push( b"\nlater", now + Duration::from_secs(1) );
+        history.trim( now + Duration::from_secs(2) );
+        assert_eq!( history.to_bytes(), b"\nlater" );
+        history.trim( now + Duration::from_secs(3) );
+        assert_eq!( history.len(), 0 );
+        assert_eq!( history.truncated(), 100 * line.len() + 18 );
+    }
+}
@@ -0,0 +1,143 @@
+//! Rendering text with ANSI escape codes as HTML.
+
+use std::fmt::Write;
+
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// The graphic rendition set by SGR escape codes.
+#[derive( Clone, Debug, Default, PartialEq, Eq )]
+struct Style {
+    fg        : Option<String>,
+    bg        : Option<String>,
+    bold      : bool,
+    italic    : bool,
+    underline : bool,
+}
+
+impl Style {
+    /// Applies the parameters of an SGR escape code, e.g. "1;31".
+    fn apply( &mut self, params: &str ) {
+        let mut params = params.split( ';' ).map( |param| param.parse::<u8>().unwrap_or(0) );
+        while let Some( param ) = params.next() {
+            match param {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some( PALETTE[ (param-30) as usize ].to_owned() ),
+                38 => self.fg = extended( &mut params ),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some( PALETTE[ (param-40) as usize ].to_owned() ),
+                48 => self.bg = extended( &mut params ),
+                49 => self.bg = None,
+                90..=97 => self.fg = Some( PALETTE[ (param-82) as usize ].to_owned() ),
+                100..=107 => self.bg = Some( PALETTE[ (param-92) as usize ].to_owned() ),
+                _ => (),
+            }
+        }
+    }
+
+    /// Returns the CSS of this style.
+    fn css( &self ) -> String {
+        let mut css = String::new();
+        if let Some( fg ) = &self.fg { let _ = write!( css, "color:{fg};" ); }
+        if let Some( bg ) = &self.bg { let _ = write!( css, "background:{bg};" ); }
+        if self.bold { css.push_str( "font-weight:bold;" ); }
+        if self.italic { css.push_str( "font-style:italic;" ); }
+        if self.underline { css.push_str( "text-decoration:underline;" ); }
+        css
+    }
+}
+
+/// Parses the color of "38;5;n" or "38;2;r;g;b" after the 38 or 48.
+fn extended( params: &mut impl Iterator<Item=u8> ) -> Option<String> {
+    match params.next()? {
+        5 => {
+            let n = params.next()?;
+            Some( match n {
+                0..=15 => PALETTE[ n as usize ].to_owned(),
+                16..=231 => {
+                    let level = |c: u8| if c == 0 { 0 } else { 55 + c * 40 };
+                    let n = n - 16;
+                    format!( "#{:02x}{:02x}{:02x}", level( n/36 ), level( n/6%6 ), level( n%6 ))
+                }
+                _ => {
+                    let gray = 8 + ( n-232 ) * 10;
+                    format!( "#{gray:02x}{gray:02x}{gray:02x}" )
+                }
+            })
+        }
+        2 => Some( format!( "#{:02x}{:02x}{:02x}", params.next()?, params.next()?, params.next()? )),
+        _ => None,
+    }
+}
+
+/// Escapes `text` for HTML.
+pub(crate) fn escape( text: &str, html: &mut String ) {
+    for ch in text.chars() {
+        match ch {
+            '&' => html.push_str( "&amp;" ),
+            '<' => html.push_str( "&lt;" ),
+            '>' => html.push_str( "&gt;" ),
+            '"' => html.push_str( "&quot;" ),
+            ch => html.push( ch ),
+        }
+    }
+}
+
+/// Renders `text` as HTML, turning SGR escape codes into styled spans and dropping
+/// other escape codes.
+pub(crate) fn render( text: &str ) -> String {
+    let mut html = String::new();
+    let mut style = Style::default();
+    let mut parts = text.split( '\x1b' );
+    escape( parts.next().unwrap_or_default(), &mut html );
+    for part in parts {
+        let text = match part.strip_prefix( '[' ) {
+            Some( csi ) => match csi.find( |ch| ( '\x40'..='\x7e' ).contains( &ch )) {
+                Some( end ) => {
+                    if csi[ end.. ].starts_with( 'm' ) {
+                        let old = style.clone();
+                        style.apply( &csi[ ..end ]);
+                        if style != old {
+                            if old != Style::default() {
+                                html.push_str( "</span>" );
+                            }
+                            if style != Style::default() {
+                                let _ = write!( html, r#"<span style="{}">"#, style.css() );
+                            }
+                        }
+                    }
+                    &csi[ end+1.. ]
+                }
+                None => "",
+            },
+            None => part,
+        };
+        escape( text, &mut html );
+    }
+    if style != Style::default() {
+        html.push_str( "</span>" );
+    }
+    html
+}
+
+#[cfg( test )]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn ansi() {
+        assert_eq!( render( "a<b & \"c\"" ), "a&lt;b &amp; &quot;c&quot;" );
+        assert_eq!( render( "\x1b[1;31mred\x1b[0m plain" ),
+            r#"<span style="color:#cd0000;font-weight:bold;">red</span> plain"# );
+        assert_eq!( render( "\x1b[38;2;1;2;3mrgb\x1b[2Kcleared" ), r#"<span style="color:#010203;">rgbcleared</span>"# );
+        assert_eq!( render( "\x1b[38;5;196mx" ), r#"<span style="color:#ff0000;">x</span>"# );
+    }
+}
@@ -0,0 +1,96 @@
+//! Seeded schedule of the visibility of written text, for flushing out drivers which
+//! assume that one write of the tool is received as a whole.
+
+use crate::rng::Rng;
+
+/// Text written into an output stream but not yet visible to receivers.
+#[derive( Debug )]
+pub(crate) struct Interleave {
+    seed    : u64,
+    rng     : Rng,
+    pending : Vec<u8>,
+}
+
+impl Interleave {
+    pub(crate) fn new( seed: u64 ) -> Self {
+        Interleave{ seed, rng: Rng::seeded( seed ), pending: Vec::new() }
+    }
+
+    pub(crate) fn seed( &self ) -> u64 { self.seed }
+
+    /// Returns the amount of bytes held back.
+    pub(crate) fn held( &self ) -> usize { self.pending.len() }
+
+    /// Holds written `bytes` back, returning those which become visible now.
+    ///
+    /// The pending text may be released as a whole, partially, or not at all and
+    /// merged with following writes.
+    pub(crate) fn write( &mut self, bytes: &[u8] ) -> Vec<u8> {
+        self.pending.extend_from_slice( bytes );
+        match self.rng.up_to(3) {
+            0 | 1 => Vec::new(),
+            2 => self.release( self.pending.len() ),
+            _ => self.release_some(),
+        }
+    }
+
+    /// Returns pending bytes which become visible on receiving.
+    pub(crate) fn poll( &mut self ) -> Vec<u8> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        match self.rng.up_to(3) {
+            0 => Vec::new(),
+            1 => self.release_some(),
+            _ => self.release( self.pending.len() ),
+        }
+    }
+
+    /// Releases a random amount of pending bytes.
+    fn release_some( &mut self ) -> Vec<u8> {
+        let n = self.rng.up_to( self.pending.len() as u64 ) as usize;
+        self.release( n )
+    }
+
+    /// Releases the first `n` pending bytes, never splitting a UTF-8 encoded char.
+    fn release( &mut self, mut n: usize ) -> Vec<u8> {
+        while n > 0 && n < self.pending.len() && ( self.pending[n] as i8 ) < -0x40 {
+            n -= 1;
+        }
+        self.pending.drain( ..n ).collect()
+    }
+
+    /// Releases all pending bytes.
+    pub(crate) fn flush( &mut self ) -> Vec<u8> {
+        std::mem::take( &mut self.pending )
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::Interleave;
+
+    fn schedule( seed: u64 ) -> Vec<Vec<u8>> {
+        let mut interleave = Interleave::new( seed );
+        let mut visible = Vec::new();
+        for text in ["abc\n", "\u{4e2d}\u{6587}\n", "defg\n"] {
+            visible.push( interleave.write( text.as_bytes() ));
+            visible.push( interleave.poll() );
+        }
+        visible.push( interleave.flush() );
+        visible
+    }
+
+    #[test]
+    fn reproducible() {
+        for seed in 0..16 {
+            let visible = schedule( seed );
+            assert_eq!( visible, schedule( seed ));
+            let text = visible.concat();
+            assert_eq!( String::from_utf8( text ).unwrap(), "abc\n\u{4e2d}\u{6587}\ndefg\n" );
+            for bytes in visible {
+                assert!( std::str::from_utf8( &bytes ).is_ok() );
+            }
+        }
+    }
+}
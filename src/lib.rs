@@ -10,6 +10,27 @@
 //! altio = ["altio/altio"]
 //! ```
 //!
+//! To capture io in the tool's own unit tests, enable the simulated io for tests
+//! only, while other builds still use stdio:
+//!
+//! ```toml
+//! [dependencies]
+//! altio = { version = "0.2", default-features = false }
+//!
+//! [dev-dependencies]
+//! altio = { version = "0.2" }
+//!
+//! [features]
+//! altio = ["altio/altio"]
+//! ```
+//!
+//! Cargo enables the features of dev-dependencies only for the targets needing them,
+//! so `Altio` simulates io in tests and `cfg( test )` selects the driving code:
+//! `#[cfg( test )] io.send_line( .. );`. However, `echo!` and `impl_altio_output!`
+//! check `feature = "altio"` of the tool's crate, not of altio, so without the tool's
+//! own feature above their output goes to stdio even in tests. Run the tests with
+//! `cargo test --features altio`, or use `writeln!( io.out(), .. )` directly.
+//!
 //! # Why this crate
 //!
 //! Interactive command tools utilize stdin, stdout and stderr for communication.
@@ -19,21 +40,346 @@
 //! 1. Define an `Altio` variable e.g. `let io = Altio::default();`.
 //!
 //! 2. Replace std APIs with altio's equivalents, e.g. replace `println!(...)` with
-//! `writeln!( io.out(), ... )`, replace `std::io::stdin()` with `io.input()`.
+//!    `writeln!( io.out(), ... )`, replace `std::io::stdin()` with `io.input()`.
 //!
 //! 3. Keep main.rs as simple as possible, e.g. `fn main() { the_tool::run( std::env::args_os() )}`.
 //!
+//! # Features
+//!
+//! - `altio`: enables the simulated io. Without it, `Altio` falls back to stdio.
+//!
+//! - `memchr`: searches newlines and delimiters with the `memchr` crate.
+//!
+//! - `zstd`: compresses the retained output history.
+//!
+//! - `trace-io`: emits `tracing` events with target "altio" for every send, write and
+//!   receive, and spans for blocking waits. Mirrored traffic can be emitted as
+//!   `tracing` events too, see `MirrorTarget`.
+//!
+//! - `proptest`: property-based testing helpers in module `prop`, generating input
+//!   of random line lengths and unicode content sent in random pieces.
+//!
+//! - `predicates`: `AltOutput::assert()` for `assert_cmd` style assertions with the
+//!   `predicates` crate, in module `assert`.
+//!
+//! - `trycmd`: runs `trycmd` case files against embedded tools in module `trycmd`,
+//!   without spawning binaries.
+//!
+//! - `expectrl`: drives embedded tools with `expectrl` sessions in module `expectrl`,
+//!   without a PTY.
+//!
+//! - `cucumber`: reusable `cucumber` steps for interactive sessions in module
+//!   `cucumber`.
+//!
+//! - `anstyle`: `anstream` streams writing into altio streams, which strip ANSI
+//!   escape codes unless colors are enabled by `Altio::color_support()`.
+//!
+//! - `os-pipe`: `backend::OsPipes` backing the streams by OS pipes, whose file
+//!   descriptors the tool can use directly.
+//!
+//! - `shm`: `backend::SharedMemory` sharing the streams with another process through
+//!   ring buffers in a shared memory file, on unix.
+//!
+//! - `duct`: composes embedded tools with external commands in `duct` pipelines, in
+//!   module `duct`.
+//!
+//! - `regex`: `Altio::auto_respond()` answering prompts matching regular expressions,
+//!   `MatchKind::Regex` patterns, and `Altio::expect_regex()` returning capture groups.
+//!
+//! - `tokio`: `Altio::feed_from_channel()` forwarding the messages of a `tokio` channel
+//!   into the input stream, and `TokioClock` measuring timeouts by the clock of the
+//!   runtime, so that tests pausing the time run instantly.
+//!
+//! - `monitor`: a live `ratatui` terminal UI showing the streams of the sessions in
+//!   `registry`, in module `monitor`.
+//!
+//! # Timeouts
+//!
+//! Receiving functions with the `_timeout` suffix give up after a timeout, measured
+//! by the clock given to `AltioBuilder::clock()`. Tests can use a `ManualClock` to
+//! advance time instead of sleeping.
+//!
+//! # Async
+//!
+//! Receiving functions with the `_async` suffix return futures which are woken when
+//! the stream changes, so async host applications can drive tools without wrapping
+//! every call in `spawn_blocking`. They work with any executor.
+//!
 //! # License
 //!
 //! Under Apache License 2.0 or MIT License, at your will.
 
+mod buffer;
+pub use buffer::Buffer;
+use buffer::{into_string, raw_offset};
+
+mod history;
+pub use history::Retention;
+use history::History;
+
+mod metrics;
+pub use metrics::{Metrics, StreamMetrics};
+use metrics::Stats;
+
+mod trace;
+use trace::WaitSpan;
+
+mod fault;
+pub use fault::{Delay, When};
+use fault::{Injector, Latency};
+
+mod cooked;
+pub use cooked::{ControlChars, InvalidUtf8};
+use cooked::LineEditor;
+
+pub mod source;
+use source::{Next, Source, Sources};
+
+mod transaction;
+pub use transaction::Transaction;
+
+mod buffering;
+pub use buffering::Buffering;
+use buffering::WriteBuffer;
+
+mod console;
+pub use console::Console;
+use console::Translator;
+
+mod frame;
+use frame::Frames;
+
+mod peek;
+use peek::Snapshot;
+
+mod pattern;
+pub use pattern::{MatchKind, Pattern, StreamMatcher};
+#[cfg( feature = "regex" )]
+pub use pattern::Match;
+
+#[cfg( feature = "altio" )]
+mod log;
+
+#[cfg( feature = "altio" )]
+mod mirror;
+#[cfg( feature = "altio" )]
+use mirror::Mirror;
+#[cfg( feature = "altio" )]
+pub use mirror::MirrorTarget;
+
+#[cfg( feature = "altio" )]
+mod hex;
+
+mod html;
+
+mod channel;
+pub use channel::ProgressEvent;
+#[cfg( feature = "altio" )]
+pub use channel::{Events, IoEvent, StatusWatcher};
+#[cfg( feature = "altio" )]
+use channel::{Channels, Checks, EventLog};
+
+pub mod prompt;
+pub use prompt::{Prompt, PromptEvent};
+#[cfg( feature = "altio" )]
+use prompt::CONTROL;
+
+pub mod transcript;
+#[cfg( feature = "altio" )]
+use transcript::{Recording, Transcript};
+
+mod interleave;
+use interleave::Interleave;
+
+mod rng;
+
+mod ready;
+use ready::Ready;
+
+mod env;
+pub use env::{Altenv, ColorChoice};
+
+#[cfg( feature = "altio" )]
+mod command;
+#[cfg( feature = "altio" )]
+pub use command::{AltChild, AltCommand, AltOutput, IntoExitCode, run_to_completion};
+
+#[cfg( feature = "altio" )]
+pub mod backend;
+#[cfg( feature = "altio" )]
+use backend::{AltBackend, Backend, Sink};
+
+#[cfg( feature = "altio" )]
+mod cursor;
+#[cfg( feature = "altio" )]
+pub use cursor::OutputCursor;
+
+#[cfg( feature = "altio" )]
+mod token;
+#[cfg( feature = "altio" )]
+pub use token::SendToken;
+
+#[cfg( feature = "altio" )]
+pub mod registry;
+
+#[cfg( feature = "altio" )]
+pub mod expect;
+
+#[cfg( feature = "altio" )]
+pub mod assert;
+#[cfg( feature = "altio" )]
+mod diff;
+#[cfg( feature = "altio" )]
+pub use assert::install_panic_dump;
+
+#[cfg( feature = "trycmd" )]
+pub mod trycmd;
+
+#[cfg( feature = "expectrl" )]
+pub mod expectrl;
+
+#[cfg( feature = "cucumber" )]
+pub mod cucumber;
+
+#[cfg( feature = "anstyle" )]
+pub mod anstyle;
+
+#[cfg( feature = "monitor" )]
+pub mod monitor;
+
+#[cfg( feature = "duct" )]
+pub mod duct;
+
+#[cfg( feature = "regex" )]
+mod respond;
+#[cfg( feature = "regex" )]
+use respond::Responder;
+
+mod clock;
+pub use clock::{Clock, ManualClock, SystemClock};
+#[cfg( feature = "tokio" )]
+pub use clock::TokioClock;
+
+mod sched;
+pub use sched::Scheduler;
+use sched::Hook;
+
+#[cfg( feature = "proptest" )]
+pub mod prop;
+
 use std::{
-    fmt::Arguments,
-    io::Result,
+    cell::{Cell, OnceCell},
+    fmt::{self, Arguments},
+    io::{Error, ErrorKind, Result},
     ops::{Deref, DerefMut},
-    sync::{Mutex, MutexGuard},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError, RwLock, TryLockError,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg( feature = "altio" )]
+use std::{
+    future::poll_fn,
+    task::{Context, Poll},
 };
 
+/// The longest time a blocking read sleeps before polling its stream again without
+/// being notified, e.g. for text held back by interleaving.
+const POLL_INTERVAL: Duration = Duration::from_millis( 1 );
+
+/// The error of operations on the streams of a killed tool, see `AltChild::kill()`.
+fn killed() -> Error {
+    Error::new( ErrorKind::BrokenPipe, "tool killed by altio" )
+}
+
+/// Locks `mutex` unless it is locked by now, ignoring poisoning like other locks.
+fn try_lock<T>( mutex: &Mutex<T> ) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok( guard ) => Some( guard ),
+        Err( TryLockError::Poisoned( poisoned )) => Some( poisoned.into_inner() ),
+        Err( TryLockError::WouldBlock ) => None,
+    }
+}
+
+/// Blocks the current thread until `f` returns some text from the data guarded by
+/// `mutex`, sleeping on `ready` or calling the scheduler of `stream` between
+/// attempts. Gives up and returns `None` once `clock` reaches the `deadline`, if any.
+fn wait_for<T,R>( mutex: &Mutex<T>, ready: &Ready, stream: Stream, hook: &Hook,
+                  clock: &dyn Clock, deadline: Option<Instant>,
+                  mut f: impl FnMut( &mut T ) -> Option<R> ) -> Option<R>
+{
+    loop {
+        let mut guard = mutex.lock().unwrap_or_else( PoisonError::into_inner );
+        let scheduler = loop {
+            if let Some( received ) = f( &mut guard ) {
+                return Some( received );
+            }
+            if deadline.is_some_and( |deadline| clock.now() >= deadline ) {
+                return None;
+            }
+            if let Some( scheduler ) = hook.get() {
+                break scheduler;
+            }
+            guard = ready.wait_timeout( guard, POLL_INTERVAL ).unwrap_or_else( PoisonError::into_inner ).0;
+        };
+        drop( guard );
+        scheduler.block( stream );
+    }
+}
+
+/// Identifies one of the streams of a simulated program.
+#[derive( Clone, Copy, Debug, PartialEq, Eq, Hash )]
+pub enum Stream {
+    /// The input stream, corresponding to stdin.
+    In,
+    /// The output stream, corresponding to stdout.
+    Out,
+    /// The error stream, corresponding to stderr.
+    Err,
+}
+
+impl Stream {
+    /// Returns "in", "out" or "err".
+    pub fn name( self ) -> &'static str {
+        match self {
+            Stream::In  => "in",
+            Stream::Out => "out",
+            Stream::Err => "err",
+        }
+    }
+}
+
+/// A line received by `recv_line_partial_timeout()`.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub enum Line {
+    /// A line ending with a newline.
+    Complete( String ),
+    /// The text pending when no newline arrived in time, or the rest of a closed
+    /// stream without trailing newline.
+    Partial( String ),
+}
+
+impl Line {
+    /// Returns the text received.
+    pub fn as_str( &self ) -> &str {
+        match self {
+            Line::Complete( text ) | Line::Partial( text ) => text,
+        }
+    }
+
+    /// Returns true if the line has no trailing newline.
+    pub fn is_partial( &self ) -> bool { matches!( self, Line::Partial(_) )}
+
+    /// Returns the text received.
+    pub fn into_string( self ) -> String {
+        match self {
+            Line::Complete( text ) | Line::Partial( text ) => text,
+        }
+    }
+}
+
 /// This macro `write`s formatted data into a buffer, or panic on failures.
 ///
 /// In the form of `echo!( -n, ... )`, the data will be written as is, otherwise an
@@ -55,100 +401,452 @@ macro_rules! echo {
 }
 
 /// Corresponding to std::io::StdinLock
+///
+/// Blocking reads release the lock while waiting, so that other threads can send
+/// text.
 pub struct AltinLock<'a> {
-    inner: MutexGuard<'a, String>,
+    inner: Option<MutexGuard<'a, Buffer>>, // `None` only while waiting
+    altin: &'a Altin,
 }
 
 impl<'a> AltinLock<'a> {
-    /// Reads a line of input, appending it to the specified buffer.
+    /// Reads a line of input, appending it to the specified buffer, blocking until a
+    /// complete line is sent. Returns the number of bytes appended, and 0 only at
+    /// end-of-file, like `Altin::read_line()`.
+    ///
+    /// Migration note: earlier versions returned the length of `buf` instead, and 0
+    /// without blocking if no complete line was available, which `try_read_line()`
+    /// does now with an error of `ErrorKind::WouldBlock`.
     pub fn read_line( &mut self, buf: &mut String ) -> Result<usize> {
-        if let Some( offset ) = self.inner.find( '\n' ) {
-            buf.extend( self.inner.drain( ..=offset ));
-            Ok( buf.len() )
-        } else {
-            Ok( 0 )
+        self.altin.check()?;
+        let altin = self.altin;
+        match altin.find_newline( self.input() ) {
+            Some( offset ) if !altin.latency.is_injected() => self.take_line( offset+1, buf ),
+            _ => self.unlocked( |altin| altin.read_line( buf )),
+        }
+    }
+
+    /// Reads a line of input like `read_line()` without blocking, failing with
+    /// `ErrorKind::WouldBlock` if no complete line is available. Once the input is
+    /// closed, the rest of it is read as the last line.
+    pub fn try_read_line( &mut self, buf: &mut String ) -> Result<usize> {
+        self.altin.check()?;
+        self.delay();
+        let altin = self.altin;
+        match altin.find_newline( self.input() ) {
+            Some( offset ) => self.take_line( offset+1, buf ),
+            None if altin.at_eof() => {
+                let len = self.input().len();
+                self.take_line( len, buf )
+            }
+            None => {
+                altin.stats.received( 0 );
+                Err( ErrorKind::WouldBlock.into() )
+            }
         }
     }
 
     /// Reads all contents in this source, appending them to buf.
     pub fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
-        if !self.inner.is_empty() {
-            let len = self.inner.len();
-            buf.extend( self.inner.drain(..) );
+        self.altin.check()?;
+        self.delay();
+        let altin = self.altin;
+        if !self.input().is_empty() || altin.pull( self.input() ) {
+            let len = self.input().len();
+            let text = self.input().take_all();
+            altin.stats.received( len );
+            altin.consumed( len );
+            buf.push_str( &altin.decode( text )? );
             Ok( len )
         } else {
+            altin.stats.received( 0 );
             Ok(0)
         }
     }
 
+    /// Reads all bytes until end-of-file, see `Altin::read_to_end()`.
+    pub fn read_to_end( &mut self, buf: &mut Vec<u8> ) -> Result<usize> {
+        self.unlocked( |altin| altin.read_to_end( buf ))
+    }
+
+    /// Pushes `text` back to the front of the input, so the next read sees it first.
+    pub fn unread( &mut self, text: &str ) {
+        self.input().push_front( text.as_bytes() );
+    }
+
     /// Returns false to indicate it isn't a terminal/tty.
     pub fn is_terminal( &self ) -> bool { false }
+
+    fn input( &mut self ) -> &mut Buffer {
+        self.inner.as_mut().expect( "altin lock is held except while waiting" )
+    }
+
+    /// Sleeps for the latency injected with the lock released, if any.
+    fn delay( &mut self ) {
+        if self.altin.latency.is_injected() {
+            self.unlocked( |altin| altin.latency.apply() );
+        }
+    }
+
+    /// Calls `f` with the lock released, e.g. to wait for input.
+    fn unlocked<R>( &mut self, f: impl FnOnce( &Altin ) -> R ) -> R {
+        self.inner = None;
+        let result = f( self.altin );
+        self.inner = Some( self.altin.input.lock().unwrap_or_else( PoisonError::into_inner ));
+        result
+    }
+
+    /// Removes the first `len` bytes of input as a line, appending it to `buf`.
+    fn take_line( &mut self, len: usize, buf: &mut String ) -> Result<usize> {
+        let line = self.input().take( len );
+        self.altin.stats.received( len );
+        self.altin.consumed( len );
+        let line = self.altin.decode( line )?;
+        let line = self.altin.control.lock().unwrap_or_else( PoisonError::into_inner ).apply( line )?;
+        buf.push_str( &line );
+        Ok( line.len() )
+    }
+}
+
+/// Reads the input, blocking until some is sent, and reads as end-of-file once the
+/// input is closed.
+impl<'a> std::io::Read for AltinLock<'a> {
+    fn read( &mut self, buf: &mut [u8] ) -> Result<usize> {
+        let available = std::io::BufRead::fill_buf( self )?;
+        let len = available.len().min( buf.len() );
+        buf[ ..len ].copy_from_slice( &available[ ..len ]);
+        std::io::BufRead::consume( self, len );
+        Ok( len )
+    }
+}
+
+impl<'a> std::io::BufRead for AltinLock<'a> {
+    fn fill_buf( &mut self ) -> Result<&[u8]> {
+        self.altin.check()?;
+        let altin = self.altin;
+        if self.input().is_empty() && !altin.pull( self.input() ) {
+            self.unlocked( |altin| altin.wait( |input| ( !input.is_empty() ).then( Vec::new )))?;
+        }
+        Ok( self.input().front() )
+    }
+
+    fn consume( &mut self, amt: usize ) {
+        self.input().take( amt );
+        self.altin.stats.received( amt );
+        self.altin.consumed( amt );
+    }
 }
 
 /// Corresponding to `std::io::Lines`
+///
+/// The input is locked for reading each line only, so that text can be sent in
+/// between, e.g. by a driver while a tool iterates over the lines lazily. Unlike
+/// `std::io::Lines`, yielding no errors, it ends at a line failing to be read, e.g.
+/// one of invalid UTF-8.
 pub struct Lines<'a> {
-    inner: MutexGuard<'a, String>,
+    altin    : &'a Altin,
+    blocking : bool,
 }
 
 impl<'a> Iterator for Lines<'a> {
     type Item = String;
     fn next( &mut self ) -> Option<String> {
-        self.inner
-            .find( '\n' )
-            .map( |offset| String::from_iter( self.inner.drain( ..=offset )))
+        let mut line = String::new();
+        let read = if self.blocking {
+            self.altin.read_line( &mut line )
+        } else {
+            self.altin.try_read_line( &mut line )
+        };
+        match read {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some( line ),
+        }
     }
 }
 
 /// Corresponding to std::io::Stdin
-#[derive( Debug, Default )]
-pub struct Altin( Mutex<String> );
+///
+/// Sending functions are safe to call from several threads: the text of each call
+/// is appended as a whole, never interleaved with the text of other calls, and in
+/// FIFO order, i.e. after the text of all calls returned before it started.
+#[derive( Debug )]
+pub struct Altin {
+    input   : Mutex<Buffer>,
+    editor  : Mutex<Option<LineEditor>>,
+    control : Mutex<ControlChars>,
+    utf8    : Mutex<InvalidUtf8>,
+    console : Mutex<Translator>,
+    ready   : Ready,
+    stats   : Stats,
+    fault   : Injector,
+    latency : Latency,
+    hook    : Hook,
+    clock   : Arc<dyn Clock>,
+    killed  : AtomicBool,
+    closed  : AtomicBool,
+    urgent  : AtomicU64, // where the lines sent by `send_priority_line()` end
+    #[cfg( feature = "altio" )]
+    tracked : AtomicUsize, // the number of lines sent by `Altio::send_line_tracked()`
+    sources : Mutex<Sources>,
+    stdio   : AtomicBool,
+    #[cfg( feature = "altio" )]
+    mirror  : Mirror,
+    #[cfg( feature = "altio" )]
+    sink    : Mutex<Option<Sink>>,
+    #[cfg( feature = "altio" )]
+    events  : Arc<EventLog>,
+}
+
+impl Default for Altin {
+    fn default() -> Self {
+        Altin {
+            input   : Mutex::default(),
+            editor  : Mutex::default(),
+            control : Mutex::default(),
+            utf8    : Mutex::default(),
+            console : Mutex::default(),
+            ready   : Ready::default(),
+            stats   : Stats::new( Stream::In ),
+            fault   : Injector::default(),
+            latency : Latency::default(),
+            hook    : Hook::default(),
+            clock   : Arc::new( SystemClock ),
+            killed  : AtomicBool::new( false ),
+            closed  : AtomicBool::new( false ),
+            urgent  : AtomicU64::new( 0 ),
+            #[cfg( feature = "altio" )]
+            tracked : AtomicUsize::new( 0 ),
+            sources : Mutex::default(),
+            stdio   : AtomicBool::new( false ),
+            #[cfg( feature = "altio" )]
+            mirror  : Mirror::new( Stream::In ),
+            #[cfg( feature = "altio" )]
+            sink    : Mutex::default(),
+            #[cfg( feature = "altio" )]
+            events  : Arc::default(),
+        }
+    }
+}
 
 impl Altin {
+    /// Blocks until `f` returns some bytes, polling the input whenever it is sent, or
+    /// fails if the tool is killed. Once the input is closed, returns the rest of it
+    /// instead of blocking, which is empty at end-of-file.
+    fn wait( &self, mut f: impl FnMut( &mut Buffer ) -> Option<Vec<u8>> ) -> Result<Vec<u8>> {
+        let since = Instant::now();
+        let received = {
+            let _span = WaitSpan::enter( Stream::In );
+            wait_for( &self.input, &self.ready, Stream::In, &self.hook, &*self.clock, None, |input| {
+                if self.killed.load( Ordering::Relaxed ) {
+                    Some( Err( killed() ))
+                } else {
+                    let received = loop {
+                        match f( input ) {
+                            Some( received ) => break Some( received ),
+                            None if !self.pull( input ) => break None,
+                            None => (),
+                        }
+                    };
+                    received
+                        .or_else( || self.at_eof().then( || input.take_all() ))
+                        .map( Ok )
+                }
+            }).expect( "waiting without deadline" )?
+        };
+        self.latency.apply();
+        self.stats.received_since( since, received.len() );
+        self.consumed( received.len() );
+        Ok( received )
+    }
+
+    /// Decodes the bytes read as text, see `InvalidUtf8`.
+    fn decode( &self, bytes: Vec<u8> ) -> Result<String> {
+        self.utf8.lock().unwrap_or_else( PoisonError::into_inner ).decode( bytes )
+    }
+
+    /// Notifies the driver that the tool has read `bytes` bytes of the input.
+    fn consumed( &self, bytes: usize ) {
+        if bytes > 0 {
+            #[cfg( feature = "altio" )]
+            self.events.record( || IoEvent::InputConsumed{ bytes });
+            self.ready.notify_all();
+        }
+    }
+
+    /// Waits until the tool has read all the input sent, blocking at most `timeout`
+    /// measured by the clock of the `Altio`. Returns false on timeout.
+    pub fn wait_consumed( &self, timeout: Duration ) -> bool {
+        let deadline = self.clock.now() + timeout;
+        wait_for( &self.input, &self.ready, Stream::In, &self.hook, &*self.clock, Some( deadline ),
+            |input| input.is_empty().then_some(()) ).is_some()
+    }
+
+    /// Fails if the tool is killed or an error is injected.
+    fn check( &self ) -> Result<()> {
+        if self.killed.load( Ordering::Relaxed ) {
+            return Err( killed() );
+        }
+        self.fault.check()
+    }
+
+    /// Returns true if the input is closed, or attached to the real stdin which has
+    /// been read to the end.
+    fn at_eof( &self ) -> bool {
+        self.closed.load( Ordering::Relaxed )
+            || self.stdio.load( Ordering::Relaxed ) && source::stdin_done()
+    }
+
+    /// Pulls a line from the real stdin if attached, otherwise from the attached
+    /// sources, into `input`, returning false if none of them has one.
+    fn pull( &self, input: &mut Buffer ) -> bool {
+        let line = if self.stdio.load( Ordering::Relaxed ) {
+            match source::Stdin.next_line() {
+                Next::Line( line ) => Some( line ),
+                _ => None,
+            }
+        } else {
+            self.sources.lock().unwrap_or_else( PoisonError::into_inner ).next_line()
+        };
+        match line {
+            Some( line ) => {
+                let ( line, newline ) = {
+                    let mut console = self.console.lock().unwrap_or_else( PoisonError::into_inner );
+                    ( console.send( &line ).into_owned(), console.send( "\n" ))
+                };
+                input.push( line.as_bytes() );
+                input.push( newline.as_bytes() );
+                self.stats.wrote( line.as_bytes(), input.len() );
+                self.stats.wrote( newline.as_bytes(), input.len() );
+                #[cfg( feature = "altio" )]
+                {
+                    self.mirror.write( line.as_bytes() );
+                    self.mirror.write( newline.as_bytes() );
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the offset of the first newline in `input`, pulling lines from the
+    /// attached sources if there is none.
+    fn find_newline( &self, input: &mut Buffer ) -> Option<usize> {
+        loop {
+            match input.find_newline() {
+                Some( offset ) => return Some( offset ),
+                None if !self.pull( input ) => return None,
+                None => (),
+            }
+        }
+    }
+
+    /// Attaches a source of lines, asked for one when the tool reads a line and
+    /// none is pending, before the sources of lower `precedence` and after those
+    /// attached before with the same one. Exhausted sources are detached.
+    pub fn attach( &self, source: impl Source + 'static, precedence: i32 ) {
+        self.sources.lock().unwrap_or_else( PoisonError::into_inner ).attach( Box::new( source ), precedence );
+        self.ready.notify_all();
+    }
+
+    /// Detaches all sources, see `attach()`.
+    pub fn detach_all( &self ) { self.sources.lock().unwrap_or_else( PoisonError::into_inner ).clear() }
+
+    /// Closes the input stream, like closing the writing end of a pipe: blocking reads
+    /// return the rest of the input, then end-of-file, i.e. 0 bytes read.
+    pub fn close( &self ) {
+        let mut editor = self.editor.lock().unwrap_or_else( PoisonError::into_inner );
+        if let Some( editor ) = editor.as_mut() {
+            self.push( &[ editor.finish().as_bytes() ]);
+        }
+        self.closed.store( true, Ordering::Relaxed );
+        #[cfg( feature = "altio" )]
+        self.mirror.log( log::Event::Eof( Stream::In ));
+        self.ready.notify_all();
+    }
+
+    /// Turns on or off canonical line editing, like a terminal in cooked mode: sent
+    /// text is held back until a newline, and backspace or delete ("\x08", "\x7f")
+    /// erases the previous char, Ctrl-U ("\x15") the line, Ctrl-W ("\x17") the word.
+    ///
+    /// Turning it off makes the line being edited visible as is.
+    pub fn cooked( &self, enabled: bool ) {
+        let mut editor = self.editor.lock().unwrap_or_else( PoisonError::into_inner );
+        if enabled {
+            editor.get_or_insert_with( LineEditor::default );
+        } else if let Some( mut editor ) = editor.take() {
+            self.push( &[ editor.finish().as_bytes() ]);
+        }
+    }
+
+    /// Makes reads fail from now on, waking up the blocked ones.
+    #[cfg( feature = "altio" )]
+    fn kill( &self ) {
+        self.killed.store( true, Ordering::Relaxed );
+        self.ready.notify_all();
+    }
+
     /// Locks this handle to the altio input stream, returning a readable guard.
     ///
     /// The lock is released when the returned lock goes out of scope.
     /// The returned guard also provides read_line(), read_to_string(), is_terminal()
     /// for accessing the underlying data.
     pub fn lock( &self ) -> AltinLock<'_> {
-        loop {
-            if let Ok( lock ) = self.0.lock() {
-                break AltinLock{ inner: lock };
-            }
-        }
+        AltinLock{ inner: Some( self.input.lock().unwrap_or_else( PoisonError::into_inner )), altin: self }
     }
 
-    /// Consumes this handle and returns an iterator over input lines.
+    /// Returns an iterator over input lines, blocking for each line until it is sent
+    /// and ending at end-of-file. Like `BufRead::lines()`, the rest of the input after
+    /// the last newline is yielded as the last line.
     pub fn lines( &self ) -> Lines<'_> {
-        loop {
-            if let Ok( lock ) = self.0.lock() {
-                break Lines{ inner: lock };
-            }
-        }
+        Lines{ altin: self, blocking: true }
+    }
+
+    /// Returns an iterator over the input lines available now, without blocking.
+    pub fn try_lines( &self ) -> Lines<'_> {
+        Lines{ altin: self, blocking: false }
     }
 
     /// Locks this handle and reads a line of input, appending it to the specified buffer.
+    /// Returns the number of bytes appended, like `std::io::Stdin::read_line()`, and 0
+    /// only at end-of-file.
+    ///
+    /// Migration note: earlier versions returned the length of `buf` instead. Loops
+    /// reading until 0 and clearing `buf` in between work the same, while code taking
+    /// the result as the length of `buf` should call `buf.len()`.
     pub fn read_line( &self, buf: &mut String ) -> Result<usize> {
-        loop {
-            if let Ok( ref mut input ) = self.0.lock() {
-                if let Some( offset ) = input.find( '\n' ) {
-                    buf.extend( input.drain( ..=offset ));
-                    return Ok( buf.len() );
-                }
-            }
-        }
+        self.check()?;
+        let line = self.wait( |input| input.find_newline().map( |offset| input.take( offset+1 )))?;
+        let line = self.decode( line )?;
+        let line = self.control.lock().unwrap_or_else( PoisonError::into_inner ).apply( line )?;
+        buf.push_str( &line );
+        Ok( line.len() )
+    }
+
+    /// Reads a line of input without blocking, see `AltinLock::try_read_line()`.
+    pub fn try_read_line( &self, buf: &mut String ) -> Result<usize> {
+        self.lock().try_read_line( buf )
     }
 
     /// Read all contents in this source, appending them to buf.
     pub fn read_to_string(&self, buf: &mut String) -> Result<usize> {
-        loop {
-            if let Ok( ref mut input ) = self.0.lock() {
-                if !input.is_empty() {
-                    let len = input.len();
-                    buf.extend( input.drain(..) );
-                    return Ok( len );
-                }
-            }
-        }
+        self.check()?;
+        let text = self.wait( |input| ( !input.is_empty() ).then( || input.take_all() ))?;
+        let text = self.decode( text )?;
+        buf.push_str( &text );
+        Ok( text.len() )
+    }
+
+    /// Reads all bytes until end-of-file, blocking until the input is closed, appending
+    /// them to `buf`. Returns the number of bytes read.
+    pub fn read_to_end( &self, buf: &mut Vec<u8> ) -> Result<usize> {
+        self.check()?;
+        let mut bytes = self.wait( |input| self.at_eof().then( || {
+            while self.pull( input ) {}
+            input.take_all()
+        }))?;
+        let len = bytes.len();
+        buf.append( &mut bytes );
+        Ok( len )
     }
 
     /// Returns false to indicate it isn't a terminal/tty.
@@ -156,614 +854,4516 @@ impl Altin {
 }
 
 /// Corresponding to std::io::StdoutLock
+///
+/// Dereferences to the pending text as a `String`, joined on demand. Changes made
+/// through it replace the pending text before the next write, or on unlocking. If
+/// text spilled to disk can't be read back, it dereferences to the text in memory,
+/// changes are dropped and the next write fails with the error.
 pub struct AltoutLock<'a> {
-    inner: MutexGuard<'a, String>,
+    inner  : Option<MutexGuard<'a, Output>>, // `None` only while delaying
+    altout : &'a Altout,
+    joined : OnceCell<String>,
+    edited : bool,
+    failed : Cell<Option<Error>>, // of joining the pending text
 }
 
 impl<'a> AltoutLock<'a> {
-    /// Writes a formatted string into Altout, won't returning any error.
+    /// Writes a formatted string into Altout, won't returning any error unless
+    /// injected by `Altio::inject_error()`, or of reading back text spilled to disk,
+    /// see `Altio::spill_to_disk()`, or of a `Display` impl failing, in which case the
+    /// text formatted before the failure is still written.
     pub fn write_fmt( &mut self, args: Arguments<'_> ) -> Result<()> {
-        use std::fmt::Write;
-        self.inner.write_fmt( args ).map_err( |_| unreachable!() )
+        self.check()?;
+        self.delay();
+        let result = fmt::Write::write_fmt( self, args ).map_err( |_| Error::other( "formatter error" ));
+        self.output().end_message();
+        result
     }
-}
 
-impl<'a> Deref for AltoutLock<'a> {
-    type Target = String;
-    fn deref( &self ) -> &String {
-        self.inner.deref()
+    /// Appends a string slice to Altout.
+    pub fn push_str( &mut self, text: &str ) {
+        self.delay();
+        self.write( text );
+        self.output().end_message();
     }
-}
 
-impl<'a> DerefMut for AltoutLock<'a> {
-    fn deref_mut( &mut self ) -> &mut String {
-        self.inner.deref_mut()
+    fn write( &mut self, text: &str ) { self.write_bytes( text.as_bytes() )}
+
+    /// Joins the pending text, or the text in memory if the spilled text can't be
+    /// read back, keeping the error.
+    fn join( &self ) -> String {
+        let buf = &self.inner.as_ref().expect( "altout lock is held except while delaying" ).buf;
+        buf.joined().unwrap_or_else( |( err, text )| {
+            self.failed.set( Some( err ));
+            text
+        })
     }
-}
 
-/// Corresponding to std::io::Stdout
-#[derive( Debug, Default )]
-pub struct Altout( Mutex<String> );
+    fn output( &mut self ) -> &mut Output {
+        self.inner.as_mut().expect( "altout lock is held except while delaying" )
+    }
 
-impl Altout {
-    /// Locks this handle to the altio output stream, returning a writable guard.
-    ///
-    /// The lock is released when the returned lock goes out of scope. The returned
-    /// guard also provide write_fmt() for writing data.
-    pub fn lock( &self ) -> AltoutLock<'_> {
-        loop {
-            if let Ok( lock ) = self.0.lock() {
-                return AltoutLock{ inner: lock };
-            }
+    /// Sleeps for the latency injected with the lock released, so that receivers are
+    /// not blocked meanwhile.
+    fn delay( &mut self ) {
+        if self.altout.latency.is_injected() {
+            self.write_back();
+            self.inner = None;
+            self.altout.latency.apply();
+            self.inner = Some( self.altout.lock_output() );
         }
     }
-    /// Writes a formatted string into Altout, won't returning any error.
-    pub fn write_fmt( &mut self, args: Arguments<'_> ) -> Result<()> {
-        use std::fmt::Write;
-        self.lock().inner.write_fmt( args ).map_err( |_| unreachable!() )
-    }
-    /// No-op.
-    pub fn flush( &mut self ) -> Result<()> {
-        Ok(())
+
+    /// Fails with the error injected, or of reading back the spilled text which has
+    /// been lost, reported once.
+    fn check( &mut self ) -> Result<()> {
+        self.altout.check()?;
+        self.write_back();
+        self.output().buf.take_error().map_or( Ok(()), Err )
     }
 
-    /// Returns false to indicate it isn't a terminal/tty.
-    pub fn is_terminal( &self ) -> bool { false }
-}
+    fn write_bytes( &mut self, bytes: &[u8] ) {
+        self.write_back();
+        let translated = self.output().console.write( bytes );
+        let bytes = &*translated;
+        #[cfg( feature = "altio" )]
+        self.altout.events.record( || IoEvent::written( self.altout.stats.stream, bytes ));
+        #[cfg( feature = "altio" )]
+        if let Some( recent ) = self.altout.recent.write().unwrap_or_else( PoisonError::into_inner ).as_mut() {
+            recent.extend_from_slice( bytes );
+        }
+        #[cfg( feature = "altio" )]
+        if self.altout.strict {
+            let mut violations = self.altout.flagged.lock().unwrap_or_else( PoisonError::into_inner );
+            if violations.is_empty() {
+                violations.push( format!( "{} stream written in strict mode: {:?}", self.altout.stats.stream.name(), into_string( bytes.to_vec() )));
+            }
+        }
+        if self.altout.stdio.load( Ordering::Relaxed ) {
+            self.altout.stats.wrote( bytes, 0 );
+            #[cfg( feature = "altio" )]
+            self.altout.mirror.record( bytes );
+            self.altout.passthrough( bytes );
+            return;
+        }
+        #[cfg( feature = "altio" )]
+        if let Some( Sink( sink )) = self.altout.sink.lock().unwrap_or_else( PoisonError::into_inner ).as_mut() {
+            self.altout.stats.wrote( bytes, 0 );
+            self.altout.mirror.write( bytes );
+            let _ = sink.write_all( bytes );
+            return;
+        }
+        let now = self.altout.clock.now();
+        self.output().write( bytes, now );
+        self.altout.stats.wrote( bytes, self.output().len() );
+        #[cfg( feature = "altio" )]
+        self.altout.mirror.write( bytes );
+        #[cfg( feature = "regex" )]
+        self.altout.respond.lock().unwrap_or_else( PoisonError::into_inner ).scan( bytes );
+        self.altout.ready.notify_all();
+    }
 
-#[inline]
-fn get_lines<'a>( buf: &mut MutexGuard<'a,String>, mut cnt: usize, peek_only: bool ) -> Option<String> {
-    let mut offset = 0;
-    while let Some( mut off ) = buf[offset..].find( '\n' ) {
-        off += 1;
-        offset += off;
-        cnt -= 1;
-        if cnt == 0 {
-            break;
+    /// Makes all written text visible to receivers, or fails with an error injected
+    /// by `Altio::inject_error()`.
+    pub fn flush( &mut self ) -> Result<()> {
+        self.check()?;
+        self.write_back();
+        self.output().flush();
+        self.altout.ready.notify_all();
+        if self.altout.stdio.load( Ordering::Relaxed ) {
+            self.altout.flush_stdio()?;
+        }
+        #[cfg( feature = "altio" )]
+        if let Some( Sink( sink )) = self.altout.sink.lock().unwrap_or_else( PoisonError::into_inner ).as_mut() {
+            sink.flush()?;
         }
+        Ok(())
     }
-    if cnt != 0 {
-        None
-    } else if peek_only {
-        Some( buf[ ..offset ].to_owned() )
-    } else {
-        Some( String::from_iter( buf.drain( ..offset )))
+
+    /// Appends a char to Altout.
+    pub fn push( &mut self, ch: char ) {
+        self.push_str( ch.encode_utf8( &mut [0; 4] ));
     }
-}
 
-impl Altin {
-    /// Sends text to altio input stream, without additional newline.
-    pub fn send( &self, text: &str ) {
-        if !text.is_empty() {
-            loop {
-                if let Ok( mut buf ) = self.0.lock() {
-                    buf.push_str( text );
-                    return;
-                }
+    /// Drops the joined pending text, replacing the pending text with it if changed
+    /// and joined completely, otherwise keeping the error of joining to be reported.
+    fn write_back( &mut self ) {
+        if let Some( text ) = self.joined.take() {
+            let edited = std::mem::take( &mut self.edited );
+            if let Some( err ) = self.failed.take() {
+                self.output().buf.set_error( err );
+            } else if edited {
+                self.output().edit( text.as_bytes() );
+                self.altout.ready.notify_all();
             }
         }
     }
+}
 
-    /// Sends text to altio input stream, with an additional newline.
-    pub fn send_line( &self, text: &str ) {
-        loop {
-            if let Ok( mut buf ) = self.0.lock() {
-                buf.push_str( text );
-                buf.push( '\n' );
-                return;
-            }
-        }
+impl<'a> std::io::Write for AltoutLock<'a> {
+    fn write( &mut self, buf: &[u8] ) -> Result<usize> {
+        self.check()?;
+        self.delay();
+        self.write_bytes( buf );
+        self.output().end_message();
+        Ok( buf.len() )
+    }
+
+    fn flush( &mut self ) -> Result<()> { AltoutLock::flush( self )}
+}
 
+impl<'a> fmt::Write for AltoutLock<'a> {
+    fn write_str( &mut self, text: &str ) -> fmt::Result {
+        self.write( text );
+        Ok(())
     }
 }
 
-impl Altout {
-    /// Receives text from altio output stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv( &self ) -> String {
-        loop {
-            if let Ok( ref mut buf ) = self.0.lock() {
-                if !buf.is_empty() {
-                    let mut received = String::new();
-                    std::mem::swap( &mut received, buf );
-                    return received;
-                }
-            }
-        }
+impl<'a> Deref for AltoutLock<'a> {
+    type Target = String;
+    fn deref( &self ) -> &String {
+        self.joined.get_or_init( || self.join() )
     }
+}
 
-    /// Tries to receive text from altio output stream, without blocking.
-    pub fn try_recv( &self ) -> Option<String> {
-        if let Ok( ref mut buf ) = self.0.try_lock() {
-            if !buf.is_empty() {
-                let mut received = String::new();
-                std::mem::swap( &mut received, buf );
-                return Some( received );
-            }
+impl<'a> DerefMut for AltoutLock<'a> {
+    fn deref_mut( &mut self ) -> &mut String {
+        if self.joined.get().is_none() {
+            self.joined = OnceCell::from( self.join() );
         }
-        None
+        self.edited = true;
+        self.joined.get_mut().expect( "joined above" )
     }
+}
 
-    /// Receives one line of text from altio output stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_line( &self ) -> String {
-        loop {
-            if let Ok( ref mut buf ) = self.0.lock() {
-                if let Some( offset ) = buf.find( '\n' ) {
-                    return String::from_iter( buf.drain( ..=offset ));
-                }
+impl<'a> Drop for AltoutLock<'a> {
+    fn drop( &mut self ) { self.write_back() }
+}
+
+/// The state of an output stream.
+#[derive( Debug, Default )]
+struct Output {
+    buf        : Buffer,
+    history    : Option<History>,
+    buffer     : Option<WriteBuffer>,
+    interleave : Option<Interleave>,
+    frames     : Option<Frames>,
+    console    : Translator,
+    written    : u64,
+    barrier    : u64, // the amount of bytes written before the last flush
+}
+
+impl Output {
+    fn write( &mut self, bytes: &[u8], now: Instant ) {
+        if let Some( history ) = self.history.as_mut() {
+            history.push( bytes, now );
+        }
+        if let Some( frames ) = self.frames.as_mut() {
+            frames.wrote( bytes.len() );
+        }
+        self.written += bytes.len() as u64;
+        match self.buffer.as_mut() {
+            Some( buffer ) => {
+                let flushed = buffer.write( bytes );
+                self.show( &flushed );
             }
+            None => self.show( bytes ),
         }
     }
 
-    /// Tries to receive one line of text from altio output stream, without blocking.
-    pub fn try_recv_line( &self ) -> Option<String> {
-        if let Ok( ref mut buf ) = self.0.try_lock() {
-            if let Some( offset ) = buf.find( '\n' ) {
-                return Some( String::from_iter( buf.drain( ..=offset )));
-            }
+    /// Passes text flushed by the tool to receivers, through interleaving.
+    fn show( &mut self, bytes: &[u8] ) {
+        match self.interleave.as_mut() {
+            Some( interleave ) => self.buf.push( &interleave.write( bytes )),
+            None => self.buf.push( bytes ),
         }
-        None
     }
 
-    /// Receives certain amount lines of text from altio output stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_lines( &self, cnt: usize ) -> String {
-        if cnt == 0 {
-            String::new()
-        } else {
-            loop {
-                if let Some( received ) = self.try_recv_lines( cnt ) {
-                    break received;
-                }
-            }
+    /// Replaces the pending text with `edited`, which becomes one message.
+    fn edit( &mut self, edited: &[u8] ) {
+        let ( pending, held ) = ( self.buf.len(), self.held() );
+        if let Some( frames ) = self.frames.as_mut() {
+            frames.edited( pending, edited.len(), held );
         }
+        self.buf.replace_all( edited );
     }
 
-    /// Tries to receive certain amount lines of text from altio output stream.
-    pub fn try_recv_lines( &self, cnt: usize ) -> Option<String> {
-        if cnt != 0 {
-            if let Ok( ref mut buf ) = self.0.try_lock() {
-                return get_lines( buf, cnt, false );
-            }
+    /// Ends the message being written, if framing is enabled.
+    fn end_message( &mut self ) {
+        if let Some( frames ) = self.frames.as_mut() {
+            frames.end();
         }
-        None
     }
 
-    /// Read one line of text in altio output stream, leaving it in the stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_line( &self ) -> Option<String> {
-        if let Ok( ref mut buf ) = self.0.try_lock() {
-            if let Some( offset ) = buf.find( '\n' ) {
-                return Some( buf[ ..=offset ].to_owned() );
-            }
+    /// Returns the amount of written bytes held back by buffering and interleaving.
+    fn held( &self ) -> usize {
+        self.buffer.as_ref().map_or( 0, WriteBuffer::held ) + self.interleave.as_ref().map_or( 0, Interleave::held )
+    }
+
+    /// Makes some of the text held back by interleaving visible.
+    fn release( &mut self ) {
+        if let Some( interleave ) = self.interleave.as_mut() {
+            self.buf.push( &interleave.poll() );
         }
-        None
     }
 
-    /// Tries to receive certain amount lines of text in altio output stream,
-    /// leaving it in the stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_lines( &self, cnt: usize ) -> Option<String> {
-        if cnt != 0 {
-            if let Ok( ref mut buf ) = self.0.try_lock() {
-                return get_lines( buf, cnt, true );
-            }
+    /// Makes all written text visible, recording a barrier, see `recv_until_flush()`.
+    fn flush( &mut self ) {
+        self.barrier = self.written;
+        if let Some( buffer ) = self.buffer.as_mut() {
+            let flushed = buffer.flush();
+            self.show( &flushed );
+        }
+        if let Some( interleave ) = self.interleave.as_mut() {
+            self.buf.push( &interleave.flush() );
         }
-        None
     }
 }
 
-#[cfg( not( feature = "altio" ))]
-#[derive( Debug, Default )]
-/// Placeholder for simulating a program's Stdin,Stdout,Stderr.
-pub struct Altio;
+impl Deref for Output {
+    type Target = Buffer;
+    fn deref( &self ) -> &Buffer { &self.buf }
+}
 
-#[cfg( not( feature = "altio" ))]
-impl Altio {
-    /// Returns `Stdin`.
-    pub fn input( &self ) -> std::io::Stdin { std::io::stdin() }
-    /// Returns `Stdout`.
-    pub fn out( &self ) -> std::io::Stdout { std::io::stdout() }
-    /// Returns `Stderr`.
-    pub fn err( &self ) -> std::io::Stderr { std::io::stderr() }
+impl DerefMut for Output {
+    fn deref_mut( &mut self ) -> &mut Buffer { &mut self.buf }
 }
 
-#[cfg( feature = "altio" )]
-#[derive( Clone, Debug, Default )]
-/// Simulates a program's Stdin,Stdout,Stderr.
-pub struct Altio( std::sync::Arc<(Altin, Altout, Altout)> );
+/// Corresponding to std::io::Stdout
+#[derive( Debug )]
+pub struct Altout {
+    output  : Mutex<Output>,
+    ready   : Ready,
+    stats   : Stats,
+    fault   : Injector,
+    latency : Latency,
+    hook    : Hook,
+    clock   : Arc<dyn Clock>,
+    killed  : AtomicBool,
+    closed  : AtomicBool,
+    #[cfg_attr( not( feature = "anstyle" ), allow( dead_code ))]
+    color   : AtomicBool,
+    stdio   : AtomicBool,
+    version : AtomicU64, // bumped whenever the stream is locked for changes
+    peeked  : RwLock<Snapshot>,
+    window  : AtomicUsize, // the lookbehind of pattern matching, usize::MAX if unbounded
+    #[cfg( feature = "altio" )]
+    mirror  : Mirror,
+    #[cfg( feature = "altio" )]
+    sink    : Mutex<Option<Sink>>,
+    #[cfg( feature = "altio" )]
+    events  : Arc<EventLog>,
+    #[cfg( feature = "altio" )]
+    strict  : bool,
+    #[cfg( feature = "altio" )]
+    flagged : Mutex<Vec<String>>, // violations of the strict mode
+    #[cfg( feature = "altio" )]
+    recent  : RwLock<Option<Vec<u8>>>, // written since the last generated line of input
+    #[cfg( feature = "regex" )]
+    respond : Mutex<Responder>,
+}
 
-#[cfg( feature = "altio" )]
-impl Altio {
-    /// Corresponding to Stdin.
-    pub fn input( &self ) -> &Altin { &self.0.0 }
+impl Default for Altout {
+    fn default() -> Self { Altout::new( Stream::Out )}
+}
 
-    /// Corresponding to Stdout.
-    pub fn out( &self ) -> AltoutLock { self.0.1.lock() }
+impl Altout {
+    fn new( stream: Stream ) -> Self {
+        Altout {
+            output  : Mutex::default(),
+            ready   : Ready::default(),
+            stats   : Stats::new( stream ),
+            fault   : Injector::default(),
+            latency : Latency::default(),
+            hook    : Hook::default(),
+            clock   : Arc::new( SystemClock ),
+            killed  : AtomicBool::new( false ),
+            closed  : AtomicBool::new( false ),
+            color   : AtomicBool::new( false ),
+            stdio   : AtomicBool::new( false ),
+            version : AtomicU64::new( 0 ),
+            peeked  : RwLock::default(),
+            window  : AtomicUsize::new( usize::MAX ),
+            #[cfg( feature = "altio" )]
+            mirror  : Mirror::new( stream ),
+            #[cfg( feature = "altio" )]
+            sink    : Mutex::default(),
+            #[cfg( feature = "altio" )]
+            events  : Arc::default(),
+            #[cfg( feature = "altio" )]
+            strict  : false,
+            #[cfg( feature = "altio" )]
+            flagged : Mutex::default(),
+            #[cfg( feature = "altio" )]
+            recent  : RwLock::default(),
+            #[cfg( feature = "regex" )]
+            respond : Mutex::default(),
+        }
+    }
 
-    /// Corresponding to Stderr.
-    pub fn err( &self ) -> AltoutLock { self.0.2.lock() }
+    /// Tells peeking threads that `output` locked by the caller may change.
+    fn changing<'a>( &self, output: &'a mut Output ) -> &'a mut Output {
+        self.version.fetch_add( 1, Ordering::Release );
+        output
+    }
 
-    /// Sends text to altio input stream, without additional newline.
-    pub fn send( &self, text: &str ) { self.0.0.send( text )}
+    /// Peeks the first `cnt` lines from the snapshot if it's up to date, otherwise
+    /// from the stream if not locked, updating the snapshot.
+    fn peek( &self, cnt: usize ) -> Option<String> {
+        let version = self.version.load( Ordering::Acquire );
+        if let Some( peeked ) = self.peeked.read().unwrap_or_else( PoisonError::into_inner ).get( version, cnt ) {
+            return peeked;
+        }
+        let mut output = try_lock( &self.output )?;
+        let version = self.version.load( Ordering::Acquire );
+        let snapshot = Snapshot::take( &mut output, version, cnt );
+        let peeked = snapshot.get( version, cnt ).flatten();
+        *self.peeked.write().unwrap_or_else( PoisonError::into_inner ) = snapshot;
+        peeked
+    }
+    fn check( &self ) -> Result<()> {
+        if self.killed.load( Ordering::Relaxed ) {
+            return Err( killed() );
+        }
+        self.fault.check()
+    }
 
-    /// Sends text to altio input stream, with an additional newline.
-    pub fn send_line( &self, text: &str ) { self.0.0.send_line( text )}
+    /// Writes `bytes` to the real stdout or stderr, ignoring errors as a tool writing
+    /// into altio streams doesn't expect any.
+    fn passthrough( &self, bytes: &[u8] ) {
+        use std::io::Write;
+        let _ = match self.stats.stream {
+            Stream::Err => std::io::stderr().write_all( bytes ),
+            _ => std::io::stdout().write_all( bytes ),
+        };
+    }
 
-    /// Receives text from altio output stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv( &self ) -> String { self.0.1.recv() }
+    fn flush_stdio( &self ) -> Result<()> {
+        use std::io::Write;
+        match self.stats.stream {
+            Stream::Err => std::io::stderr().flush(),
+            _ => std::io::stdout().flush(),
+        }
+    }
 
-    /// Tries to receive text from altio output stream, without blocking.
-    pub fn try_recv( &self ) -> Option<String> { self.0.1.try_recv() }
+    /// Makes writes fail from now on.
+    #[cfg( feature = "altio" )]
+    fn kill( &self ) {
+        self.killed.store( true, Ordering::Relaxed );
+    }
 
-    /// Receives one line of text from altio output stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_line( &self ) -> String { self.0.1.recv_line() }
+    /// Marks the stream as closed by the tool, i.e. no more text will be written.
+    #[cfg( feature = "altio" )]
+    fn close( &self ) {
+        self.closed.store( true, Ordering::Relaxed );
+        #[cfg( feature = "altio" )]
+        self.mirror.log( log::Event::Eof( self.stats.stream ));
+        self.ready.notify_all();
+    }
 
-    /// Tries to receive one line of text from altio output stream, without blocking.
-    pub fn try_recv_line( &self ) -> Option<String> { self.0.1.try_recv_line() }
+    /// Locks the state of the stream, ignoring poisoning.
+    #[cfg( feature = "expectrl" )]
+    fn output( &self ) -> MutexGuard<'_, Output> {
+        let mut output = self.output.lock().unwrap_or_else( PoisonError::into_inner );
+        self.changing( &mut output );
+        output
+    }
 
-    /// Receives certain amount lines of text from altio output stream.
+    /// Tries to receive at most `max` bytes from the locked `output`, which may end
+    /// inside a UTF-8 encoded char.
+    #[cfg( feature = "expectrl" )]
+    fn try_recv_bytes( &self, output: &mut Output, max: usize ) -> Option<Vec<u8>> {
+        output.release();
+        let len = output.len().min( max );
+        let received = ( len != 0 ).then( || output.take( len ));
+        self.stats.received( received.as_ref().map_or( 0, Vec::len ));
+        received
+    }
+
+    /// Locks this handle to the altio output stream, returning a writable guard.
     ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_lines( &self, cnt: usize ) -> String { self.0.1.recv_lines(cnt) }
+    /// The lock is released when the returned lock goes out of scope. The returned
+    /// guard also provide write_fmt() for writing data.
+    pub fn lock( &self ) -> AltoutLock<'_> {
+        AltoutLock{ inner: Some( self.lock_output() ), altout: self, joined: OnceCell::new(), edited: false, failed: Cell::new( None )}
+    }
 
-    /// Tries to receive certain amount lines of text from altio output stream.
-    pub fn try_recv_lines( &self, cnt: usize ) -> Option<String> { self.0.1.try_recv_lines(cnt) }
+    fn lock_output( &self ) -> MutexGuard<'_, Output> {
+        let mut lock = self.output.lock().unwrap_or_else( PoisonError::into_inner );
+        self.changing( &mut lock );
+        lock
+    }
+    /// Writes a formatted string into Altout, won't returning any error.
+    pub fn write_fmt( &mut self, args: Arguments<'_> ) -> Result<()> {
+        self.lock().write_fmt( args )
+    }
+    /// Keeps at most `threshold` bytes of pending text in memory, spilling the rest
+    /// into a temporary file. Spilled text is loaded back transparently on receiving.
+    pub fn spill_to_disk( &self, threshold: usize ) -> Result<()> {
+        self.lock().output().spill_to_disk( threshold )
+    }
 
-    /// Reads one line of text in altio output stream, leaving it in the
-    /// stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_line( &self ) -> Option<String> { self.0.1.peek_line() }
+    /// Starts retaining a copy of everything written from now on, regardless of being
+    /// received or not.
+    pub fn keep_history( &self ) {
+        self.lock().output().history.get_or_insert_with( History::default );
+    }
 
-    /// Reads certain amount lines of text in altio output stream, leaving it in the
-    /// stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_lines( &self, cnt: usize ) -> Option<String> { self.0.1.peek_lines(cnt) }
+    /// Keeps the history within the limits of `retention`, starting retaining if not
+    /// yet.
+    pub fn retain_history( &self, retention: Retention ) {
+        let now = self.clock.now();
+        self.lock().output().history.get_or_insert_with( History::default ).retain( retention, now );
+    }
 
-    /// Receives text from altio error stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_err( &self ) -> String { self.0.2.recv() }
+    /// Returns the retained text, or an empty string if no history is kept.
+    pub fn history( &self ) -> String {
+        let now = self.clock.now();
+        let mut lock = self.lock();
+        lock.output().history.as_mut().map( |history| {
+            history.trim( now );
+            into_string( history.to_bytes() )
+        }).unwrap_or_default()
+    }
 
-    /// Tries to receive text from altio error stream, without blocking.
-    pub fn try_recv_err( &self ) -> Option<String> { self.0.2.try_recv() }
+    /// Returns the amount of bytes dropped from the history by its retention.
+    pub fn history_truncated( &self ) -> usize {
+        self.lock().output().history.as_ref().map_or( 0, History::truncated )
+    }
 
-    /// Receives one line of text from altio error stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_err_line( &self ) -> String { self.0.2.recv_line() }
+    /// Returns the counters of this stream.
+    pub fn metrics( &self ) -> StreamMetrics { self.stats.snapshot() }
 
-    /// Tries to receive one line of text from altio error stream, without blocking.
-    pub fn try_recv_err_line( &self ) -> Option<String> { self.0.2.try_recv_line() }
+    /// Makes the next or every nth write/flush fail with an error of `kind`.
+    pub fn inject_error( &self, kind: ErrorKind, when: When ) { self.fault.inject( kind, when )}
 
-    /// Receives certain amount lines of text from altio error stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn recv_err_lines( &self, cnt: usize ) -> String { self.0.2.recv_lines(cnt) }
+    /// Stops failing writes/flushes, see `inject_error()`.
+    pub fn clear_error( &self ) { self.fault.clear() }
 
-    /// Tries to receive certain amount lines of text from altio error stream, without
-    /// blocking.
-    pub fn try_recv_err_lines( &self, cnt: usize ) -> Option<String> { self.0.2.try_recv_lines(cnt) }
+    /// Delays every write before it becomes visible.
+    pub fn inject_latency( &self, delay: Delay ) { self.latency.inject( delay )}
 
-    /// Reads one line of text in altio error stream, leaving it in the stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_err_line( &self ) -> Option<String> { self.0.2.peek_line() }
+    /// Stops delaying writes, see `inject_latency()`.
+    pub fn clear_latency( &self ) { self.latency.clear() }
 
-    /// Reads certain amount line of text in altio error stream, leaving it in the
-    /// stream.
-    ///
-    /// This function will always block the current thread if there is no data
-    /// available.
-    pub fn peek_err_lines( &self, cnt: usize ) -> Option<String> { self.0.2.peek_lines(cnt) }
-}
+    /// Calls `scheduler` instead of sleeping in blocking receives.
+    pub fn schedule( &self, scheduler: Arc<dyn Scheduler> ) { self.hook.set( scheduler )}
 
-/// Provides delegated `out()`/`err()` methods for the type which contains a field
-/// named `altio`.
-#[macro_export]
-macro_rules! impl_altio_output {
-    ($ty:ty) => {
-        #[cfg( feature = "altio" )]
-        impl $ty {
-            pub fn out( &self ) -> altio::AltoutLock { self.altio.out() }
-            pub fn err( &self ) -> altio::AltoutLock { self.altio.err() }
-        }
+    /// Stops calling the scheduler, see `schedule()`.
+    pub fn clear_scheduler( &self ) { self.hook.clear() }
 
-        #[cfg( not( feature = "altio" ))]
-        impl $ty {
-            pub fn out( &self ) -> std::io::Stdout { std::io::stdout() }
-            pub fn err( &self ) -> std::io::Stderr { std::io::stderr() }
-        }
-    };
-}
+    /// Holds written text back and makes it visible in pieces scheduled by a PRNG
+    /// seeded with `seed`.
+    pub fn interleave( &self, seed: u64 ) {
+        let mut lock = self.lock();
+        lock.output().flush();
+        lock.output().interleave = Some( Interleave::new( seed ));
+    }
 
-#[cfg( all( test, feature="altio" ))]
-pub mod tests {
-    use super::{Altio, echo};
+    /// Returns the seed of interleaving, if enabled.
+    pub fn interleave_seed( &self ) -> Option<u64> {
+        self.lock().output().interleave.as_ref().map( Interleave::seed )
+    }
+
+    /// Makes all written text visible to receivers, or fails with an error injected
+    /// by `Altio::inject_error()`.
+    pub fn flush( &mut self ) -> Result<()> {
+        self.lock().flush()
+    }
+
+    /// Returns false to indicate it isn't a terminal/tty.
+    pub fn is_terminal( &self ) -> bool { false }
+}
+
+/// Takes all pending text if it ends with `suffix`, leaving the suffix unless
+/// `consume`.
+fn take_until_suffix( buf: &mut Buffer, suffix: &str, consume: bool ) -> Option<String> {
+    ( !buf.is_empty() && buf.ends_with( suffix.as_bytes() )).then( || {
+        let len = if consume { buf.len() } else { buf.len() - suffix.len() };
+        buf.take_string( len )
+    })
+}
+
+/// Takes the pending text up to and including the earliest match of any of `patterns`
+/// where `scan` looks for them, with the index of the pattern matched, the first listed
+/// if several match at the same position.
+fn take_first_match( buf: &mut Buffer, patterns: &[Pattern], scan: &mut Scan ) -> Option<( usize, String )> {
+    let ( skip, bytes ) = copy_window( buf, scan.start( buf )? );
+    let ( index, end ) = pattern::first_match( patterns, &String::from_utf8_lossy( &bytes ))?;
+    Some(( index, buf.take_string( skip + raw_offset( &bytes, end ))))
+}
+
+/// Takes the pending text up to and including the first match of `regex` where `scan`
+/// looks for it, with its capture groups.
+#[cfg( feature = "regex" )]
+fn take_regex_match( buf: &mut Buffer, regex: &regex::Regex, scan: &mut Scan ) -> Option<Match> {
+    let ( skip, bytes ) = copy_window( buf, scan.start( buf )? );
+    let text = String::from_utf8_lossy( &bytes );
+    let captures = regex.captures( &text )?;
+    let whole = captures.get(0)?;
+    let ( start, end ) = ( raw_offset( &bytes, whole.start() ), raw_offset( &bytes, whole.end() ));
+    let before = buf.take_string( skip + start );
+    buf.take( end - start );
+    Some( Match::new( before, regex, &captures ))
+}
+
+/// Copies the pending bytes after the first `from`, moved back to a char boundary,
+/// with the offset they start at.
+fn copy_window( buf: &mut Buffer, from: usize ) -> ( usize, Vec<u8> ) {
+    let back = from.min( 3 );
+    let mut bytes = buf.copy_from( from - back );
+    let lead = ( 0..=back ).rev()
+        .find( |&i| bytes.get(i).is_none_or( |&byte| ( byte as i8 ) >= -0x40 ))
+        .unwrap_or( 0 );
+    bytes.drain( ..lead );
+    ( from - back + lead, bytes )
+}
+
+/// Where the expect family of functions looks for matches in the pending text, which
+/// is the last `lookbehind` bytes, skipping the text already looked into but the
+/// last bytes of it a match ending in new text may span.
+struct Scan {
+    lookbehind : usize,
+    reach      : Option<usize>, // the most bytes a match spans, unbounded if `None`
+    checked    : Option<u64>,   // the stream position of the end of the text looked into
+}
+
+impl Scan {
+    fn new( lookbehind: usize, reach: Option<usize> ) -> Self {
+        Scan{ lookbehind, reach, checked: None }
+    }
+
+    /// Returns the offset of the pending text to look for matches from, or `None` if
+    /// nothing has been written since the last call.
+    fn start( &mut self, buf: &Buffer ) -> Option<usize> {
+        let taken = buf.taken();
+        let end = taken.wrapping_add( buf.len() as u64 );
+        let checked = match self.checked.replace( end ) {
+            Some( checked ) if checked == end => return None,
+            Some( checked ) => ( checked.wrapping_sub( taken ) as i64 ).clamp( 0, buf.len() as i64 ) as usize,
+            None => 0,
+        };
+        let resumed = self.reach.map_or( 0, |reach| checked.saturating_sub( reach ));
+        Some( resumed.max( buf.len().saturating_sub( self.lookbehind )))
+    }
+}
+
+/// Takes the text written before the last flush, if not received yet.
+fn take_until_flush( output: &mut Output ) -> Option<String> {
+    let received = output.written - ( output.held() + output.len() ) as u64;
+    let len = output.barrier.saturating_sub( received ) as usize;
+    ( len != 0 ).then( || output.take_string( len ))
+}
+
+/// Takes the first message if framing is enabled, or all pending text otherwise.
+fn take_message( output: &mut Output ) -> Option<String> {
+    let ( pending, held ) = ( output.len(), output.held() );
+    match output.frames.as_mut() {
+        Some( frames ) => frames.next( pending, held ).map( |len| output.take_string( len )),
+        None => take_all( output ),
+    }
+}
+
+#[inline]
+fn take_all( buf: &mut Buffer ) -> Option<String> {
+    if buf.is_empty() {
+        None
+    } else {
+        Some( into_string( buf.take_all() ))
+    }
+}
+
+#[inline]
+fn take_max( buf: &mut Buffer, max: usize ) -> Option<String> {
+    if buf.is_empty() {
+        None
+    } else {
+        let offset = buf.char_boundary( max );
+        Some( buf.take_string( offset ))
+    }
+}
+
+/// Takes the text up to and including the first well-formed control line of a prompt.
+#[cfg( feature = "altio" )]
+fn take_prompt( buf: &mut Buffer ) -> Option<String> {
+    let mut from = 0;
+    loop {
+        let start = buf.find( from, CONTROL as u8 )?;
+        let end = buf.find( start, b'\n' )? + 1;
+        let line = buf.copy( end ).split_off( start );
+        if Prompt::from_control_line( &String::from_utf8_lossy( &line )).is_some() {
+            return Some( buf.take_string( end ));
+        }
+        from = end;
+    }
+}
+
+/// Splits the text taken by `take_prompt()` into the prompt and the text before it.
+#[cfg( feature = "altio" )]
+fn prompt_event( mut text: String ) -> PromptEvent {
+    let start = text.rfind( CONTROL ).expect( "a control line" );
+    let prompt = Prompt::from_control_line( &text.split_off( start )).expect( "a well-formed control line" );
+    PromptEvent{ text, prompt }
+}
+
+#[inline]
+fn get_lines( buf: &mut Buffer, mut cnt: usize ) -> Option<String> {
+    let mut offset = buf.find_newline()? + 1;
+    while cnt > 1 {
+        offset = buf.find( offset, b'\n' )? + 1;
+        cnt -= 1;
+    }
+    Some( buf.take_string( offset ))
+}
+
+impl Altin {
+    /// Sends text to altio input stream, without additional newline.
+    pub fn send( &self, text: &str ) { self.send_atomic( &[ text ])}
+
+    /// Sends text to altio input stream, with an additional newline.
+    pub fn send_line( &self, text: &str ) { self.send_atomic( &[ text, "\n" ])}
+
+    /// Sends all pieces of text to altio input stream as a whole, e.g. a multi-line
+    /// message which must not be interleaved with the sends of other threads.
+    pub fn send_atomic( &self, pieces: &[&str] ) { self.send_pieces( pieces ); }
+
+    /// Sends the pieces like `send_atomic()`, returning the position past them, see
+    /// `push()`.
+    fn send_pieces( &self, pieces: &[&str] ) -> Option<u64> {
+        let mut editor = self.editor.lock().unwrap_or_else( PoisonError::into_inner );
+        let mut console = self.console.lock().unwrap_or_else( PoisonError::into_inner );
+        match editor.as_mut() {
+            Some( editor ) => self.push( &[ console.send( &editor.feed( &pieces.concat() )).as_bytes() ]),
+            None => {
+                let pieces = pieces.iter().map( |piece| console.send( piece )).collect::<Vec<_>>();
+                self.push( &pieces.iter().map( |piece| piece.as_bytes() ).collect::<Vec<_>>() )
+            }
+        }
+    }
+
+    /// Sends text with an additional newline like `send_line()`, then blocks until the
+    /// tool has read past it or is killed, so that a scripted driver can pace itself to
+    /// the tool instead of sending all the answers up front. Blocks forever if the tool
+    /// never reads it, see `send_line_timeout()`.
+    pub fn send_line_blocking( &self, text: &str ) {
+        self.send_line_until( text, None );
+    }
+
+    /// Sends text with an additional newline like `send_line_blocking()`, blocking at
+    /// most `timeout` measured by the clock of the `Altio`. Returns false if the tool
+    /// has not read past it on timeout, or is killed.
+    pub fn send_line_timeout( &self, text: &str, timeout: Duration ) -> bool {
+        self.send_line_until( text, Some( self.clock.now() + timeout ))
+    }
+
+    fn send_line_until( &self, text: &str, deadline: Option<Instant> ) -> bool {
+        match self.send_pieces( &[ text, "\n" ]) {
+            Some( end ) => self.wait_read( end, deadline ),
+            None => true,
+        }
+    }
+
+    /// Blocks until the tool has read past the position `end` in the stream, or until
+    /// `deadline` measured by the clock of the `Altio`. Returns false on timeout or if
+    /// the tool is killed.
+    fn wait_read( &self, end: u64, deadline: Option<Instant> ) -> bool {
+        wait_for( &self.input, &self.ready, Stream::In, &self.hook, &*self.clock, deadline, |input| {
+            let read = input.taken().wrapping_sub( end ) as i64 >= 0;
+            ( read || self.killed.load( Ordering::Relaxed )).then_some( read )
+        }).unwrap_or( false )
+    }
+
+    /// Sends text to altio input stream with an additional newline, ahead of the
+    /// pending input except the lines sent by this function before, bypassing line
+    /// editing.
+    pub fn send_priority_line( &self, text: &str ) {
+        let _editor = self.editor.lock().unwrap_or_else( PoisonError::into_inner );
+        let line = self.console.lock().unwrap_or_else( PoisonError::into_inner ).send( &format!( "{text}\n" )).into_owned();
+        let mut buf = self.input.lock().unwrap_or_else( PoisonError::into_inner );
+        let offset = self.urgent.load( Ordering::Relaxed ).wrapping_sub( buf.taken() );
+        let offset = if offset > buf.len() as u64 { 0 } else { offset as usize };
+        buf.insert( offset, line.as_bytes() );
+        self.urgent.store( buf.taken().wrapping_add(( offset + line.len() ) as u64 ), Ordering::Relaxed );
+        self.stats.wrote( line.as_bytes(), buf.len() );
+        #[cfg( feature = "altio" )]
+        self.mirror.write( line.as_bytes() );
+        self.ready.notify_all();
+    }
+
+    /// Pushes `text` back to the front of altio input stream, so the next read sees
+    /// it before anything else, e.g. a line read by the tool for lookahead. Unlike
+    /// sending, line editing is bypassed.
+    pub fn unread( &self, text: &str ) {
+        self.lock().unread( text );
+        self.ready.notify_all();
+    }
+
+    /// Sends bytes to altio input stream as they are, bypassing line editing, e.g.
+    /// invalid UTF-8 or control characters for testing how the tool copes with them.
+    pub fn send_raw( &self, bytes: &[u8] ) {
+        let _editor = self.editor.lock().unwrap_or_else( PoisonError::into_inner );
+        self.push( &[ bytes ]);
+    }
+
+    /// Sets how `read_line()` delivers control characters other than tab, carriage
+    /// return and newline, verbatim by default.
+    pub fn control_chars( &self, policy: ControlChars ) {
+        *self.control.lock().unwrap_or_else( PoisonError::into_inner ) = policy;
+    }
+
+    /// Sends bytes which are not valid UTF-8 to altio input stream as they are, for
+    /// testing the error paths of the tool, see `InvalidUtf8` for how string-level
+    /// reads deliver them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` are valid UTF-8, which should be sent by `send()` instead.
+    pub fn send_invalid_utf8( &self, bytes: &[u8] ) {
+        assert!( std::str::from_utf8( bytes ).is_err(), "valid UTF-8 sent as invalid: {bytes:?}" );
+        self.send_raw( bytes );
+    }
+
+    /// Sets how string-level reads deliver input which is not valid UTF-8, failing
+    /// like `std::io::Stdin` by default.
+    pub fn invalid_utf8( &self, policy: InvalidUtf8 ) {
+        *self.utf8.lock().unwrap_or_else( PoisonError::into_inner ) = policy;
+    }
+
+    /// Sets how the text sent from now on is translated before the tool reads it,
+    /// see `Console`. Raw bytes sent by `send_raw()` are not translated.
+    pub fn console( &self, console: Console ) {
+        self.console.lock().unwrap_or_else( PoisonError::into_inner ).console = console;
+    }
+
+    /// Makes the pieces of text visible to reads at once, returning the position in
+    /// the stream past them, or `None` if they are written into the sink of a backend.
+    fn push( &self, pieces: &[&[u8]] ) -> Option<u64> {
+        #[cfg( feature = "altio" )]
+        if let Some( Sink( sink )) = self.sink.lock().unwrap_or_else( PoisonError::into_inner ).as_mut() {
+            for piece in pieces {
+                self.stats.wrote( piece, 0 );
+                self.mirror.write( piece );
+                let _ = sink.write_all( piece );
+            }
+            let _ = sink.flush();
+            return None;
+        }
+        let mut buf = self.input.lock().unwrap_or_else( PoisonError::into_inner );
+        if pieces.iter().any( |piece| !piece.is_empty() ) {
+            for piece in pieces.iter().filter( |piece| !piece.is_empty() ) {
+                buf.push( piece );
+                self.stats.wrote( piece, buf.len() );
+                #[cfg( feature = "altio" )]
+                self.mirror.write( piece );
+            }
+            self.ready.notify_all();
+        }
+        Some( buf.taken().wrapping_add( buf.len() as u64 ))
+    }
+
+    /// Returns the counters of this stream.
+    pub fn metrics( &self ) -> StreamMetrics { self.stats.snapshot() }
+
+    /// Makes the next or every nth read fail with an error of `kind`.
+    pub fn inject_error( &self, kind: ErrorKind, when: When ) { self.fault.inject( kind, when )}
+
+    /// Stops failing reads, see `inject_error()`.
+    pub fn clear_error( &self ) { self.fault.clear() }
+
+    /// Delays every read before it returns.
+    pub fn inject_latency( &self, delay: Delay ) { self.latency.inject( delay )}
+
+    /// Stops delaying reads, see `inject_latency()`.
+    pub fn clear_latency( &self ) { self.latency.clear() }
+
+    /// Calls `scheduler` instead of sleeping in blocking reads.
+    pub fn schedule( &self, scheduler: Arc<dyn Scheduler> ) { self.hook.set( scheduler )}
+
+    /// Stops calling the scheduler, see `schedule()`.
+    pub fn clear_scheduler( &self ) { self.hook.clear() }
+}
+
+impl Altout {
+    /// Blocks until `f` returns some received text, polling the stream whenever it
+    /// is written.
+    fn wait( &self, f: impl FnMut( &mut Output ) -> Option<String> ) -> String {
+        self.wait_until( None, f ).expect( "waiting without deadline" )
+    }
+
+    /// Like `wait()`, but gives up once the clock reaches `deadline`.
+    fn wait_until( &self, deadline: Option<Instant>, mut f: impl FnMut( &mut Output ) -> Option<String> )
+        -> Option<String>
+    {
+        let since = Instant::now();
+        let _span = WaitSpan::enter( self.stats.stream );
+        let stream = self.stats.stream;
+        let received = wait_for( &self.output, &self.ready, stream, &self.hook, &*self.clock, deadline, |output| {
+            let output = self.changing( output );
+            output.release();
+            f( output )
+        });
+        self.stats.received_since( since, received.as_ref().map_or( 0, String::len ));
+        received
+    }
+
+    /// Takes `cnt` lines from `buf`, or all the rest once the stream is closed, the
+    /// last line of which may have no trailing newline.
+    fn get_lines( &self, buf: &mut Buffer, cnt: usize ) -> Option<String> {
+        get_lines( buf, cnt ).or_else( || if self.closed.load( Ordering::Relaxed ) {
+            take_all( buf )
+        } else {
+            None
+        })
+    }
+
+    /// Receives the rest of the text once the tool has closed the stream, blocking at
+    /// most until the clock reaches `deadline` if any.
+    #[cfg( feature = "altio" )]
+    fn recv_eof_until( &self, deadline: Option<Instant> ) -> Option<String> {
+        self.wait_until( deadline, |buf| self.closed.load( Ordering::Relaxed ).then( || take_all( buf ).unwrap_or_default() ))
+    }
+
+    /// Takes all pending bytes, or an empty vector once the tool has closed the stream
+    /// and all of it has been received, blocking at most `timeout` of real time.
+    #[cfg( feature = "duct" )]
+    fn recv_bytes_for( &self, timeout: Duration ) -> Option<Vec<u8>> {
+        let since = Instant::now();
+        let received = wait_for( &self.output, &self.ready, self.stats.stream, &self.hook, &SystemClock, Some( since + timeout ), |output| {
+            let closed = self.closed.load( Ordering::Relaxed );
+            let output = self.changing( output );
+            output.release();
+            ( closed || !output.is_empty() ).then( || output.take_all() )
+        });
+        if let Some( bytes ) = received.as_ref().filter( |bytes| !bytes.is_empty() ) {
+            self.stats.received_since( since, bytes.len() );
+        }
+        received
+    }
+
+    /// Returns the instant `timeout` from now, measured by the clock of this stream.
+    fn deadline( &self, timeout: Duration ) -> Option<Instant> {
+        Some( self.clock.now() + timeout )
+    }
+
+    /// Polls the stream with `f` for a future, which is woken when the stream changes.
+    #[cfg( feature = "altio" )]
+    fn poll_async( &self, cx: &mut Context<'_>, f: impl FnOnce( &mut Output ) -> Option<String> ) -> Poll<String> {
+        self.ready.register( cx.waker() );
+        let mut output = self.output.lock().unwrap_or_else( PoisonError::into_inner );
+        let output = self.changing( &mut output );
+        output.release();
+        match f( output ) {
+            Some( received ) => {
+                self.stats.received( received.len() );
+                Poll::Ready( received )
+            }
+            None => {
+                // text held back by interleaving is released by receiving only
+                if output.interleave.as_ref().is_some_and( |interleave| interleave.held() > 0 ) {
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Polls the stream with `f` once, without blocking.
+    fn poll( &self, f: impl FnOnce( &mut Output ) -> Option<String> ) -> Option<String> {
+        let received = try_lock( &self.output ).and_then( |mut output| {
+            let output = self.changing( &mut output );
+            output.release();
+            f( output )
+        });
+        self.stats.received( received.as_ref().map_or( 0, String::len ));
+        received
+    }
+
+    /// Receives text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv( &self ) -> String {
+        self.wait( |buf| take_all( buf ))
+    }
+
+    /// Receives text from altio output stream, blocking at most `timeout` measured by
+    /// the clock of the `Altio`.
+    pub fn recv_timeout( &self, timeout: Duration ) -> Option<String> {
+        self.wait_until( self.deadline( timeout ), |buf| take_all( buf ))
+    }
+
+    /// Tries to receive text from altio output stream, without blocking.
+    pub fn try_recv( &self ) -> Option<String> {
+        self.poll( |buf| take_all( buf ))
+    }
+
+    /// Receives text from altio output stream until none is written for `quiet`, or
+    /// at most `max`, both measured by the clock of the `Altio`.
+    pub fn recv_settled( &self, quiet: Duration, max: Duration ) -> String {
+        let deadline = self.clock.now() + max;
+        let mut received = String::new();
+        loop {
+            let now = self.clock.now();
+            if now >= deadline {
+                break received;
+            }
+            match self.wait_until( Some(( now + quiet ).min( deadline )), |buf| take_all( buf )) {
+                Some( text ) => received.push_str( &text ),
+                None => break received,
+            }
+        }
+    }
+
+    /// Sets how the text written from now on is translated, see `Console`.
+    pub fn console( &self, console: Console ) {
+        self.lock().output().console.console = console;
+    }
+
+    /// Sets when written text becomes visible to receivers: instantly by default, or
+    /// at newlines or when the buffer is full, until the tool calls `flush()`. Text
+    /// buffered before is flushed.
+    pub fn buffering( &self, mode: Buffering ) {
+        let mut lock = self.lock();
+        if let Some( mut buffer ) = lock.output().buffer.take() {
+            let flushed = buffer.flush();
+            lock.output().show( &flushed );
+        }
+        lock.output().buffer = ( mode != Buffering::Unbuffered ).then( || WriteBuffer::new( mode ));
+        lock.altout.ready.notify_all();
+    }
+
+    /// Receives all pending text once it ends with `suffix`, e.g. a prompt, which is
+    /// received too if `consume`, or left pending otherwise.
+    ///
+    /// This function will always block the current thread until the pending text
+    /// ends with `suffix`.
+    pub fn recv_until_suffix( &self, suffix: &str, consume: bool ) -> String {
+        self.wait( |buf| take_until_suffix( buf, suffix, consume ))
+    }
+
+    /// Receives all pending text once it ends with `suffix`, blocking at most
+    /// `timeout` measured by the clock of the `Altio`.
+    pub fn recv_until_suffix_timeout( &self, suffix: &str, consume: bool, timeout: Duration ) -> Option<String> {
+        self.wait_until( self.deadline( timeout ), |buf| take_until_suffix( buf, suffix, consume ))
+    }
+
+    /// Receives the pending text up to and including the earliest match of any of
+    /// `patterns`, with the index of the pattern matched, blocking at most `timeout`
+    /// measured by the clock of the `Altio`.
+    pub fn expect_any( &self, patterns: &[&str], timeout: Duration ) -> Option<( usize, String )> {
+        self.expect_any_match( &patterns.iter().map( |&pattern| Pattern::exact( pattern )).collect::<Vec<_>>(), timeout )
+    }
+
+    /// Receives the pending text up to and including the earliest match of any of
+    /// `patterns`, like `expect_any()`, looking only into the last bytes of the
+    /// pending text as set by `set_match_lookbehind()`.
+    pub fn expect_any_match( &self, patterns: &[Pattern], timeout: Duration ) -> Option<( usize, String )> {
+        let reach = patterns.iter().try_fold( 0, |reach, pattern| Some( reach.max( pattern.max_len()? )));
+        let mut scan = Scan::new( self.window.load( Ordering::Relaxed ), reach );
+        let mut matched = 0;
+        self.wait_until( self.deadline( timeout ), |buf| take_first_match( buf, patterns, &mut scan ).map( |( index, text )| {
+            matched = index;
+            text
+        })).map( |text| ( matched, text ))
+    }
+
+    /// Receives the pending text up to and including the first match of `regex`,
+    /// blocking at most `timeout` measured by the clock of the `Altio`, and returns
+    /// the match with its capture groups and the text before it.
+    #[cfg( feature = "regex" )]
+    pub fn expect_regex( &self, regex: &regex::Regex, timeout: Duration ) -> Option<Match> {
+        let mut scan = Scan::new( self.window.load( Ordering::Relaxed ), None );
+        let mut found = None;
+        self.wait_until( self.deadline( timeout ), |buf| take_regex_match( buf, regex, &mut scan ).map( |matched| {
+            let text = [ matched.before(), matched.as_str() ].concat();
+            found = Some( matched );
+            text
+        }));
+        found
+    }
+
+    /// Receives the text written before the last flush of the tool, including the
+    /// text received by other functions, and stops at that flush.
+    ///
+    /// This function will always block the current thread until a flush after the
+    /// text received so far.
+    pub fn recv_until_flush( &self ) -> String {
+        self.wait( take_until_flush )
+    }
+
+    /// Receives the text written before the last flush of the tool, blocking at most
+    /// `timeout` measured by the clock of the `Altio`.
+    pub fn recv_until_flush_timeout( &self, timeout: Duration ) -> Option<String> {
+        self.wait_until( self.deadline( timeout ), take_until_flush )
+    }
+
+    /// Tries to receive the text written before the last flush of the tool, without
+    /// blocking.
+    pub fn try_recv_until_flush( &self ) -> Option<String> {
+        self.poll( take_until_flush )
+    }
+
+    /// Turns on or off message framing: each write call, e.g. `write_fmt()` or
+    /// `push_str()`, makes a message received as a whole by `recv_message()`. Text
+    /// pending when turning it on makes a message of its own.
+    pub fn frame_messages( &self, enabled: bool ) {
+        let mut lock = self.lock();
+        let pending = lock.output().len() + lock.output().held();
+        lock.output().frames = enabled.then( || Frames::new( pending ));
+    }
+
+    /// Receives the text written by one write call, or all pending text if message
+    /// framing is off, see `frame_messages()`.
+    ///
+    /// This function will always block the current thread if there is no message
+    /// available.
+    pub fn recv_message( &self ) -> String {
+        self.wait( take_message )
+    }
+
+    /// Receives the text written by one write call, blocking at most `timeout`
+    /// measured by the clock of the `Altio`.
+    pub fn recv_message_timeout( &self, timeout: Duration ) -> Option<String> {
+        self.wait_until( self.deadline( timeout ), take_message )
+    }
+
+    /// Tries to receive the text written by one write call, without blocking.
+    pub fn try_recv_message( &self ) -> Option<String> {
+        self.poll( take_message )
+    }
+
+    /// Receives at most `max` bytes of text from altio output stream.
+    ///
+    /// The text is never split inside a UTF-8 encoded char. If `max` is smaller than
+    /// the first pending char, that char is received as a whole so that the stream
+    /// always makes progress.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_max( &self, max: usize ) -> String {
+        if max == 0 {
+            String::new()
+        } else {
+            self.wait( |buf| take_max( buf, max ))
+        }
+    }
+
+    /// Receives exactly `n` chars of text from altio output stream, leaving the rest.
+    ///
+    /// This function will always block the current thread until `n` chars are
+    /// available.
+    pub fn recv_exact( &self, n: usize ) -> String {
+        if n == 0 {
+            String::new()
+        } else {
+            self.wait( |buf| buf.char_offset( n ).map( |offset| buf.take_string( offset )))
+        }
+    }
+
+    /// Tries to receive at most `max` bytes of text from altio output stream, without
+    /// blocking.
+    pub fn try_recv_max( &self, max: usize ) -> Option<String> {
+        if max != 0 {
+            self.poll( |buf| take_max( buf, max ))
+        } else {
+            None
+        }
+    }
+
+    /// Receives one line of text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available. Once the tool has closed the stream, the rest of it is received as
+    /// the last line, which may have no trailing newline.
+    pub fn recv_line( &self ) -> String {
+        self.wait( |buf| self.get_lines( buf, 1 ))
+    }
+
+    /// Receives one line of text from altio output stream, blocking at most
+    /// `timeout` measured by the clock of the `Altio`.
+    pub fn recv_line_timeout( &self, timeout: Duration ) -> Option<String> {
+        self.wait_until( self.deadline( timeout ), |buf| self.get_lines( buf, 1 ))
+    }
+
+    /// Tries to receive one line of text from altio output stream, without blocking.
+    pub fn try_recv_line( &self ) -> Option<String> {
+        self.poll( |buf| self.get_lines( buf, 1 ))
+    }
+
+    /// Receives one line of text from altio output stream, blocking at most `timeout`
+    /// measured by the clock of the `Altio`, then receiving the text pending without
+    /// newline, if any, as a partial line.
+    pub fn recv_line_partial_timeout( &self, timeout: Duration ) -> Option<Line> {
+        self.recv_line_timeout( timeout )
+            .or_else( || self.wait_until( Some( self.clock.now() ), |buf| self.get_lines( buf, 1 ).or_else( || take_all( buf ))))
+            .map( |text| if text.ends_with( '\n' ) { Line::Complete( text )} else { Line::Partial( text )})
+    }
+
+    /// Receives certain amount lines of text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_lines( &self, cnt: usize ) -> String {
+        if cnt == 0 {
+            String::new()
+        } else {
+            self.wait( |buf| self.get_lines( buf, cnt ))
+        }
+    }
+
+    /// Tries to receive certain amount lines of text from altio output stream.
+    pub fn try_recv_lines( &self, cnt: usize ) -> Option<String> {
+        if cnt != 0 {
+            self.poll( |buf| self.get_lines( buf, cnt ))
+        } else {
+            None
+        }
+    }
+
+    /// Read one line of text in altio output stream, leaving it in the stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_line( &self ) -> Option<String> { self.peek(1) }
+
+    /// Tries to receive certain amount lines of text in altio output stream,
+    /// leaving it in the stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_lines( &self, cnt: usize ) -> Option<String> {
+        if cnt != 0 { self.peek( cnt )} else { None }
+    }
+}
+
+#[cfg( not( feature = "altio" ))]
+#[derive( Debug, Default )]
+/// Placeholder for simulating a program's Stdin,Stdout,Stderr.
+pub struct Altio;
+
+#[cfg( not( feature = "altio" ))]
+impl Altio {
+    /// Returns `Stdin`.
+    pub fn input( &self ) -> std::io::Stdin { std::io::stdin() }
+    /// Returns `Stdout`.
+    pub fn out( &self ) -> std::io::Stdout { std::io::stdout() }
+    /// Returns `Stderr`.
+    pub fn err( &self ) -> std::io::Stderr { std::io::stderr() }
+    /// Returns an overlay of the process environment, shared by all placeholders.
+    pub fn env( &self ) -> &'static Altenv {
+        static ENV: Altenv = Altenv::new();
+        &ENV
+    }
+    /// Returns the color choice of the user, see `Altenv::color_choice()`.
+    pub fn color_choice( &self ) -> ColorChoice { self.env().color_choice() }
+    /// Writes `prompt` rendered as plain text into `Stdout`.
+    pub fn emit_prompt( &self, prompt: &Prompt ) -> Result<()> {
+        let mut out = std::io::stdout().lock();
+        std::io::Write::write_all( &mut out, prompt.render().as_bytes() )?;
+        std::io::Write::flush( &mut out )
+    }
+    /// Writes the progress as a textual progress line into `Stderr`.
+    pub fn progress( &self, done: u64, total: u64, message: &str ) {
+        let event = ProgressEvent{ done, total, message: message.to_owned() };
+        let _ = std::io::Write::write_all( &mut std::io::stderr().lock(), event.render().as_bytes() );
+    }
+    /// Does nothing, as there is no driver to watch the status.
+    pub fn set_status( &self, _key: &str, _value: &str ) {}
+    /// Writes the text written by `f` into `Stdout` as a whole.
+    pub fn transaction<R>( &self, f: impl FnOnce( &mut Transaction ) -> R ) -> Result<R> {
+        let ( value, text ) = Transaction::run( f );
+        std::io::Write::write_all( &mut std::io::stdout().lock(), text.as_bytes() )?;
+        Ok( value )
+    }
+    /// Writes the text written by `f` into `Stderr` as a whole.
+    pub fn transaction_err<R>( &self, f: impl FnOnce( &mut Transaction ) -> R ) -> Result<R> {
+        let ( value, text ) = Transaction::run( f );
+        std::io::Write::write_all( &mut std::io::stderr().lock(), text.as_bytes() )?;
+        Ok( value )
+    }
+}
+
+#[cfg( feature = "altio" )]
+#[derive( Clone, Debug )]
+/// Simulates a program's Stdin,Stdout,Stderr.
+pub struct Altio( std::sync::Arc<(Altin, Altout, Altout, Altenv, Backend, Channels)> );
+
+#[cfg( feature = "altio" )]
+impl Default for Altio {
+    fn default() -> Self {
+        AltioBuilder::default().build()
+    }
+}
+
+/// Runs the checks set by `AltioBuilder` when the last handle is dropped, unless
+/// already panicking or run by `Altio::finish()`.
+#[cfg( feature = "altio" )]
+impl Drop for Altio {
+    fn drop( &mut self ) {
+        if Arc::strong_count( &self.0 ) != 1 || std::thread::panicking() || self.0.5.finished.load( Ordering::Relaxed ) {
+            return;
+        }
+        let failures = self.check_failures();
+        if !failures.is_empty() {
+            panic!( "altio: {}", failures.join( "; " ));
+        }
+    }
+}
+
+#[cfg( feature = "altio" )]
+/// Builds an `Altio` with non-default settings.
+///
+/// ```rust
+/// use altio::{Altio, ManualClock};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let clock = Arc::new( ManualClock::new() );
+/// let io = Altio::builder().clock( clock.clone() ).build();
+///
+/// let driver = {
+///     let io = io.clone();
+///     std::thread::spawn( move || io.recv_timeout( Duration::from_secs( 60 )))
+/// };
+/// std::thread::sleep( Duration::from_millis( 10 ));
+/// clock.advance( Duration::from_secs( 60 ));
+/// assert_eq!( driver.join().unwrap(), None );
+/// ```
+#[derive( Debug, Default )]
+pub struct AltioBuilder {
+    clock   : Option<Arc<dyn Clock>>,
+    backend : Option<Box<dyn AltBackend>>,
+    checks  : Checks,
+    seed    : Option<u64>,
+}
+
+#[cfg( feature = "altio" )]
+impl AltioBuilder {
+    /// Measures timeouts with `clock` instead of the clock of the operating system,
+    /// e.g. a `ManualClock` advanced by the test instead of sleeping. With the "tokio"
+    /// feature, an `Altio` built within a runtime uses its `TokioClock` by default.
+    pub fn clock( mut self, clock: impl Clock + 'static ) -> Self {
+        self.clock = Some( Arc::new( clock ));
+        self
+    }
+
+    /// Connects the streams to the transports of `backend` instead of storing all
+    /// text in memory, see module `backend`.
+    pub fn backend( mut self, backend: impl AltBackend + 'static ) -> Self {
+        self.backend = Some( Box::new( backend ));
+        self
+    }
+
+    /// Panics when the last handle of the `Altio` is dropped, or by `Altio::finish()`,
+    /// if some input sent was never read by the tool, see `Altio::verify_consumed()`.
+    pub fn check_input_consumed( mut self, check: bool ) -> Self {
+        self.checks.input = check;
+        self
+    }
+
+    /// Panics when the last handle of the `Altio` is dropped, or by `Altio::finish()`,
+    /// if some text written into output or error streams was never received, see
+    /// `Altio::verify_output_consumed()`, catching tests which forget to check part of
+    /// the output.
+    pub fn check_output_consumed( mut self, check: bool ) -> Self {
+        self.checks.output = check;
+        self
+    }
+
+    /// Regards any write into the error stream as a failure, e.g. for tools which
+    /// must not warn: the first one is recorded as a violation, see
+    /// `Altio::violations()`, and the last handle of the `Altio` panics when dropped,
+    /// or `Altio::finish()` does.
+    pub fn strict_err( mut self, strict: bool ) -> Self {
+        self.checks.strict_err = strict;
+        self
+    }
+
+    /// Seeds the jitter of latency injected by `Altio::inject_latency()`, so that a
+    /// failing run can be replayed with the same delays. Seeded from the system time
+    /// by default.
+    pub fn latency_seed( mut self, seed: u64 ) -> Self {
+        self.seed = Some( seed );
+        self
+    }
+
+    /// Creates the `Altio`.
+    pub fn build( self ) -> Altio {
+        #[cfg( feature = "tokio" )]
+        let clock = self.clock.or_else( || TokioClock::current().map( |clock| Arc::new( clock ) as Arc<dyn Clock> ));
+        #[cfg( not( feature = "tokio" ))]
+        let clock = self.clock;
+        let clock = clock.unwrap_or_else( || Arc::new( SystemClock ));
+        let events = Arc::new( EventLog::default() );
+        let io = Altio( Arc::new((
+            Altin{ clock: clock.clone(), events: events.clone(), latency: Latency::seeded( self.seed ), ..Altin::default() },
+            Altout{ clock: clock.clone(), events: events.clone(), latency: Latency::seeded( self.seed ), ..Altout::default() },
+            Altout{ clock, events, strict: self.checks.strict_err, latency: Latency::seeded( self.seed ), ..Altout::new( Stream::Err )},
+            Altenv::new(),
+            Backend::default(),
+            Channels{ checks: self.checks, ..Channels::default() },
+        )));
+        if let Some( mut backend ) = self.backend {
+            for stream in [ Stream::In, Stream::Out, Stream::Err ] {
+                let port = backend.open( stream );
+                if let Some( sink ) = port.sink {
+                    let slot = match stream {
+                        Stream::In  => &io.0.0.sink,
+                        Stream::Out => &io.0.1.sink,
+                        Stream::Err => &io.0.2.sink,
+                    };
+                    *slot.lock().unwrap_or_else( PoisonError::into_inner ) = Some( Sink( sink ));
+                }
+                if let Some( source ) = port.source {
+                    backend::pump( &io, stream, source );
+                }
+            }
+            *io.0.4.0.lock().unwrap_or_else( PoisonError::into_inner ) = Some( backend );
+        }
+        io
+    }
+}
+
+#[cfg( feature = "altio" )]
+impl Altio {
+    /// Returns a builder for an `Altio` with non-default settings.
+    pub fn builder() -> AltioBuilder { AltioBuilder::default() }
+
+    /// Corresponding to Stdin.
+    pub fn input( &self ) -> &Altin { &self.0.0 }
+
+    /// Corresponding to Stdout.
+    pub fn out( &self ) -> AltoutLock<'_> { self.0.1.lock() }
+
+    /// Corresponding to Stderr.
+    pub fn err( &self ) -> AltoutLock<'_> { self.0.2.lock() }
+
+    /// Runs `f` to write a block of text, e.g. a table, which becomes visible in the
+    /// output stream as a whole when `f` returns, so receivers never see half of it.
+    /// Returns the value of `f`, or the error of writing, see `AltoutLock::write_fmt()`.
+    ///
+    /// ```rust
+    /// use altio::Altio;
+    ///
+    /// let io = Altio::default();
+    /// io.transaction( |t| {
+    ///     writeln!( t, "name  size" )?;
+    ///     writeln!( t, "a.txt 42" )
+    /// }).unwrap().unwrap();
+    /// assert_eq!( io.recv(), "name  size\na.txt 42\n" );
+    /// ```
+    pub fn transaction<R>( &self, f: impl FnOnce( &mut Transaction ) -> R ) -> Result<R> {
+        let ( value, text ) = Transaction::run( f );
+        self.out().write_fmt( format_args!( "{text}" ))?;
+        Ok( value )
+    }
+
+    /// Like `transaction()`, but for the error stream.
+    pub fn transaction_err<R>( &self, f: impl FnOnce( &mut Transaction ) -> R ) -> Result<R> {
+        let ( value, text ) = Transaction::run( f );
+        self.err().write_fmt( format_args!( "{text}" ))?;
+        Ok( value )
+    }
+
+    /// Makes the tool's reads and fallible writes fail with `ErrorKind::BrokenPipe`,
+    /// waking up blocked reads, see `AltChild::kill()`.
+    pub(crate) fn kill( &self ) {
+        self.0.0.kill();
+        self.0.1.kill();
+        self.0.2.kill();
+    }
+
+    /// Corresponding to the environment variables in `std::env`.
+    pub fn env( &self ) -> &Altenv { &self.0.3 }
+
+    /// Returns the color choice of the user, according to the color variables in
+    /// `env()`, see `Altenv::color_choice()`. Drivers can set it with
+    /// `Altenv::set_color_choice()` without touching the process environment.
+    pub fn color_choice( &self ) -> ColorChoice { self.env().color_choice() }
+
+    /// Keeps at most `threshold` bytes of pending text in memory for both output and
+    /// error streams, spilling the rest into temporary files.
+    ///
+    /// Useful for capturing tools which dump huge reports, without draining them
+    /// constantly.
+    ///
+    /// If a temporary file can't be read back, the text spilled into it is lost and
+    /// the next write into the stream fails with the error.
+    pub fn spill_to_disk( &self, threshold: usize ) -> Result<()> {
+        self.0.1.spill_to_disk( threshold )?;
+        self.0.2.spill_to_disk( threshold )
+    }
+
+    /// Returns the counters of all streams, e.g. for exposing them on a metrics
+    /// endpoint to spot runaway output or stuck prompts.
+    pub fn metrics( &self ) -> Metrics {
+        Metrics {
+            input : self.0.0.metrics(),
+            out   : self.0.1.metrics(),
+            err   : self.0.2.metrics(),
+        }
+    }
+
+    /// Makes reads or writes on `stream` fail with an error of `kind`, either the next
+    /// one or every nth one, to test the tool's error handling paths.
+    ///
+    /// Tool side operations returning `std::io::Result`, i.e. reads of the input
+    /// stream and writes/flushes of the output/error streams, are affected.
+    pub fn inject_error( &self, stream: Stream, kind: ErrorKind, when: When ) {
+        match stream {
+            Stream::In  => self.0.0.inject_error( kind, when ),
+            Stream::Out => self.0.1.inject_error( kind, when ),
+            Stream::Err => self.0.2.inject_error( kind, when ),
+        }
+    }
+
+    /// Emulates the buffering of real output streams, e.g. `Buffering::Line` for the
+    /// output stream of a tool running in a terminal, so that a missing `flush()` in
+    /// the tool is reproducible. Written text becomes visible to receivers at
+    /// newlines or when the buffer is full, or when the tool flushes or finishes.
+    /// Does nothing for `Stream::In`.
+    pub fn buffering( &self, stream: Stream, mode: Buffering ) {
+        match stream {
+            Stream::In  => (),
+            Stream::Out => self.0.1.buffering( mode ),
+            Stream::Err => self.0.2.buffering( mode ),
+        }
+    }
+
+    /// Emulates the console of the platform the tool is deployed on for all the
+    /// streams. With `Console::Windows`, newlines written by the tool are received as
+    /// `"\r\n"`, and newlines sent are read as `"\r\n"` by the tool, so that e.g. a
+    /// line trimmed of `'\n'` only reproduces the bugs seen on Windows.
+    ///
+    /// ```rust
+    /// use altio::{Altio, Console, echo};
+    ///
+    /// let io = Altio::default();
+    /// io.console( Console::WindowsOem );
+    /// io.send_line( "naïve \u{4e2d}" );
+    /// let mut line = String::new();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "naïve ?\r\n" );
+    /// echo!( io.out(), "done" );
+    /// assert_eq!( io.recv_line(), "done\r\n" );
+    /// ```
+    pub fn console( &self, console: Console ) {
+        self.0.0.console( console );
+        self.0.1.console( console );
+        self.0.2.console( console );
+    }
+
+    /// Stops failing operations on `stream`, see `inject_error()`.
+    pub fn clear_error( &self, stream: Stream ) {
+        match stream {
+            Stream::In  => self.0.0.clear_error(),
+            Stream::Out => self.0.1.clear_error(),
+            Stream::Err => self.0.2.clear_error(),
+        }
+    }
+
+    /// Delays operations on `stream` for a fixed or jittered duration, to reproduce
+    /// races which only appear when the tool is slow to respond.
+    ///
+    /// Reads of the input stream are delayed before they return, and writes into the
+    /// output/error streams are delayed before they become visible.
+    pub fn inject_latency( &self, stream: Stream, delay: Delay ) {
+        match stream {
+            Stream::In  => self.0.0.inject_latency( delay ),
+            Stream::Out => self.0.1.inject_latency( delay ),
+            Stream::Err => self.0.2.inject_latency( delay ),
+        }
+    }
+
+    /// Stops delaying operations on `stream`, see `inject_latency()`.
+    pub fn clear_latency( &self, stream: Stream ) {
+        match stream {
+            Stream::In  => self.0.0.clear_latency(),
+            Stream::Out => self.0.1.clear_latency(),
+            Stream::Err => self.0.2.clear_latency(),
+        }
+    }
+
+    /// Calls `scheduler` whenever a blocking read of any stream finds nothing to
+    /// receive, instead of sleeping until the stream is written.
+    ///
+    /// Test harnesses can use this to step the tool and the driver in turns, making
+    /// tests of interactive tools deterministic instead of sleep-based.
+    pub fn schedule( &self, scheduler: impl Scheduler + 'static ) {
+        let scheduler = Arc::new( scheduler );
+        self.0.0.schedule( scheduler.clone() );
+        self.0.1.schedule( scheduler.clone() );
+        self.0.2.schedule( scheduler );
+    }
+
+    /// Stops calling the scheduler, see `schedule()`.
+    pub fn clear_scheduler( &self ) {
+        self.0.0.clear_scheduler();
+        self.0.1.clear_scheduler();
+        self.0.2.clear_scheduler();
+    }
+
+    /// Enters a test mode in which the visibility of text written into output and
+    /// error streams is scheduled by a PRNG: the text of one write may be received in
+    /// pieces, merged with following writes, or delayed until later receives.
+    ///
+    /// This flushes out driver code assuming "one println == one recv". For the same
+    /// sequence of writes and receives, the same seed gives the same schedule. If
+    /// `seed` is `None`, it is read from the `ALTIO_SEED` environment variable, or
+    /// generated. The seed in use is returned for reproducing failures.
+    pub fn interleave( &self, seed: Option<u64> ) -> u64 {
+        let seed = seed
+            .or_else( || std::env::var( "ALTIO_SEED" ).ok()?.parse().ok() )
+            .unwrap_or_else( || rng::Rng::from_time().next_u64() );
+        self.0.1.interleave( seed );
+        self.0.2.interleave( seed );
+        seed
+    }
+
+    /// Returns the seed of the interleaving test mode, if enabled.
+    pub fn interleave_seed( &self ) -> Option<u64> { self.0.1.interleave_seed() }
+
+    /// Starts retaining a copy of everything written into output and error streams.
+    ///
+    /// With the "zstd" feature, the retained text is stored compressed.
+    pub fn keep_history( &self ) {
+        self.0.1.keep_history();
+        self.0.2.keep_history();
+    }
+
+    /// Returns everything written into altio output stream since `keep_history()`.
+    pub fn history( &self ) -> String { self.0.1.history() }
+
+    /// Returns everything written into altio error stream since `keep_history()`.
+    pub fn history_err( &self ) -> String { self.0.2.history() }
+
+    /// Keeps the history of output and error streams within the limits of `retention`,
+    /// dropping the oldest text beyond them. Starts retaining if not yet.
+    ///
+    /// ```rust
+    /// use altio::{Altio, Retention, echo};
+    ///
+    /// let io = Altio::default();
+    /// io.retain_history( Retention{ max_lines: Some(2), ..Retention::default() });
+    /// echo!( io.out(), "one\ntwo\nthree" );
+    /// assert_eq!( io.history(), "two\nthree\n" );
+    /// assert_eq!( io.history_truncated(), 4 );
+    /// ```
+    pub fn retain_history( &self, retention: Retention ) {
+        self.0.1.retain_history( retention );
+        self.0.2.retain_history( retention );
+    }
+
+    /// Returns the amount of bytes dropped from the history of altio output stream.
+    pub fn history_truncated( &self ) -> usize { self.0.1.history_truncated() }
+
+    /// Returns the amount of bytes dropped from the history of altio error stream.
+    pub fn history_err_truncated( &self ) -> usize { self.0.2.history_truncated() }
+
+    /// Returns a reader of altio output stream which doesn't drain it, starting at the
+    /// beginning of the history, which is kept from now on if not yet.
+    pub fn cursor( &self ) -> OutputCursor { OutputCursor::new( self.clone(), Stream::Out )}
+
+    /// Returns a reader of altio error stream which doesn't drain it, see `cursor()`.
+    pub fn cursor_err( &self ) -> OutputCursor { OutputCursor::new( self.clone(), Stream::Err )}
+
+    /// Sends text to altio input stream, without additional newline.
+    pub fn send( &self, text: &str ) { self.0.0.send( text )}
+
+    /// Sends text to altio input stream, with an additional newline.
+    pub fn send_line( &self, text: &str ) { self.0.0.send_line( text )}
+
+    /// Sends text to altio input stream with an additional newline, then blocks until
+    /// the tool has read past it, see `Altin::send_line_blocking()`.
+    ///
+    /// ```rust
+    /// use altio::{AltCommand, Altio, echo};
+    ///
+    /// let mut child = AltCommand::new( |io: Altio, _| {
+    ///     for prompt in [ "name?", "age?" ] {
+    ///         echo!( io.out(), "{prompt}" );
+    ///         let mut answer = String::new();
+    ///         io.input().read_line( &mut answer ).unwrap();
+    ///     }
+    ///     0
+    /// }).spawn();
+    ///
+    /// let io = child.io().clone();
+    /// assert_eq!( io.recv_line(), "name?\n" );
+    /// io.send_line_blocking( "Alice" );
+    /// assert_eq!( io.recv_line(), "age?\n" );
+    /// io.send_line_blocking( "42" );
+    /// assert_eq!( child.wait().unwrap(), 0 );
+    /// ```
+    pub fn send_line_blocking( &self, text: &str ) { self.0.0.send_line_blocking( text )}
+
+    /// Sends text to altio input stream with an additional newline, returning a token
+    /// resolving once the tool has read past it, see `SendToken`.
+    pub fn send_line_tracked( &self, text: &str ) -> SendToken {
+        let end = self.0.0.send_pieces( &[ text, "\n" ]);
+        let number = self.0.0.tracked.fetch_add( 1, Ordering::Relaxed ) + 1;
+        SendToken::new( self.clone(), end, number, text )
+    }
+
+    /// Sends text to altio input stream with an additional newline, then blocks until
+    /// the tool has read past it, at most `timeout` measured by the clock of this
+    /// `Altio`. Returns false on timeout, see `Altin::send_line_timeout()`.
+    pub fn send_line_timeout( &self, text: &str, timeout: Duration ) -> bool { self.0.0.send_line_timeout( text, timeout )}
+
+    /// Attaches a source of lines to altio input stream, e.g. a `source::Script` of
+    /// answers, or a `source::Channel` of manual ones. When the tool reads and no
+    /// sent line is pending, the sources are asked for one in order of `precedence`,
+    /// see `Altin::attach()`.
+    ///
+    /// ```rust
+    /// use altio::{Altio, source::{Channel, Script}};
+    ///
+    /// let io = Altio::default();
+    /// let ( answer, answers ) = std::sync::mpsc::channel();
+    /// io.attach_input( Script::new([ "first", "second" ]), 0 );
+    /// io.attach_input( Channel( answers ), 1 );
+    ///
+    /// let mut line = String::new();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// answer.send( "manual".to_owned() ).unwrap();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "first\nmanual\nsecond\n" );
+    /// ```
+    pub fn attach_input( &self, source: impl Source + 'static, precedence: i32 ) {
+        self.0.0.attach( source, precedence );
+    }
+
+    /// Detaches all sources of altio input stream, see `attach_input()`, including the
+    /// replies of `auto_respond()` until a rule is added again.
+    pub fn detach_inputs( &self ) {
+        #[cfg( feature = "regex" )]
+        self.0.1.respond.lock().unwrap_or_else( PoisonError::into_inner ).disconnect();
+        self.0.0.detach_all();
+        *self.0.1.recent.write().unwrap_or_else( PoisonError::into_inner ) = None;
+    }
+
+    /// Forwards lines of the real stdin into altio input stream whenever neither the
+    /// driver nor the other attached sources provide one, letting a human take over an
+    /// automated session when the script runs out. Detached by `detach_inputs()`.
+    pub fn pass_through_stdin( &self ) { self.attach_input( source::Stdin, i32::MIN )}
+
+    /// Sets `generate` to compute the lines of input on demand: whenever the tool reads
+    /// a line and none is pending nor provided by the other attached sources, it is
+    /// called with what the tool has written since the previous call, received or
+    /// not, until it returns `None`. The output stream is not locked meanwhile, so
+    /// the tool may keep it locked while reading. The lines are
+    /// without trailing newlines, like those of `attach_input()`, and `generate`
+    /// should return them rather than send them. Detached by `detach_inputs()`.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::default();
+    /// io.set_input_generator( |request| {
+    ///     let answer = if request.output.contains( "name" ) { "Alice" } else { "y" };
+    ///     ( request.count < 2 ).then( || answer.to_owned() )
+    /// });
+    ///
+    /// let mut answers = String::new();
+    /// echo!( io.out(), "Continue?" );
+    /// io.input().read_line( &mut answers ).unwrap();
+    /// assert_eq!( io.recv_line(), "Continue?\n" );
+    /// echo!( io.out(), "Your name:" );
+    /// io.input().read_line( &mut answers ).unwrap();
+    /// assert_eq!( answers, "y\nAlice\n" );
+    /// ```
+    pub fn set_input_generator( &self, mut generate: impl FnMut( source::InputRequest ) -> Option<String> + Send + 'static ) {
+        self.0.1.recent.write().unwrap_or_else( PoisonError::into_inner ).get_or_insert_with( Vec::new );
+        let io = std::sync::Arc::downgrade( &self.0 );
+        let mut count = 0;
+        self.attach_input( source::Generator( move || {
+            let recent = io.upgrade()?.1.recent.write().unwrap_or_else( PoisonError::into_inner ).as_mut().map( std::mem::take );
+            let output = into_string( recent.unwrap_or_default() );
+            let line = generate( source::InputRequest{ output, count });
+            count += 1;
+            line
+        }), i32::MIN );
+    }
+
+    /// Sends each line of `timeline` into altio input stream at its offset from now,
+    /// measured by the clock of this `Altio`, from a timer thread, for reproducing
+    /// timing-sensitive interactions, e.g. input arriving while the tool is rendering.
+    /// The lines are without trailing newlines. Returns the timer thread, which ends
+    /// after sending the last line, or once this `Altio` is dropped.
+    ///
+    /// ```rust
+    /// use altio::{Altio, ManualClock};
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// let clock = Arc::new( ManualClock::new() );
+    /// let io = Altio::builder().clock( clock.clone() ).build();
+    /// let timer = io.schedule_input( &[
+    ///     ( Duration::from_secs( 2 ), "later" ),
+    ///     ( Duration::ZERO, "now" ),
+    /// ]);
+    /// let mut line = String::new();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "now\n" );
+    ///
+    /// clock.advance( Duration::from_secs( 2 ));
+    /// timer.join().unwrap();
+    /// io.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "now\nlater\n" );
+    /// ```
+    pub fn schedule_input( &self, timeline: &[( Duration, &str )] ) -> std::thread::JoinHandle<()> {
+        let start = self.0.0.clock.now();
+        let mut timeline = timeline.iter()
+            .map( |&( offset, line )| ( start + offset, line.to_owned() ))
+            .collect::<Vec<_>>();
+        timeline.sort_by_key( |&( at, _ )| at );
+        let io = std::sync::Arc::downgrade( &self.0 );
+        std::thread::spawn( move || {
+            for ( at, line ) in timeline {
+                loop {
+                    let Some( io ) = io.upgrade() else { return };
+                    let now = io.0.clock.now();
+                    if now >= at {
+                        io.0.send_line( &line );
+                        break;
+                    }
+                    drop( io );
+                    std::thread::sleep(( at - now ).min( POLL_INTERVAL ));
+                }
+            }
+        })
+    }
+
+    /// Forwards the messages arriving on an async channel into altio input stream as
+    /// lines, in a background thread, and closes the input stream once the channel is
+    /// closed, e.g. all senders are dropped. With the "tokio" feature.
+    ///
+    /// ```rust
+    /// use altio::Altio;
+    ///
+    /// let io = Altio::default();
+    /// let ( tx, rx ) = tokio::sync::mpsc::channel( 8 );
+    /// io.feed_from_channel( rx );
+    /// tx.blocking_send( "first".to_owned() ).unwrap();
+    /// tx.blocking_send( "second".to_owned() ).unwrap();
+    /// drop( tx );
+    ///
+    /// assert_eq!( io.input().lines().collect::<Vec<_>>(), [ "first\n", "second\n" ]);
+    /// ```
+    #[cfg( feature = "tokio" )]
+    pub fn feed_from_channel( &self, mut rx: tokio::sync::mpsc::Receiver<String> ) {
+        let io = std::sync::Arc::downgrade( &self.0 );
+        std::thread::spawn( move || {
+            while let Some( line ) = rx.blocking_recv() {
+                match io.upgrade() {
+                    Some( io ) => io.0.send_line( &line ),
+                    None => return,
+                }
+            }
+            if let Some( io ) = io.upgrade() {
+                io.0.close();
+            }
+        });
+    }
+
+    /// Switches the tool to real stdio at runtime: from now on it reads lines from the
+    /// real stdin, after those already sent, and writes to the real stdout and stderr,
+    /// while the text it wrote before can still be received. Attached input sources are
+    /// not asked until `detach()`.
+    pub fn attach_to_stdio( &self ) { self.set_stdio( true )}
+
+    /// Switches the tool back to altio streams after `attach_to_stdio()`.
+    pub fn detach( &self ) { self.set_stdio( false )}
+
+    /// Returns true if the tool is attached to real stdio, see `attach_to_stdio()`.
+    pub fn is_attached_to_stdio( &self ) -> bool { self.0.0.stdio.load( Ordering::Relaxed )}
+
+    /// Sets whether the text written into `stream` is copied to the real stderr, e.g.
+    /// to watch an embedded tool while debugging a failing test. Without code changes,
+    /// the `ALTIO_MIRROR` environment variable turns mirroring on for a comma-separated
+    /// list of "in", "out", "err" or "all" streams.
+    pub fn mirror( &self, stream: Stream, enabled: bool ) {
+        match stream {
+            Stream::In  => self.0.0.mirror.set( enabled ),
+            Stream::Out => self.0.1.mirror.set( enabled ),
+            Stream::Err => self.0.2.mirror.set( enabled ),
+        }
+    }
+
+    /// Sets the prefix of each mirrored line, e.g. "[worker-3] ", telling apart the
+    /// sessions mirrored to the same console, see `mirror()`.
+    pub fn set_mirror_prefix( &self, prefix: &str ) {
+        self.0.0.mirror.set_prefix( prefix );
+        self.0.1.mirror.set_prefix( prefix );
+        self.0.2.mirror.set_prefix( prefix );
+    }
+
+    /// Sets whether mirrored traffic is color-coded if the real stderr is a terminal:
+    /// output plain, errors red and input cyan, see `mirror()`.
+    pub fn color_mirror( &self, enabled: bool ) {
+        self.0.0.mirror.set_colored( enabled );
+        self.0.1.mirror.set_colored( enabled );
+        self.0.2.mirror.set_colored( enabled );
+    }
+
+    /// Sets whether mirrored traffic is shown as hex+ASCII dumps, revealing stray
+    /// carriage returns, byte order marks or NULs, e.g. sent by `send_raw()`. The
+    /// offsets count the bytes mirrored since this call, see `mirror()`.
+    pub fn hex_mirror( &self, enabled: bool ) {
+        self.0.0.mirror.set_hex( enabled );
+        self.0.1.mirror.set_hex( enabled );
+        self.0.2.mirror.set_hex( enabled );
+    }
+
+    /// Mirrors only the lines for which `filter` returns true, given without their line
+    /// endings, e.g. the errors of a chatty tool. Lines are mirrored once finished.
+    /// See `mirror()`.
+    ///
+    /// `filter` is called by the write finishing a line, with the stream written
+    /// still locked, so it must not lock that stream, e.g. by `out()` for the output.
+    ///
+    /// ```rust
+    /// use altio::{Altio, Stream};
+    ///
+    /// let io = Altio::default();
+    /// io.mirror( Stream::Out, true );
+    /// io.filter_mirror( |line| line.contains( "ERROR" ));
+    /// ```
+    pub fn filter_mirror( &self, filter: impl Fn( &str ) -> bool + Send + Sync + 'static ) {
+        let filter = mirror::Filter( Arc::new( filter ));
+        self.0.0.mirror.set_filter( Some( filter.clone() ));
+        self.0.1.mirror.set_filter( Some( filter.clone() ));
+        self.0.2.mirror.set_filter( Some( filter ));
+    }
+
+    /// Mirrors only the lines in which `pattern` is found, see `filter_mirror()`.
+    pub fn filter_mirror_matching( &self, pattern: Pattern ) {
+        self.filter_mirror( move |line| pattern.find( line ).is_some() );
+    }
+
+    /// Mirrors all the traffic again after `filter_mirror()`, dropping the unfinished
+    /// lines held by the filter.
+    pub fn unfilter_mirror( &self ) {
+        self.0.0.mirror.set_filter( None );
+        self.0.1.mirror.set_filter( None );
+        self.0.2.mirror.set_filter( None );
+    }
+
+    /// Sets where the traffic of the streams mirrored goes, e.g. `tracing` events
+    /// instead of the real stderr with the "trace-io" feature, see `mirror()`.
+    ///
+    /// ```rust
+    /// # #[cfg( feature = "trace-io" )] {
+    /// use altio::{Altio, MirrorTarget, Stream};
+    ///
+    /// let io = Altio::default();
+    /// io.mirror( Stream::Err, true );
+    /// io.set_mirror_target( MirrorTarget::Tracing{ session: "indexer".to_owned() });
+    /// # }
+    /// ```
+    pub fn set_mirror_target( &self, target: MirrorTarget ) {
+        self.0.0.mirror.set_target( target.clone() );
+        self.0.1.mirror.set_target( target.clone() );
+        self.0.2.mirror.set_target( target );
+    }
+
+    /// Returns the hex+ASCII dump of the bytes pending in `stream`, not received or
+    /// read yet, leaving them in the stream.
+    pub fn dump_pending_hex( &self, stream: Stream ) -> String {
+        hex::dump( &self.pending( stream ), 0 )
+    }
+
+    /// Copies the bytes pending in `stream`.
+    fn pending( &self, stream: Stream ) -> Vec<u8> {
+        match stream {
+            Stream::In  => self.0.0.lock().input().copy_all(),
+            Stream::Out => self.0.1.lock().output().copy_all(),
+            Stream::Err => self.0.2.lock().output().copy_all(),
+        }
+    }
+
+    /// Replies `reply` as a line of input whenever the tool writes text matching the
+    /// regular expression `pattern` into the output stream, e.g. answering "y" to
+    /// "Continue? [y/N]", so the driver can focus on the interesting parts of the
+    /// interaction. Capture groups are substituted for `$1` or `${name}` in `reply`,
+    /// and empty matches are ignored. Replies are read before the other attached input
+    /// sources, after the input already sent. With the "regex" feature.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::default();
+    /// io.auto_respond( r"Continue\? \[y/N\]", "y" ).unwrap();
+    /// echo!( -n, io.out(), "Continue? [y/N] " );
+    /// assert_eq!( io.input().lines().next().as_deref(), Some( "y\n" ));
+    /// ```
+    #[cfg( feature = "regex" )]
+    pub fn auto_respond( &self, pattern: &str, reply: &str ) -> std::result::Result<(), regex::Error> {
+        self.auto_respond_rule( pattern, reply, None )
+    }
+
+    /// Replies at most `max` times, see `auto_respond()`. With the "regex" feature.
+    #[cfg( feature = "regex" )]
+    pub fn auto_respond_max( &self, pattern: &str, reply: &str, max: usize ) -> std::result::Result<(), regex::Error> {
+        self.auto_respond_rule( pattern, reply, Some( max ))
+    }
+
+    #[cfg( feature = "regex" )]
+    fn auto_respond_rule( &self, pattern: &str, reply: &str, max: Option<usize> ) -> std::result::Result<(), regex::Error> {
+        let regex = regex::Regex::new( pattern )?;
+        let mut responder = self.0.1.respond.lock().unwrap_or_else( PoisonError::into_inner );
+        if !responder.is_connected() {
+            let ( sender, receiver ) = std::sync::mpsc::channel();
+            responder.connect( sender );
+            self.attach_input( source::Channel( receiver ), i32::MAX );
+        }
+        responder.add( regex, reply, max );
+        Ok(())
+    }
+
+    /// Removes all rules added by `auto_respond()`. With the "regex" feature.
+    #[cfg( feature = "regex" )]
+    pub fn clear_auto_responses( &self ) { self.0.1.respond.lock().unwrap_or_else( PoisonError::into_inner ).clear() }
+
+    /// Logs everything sent into, written to or read from real stdin by the streams
+    /// into the file at `path` as it happens, with the closing of streams and the exit
+    /// code of an `AltCommand`, one line per event tagged with the seconds since this
+    /// call. Replaces the log in progress, and truncates the file.
+    ///
+    /// ```text
+    ///    0.000152 out  "Name: "
+    ///    0.803114 in   "Alice\n"
+    ///    0.803290 err  "warning: no profile\n"
+    ///    0.803311 eof  out
+    ///    0.803315 eof  err
+    ///    0.803318 exit 0
+    /// ```
+    ///
+    /// Text is quoted like in transcripts. A line written in pieces is logged as they
+    /// come, at the time of the first one, so that the log is current when a test
+    /// hangs.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let path = std::env::temp_dir().join( "altio-doc-session.log" );
+    /// let io = Altio::default();
+    /// io.log_session_to( &path ).unwrap();
+    /// echo!( -n, io.out(), "Name: " );
+    /// io.send_line( "Alice" );
+    /// io.stop_session_log();
+    ///
+    /// let logged = std::fs::read_to_string( &path ).unwrap();
+    /// let events = logged.lines().map( |line| line.trim_start().split_once( ' ' ).unwrap().1 ).collect::<Vec<_>>();
+    /// assert_eq!( events, [ r#"out  "Name: ""#, r#"in   "Alice\n""# ]);
+    /// # std::fs::remove_file( &path ).unwrap();
+    /// ```
+    pub fn log_session_to( &self, path: impl AsRef<std::path::Path> ) -> Result<()> {
+        self.set_session_log( Some( Arc::new( log::SessionLog::create( path.as_ref(), None )? )));
+        Ok(())
+    }
+
+    /// Logs like `log_session_to()`, rotating the file once it reaches `max_bytes`:
+    /// it is renamed with the suffix ".1", the files rotated before shifting to ".2"
+    /// and so on, and logging goes on into a new file. At most `max_files` files are
+    /// kept including the current one, the oldest being removed, so that always-on
+    /// logging of long-running tools doesn't fill the disk. With `max_files` of 1,
+    /// the file is truncated instead.
+    ///
+    /// A file exceeds `max_bytes` by the last event logged into it at most, and the
+    /// line written in pieces at the time of rotation goes on in the new file.
+    pub fn log_session_rotating( &self, path: impl AsRef<std::path::Path>, max_bytes: u64, max_files: usize ) -> Result<()> {
+        let rotation = log::Rotation{ max_bytes, max_files };
+        self.set_session_log( Some( Arc::new( log::SessionLog::create( path.as_ref(), Some( rotation ))? )));
+        Ok(())
+    }
+
+    /// Stops logging started by `log_session_to()`, closing the file.
+    pub fn stop_session_log( &self ) { self.set_session_log( None )}
+
+    fn set_session_log( &self, log: Option<Arc<log::SessionLog>> ) {
+        self.0.0.mirror.set_log( log.clone() );
+        self.0.1.mirror.set_log( log.clone() );
+        self.0.2.mirror.set_log( log );
+    }
+
+    /// Starts recording a timestamped transcript of everything sent into, written to
+    /// or read from real stdin by the streams, replacing the recording in progress.
+    pub fn start_recording( &self ) {
+        let recording = Some( Arc::new( Recording::new() ));
+        self.0.0.mirror.set_recording( recording.clone() );
+        self.0.1.mirror.set_recording( recording.clone() );
+        self.0.2.mirror.set_recording( recording );
+    }
+
+    /// Renders the session recorded so far by `start_recording()` for humans, e.g. to
+    /// embed in failure messages: its lines of input as `>>> `, output as `out: ` and
+    /// errors as `err: `, interleaved in order. Empty unless recording.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::default();
+    /// io.start_recording();
+    /// echo!( io.out(), "Name?" );
+    /// io.send_line( "Alice" );
+    /// echo!( io.err(), "unknown user" );
+    /// assert_eq!( io.format_session(), "out: Name?\n>>> Alice\nerr: unknown user\n" );
+    /// ```
+    pub fn format_session( &self ) -> String {
+        self.0.0.mirror.recording()
+            .map( |recording| format!( "{:#}", recording.transcript() ))
+            .unwrap_or_default()
+    }
+
+    /// Stops recording and returns the transcript, empty if not recording.
+    pub fn stop_recording( &self ) -> Transcript {
+        let recording = self.0.0.mirror.set_recording( None );
+        self.0.1.mirror.set_recording( None );
+        self.0.2.mirror.set_recording( None );
+        recording.map( |recording| recording.transcript() ).unwrap_or_default()
+    }
+
+    /// Records a human-driven session: attaches the tool to real stdio, passing input
+    /// and output through while recording them, until `stop_recording()` and
+    /// `detach()`. The transcript can be saved and replayed as an automated test.
+    ///
+    /// ```rust,no_run
+    /// use altio::{Altio, Stream};
+    ///
+    /// let io = Altio::default();
+    /// io.record_stdio();
+    /// // run the tool ...
+    /// let transcript = io.stop_recording();
+    /// io.detach();
+    /// transcript.save( "session.transcript" ).unwrap();
+    ///
+    /// let replay = Altio::default();
+    /// replay.attach_input( transcript.input_script(), 0 );
+    /// // run the tool again, then compare:
+    /// assert_eq!( replay.recv(), transcript.text( Stream::Out ));
+    /// ```
+    pub fn record_stdio( &self ) {
+        self.start_recording();
+        self.attach_to_stdio();
+    }
+
+    /// Pauses capturing, making the tool write to the real stdout and stderr until
+    /// `resume_capture()`, e.g. while a debugger or a human watches it live. The text
+    /// written before can still be received, the input stream is not affected.
+    pub fn pause_capture( &self ) { self.pass_through_output( true )}
+
+    /// Resumes capturing output paused by `pause_capture()`.
+    pub fn resume_capture( &self ) { self.pass_through_output( false )}
+
+    /// Returns the handles for `Command::stdin()`, `stdout()` and `stderr()`, so that
+    /// the processes spawned by the tool read from and write into the streams of this
+    /// `Altio` instead of escaping capture. Fails with `ErrorKind::Unsupported` unless
+    /// the backend has such handles, e.g. `backend::OsPipes`.
+    ///
+    /// ```rust,no_run
+    /// # #[cfg( feature = "os-pipe" )] {
+    /// use altio::{Altio, backend::OsPipes};
+    /// use std::process::Command;
+    ///
+    /// let io = Altio::builder().backend( OsPipes::new().unwrap() ).build();
+    /// let ( stdin, stdout, stderr ) = io.inheritable_stdio().unwrap();
+    /// Command::new( "ls" ).stdin( stdin ).stdout( stdout ).stderr( stderr ).status().unwrap();
+    /// # }
+    /// ```
+    pub fn inheritable_stdio( &self ) -> Result<( std::process::Stdio, std::process::Stdio, std::process::Stdio )> {
+        match self.0.4.0.lock().unwrap_or_else( PoisonError::into_inner ).as_ref() {
+            Some( backend ) => backend.stdio(),
+            None => Err( ErrorKind::Unsupported.into() ),
+        }
+    }
+
+    /// Merges the output of a child process spawned by the tool into the output stream
+    /// in a background thread, line by line, each line prefixed with `tag` which may be
+    /// empty. The lines are kept whole among the text written by the tool itself.
+    ///
+    /// ```rust
+    /// # #[cfg( unix )] {
+    /// use altio::{Altio, echo};
+    /// use std::process::{Command, Stdio};
+    ///
+    /// let io = Altio::default();
+    /// let mut child = Command::new( "echo" ).arg( "from child" ).stdout( Stdio::piped() ).spawn().unwrap();
+    /// io.adopt_child_stdout( child.stdout.take().unwrap(), "[echo] " );
+    /// child.wait().unwrap();
+    /// assert_eq!( io.recv_line(), "[echo] from child\n" );
+    /// # }
+    /// ```
+    pub fn adopt_child_stdout( &self, stdout: impl std::io::Read + Send + 'static, tag: &str ) {
+        backend::adopt( self, Stream::Out, stdout, tag.to_owned() );
+    }
+
+    /// Merges the error output of a child process spawned by the tool into the error
+    /// stream, see `adopt_child_stdout()`.
+    pub fn adopt_child_stderr( &self, stderr: impl std::io::Read + Send + 'static, tag: &str ) {
+        backend::adopt( self, Stream::Err, stderr, tag.to_owned() );
+    }
+
+    fn set_stdio( &self, attached: bool ) {
+        self.pass_through_output( attached );
+        self.0.0.stdio.store( attached, Ordering::Relaxed );
+        self.0.0.ready.notify_all();
+    }
+
+    /// Switches output and error streams to or from the real ones, flushing what the
+    /// tool wrote before so that it is received, or shown, before what it writes after.
+    fn pass_through_output( &self, enabled: bool ) {
+        for altout in [ &self.0.1, &self.0.2 ] {
+            let mut lock = altout.lock();
+            lock.output().flush();
+            if altout.stdio.load( Ordering::Relaxed ) {
+                let _ = altout.flush_stdio();
+            }
+            altout.stdio.store( enabled, Ordering::Relaxed );
+            altout.ready.notify_all();
+        }
+    }
+
+    /// Sends text to altio input stream with an additional newline, jumping ahead of
+    /// the queued input, e.g. an abort command while scripted answers are pending.
+    /// Priority lines are read in the order they are sent.
+    pub fn send_priority_line( &self, text: &str ) { self.0.0.send_priority_line( text )}
+
+    /// Prepends text to altio input stream, e.g. an answer that must come before the
+    /// queued ones, see `Altin::unread()`.
+    pub fn push_front( &self, text: &str ) { self.0.0.unread( text )}
+
+    /// Sends all pieces of text to altio input stream as a whole, never interleaved
+    /// with the sends of other threads, see `Altin::send_atomic()`.
+    pub fn send_atomic( &self, pieces: &[&str] ) { self.0.0.send_atomic( pieces )}
+
+    /// Waits until the tool has read all the text sent into altio input stream,
+    /// blocking at most `timeout` measured by the clock of this `Altio`. Returns false
+    /// if some is still waiting to be read on timeout, e.g. an answer sent before the
+    /// tool asked for it.
+    ///
+    /// ```rust
+    /// use altio::Altio;
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// io.send_line( "y" );
+    /// assert!( !io.wait_input_consumed( Duration::from_millis( 10 )));
+    /// let mut answer = String::new();
+    /// io.input().read_line( &mut answer ).unwrap();
+    /// assert!( io.wait_input_consumed( Duration::ZERO ));
+    /// ```
+    pub fn wait_input_consumed( &self, timeout: Duration ) -> bool { self.0.0.wait_consumed( timeout )}
+
+    /// Fails with the leftover text if some text written into output or error streams
+    /// was never received.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "checked\nforgotten" );
+    /// io.recv_line();
+    /// assert_eq!( io.verify_output_consumed().unwrap_err().to_string(),
+    ///     r#"output written but never received: "forgotten\n""# );
+    /// ```
+    pub fn verify_output_consumed( &self ) -> Result<()> {
+        let leftovers = [ Stream::Out, Stream::Err ].into_iter()
+            .map( |stream| ( stream, into_string( self.pending( stream ))))
+            .filter( |( _, leftover )| !leftover.is_empty() )
+            .map( |( stream, leftover )| format!( "{} written but never received: {leftover:?}", match stream {
+                Stream::Err => "error",
+                _ => "output",
+            }))
+            .collect::<Vec<_>>();
+        if leftovers.is_empty() {
+            Ok(())
+        } else {
+            Err( Error::new( ErrorKind::InvalidData, leftovers.join( "; " )))
+        }
+    }
+
+    /// Returns the violations of the strict mode set by `AltioBuilder::strict_err()`.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::builder().strict_err( true ).build();
+    /// echo!( io.err(), "warning: deprecated" );
+    /// assert_eq!( io.violations(), [ r#"err stream written in strict mode: "warning: deprecated\n""# ]);
+    ///
+    /// // panics when dropped
+    /// assert!( std::thread::spawn( move || drop( io )).join().is_err() );
+    /// ```
+    pub fn violations( &self ) -> Vec<String> { self.0.2.flagged.lock().unwrap_or_else( PoisonError::into_inner ).clone() }
+
+    /// Runs the checks set by `AltioBuilder` now, panicking with the failures, instead
+    /// of when the last handle is dropped.
+    ///
+    /// Call it on the driver side once the tool is done: the last handle may be held
+    /// by the thread of the tool, where the panic surfaces only if the thread is
+    /// joined, or by handles which may never be dropped, e.g. of `OutputCursor`,
+    /// `SendToken`, `StatusWatcher`, or registered by `registry::register()`, silently
+    /// skipping the checks.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    ///
+    /// let io = Altio::builder().check_output_consumed( true ).build();
+    /// let cursor = io.cursor();
+    /// echo!( io.out(), "forgotten" );
+    /// assert!( std::thread::spawn( move || io.finish() ).join().is_err() );
+    /// drop( cursor );
+    /// ```
+    #[track_caller]
+    pub fn finish( self ) {
+        self.0.5.finished.store( true, Ordering::Relaxed );
+        let failures = self.check_failures();
+        if !failures.is_empty() {
+            panic!( "altio: {}", failures.join( "; " ));
+        }
+    }
+
+    /// Returns the failures of the checks set by `AltioBuilder`.
+    fn check_failures( &self ) -> Vec<String> {
+        let checks = self.0.5.checks;
+        let mut failures = Vec::new();
+        if checks.input {
+            failures.extend( self.verify_consumed().err().map( |err| err.to_string() ));
+        }
+        if checks.output {
+            failures.extend( self.verify_output_consumed().err().map( |err| err.to_string() ));
+        }
+        failures.extend( self.violations() );
+        failures
+    }
+
+    /// Fails with the leftover text if some input sent was never read by the tool,
+    /// which usually means that the script and the tool got out of sync.
+    ///
+    /// ```rust
+    /// use altio::Altio;
+    ///
+    /// let io = Altio::default();
+    /// io.send_line( "y" );
+    /// io.send_line( "y" );
+    /// let mut answer = String::new();
+    /// io.input().read_line( &mut answer ).unwrap();
+    /// assert_eq!( io.verify_consumed().unwrap_err().to_string(), r#"input sent but never read: "y\n""# );
+    /// ```
+    pub fn verify_consumed( &self ) -> Result<()> {
+        let leftover = self.pending( Stream::In );
+        if leftover.is_empty() {
+            Ok(())
+        } else {
+            Err( Error::new( ErrorKind::InvalidData, format!( "input sent but never read: {:?}", into_string( leftover ))))
+        }
+    }
+
+    /// Closes altio input stream: blocking reads return the rest of the input, then
+    /// end-of-file.
+    pub fn close_input( &self ) { self.0.0.close() }
+
+    /// Returns the output stream if `stream` is `Stream::Out`, the error stream otherwise.
+    fn altout( &self, stream: Stream ) -> &Altout {
+        match stream {
+            Stream::Err => &self.0.2,
+            _ => &self.0.1,
+        }
+    }
+
+    /// Turns on or off canonical line editing of altio input stream: sent text is
+    /// held back until a newline, and backspace, delete, Ctrl-U and Ctrl-W edit the
+    /// line being "typed", see `Altin::cooked()`.
+    pub fn cooked_input( &self, enabled: bool ) { self.0.0.cooked( enabled )}
+
+    /// Sends bytes to altio input stream as they are, see `Altin::send_raw()`.
+    pub fn send_raw( &self, bytes: &[u8] ) { self.0.0.send_raw( bytes )}
+
+    /// Sets how `read_line()` delivers control characters, see `ControlChars`.
+    pub fn control_chars( &self, policy: ControlChars ) { self.0.0.control_chars( policy )}
+
+    /// Sends bytes which are not valid UTF-8 to altio input stream, see
+    /// `Altin::send_invalid_utf8()`.
+    ///
+    /// ```rust
+    /// use altio::{Altio, InvalidUtf8};
+    /// use std::io::ErrorKind;
+    ///
+    /// let io = Altio::default();
+    /// io.send_invalid_utf8( b"caf\xe9\n" );
+    /// io.send_invalid_utf8( b"caf\xe9\n" );
+    /// let mut line = String::new();
+    /// assert_eq!( io.input().read_line( &mut line ).unwrap_err().kind(), ErrorKind::InvalidData );
+    /// assert_eq!( line, "" );
+    ///
+    /// io.invalid_utf8( InvalidUtf8::Lossy );
+    /// io.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "caf\u{fffd}\n" );
+    /// ```
+    pub fn send_invalid_utf8( &self, bytes: &[u8] ) { self.0.0.send_invalid_utf8( bytes )}
+
+    /// Sets how string-level reads deliver input which is not valid UTF-8, see
+    /// `InvalidUtf8`.
+    pub fn invalid_utf8( &self, policy: InvalidUtf8 ) { self.0.0.invalid_utf8( policy )}
+
+    /// Receives text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv( &self ) -> String { self.0.1.recv() }
+
+    /// Receives text from altio output stream, blocking at most `timeout` measured by
+    /// the clock of this `Altio`, see `AltioBuilder::clock()`.
+    pub fn recv_timeout( &self, timeout: Duration ) -> Option<String> { self.0.1.recv_timeout( timeout )}
+
+    /// Receives text from altio output stream until none is written for `quiet`, or
+    /// at most `max`, e.g. one screen of a menu rendered in several writes. Both are
+    /// measured by the clock of this `Altio`.
+    pub fn recv_settled( &self, quiet: Duration, max: Duration ) -> String { self.0.1.recv_settled( quiet, max )}
+
+    /// Tries to receive text from altio output stream, without blocking.
+    pub fn try_recv( &self ) -> Option<String> { self.0.1.try_recv() }
+
+    /// Receives all pending text of altio output stream once it ends with `suffix`,
+    /// which is received too if `consume`, or left pending otherwise. Unlike
+    /// `recv_line()`, works with prompts not ending in a newline, e.g. `"> "`.
+    ///
+    /// This function will always block the current thread until the pending text
+    /// ends with `suffix`.
+    pub fn recv_until_suffix( &self, suffix: &str, consume: bool ) -> String { self.0.1.recv_until_suffix( suffix, consume )}
+
+    /// Receives the text of altio output stream up to and including the first match
+    /// of any of `patterns`, blocking at most `timeout` measured by the clock of this
+    /// `Altio`. Returns the index of the pattern matched with the text received, the
+    /// rest being left pending. If several patterns match, the one matching earliest
+    /// in the text wins, like the multi-pattern `expect` of expect(1), so that
+    /// alternative outcomes are told apart by a single wait.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "Connecting...\nError: refused\nRetry? " );
+    /// let patterns = [ "Connected", "Retry? ", "Error: " ];
+    /// assert_eq!( io.expect_any( &patterns, Duration::from_secs(1) ),
+    ///     Some(( 2, "Connecting...\nError: ".to_owned() )));
+    /// assert_eq!( io.expect_any( &patterns, Duration::from_secs(1) ),
+    ///     Some(( 1, "refused\nRetry? ".to_owned() )));
+    /// assert_eq!( io.expect_any( &patterns, Duration::from_millis(10) ), None );
+    /// ```
+    pub fn expect_any( &self, patterns: &[&str], timeout: Duration ) -> Option<( usize, String )> { self.0.1.expect_any( patterns, timeout )}
+
+    /// Receives the text of altio output stream up to and including the first match
+    /// of any of `patterns`, like `expect_any()` with patterns matching text of any
+    /// `MatchKind`.
+    ///
+    /// ```rust
+    /// use altio::{Altio, MatchKind, Pattern, echo};
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "Build FAILED in 3s" );
+    /// let patterns = [
+    ///     Pattern::new( "build succeeded", MatchKind::CaseInsensitive ).unwrap(),
+    ///     Pattern::new( "build failed in *s", MatchKind::CaseInsensitive ).unwrap(),
+    ///     Pattern::new( "FAILED in ?s", MatchKind::Glob ).unwrap(),
+    /// ];
+    /// assert_eq!( io.expect_any_match( &patterns, Duration::from_secs(1) ),
+    ///     Some(( 2, "Build FAILED in 3s".to_owned() )));
+    /// ```
+    pub fn expect_any_match( &self, patterns: &[Pattern], timeout: Duration ) -> Option<( usize, String )> {
+        self.0.1.expect_any_match( patterns, timeout )
+    }
+
+    /// Receives the text of altio output stream up to and including the first match
+    /// of `regex`, blocking at most `timeout` measured by the clock of this `Altio`,
+    /// and returns the match with its numbered and named capture groups, and the text
+    /// received before it, e.g. to send values printed by the tool back to it. With
+    /// the "regex" feature.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    /// use regex::Regex;
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "Starting\nListening on 127.0.0.1:8080\nSession id: 4f2a" );
+    /// let address = Regex::new( r"Listening on (?<host>[\d.]+):(\d+)\n" ).unwrap();
+    /// let found = io.expect_regex( &address, Duration::from_secs(1) ).unwrap();
+    /// assert_eq!( found.before(), "Starting\n" );
+    /// assert_eq!( found.name( "host" ), Some( "127.0.0.1" ));
+    /// assert_eq!( found.get(2), Some( "8080" ));
+    /// assert_eq!( found.get(3), None );
+    /// assert_eq!( io.recv(), "Session id: 4f2a\n" );
+    /// ```
+    #[cfg( feature = "regex" )]
+    pub fn expect_regex( &self, regex: &regex::Regex, timeout: Duration ) -> Option<Match> {
+        self.0.1.expect_regex( regex, timeout )
+    }
+
+    /// Limits the expect family of functions, e.g. `expect_any()`, to look for matches
+    /// in the last `bytes` of the pending text of the output and error streams, or a
+    /// little more to avoid splitting a char, so that waiting on a tool writing a lot
+    /// doesn't copy and search all of it again on every write. Matches starting
+    /// before are missed. `None` to look into all of it, the default.
+    ///
+    /// Matches split across writes of the tool are found either way, see
+    /// `StreamMatcher`.
+    pub fn set_match_lookbehind( &self, bytes: Option<usize> ) {
+        for out in [ &self.0.1, &self.0.2 ] {
+            out.window.store( bytes.unwrap_or( usize::MAX ), Ordering::Relaxed );
+        }
+    }
+
+    /// Receives the text of altio output stream up to and including the first match
+    /// of `pattern`, blocking at most `timeout` measured by the clock of this `Altio`.
+    /// Unlike `recv_until_suffix()`, the text after the match is left pending.
+    pub fn recv_until_match( &self, pattern: &Pattern, timeout: Duration ) -> Option<String> {
+        self.0.1.expect_any_match( std::slice::from_ref( pattern ), timeout ).map( |( _, text )| text )
+    }
+
+    /// Writes `prompt` into altio output stream as a control line for `recv_prompt()`,
+    /// or rendered as plain text if attached to real stdio, see the `prompt` module.
+    pub fn emit_prompt( &self, prompt: &Prompt ) -> Result<()> {
+        let text = if self.0.1.stdio.load( Ordering::Relaxed ) {
+            prompt.render()
+        } else {
+            prompt.to_control_line()
+        };
+        let mut out = self.0.1.lock();
+        out.push_str( &text );
+        out.flush()
+    }
+
+    /// Receives the next prompt written by `emit_prompt()`, with the text before it.
+    ///
+    /// This function will always block the current thread until a prompt is written.
+    pub fn recv_prompt( &self ) -> PromptEvent { prompt_event( self.0.1.wait( |buf| take_prompt( buf )))}
+
+    /// Receives the next prompt written by `emit_prompt()`, with the text before it,
+    /// blocking at most `timeout` measured by the clock of this `Altio`.
+    pub fn recv_prompt_timeout( &self, timeout: Duration ) -> Option<PromptEvent> {
+        self.0.1.wait_until( self.0.1.deadline( timeout ), |buf| take_prompt( buf )).map( prompt_event )
+    }
+
+    /// Tries to receive the next prompt written by `emit_prompt()`, with the text
+    /// before it, without blocking.
+    pub fn try_recv_prompt( &self ) -> Option<PromptEvent> { self.0.1.poll( |buf| take_prompt( buf )).map( prompt_event )}
+
+    /// Starts recording the events of this session for `events()`, which starts it as
+    /// well. Call it before the tool starts, to receive its events from the beginning.
+    pub fn record_events( &self ) { self.0.0.events.enable() }
+
+    /// Returns an iterator over the events of all streams and channels of this session
+    /// in the order they happened, blocking for each until it happens, see `Events`.
+    /// Receiving events doesn't drain the streams.
+    pub fn events( &self ) -> Events<'_> { Events::new( self, true )}
+
+    /// Returns an iterator over the events happened so far, without blocking, see
+    /// `events()`.
+    pub fn try_events( &self ) -> Events<'_> { Events::new( self, false )}
+
+    /// Reports the progress of the tool on the progress channel, separate from output
+    /// and error streams, for `recv_progress()`. Also written into the real stderr as
+    /// a textual progress line if attached to it.
+    pub fn progress( &self, done: u64, total: u64, message: &str ) {
+        let event = ProgressEvent{ done, total, message: message.to_owned() };
+        if self.0.2.stdio.load( Ordering::Relaxed ) {
+            self.0.2.passthrough( event.render().as_bytes() );
+        }
+        self.0.0.events.record( || IoEvent::Progress( event.clone() ));
+        self.0.5.progress.lock().unwrap_or_else( PoisonError::into_inner ).push_back( event );
+        self.0.5.ready.notify_all();
+    }
+
+    /// Receives the next progress reported by `progress()`.
+    ///
+    /// This function will always block the current thread until some is reported.
+    pub fn recv_progress( &self ) -> ProgressEvent {
+        self.wait_progress( None ).expect( "waiting without deadline" )
+    }
+
+    /// Receives the next progress reported by `progress()`, blocking at most `timeout`
+    /// measured by the clock of this `Altio`.
+    pub fn recv_progress_timeout( &self, timeout: Duration ) -> Option<ProgressEvent> {
+        self.wait_progress( self.0.2.deadline( timeout ))
+    }
+
+    /// Tries to receive the next progress reported by `progress()`, without blocking.
+    pub fn try_recv_progress( &self ) -> Option<ProgressEvent> {
+        self.0.5.progress.lock().unwrap_or_else( PoisonError::into_inner ).pop_front()
+    }
+
+    /// Sets the value of `key` in the key-value status of this session, for drivers
+    /// calling `status_value()` or watching by `watch_status()`.
+    pub fn set_status( &self, key: &str, value: &str ) {
+        let mut status = self.0.5.status.lock().unwrap_or_else( PoisonError::into_inner );
+        if status.values.get( key ).map( String::as_str ) != Some( value ) {
+            status.values.insert( key.to_owned(), value.to_owned() );
+            status.version += 1;
+            drop( status );
+            self.0.5.ready.notify_all();
+        }
+    }
+
+    /// Returns the value of `key` in the key-value status set by `set_status()`.
+    pub fn status_value( &self, key: &str ) -> Option<String> {
+        self.0.5.status.lock().unwrap_or_else( PoisonError::into_inner ).values.get( key ).cloned()
+    }
+
+    /// Returns a watcher receiving the changes of the key-value status from now on.
+    pub fn watch_status( &self ) -> StatusWatcher { StatusWatcher::new( self.clone() )}
+
+    fn wait_progress( &self, deadline: Option<Instant> ) -> Option<ProgressEvent> {
+        let ( channels, err ) = ( &self.0.5, &self.0.2 );
+        wait_for( &channels.progress, &channels.ready, Stream::Err, &err.hook, &*err.clock, deadline, |progress| progress.pop_front() )
+    }
+
+    /// Waits at most `timeout` until altio output stream shows `prompt`, i.e. the
+    /// pending text ends with it, then receives the text including the prompt.
+    ///
+    /// ```rust
+    /// use altio::{Altio, echo};
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "Welcome!" );
+    /// echo!( -n, io.out(), "Password: " );
+    /// assert_eq!( io.wait_for_prompt( "Password: ", Duration::from_secs(1) ).as_deref(),
+    ///     Some( "Welcome!\nPassword: " ));
+    /// ```
+    pub fn wait_for_prompt( &self, prompt: &str, timeout: Duration ) -> Option<String> {
+        self.0.1.recv_until_suffix_timeout( prompt, true, timeout )
+    }
+
+    /// Receives the text written into altio output stream before the last flush of
+    /// the tool, e.g. `io.out().flush()`, which tells that the tool has finished
+    /// writing a block of text. The final flush when the tool finishes counts too.
+    ///
+    /// This function will always block the current thread until a flush after the
+    /// text received so far.
+    pub fn recv_until_flush( &self ) -> String { self.0.1.recv_until_flush() }
+
+    /// Receives the text written into altio output stream before the last flush of
+    /// the tool, blocking at most `timeout` measured by the clock of this `Altio`.
+    pub fn recv_until_flush_timeout( &self, timeout: Duration ) -> Option<String> { self.0.1.recv_until_flush_timeout( timeout )}
+
+    /// Tries to receive the text written into altio output stream before the last
+    /// flush of the tool, without blocking.
+    pub fn try_recv_until_flush( &self ) -> Option<String> { self.0.1.try_recv_until_flush() }
+
+    /// Receives the text written into altio error stream before the last flush of
+    /// the tool.
+    pub fn recv_err_until_flush( &self ) -> String { self.0.2.recv_until_flush() }
+
+    /// Receives the text written into altio error stream before the last flush of
+    /// the tool, blocking at most `timeout` measured by the clock of this `Altio`.
+    pub fn recv_err_until_flush_timeout( &self, timeout: Duration ) -> Option<String> { self.0.2.recv_until_flush_timeout( timeout )}
+
+    /// Tries to receive the text written into altio error stream before the last
+    /// flush of the tool, without blocking.
+    pub fn try_recv_err_until_flush( &self ) -> Option<String> { self.0.2.try_recv_until_flush() }
+
+    /// Turns on or off message framing of altio output and error streams: each write
+    /// call of the tool makes a message, see `Altout::frame_messages()`.
+    pub fn frame_messages( &self, enabled: bool ) {
+        self.0.1.frame_messages( enabled );
+        self.0.2.frame_messages( enabled );
+    }
+
+    /// Receives the text written by one write call into altio output stream, or all
+    /// pending text if message framing is off, see `frame_messages()`.
+    ///
+    /// This function will always block the current thread if there is no message
+    /// available.
+    pub fn recv_message( &self ) -> String { self.0.1.recv_message() }
+
+    /// Receives the text written by one write call into altio output stream, blocking
+    /// at most `timeout` measured by the clock of this `Altio`.
+    pub fn recv_message_timeout( &self, timeout: Duration ) -> Option<String> { self.0.1.recv_message_timeout( timeout )}
+
+    /// Tries to receive the text written by one write call into altio output stream,
+    /// without blocking.
+    pub fn try_recv_message( &self ) -> Option<String> { self.0.1.try_recv_message() }
+
+    /// Receives the text written by one write call into altio error stream.
+    pub fn recv_err_message( &self ) -> String { self.0.2.recv_message() }
+
+    /// Receives the text written by one write call into altio error stream, blocking
+    /// at most `timeout` measured by the clock of this `Altio`.
+    pub fn recv_err_message_timeout( &self, timeout: Duration ) -> Option<String> { self.0.2.recv_message_timeout( timeout )}
+
+    /// Tries to receive the text written by one write call into altio error stream,
+    /// without blocking.
+    pub fn try_recv_err_message( &self ) -> Option<String> { self.0.2.try_recv_message() }
+
+    /// Receives at most `max` bytes of text from altio output stream, never splitting
+    /// a UTF-8 encoded char.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_max( &self, max: usize ) -> String { self.0.1.recv_max( max )}
+
+    /// Receives exactly `n` chars of text from altio output stream, leaving the rest,
+    /// e.g. fixed-width fields.
+    ///
+    /// This function will always block the current thread until `n` chars are
+    /// available.
+    pub fn recv_exact( &self, n: usize ) -> String { self.0.1.recv_exact( n )}
+
+    /// Tries to receive at most `max` bytes of text from altio output stream, without
+    /// blocking.
+    pub fn try_recv_max( &self, max: usize ) -> Option<String> { self.0.1.try_recv_max( max )}
+
+    /// Receives one line of text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available. Once the tool has closed the stream, the rest of it is received as
+    /// the last line, which may have no trailing newline.
+    pub fn recv_line( &self ) -> String { self.0.1.recv_line() }
+
+    /// Receives one line of text from altio output stream, blocking at most `timeout`
+    /// measured by the clock of this `Altio`, see `AltioBuilder::clock()`.
+    pub fn recv_line_timeout( &self, timeout: Duration ) -> Option<String> { self.0.1.recv_line_timeout( timeout )}
+
+    /// Tries to receive one line of text from altio output stream, without blocking.
+    pub fn try_recv_line( &self ) -> Option<String> { self.0.1.try_recv_line() }
+
+    /// Receives one line of text from altio output stream, blocking at most `timeout`
+    /// measured by the clock of this `Altio`, then receiving the text pending without
+    /// newline, if any, as a partial line, e.g. a final status printed before the
+    /// tool blocks.
+    ///
+    /// ```rust
+    /// use altio::{Altio, Line, echo};
+    /// use std::time::Duration;
+    ///
+    /// let io = Altio::default();
+    /// echo!( io.out(), "Loaded" );
+    /// echo!( -n, io.out(), "Ready> " );
+    /// let timeout = Duration::from_millis( 10 );
+    /// assert_eq!( io.recv_line_partial_timeout( timeout ), Some( Line::Complete( "Loaded\n".to_owned() )));
+    /// assert_eq!( io.recv_line_partial_timeout( timeout ), Some( Line::Partial( "Ready> ".to_owned() )));
+    /// assert_eq!( io.recv_line_partial_timeout( timeout ), None );
+    /// ```
+    pub fn recv_line_partial_timeout( &self, timeout: Duration ) -> Option<Line> { self.0.1.recv_line_partial_timeout( timeout )}
+
+    /// Receives certain amount lines of text from altio output stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_lines( &self, cnt: usize ) -> String { self.0.1.recv_lines(cnt) }
+
+    /// Tries to receive certain amount lines of text from altio output stream.
+    pub fn try_recv_lines( &self, cnt: usize ) -> Option<String> { self.0.1.try_recv_lines(cnt) }
+
+    /// Reads one line of text in altio output stream, leaving it in the
+    /// stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_line( &self ) -> Option<String> { self.0.1.peek_line() }
+
+    /// Reads certain amount lines of text in altio output stream, leaving it in the
+    /// stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_lines( &self, cnt: usize ) -> Option<String> { self.0.1.peek_lines(cnt) }
+
+    /// Receives text from altio error stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_err( &self ) -> String { self.0.2.recv() }
+
+    /// Receives text from altio error stream, blocking at most `timeout` measured by
+    /// the clock of this `Altio`, see `AltioBuilder::clock()`.
+    pub fn recv_err_timeout( &self, timeout: Duration ) -> Option<String> { self.0.2.recv_timeout( timeout )}
+
+    /// Receives text from altio error stream until none is written for `quiet`, or at
+    /// most `max`, both measured by the clock of this `Altio`.
+    pub fn recv_err_settled( &self, quiet: Duration, max: Duration ) -> String { self.0.2.recv_settled( quiet, max )}
+
+    /// Tries to receive text from altio error stream, without blocking.
+    pub fn try_recv_err( &self ) -> Option<String> { self.0.2.try_recv() }
+
+    /// Receives at most `max` bytes of text from altio error stream, never splitting a
+    /// UTF-8 encoded char.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_err_max( &self, max: usize ) -> String { self.0.2.recv_max( max )}
+
+    /// Receives exactly `n` chars of text from altio error stream, leaving the rest.
+    ///
+    /// This function will always block the current thread until `n` chars are
+    /// available.
+    pub fn recv_err_exact( &self, n: usize ) -> String { self.0.2.recv_exact( n )}
+
+    /// Tries to receive at most `max` bytes of text from altio error stream, without
+    /// blocking.
+    pub fn try_recv_err_max( &self, max: usize ) -> Option<String> { self.0.2.try_recv_max( max )}
+
+    /// Receives one line of text from altio error stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available. Once the tool has closed the stream, the rest of it is received as
+    /// the last line, which may have no trailing newline.
+    pub fn recv_err_line( &self ) -> String { self.0.2.recv_line() }
+
+    /// Receives one line of text from altio error stream, blocking at most `timeout`
+    /// measured by the clock of this `Altio`, see `AltioBuilder::clock()`.
+    pub fn recv_err_line_timeout( &self, timeout: Duration ) -> Option<String> { self.0.2.recv_line_timeout( timeout )}
+
+    /// Tries to receive one line of text from altio error stream, without blocking.
+    pub fn try_recv_err_line( &self ) -> Option<String> { self.0.2.try_recv_line() }
+
+    /// Receives one line of text from altio error stream, blocking at most `timeout`
+    /// measured by the clock of this `Altio`, then receiving the text pending without
+    /// newline, if any, as a partial line.
+    pub fn recv_err_line_partial_timeout( &self, timeout: Duration ) -> Option<Line> { self.0.2.recv_line_partial_timeout( timeout )}
+
+    /// Receives certain amount lines of text from altio error stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn recv_err_lines( &self, cnt: usize ) -> String { self.0.2.recv_lines(cnt) }
+
+    /// Tries to receive certain amount lines of text from altio error stream, without
+    /// blocking.
+    pub fn try_recv_err_lines( &self, cnt: usize ) -> Option<String> { self.0.2.try_recv_lines(cnt) }
+
+    /// Reads one line of text in altio error stream, leaving it in the stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_err_line( &self ) -> Option<String> { self.0.2.peek_line() }
+
+    /// Reads certain amount line of text in altio error stream, leaving it in the
+    /// stream.
+    ///
+    /// This function will always block the current thread if there is no data
+    /// available.
+    pub fn peek_err_lines( &self, cnt: usize ) -> Option<String> { self.0.2.peek_lines(cnt) }
+
+    /// Sends a line of text into altio input stream, for async host applications.
+    /// Sending never blocks, see `send_line()`.
+    pub async fn send_line_async( &self, text: &str ) { self.send_line( text )}
+
+    /// Receives text from altio output stream, pending until there is some.
+    pub async fn recv_async( &self ) -> String {
+        poll_fn( |cx| self.0.1.poll_async( cx, |buf| take_all( buf ))).await
+    }
+
+    /// Receives one line of text from altio output stream, pending until there is one.
+    pub async fn recv_line_async( &self ) -> String {
+        poll_fn( |cx| self.0.1.poll_async( cx, |buf| self.0.1.get_lines( buf, 1 ))).await
+    }
+
+    /// Receives certain amount lines of text from altio output stream, pending until
+    /// there are enough.
+    pub async fn recv_lines_async( &self, cnt: usize ) -> String {
+        if cnt == 0 {
+            return String::new();
+        }
+        poll_fn( |cx| self.0.1.poll_async( cx, |buf| self.0.1.get_lines( buf, cnt ))).await
+    }
+
+    /// Receives one line of text from altio output stream, pending at most `timeout`
+    /// measured by the `tokio` clock, which `tokio::time::pause()` controls. With the
+    /// "tokio" feature.
+    #[cfg( feature = "tokio" )]
+    pub async fn recv_line_timeout_async( &self, timeout: Duration ) -> Option<String> {
+        tokio::time::timeout( timeout, self.recv_line_async() ).await.ok()
+    }
+
+    /// Receives text from altio error stream, pending until there is some.
+    pub async fn recv_err_async( &self ) -> String {
+        poll_fn( |cx| self.0.2.poll_async( cx, |buf| take_all( buf ))).await
+    }
+
+    /// Receives one line of text from altio error stream, pending until there is one.
+    pub async fn recv_err_line_async( &self ) -> String {
+        poll_fn( |cx| self.0.2.poll_async( cx, |buf| self.0.2.get_lines( buf, 1 ))).await
+    }
+
+    /// Receives certain amount lines of text from altio error stream, pending until
+    /// there are enough.
+    pub async fn recv_err_lines_async( &self, cnt: usize ) -> String {
+        if cnt == 0 {
+            return String::new();
+        }
+        poll_fn( |cx| self.0.2.poll_async( cx, |buf| self.0.2.get_lines( buf, cnt ))).await
+    }
+
+    /// Receives one line of text from altio error stream, pending at most `timeout`
+    /// measured by the `tokio` clock. With the "tokio" feature.
+    #[cfg( feature = "tokio" )]
+    pub async fn recv_err_line_timeout_async( &self, timeout: Duration ) -> Option<String> {
+        tokio::time::timeout( timeout, self.recv_err_line_async() ).await.ok()
+    }
+
+    /// Sends each line of `timeline` into altio input stream at its offset from now,
+    /// like `schedule_input()`, by `tokio` timers instead of a thread, so that tests
+    /// pausing the time run instantly. Completes after sending the last line. With
+    /// the "tokio" feature.
+    ///
+    /// ```rust
+    /// use altio::Altio;
+    /// use std::time::Duration;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().enable_time().start_paused( true ).build().unwrap().block_on( async {
+    /// let io = Altio::default();
+    /// let tool = io.clone();
+    /// let timeline = [( Duration::from_secs( 3600 ), "at last" )];
+    /// tokio::join!(
+    ///     io.schedule_input_async( &timeline ),
+    ///     async {
+    ///         altio::echo!( tool.out(), "waiting" );
+    ///         assert_eq!( io.recv_line_timeout_async( Duration::from_secs( 60 )).await.as_deref(), Some( "waiting\n" ));
+    ///         assert_eq!( io.recv_line_timeout_async( Duration::from_secs( 60 )).await, None );
+    ///     },
+    /// );
+    /// let mut line = String::new();
+    /// tool.input().read_line( &mut line ).unwrap();
+    /// assert_eq!( line, "at last\n" );
+    /// # });
+    /// ```
+    #[cfg( feature = "tokio" )]
+    pub async fn schedule_input_async( &self, timeline: &[( Duration, &str )] ) {
+        let start = tokio::time::Instant::now();
+        let mut timeline = timeline.to_vec();
+        timeline.sort_by_key( |&( offset, _ )| offset );
+        for ( offset, line ) in timeline {
+            tokio::time::sleep_until( start + offset ).await;
+            self.send_line( line );
+        }
+    }
+}
+
+/// Provides delegated `out()`/`err()` methods for the type which contains a field
+/// named `altio`.
+#[macro_export]
+macro_rules! impl_altio_output {
+    ($ty:ty) => {
+        #[cfg( feature = "altio" )]
+        impl $ty {
+            pub fn out( &self ) -> altio::AltoutLock<'_> { self.altio.out() }
+            pub fn err( &self ) -> altio::AltoutLock<'_> { self.altio.err() }
+        }
+
+        #[cfg( not( feature = "altio" ))]
+        impl $ty {
+            pub fn out( &self ) -> std::io::Stdout { std::io::stdout() }
+            pub fn err( &self ) -> std::io::Stderr { std::io::stderr() }
+        }
+    };
+}
+
+#[cfg( all( test, feature="altio" ))]
+pub mod tests {
+    use super::{AltCommand, Altio, Buffer, Buffering, Console, ControlChars, Delay, InvalidUtf8, IoEvent, Line, ManualClock, MatchKind, Pattern, ProgressEvent, Prompt, Scan, Scheduler, Stream, When};
+
+    use std::{
+        ffi::OsString,
+        io::{ErrorKind, Result},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    const ALPHABET: &str = "abcdefg\nhijklmn\nopq rst\nuvw xyz";
+
+    #[test]
+    fn altin_lock_read_line() -> Result<()> {
+        let io = Altio::default();
+
+        io.send_line( ALPHABET );
+
+        let mut lock = io.input().lock();
+        let mut buf = String::new();
+
+        assert_eq!( lock.read_line( &mut buf )?, 8 );
+        assert_eq!( buf, "abcdefg\n" );
+
+        assert_eq!( lock.read_line( &mut buf )?, 8 );
+        assert_eq!( buf, "abcdefg\nhijklmn\n" );
+
+        lock.read_line( &mut buf )?;
+        assert_eq!( buf, "abcdefg\nhijklmn\nopq rst\n" );
+
+        lock.read_line( &mut buf )?;
+        assert_eq!( buf, "abcdefg\nhijklmn\nopq rst\nuvw xyz\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn try_read_line() -> Result<()> {
+        let io = Altio::default();
+        let mut buf = String::new();
+        assert_eq!( io.input().try_read_line( &mut buf ).unwrap_err().kind(), ErrorKind::WouldBlock );
+
+        io.send( "a\nb" );
+        let mut lock = io.input().lock();
+        assert_eq!( lock.try_read_line( &mut buf )?, 2 );
+        assert_eq!( lock.try_read_line( &mut buf ).unwrap_err().kind(), ErrorKind::WouldBlock );
+
+        let tool = std::thread::spawn({
+            let io = io.clone();
+            move || {
+                let mut lock = io.input().lock();
+                let mut buf = String::new();
+                assert_eq!( lock.read_line( &mut buf ).unwrap(), 2 );
+                assert_eq!( lock.read_line( &mut buf ).unwrap(), 0 );
+                buf
+            }
+        });
+        drop( lock );
+        io.send( "\n" );
+        io.close_input();
+        assert_eq!( tool.join().unwrap(), "b\n" );
+        assert_eq!( buf, "a\n" );
+        Ok(())
+    }
+
+    #[test]
+    fn altin_lock_read_to_string() -> Result<()> {
+        let io = Altio::default();
+
+        io.send( ALPHABET );
+
+        let mut lock = io.input().lock();
+        let mut buf = String::new();
+
+        lock.read_to_string( &mut buf )?;
+        assert_eq!( buf, ALPHABET );
+
+        Ok(())
+    }
+
+    #[test]
+    fn lines() {
+        let io = Altio::default();
+
+        assert!( io.input().try_lines().collect::<String>().is_empty() );
+
+        io.send( ALPHABET );
+        assert_eq!( io.input().try_lines().collect::<Vec<String>>(),
+            vec![ "abcdefg\n".to_owned(), "hijklmn\n".to_owned(), "opq rst\n".to_owned() ]);
+    }
+
+    #[test]
+    fn trailing_partial_line() {
+        let io = Altio::default();
+        io.send( "one\ntwo" );
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(), [ "one\n" ]);
+        io.close_input();
+        assert_eq!( io.input().lines().collect::<Vec<_>>(), [ "two" ]);
+
+        echo!( -n, io.out(), "three\nfour" );
+        assert_eq!( io.try_recv_lines(2), None );
+        io.0.1.close();
+        assert_eq!( io.recv_lines(2), "three\nfour" );
+
+        echo!( -n, io.err(), "five" );
+        io.0.2.close();
+        assert_eq!( io.recv_err_line(), "five" );
+    }
+
+    #[test]
+    fn lines_while_sending() {
+        let io = Altio::default();
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || for line in io.input().lines() {
+                echo!( -n, io.out(), "{}", line.to_uppercase() );
+            })
+        };
+        io.send_line( "one" );
+        assert_eq!( io.recv_line(), "ONE\n" );
+        io.send_line( "two" );
+        assert_eq!( io.recv_line(), "TWO\n" );
+        io.close_input();
+        tool.join().unwrap();
+    }
+
+    #[test]
+    fn altin_read_line() -> Result<()> {
+        let io = Altio::default();
+
+        io.send( ALPHABET );
+
+        let mut buf = String::new();
+        assert_eq!( io.input().read_line( &mut buf )?, 8 );
+        assert_eq!( buf, "abcdefg\n" );
+        assert_eq!( io.input().read_line( &mut buf )?, 8 );
+        assert_eq!( buf, "abcdefg\nhijklmn\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn altin_read_to_string() -> Result<()> {
+        let io = Altio::default();
+
+        io.send( ALPHABET );
+
+        let mut buf = String::new();
+        io.input().read_to_string( &mut buf )?;
+        assert_eq!( buf, ALPHABET );
+
+        Ok(())
+    }
+
+    #[test]
+    fn altin_read_to_end() -> Result<()> {
+        let io = Altio::default();
+        io.send_raw( &[ 0xff, b'\n' ]);
+
+        let tool = std::thread::spawn({
+            let io = io.clone();
+            move || {
+                let mut bytes = Vec::new();
+                io.input().lock().read_to_end( &mut bytes ).map( |len| ( len, bytes ))
+            }
+        });
+        io.send( "binary" );
+        io.close_input();
+        assert_eq!( tool.join().unwrap()?, ( 8, b"\xff\nbinary".to_vec() ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn altout_lock_write_fmt() -> Result<()> {
+        let io = Altio::default();
+
+        {
+            let mut lock = io.out();
+            let contents = ALPHABET;
+            for line in contents.lines() {
+                writeln!( lock, "{}", line )?;
+            }
+        }
+
+        assert_eq!( io.recv().trim(), ALPHABET );
+
+        Ok(())
+    }
+
+    #[test]
+    fn altout_lock_deref() -> Result<()> {
+        let io = Altio::default();
+
+        {
+            let mut lock = io.out();
+            writeln!( lock, "abc" )?;
+            assert!( lock.contains( "bc" ));
+            assert_eq!( &*lock as &str, "abc\n" );
+            lock.clear();
+            lock.push_str( "def\n" );
+            assert_eq!( &*lock as &str, "def\n" );
+            lock.insert( 0, '_' );
+        }
+
+        assert_eq!( io.recv(), "_def\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn poisoned_streams() -> Result<()> {
+        let io = Altio::default();
+
+        let tool = io.clone();
+        let _ = std::thread::spawn( move || {
+            let mut lock = tool.out();
+            writeln!( lock, "before" ).unwrap();
+            panic!( "the tool panics with the lock held" );
+        }).join();
+        assert!( io.0.1.output.is_poisoned() );
+
+        writeln!( io.out(), "after" )?;
+        assert_eq!( io.recv(), "before\nafter\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn altout_write_fmt() -> Result<()> {
+        let io = Altio::default();
+
+        let contents = ALPHABET;
+        for line in contents.lines() {
+            writeln!( io.out(), "{}", line )?;
+        }
+
+        assert_eq!( io.recv().trim(), ALPHABET );
+
+        Ok(())
+    }
+    #[test]
+    fn alterr_lock_write_fmt() -> Result<()> {
+        let io = Altio::default();
+
+        {
+            let mut lock = io.err();
+            let contents = ALPHABET;
+            for line in contents.lines() {
+                writeln!( lock, "{}", line )?;
+            }
+        }
+
+        assert_eq!( io.recv_err().trim(), ALPHABET );
+
+        Ok(())
+    }
+
+    #[test]
+    fn alterr_write_fmt() -> Result<()> {
+        let io = Altio::default();
+
+        let contents = ALPHABET;
+        for line in contents.lines() {
+            writeln!( io.err(), "{}", line )?;
+        }
+
+        assert_eq!( io.recv_err().trim(), ALPHABET );
+
+        Ok(())
+    }
+
+    #[test]
+    fn nothing_received() {
+        let io = Altio::default();
+
+        assert!( io.try_recv().is_none() );
+        assert!( io.try_recv_line().is_none() );
+        assert!( io.try_recv_err().is_none() );
+        assert!( io.try_recv_err_line().is_none() );
+    }
+
+    #[test]
+    fn io_print() {
+        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_err().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+
+        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv().is_some() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_err().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_err_line().is_none() ); }
+
+        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv().is_some() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_line().is_some() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_err().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_err_line().is_none() ); }
+    }
+
+    #[test]
+    fn io_println() {
+        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv().is_some() ); }
+        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_line().is_some() ); }
+        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_err().is_none() ); }
+        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+    }
+
+    #[test]
+    fn io_eprint() {
+        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_err().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+
+        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_err().is_some() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_err_line().is_none() ); }
+
+        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_err().is_some() ); }
+        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_err_line().is_some() ); }
+    }
+
+    #[test]
+    fn io_eprintln() {
+        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv().is_none() ); }
+        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_line().is_none() ); }
+        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_err().is_some() ); }
+        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_err_line().is_some() ); }
+    }
+
+    #[test]
+    fn receive_out() {
+        let io = Altio::default();
+
+        echo!( -n, io.out(), "" );
+        assert!( io.try_recv().is_none() );
+
+        echo!( -n, io.out(), " " );
+        assert!( io.try_recv_err().is_none() );
+        assert_eq!( io.try_recv(), Some( " ".to_owned() ));
+
+        echo!( -n, io.out(), "abcdefg\nhijklmn\nopq rst\nuvw xyz" );
+        assert_eq!( io.try_recv_line(), Some( "abcdefg\n".to_owned() ));
+        assert_eq!( io.recv_line(), "hijklmn\n" );
+        assert_eq!( io.recv(), "opq rst\nuvw xyz" );
+    }
+
+    #[test]
+    fn receive_err() {
+        let io = Altio::default();
+
+        echo!( -n, io.err(), "" );
+        assert!( io.try_recv_err().is_none() );
+
+        echo!( -n, io.err(), " " );
+        assert!( io.try_recv().is_none() );
+        assert_eq!( io.try_recv_err(), Some( " ".to_owned() ));
+
+        echo!( -n, io.err(), "abcdefg\nhijklmn\nopq rst\nuvw xyz" );
+        assert_eq!( io.try_recv_err_line(), Some( "abcdefg\n".to_owned() ));
+        assert_eq!( io.recv_err_line(), "hijklmn\n" );
+        assert_eq!( io.recv_err(), "opq rst\nuvw xyz" );
+    }
+
+    #[test]
+    fn receive_lines() {
+        let io = Altio::default();
+
+        echo!( -n, io.out(), "abcd\nefg\nhijk\nlmn\nopq\nrst\nuvw\nxyz" );
+        assert_eq!( io.try_recv_lines(1), Some( "abcd\n".to_owned() ) );
+        assert_eq!( io.try_recv_lines(2), Some( "efg\nhijk\n".to_owned() ));
+        assert_eq!( io.try_recv_lines(3), Some( "lmn\nopq\nrst\n".to_owned() ));
+        assert_eq!( io.try_recv_lines(2), None );
+    }
+
+    #[test]
+    fn receive_err_lines() {
+        let io = Altio::default();
+
+        echo!( -n, io.err(), "abcd\nefg\nhijk\nlmn\nopq\nrst\nuvw\nxyz" );
+        assert_eq!( io.try_recv_err_lines(1), Some( "abcd\n".to_owned() ) );
+        assert_eq!( io.try_recv_err_lines(2), Some( "efg\nhijk\n".to_owned() ));
+        assert_eq!( io.try_recv_err_lines(3), Some( "lmn\nopq\nrst\n".to_owned() ));
+        assert_eq!( io.try_recv_err_lines(2), None );
+    }
+
+    #[test]
+    fn receive_exact() {
+        let io = Altio::default();
+        let reader = {
+            let io = io.clone();
+            std::thread::spawn( move || io.recv_exact(3) )
+        };
+        echo!( -n, io.out(), "a\u{4e2d}" );
+        std::thread::sleep( Duration::from_millis( 10 ));
+        assert!( !reader.is_finished() );
+        echo!( -n, io.out(), "bcd" );
+        assert_eq!( reader.join().unwrap(), "a\u{4e2d}b" );
+        assert_eq!( io.recv_exact(0), "" );
+        assert_eq!( io.recv(), "cd" );
+
+        echo!( -n, io.err(), "0042OK" );
+        assert_eq!( io.recv_err_exact(4), "0042" );
+        assert_eq!( io.recv_err_exact(2), "OK" );
+    }
+
+    #[test]
+    fn receive_max() {
+        let io = Altio::default();
+
+        assert!( io.try_recv_max(4).is_none() );
+
+        echo!( -n, io.out(), "ab\u{4e2d}cd" );
+        assert_eq!( io.try_recv_max(0), None );
+        assert_eq!( io.recv_max(3), "ab" );
+        assert_eq!( io.recv_max(1), "\u{4e2d}" );
+        assert_eq!( io.recv_max(8), "cd" );
+
+        echo!( -n, io.err(), "abcd" );
+        assert_eq!( io.try_recv_err_max(3), Some( "abc".to_owned() ));
+        assert_eq!( io.recv_err_max(3), "d" );
+    }
+
+    #[test]
+    fn spill_to_disk() -> Result<()> {
+        let io = Altio::default();
+        io.spill_to_disk( 16 )?;
+
+        for i in 0..100 {
+            echo!( io.out(), "line {i}" );
+        }
+        assert_eq!( io.out().len(), 790 );
+        assert_eq!( io.recv_line(), "line 0\n" );
+        assert_eq!( io.peek_lines(2), Some( "line 1\nline 2\n".to_owned() ));
+        assert_eq!( io.recv_lines(98).lines().last(), Some( "line 98" ));
+        assert_eq!( io.recv(), "line 99\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unreadable_spill_edit() -> Result<()> {
+        let io = Altio::default();
+        io.spill_to_disk( 4 )?;
+        echo!( io.out(), "abc" );
+        echo!( io.out(), "def" );
+        io.0.1.output.lock().unwrap().buf.break_spill();
+
+        {
+            let mut lock = io.out();
+            assert_eq!( *lock, "abc\n" );
+            lock.clear();
+        }
+        assert!( write!( io.out(), "ghi" ).is_err() );
+        assert_eq!( io.0.1.output.lock().unwrap().buf.len(), 8 );
+        Ok(())
+    }
+
+    #[test]
+    fn history() {
+        let io = Altio::default();
+
+        echo!( io.out(), "dropped" );
+        io.keep_history();
+        echo!( io.out(), "abcd" );
+        echo!( io.err(), "efg" );
+        assert_eq!( io.recv(), "dropped\nabcd\n" );
+        echo!( io.out(), "hijk" );
+
+        assert_eq!( io.history(), "abcd\nhijk\n" );
+        assert_eq!( io.history_err(), "efg\n" );
+    }
+
+    #[test]
+    fn metrics() -> Result<()> {
+        let io = Altio::default();
+
+        io.send_line( "abc" );
+        io.send( "d\ne" );
+        let mut buf = String::new();
+        io.input().read_line( &mut buf )?;
+
+        echo!( io.out(), "fg" );
+        echo!( -n, io.out(), "hijk" );
+        assert!( io.try_recv_line().is_some() );
+        assert!( io.try_recv_line().is_none() );
+        assert_eq!( io.recv(), "hijk" );
+
+        let metrics = io.metrics();
+        assert_eq!( metrics.input.bytes, 7 );
+        assert_eq!( metrics.input.lines, 2 );
+        assert_eq!( metrics.input.receives, 1 );
+        assert_eq!( metrics.input.max_pending, 7 );
+        assert_eq!( metrics.out.bytes, 7 );
+        assert_eq!( metrics.out.lines, 1 );
+        assert_eq!( metrics.out.receives, 3 );
+        assert_eq!( metrics.out.max_pending, 7 );
+        assert_eq!( metrics.err, Default::default() );
+
+        Ok(())
+    }
+
+    #[test]
+    fn inject_error() -> Result<()> {
+        let io = Altio::default();
+        let mut buf = String::new();
+
+        io.send_line( "abc" );
+        io.inject_error( Stream::In, ErrorKind::Interrupted, When::Next );
+        assert_eq!( io.input().read_line( &mut buf ).unwrap_err().kind(), ErrorKind::Interrupted );
+        io.input().read_line( &mut buf )?;
+        assert_eq!( buf, "abc\n" );
+
+        io.inject_error( Stream::Err, ErrorKind::BrokenPipe, When::Every(2) );
+        for i in 1..=4 {
+            let result = writeln!( io.err(), "{i}" );
+            assert_eq!( result.is_err(), i % 2 == 0 );
+        }
+        assert!( writeln!( io.out(), "ok" ).is_ok() );
+        io.clear_error( Stream::Err );
+        writeln!( io.err(), "5" )?;
+        assert_eq!( io.recv_err(), "1\n3\n5\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn inject_latency() -> Result<()> {
+        let io = Altio::default();
+        let delay = Duration::from_millis( 20 );
+
+        io.inject_latency( Stream::Out, Delay::Fixed( delay ));
+        let since = Instant::now();
+        echo!( io.out(), "abc" );
+        assert!( since.elapsed() >= delay );
+
+        io.send_line( "def" );
+        io.inject_latency( Stream::In, Delay::Jitter{ min: delay, max: 2*delay });
+        let since = Instant::now();
+        let mut buf = String::new();
+        io.input().read_line( &mut buf )?;
+        assert!( since.elapsed() >= delay );
+
+        io.clear_latency( Stream::Out );
+        let since = Instant::now();
+        echo!( io.out(), "ghi" );
+        assert!( since.elapsed() < delay );
+        assert_eq!( io.recv(), "abc\nghi\n" );
+
+        Ok(())
+    }
+
+    #[test]
+    fn latency_unlocked() {
+        let io = Altio::default();
+        let delay = Duration::from_millis( 300 );
+        io.inject_latency( Stream::Out, Delay::Fixed( delay ));
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || echo!( io.out(), "abc" ))
+        };
+        std::thread::sleep( Duration::from_millis( 50 ));
+        let since = Instant::now();
+        assert_eq!( io.try_recv(), None );
+        assert!( since.elapsed() < Duration::from_millis( 200 ));
+        tool.join().unwrap();
+        assert_eq!( io.recv(), "abc\n" );
+    }
+
+    #[test]
+    fn wake_on_write() -> Result<()> {
+        use std::sync::mpsc::{channel, Sender};
+
+        // Signals the first time the tool blocks, then lets it wait on the condvar.
+        struct Blocked( Mutex<Sender<()>>, Altio );
+
+        impl Scheduler for Blocked {
+            fn block( &self, _: Stream ) {
+                self.1.0.0.clear_scheduler();
+                self.0.lock().unwrap().send(()).unwrap();
+            }
+        }
+
+        let io = Altio::default();
+        let ( blocked, reading ) = channel();
+        io.0.0.schedule( Arc::new( Blocked( Mutex::new( blocked ), io.clone() )));
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || -> Result<()> {
+                let mut buf = String::new();
+                io.input().read_line( &mut buf )?;
+                echo!( io.out(), "got {}", buf.trim_end() );
+                Ok(())
+            })
+        };
+
+        reading.recv().unwrap();
+        std::thread::sleep( Duration::from_millis( 20 ));
+        io.send_line( "ping" );
+        assert_eq!( io.recv_line(), "got ping\n" );
+        tool.join().unwrap()?;
+        assert!( io.metrics().input.blocked >= Duration::from_millis( 20 ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn schedule() -> Result<()> {
+        use std::sync::mpsc::{channel, Receiver, Sender};
+
+        struct Stepper {
+            blocked : Mutex<Sender<Stream>>,
+            resume  : Mutex<Receiver<()>>,
+        }
+
+        impl Scheduler for Stepper {
+            fn block( &self, stream: Stream ) {
+                self.blocked.lock().unwrap().send( stream ).unwrap();
+                self.resume.lock().unwrap().recv().unwrap();
+            }
+        }
+
+        let ( blocked, tool_blocked ) = channel();
+        let ( resume_tool, resume ) = channel();
+        let io = Altio::default();
+        io.schedule( Stepper{ blocked: Mutex::new( blocked ), resume: Mutex::new( resume )});
+
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || -> Result<()> {
+                echo!( io.out(), "ready" );
+                for _ in 0..2 {
+                    let mut buf = String::new();
+                    io.input().read_line( &mut buf )?;
+                    echo!( -n, io.out(), "got " );
+                    echo!( io.out(), "{}", buf.trim_end() );
+                }
+                Ok(())
+            })
+        };
+
+        assert_eq!( tool_blocked.recv().unwrap(), Stream::In );
+        assert_eq!( io.try_recv().as_deref(), Some( "ready\n" ));
+        io.send_line( "a" );
+        resume_tool.send(()).unwrap();
+
+        assert_eq!( tool_blocked.recv().unwrap(), Stream::In );
+        assert_eq!( io.try_recv().as_deref(), Some( "got a\n" ));
+        io.send_line( "b" );
+        resume_tool.send(()).unwrap();
+
+        tool.join().unwrap()?;
+        assert_eq!( io.try_recv().as_deref(), Some( "got b\n" ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn recv_settled() {
+        let io = Altio::default();
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || for item in [ "1. new\n", "2. open\n", "> " ] {
+                echo!( -n, io.out(), "{item}" );
+                std::thread::sleep( Duration::from_millis( 10 ));
+            })
+        };
+        let screen = io.recv_settled( Duration::from_millis( 200 ), Duration::from_secs( 10 ));
+        assert_eq!( screen, "1. new\n2. open\n> " );
+        tool.join().unwrap();
+
+        let clock = Arc::new( ManualClock::new() );
+        let io = Altio::builder().clock( clock.clone() ).build();
+        echo!( -n, io.err(), "error" );
+        assert_eq!( io.recv_err_settled( Duration::from_secs( 1 ), Duration::ZERO ), "" );
+        clock.advance( Duration::from_secs( 1 ));
+        let driver = {
+            let io = io.clone();
+            std::thread::spawn( move || io.recv_err_settled( Duration::from_secs( 1 ), Duration::from_secs( 5 )))
+        };
+        while !driver.is_finished() {
+            clock.advance( Duration::from_millis( 100 ));
+            std::thread::sleep( Duration::from_millis( 1 ));
+        }
+        assert_eq!( driver.join().unwrap(), "error" );
+    }
 
-    use std::io::Result;
+    #[test]
+    fn prompt_events() {
+        let io = Altio::default();
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                echo!( io.out(), "\u{1f}not a prompt" );
+                io.emit_prompt( &Prompt::Input{ label: "Name".to_owned(), default: None }).unwrap();
+                let mut name = String::new();
+                io.input().read_line( &mut name ).unwrap();
+                echo!( io.out(), "Hello, {}!", name.trim_end() );
+            })
+        };
+        let event = io.recv_prompt();
+        assert_eq!( event.text, "\u{1f}not a prompt\n" );
+        assert_eq!( event.prompt, Prompt::Input{ label: "Name".to_owned(), default: None });
+        io.send_line( "Alice" );
+        tool.join().unwrap();
+        assert_eq!( io.recv_prompt_timeout( Duration::from_millis( 10 )), None );
+        assert_eq!( io.try_recv_prompt(), None );
+        assert_eq!( io.recv(), "Hello, Alice!\n" );
+    }
 
-    const ALPHABET: &'static str = "abcdefg\nhijklmn\nopq rst\nuvw xyz";
+    #[test]
+    fn progress_channel() {
+        let io = Altio::default();
+        assert_eq!( io.try_recv_progress(), None );
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || for done in 1..=2 {
+                io.progress( done, 2, "copying" );
+            })
+        };
+        assert_eq!( io.recv_progress(), ProgressEvent{ done: 1, total: 2, message: "copying".to_owned() });
+        assert_eq!( io.recv_progress().done, 2 );
+        tool.join().unwrap();
+        assert_eq!( io.recv_progress_timeout( Duration::from_millis( 10 )), None );
+        assert_eq!( io.try_recv(), None );
+    }
 
     #[test]
-    fn altin_lock_read_line() -> Result<()> {
+    fn status_channel() {
         let io = Altio::default();
+        assert_eq!( io.status_value( "phase" ), None );
+        let mut watcher = io.watch_status();
+        assert_eq!( watcher.try_recv(), None );
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                io.set_status( "phase", "indexing" );
+                io.set_status( "phase", "indexing" );
+            })
+        };
+        assert_eq!( watcher.recv(), [( "phase".to_owned(), "indexing".to_owned() )]);
+        tool.join().unwrap();
+        assert_eq!( watcher.recv_timeout( Duration::from_millis( 10 )), None );
+        io.set_status( "phase", "done" );
+        assert_eq!( io.status_value( "phase" ).as_deref(), Some( "done" ));
+        assert_eq!( watcher.try_recv(), Some( vec![( "phase".to_owned(), "done".to_owned() )]));
+    }
 
-        io.send_line( ALPHABET );
+    #[test]
+    fn events_in_order() {
+        let io = Altio::default();
+        echo!( io.out(), "unrecorded" );
+        io.record_events();
+        io.send_line( "yes" );
+        io.emit_prompt( &Prompt::Confirm{ question: "Sure?".to_owned(), default: None }).unwrap();
+        let mut answer = String::new();
+        io.input().read_line( &mut answer ).unwrap();
+        echo!( -n, io.err(), "o" );
+        echo!( io.err(), "k" );
+        assert_eq!( io.try_events().collect::<Vec<_>>(), [
+            IoEvent::Prompt( Prompt::Confirm{ question: "Sure?".to_owned(), default: None }),
+            IoEvent::InputConsumed{ bytes: 4 },
+            IoEvent::Err( "ok\n".to_owned() ),
+        ]);
+        assert_eq!( io.try_events().next(), None );
+        assert_eq!( io.recv_prompt().text, "unrecorded\n" );
+    }
 
-        let mut lock = io.input().lock();
-        let mut buf = String::new();
+    #[test]
+    fn input_consumed() {
+        let io = Altio::default();
+        assert!( io.wait_input_consumed( Duration::ZERO ));
+        io.send_line( "first" );
+        io.send_line( "second" );
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                let mut line = String::new();
+                io.input().read_line( &mut line ).unwrap();
+                std::thread::sleep( Duration::from_millis( 10 ));
+                io.input().read_line( &mut line ).unwrap();
+            })
+        };
+        assert!( io.wait_input_consumed( Duration::from_secs( 10 )));
+        tool.join().unwrap();
+    }
 
-        lock.read_line( &mut buf )?;
-        assert_eq!( buf, "abcdefg\n" );
+    #[test]
+    fn check_input_consumed() {
+        let io = Altio::builder().check_input_consumed( true ).build();
+        io.send_line( "read" );
+        let mut line = String::new();
+        io.input().read_line( &mut line ).unwrap();
+        drop( io );
+
+        let io = Altio::builder().check_input_consumed( true ).build();
+        io.send_line( "leftover" );
+        let driver = io.clone();
+        drop( io );
+        let panic = std::thread::spawn( move || drop( driver )).join().unwrap_err();
+        assert_eq!( panic.downcast_ref::<String>().map( String::as_str ),
+            Some( r#"altio: input sent but never read: "leftover\n""# ));
+    }
 
-        lock.read_line( &mut buf )?;
-        assert_eq!( buf, "abcdefg\nhijklmn\n" );
+    #[cfg( feature = "tokio" )]
+    #[test]
+    fn feed_from_channel() {
+        let io = Altio::builder().check_input_consumed( true ).build();
+        let ( tx, rx ) = tokio::sync::mpsc::channel( 1 );
+        io.feed_from_channel( rx );
+        tx.blocking_send( "leftover".to_owned() ).unwrap();
+        while io.pending( Stream::In ).is_empty() {
+            std::thread::yield_now();
+        }
+        let panic = std::thread::spawn( move || drop( io )).join().unwrap_err();
+        assert_eq!( panic.downcast_ref::<String>().map( String::as_str ),
+            Some( r#"altio: input sent but never read: "leftover\n""# ));
+        assert!( tx.blocking_send( "ignored".to_owned() ).is_ok() );
+    }
 
-        lock.read_line( &mut buf )?;
-        assert_eq!( buf, "abcdefg\nhijklmn\nopq rst\n" );
+    #[test]
+    fn strict_err() {
+        let io = Altio::builder().strict_err( true ).build();
+        echo!( io.out(), "fine" );
+        assert!( io.violations().is_empty() );
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                echo!( io.err(), "warning" );
+                echo!( io.err(), "again" );
+            })
+        };
+        tool.join().unwrap();
+        assert_eq!( io.violations(), [ r#"err stream written in strict mode: "warning\n""# ]);
+        let panic = std::thread::spawn( move || drop( io )).join().unwrap_err();
+        assert_eq!( panic.downcast_ref::<String>().map( String::as_str ),
+            Some( r#"altio: err stream written in strict mode: "warning\n""# ));
+    }
 
-        lock.read_line( &mut buf )?;
-        assert_eq!( buf, "abcdefg\nhijklmn\nopq rst\nuvw xyz\n" );
+    #[test]
+    fn check_output_consumed() {
+        let io = Altio::builder().check_output_consumed( true ).build();
+        echo!( io.out(), "received" );
+        assert_eq!( io.recv(), "received\n" );
+        drop( io );
+
+        let io = Altio::builder().check_output_consumed( true ).build();
+        echo!( io.out(), "out" );
+        echo!( io.err(), "err" );
+        let panic = std::thread::spawn( move || drop( io )).join().unwrap_err();
+        assert_eq!( panic.downcast_ref::<String>().map( String::as_str ),
+            Some( r#"altio: output written but never received: "out\n"; error written but never received: "err\n""# ));
+
+        let io = Altio::builder().check_output_consumed( true ).build();
+        let tool = io.clone();
+        echo!( tool.out(), "out" );
+        let panic = std::thread::spawn( move || io.finish() ).join().unwrap_err();
+        assert_eq!( panic.downcast_ref::<String>().map( String::as_str ),
+            Some( r#"altio: output written but never received: "out\n""# ));
+        drop( tool );
+    }
 
-        Ok(())
+    #[test]
+    fn recv_timeout() {
+        let clock = Arc::new( ManualClock::new() );
+        let io = Altio::builder().clock( clock.clone() ).build();
+        let driver = {
+            let io = io.clone();
+            std::thread::spawn( move || io.recv_line_timeout( Duration::from_secs( 60 )))
+        };
+        std::thread::sleep( Duration::from_millis( 10 ));
+        assert!( !driver.is_finished() );
+        clock.advance( Duration::from_secs( 60 ));
+        assert_eq!( driver.join().unwrap(), None );
+
+        echo!( -n, io.err(), "partial" );
+        assert_eq!( io.recv_err_line_timeout( Duration::ZERO ), None );
+        assert_eq!( io.recv_err_timeout( Duration::ZERO ).as_deref(), Some( "partial" ));
+        echo!( io.out(), "line" );
+        assert_eq!( io.recv_line_timeout( Duration::ZERO ).as_deref(), Some( "line\n" ));
     }
 
     #[test]
-    fn altin_lock_read_to_string() -> Result<()> {
+    fn expect_any() {
         let io = Altio::default();
+        let tool = {
+            let io = io.clone();
+            std::thread::spawn( move || {
+                echo!( -n, io.out(), "Pass" );
+                std::thread::sleep( Duration::from_millis( 10 ));
+                echo!( io.out(), "word: " );
+            })
+        };
+        assert_eq!( io.expect_any( &[ "word", "Password: " ], Duration::from_secs( 10 )),
+            Some(( 1, "Password: ".to_owned() )));
+        tool.join().unwrap();
+        assert_eq!( io.expect_any( &[ "\n", "" ], Duration::ZERO ), Some(( 0, "\n".to_owned() )));
+        assert_eq!( io.expect_any( &[ "\n" ], Duration::ZERO ), None );
+
+        std::io::Write::write_all( &mut io.out(), b"\xff\xffab> rest" ).unwrap();
+        assert_eq!( io.expect_any( &[ "> " ], Duration::ZERO ), Some(( 0, "\u{fffd}\u{fffd}ab> ".to_owned() )));
+        assert_eq!( io.recv(), "rest" );
+    }
 
-        io.send( ALPHABET );
-
-        let mut lock = io.input().lock();
-        let mut buf = String::new();
-
-        lock.read_to_string( &mut buf )?;
-        assert_eq!( buf, ALPHABET );
-
-        Ok(())
+    #[test]
+    fn scan_resumes() {
+        let mut buf = Buffer::default();
+        let mut scan = Scan::new( usize::MAX, Some( 4 ));
+        buf.push( "0123456789".as_bytes() );
+        assert_eq!( scan.start( &buf ), Some( 0 ));
+        assert_eq!( scan.start( &buf ), None );
+        buf.push( "ab".as_bytes() );
+        assert_eq!( scan.start( &buf ), Some( 6 ));
+        buf.take( 8 );
+        buf.push( "c".as_bytes() );
+        assert_eq!( scan.start( &buf ), Some( 0 ));
+
+        let mut scan = Scan::new( 2, None );
+        assert_eq!( scan.start( &buf ), Some( 3 ));
+        buf.push( "\u{4e2d}".as_bytes() );
+        assert_eq!( scan.start( &buf ), Some( 6 ));
+        assert_eq!( super::copy_window( &mut buf, 6 ), ( 5, "\u{4e2d}".as_bytes().to_vec() ));
     }
 
     #[test]
-    fn lines() {
+    #[cfg( feature = "regex" )]
+    fn expect_regex() {
         let io = Altio::default();
-
-        assert!( io.input().lines().collect::<String>().is_empty() );
-
-        io.send( ALPHABET );
-        assert_eq!( io.input().lines().collect::<Vec<String>>(),
-            vec![ "abcdefg\n".to_owned(), "hijklmn\n".to_owned(), "opq rst\n".to_owned() ]);
+        std::io::Write::write_all( &mut io.out(), b"\xffport \xff80\xff\n" ).unwrap();
+        let found = io.expect_regex( &regex::Regex::new( "port (.*)\n" ).unwrap(), Duration::ZERO ).unwrap();
+        assert_eq!(( found.before(), found.get(1) ), ( "\u{fffd}", Some( "\u{fffd}80\u{fffd}" )));
+        assert_eq!( io.try_recv(), None );
     }
 
     #[test]
-    fn altin_read_line() -> Result<()> {
+    fn match_lookbehind() {
         let io = Altio::default();
+        let ready = Pattern::new( "Ready after ?*s\n", MatchKind::Glob ).unwrap();
+        echo!( -n, io.out(), "Ready af" );
+        echo!( -n, io.out(), "ter 1" );
+        assert_eq!( io.recv_until_match( &ready, Duration::ZERO ), None );
+        echo!( io.out(), "2s" );
+        assert_eq!( io.recv_until_match( &ready, Duration::ZERO ).as_deref(), Some( "Ready after 12s\n" ));
+
+        io.set_match_lookbehind( Some( 4 ));
+        echo!( io.out(), "Ready after 3s" );
+        assert_eq!( io.recv_until_match( &ready, Duration::ZERO ), None );
+        echo!( io.out(), "é3s" );
+        assert_eq!( io.expect_any( &[ "3s" ], Duration::ZERO ).map( |( _, text )| text ).as_deref(), Some( "Ready after 3s\né3s" ));
+        io.set_match_lookbehind( None );
+        assert_eq!( io.recv_until_match( &ready, Duration::ZERO ), None );
+        assert_eq!( io.recv(), "\n" );
+    }
 
-        io.send( ALPHABET );
+    #[test]
+    fn recv_line_partial_timeout() {
+        let clock = Arc::new( ManualClock::new() );
+        let io = Altio::builder().clock( clock.clone() ).build();
+        let driver = {
+            let io = io.clone();
+            std::thread::spawn( move || io.recv_err_line_partial_timeout( Duration::from_secs( 60 )))
+        };
+        echo!( -n, io.err(), "Done. " );
+        std::thread::sleep( Duration::from_millis( 10 ));
+        assert!( !driver.is_finished() );
+        clock.advance( Duration::from_secs( 60 ));
+        let line = driver.join().unwrap().unwrap();
+        assert!( line.is_partial() );
+        assert_eq!( line.into_string(), "Done. " );
+
+        echo!( io.err(), "a\nb" );
+        assert_eq!( io.recv_err_line_partial_timeout( Duration::ZERO ), Some( Line::Complete( "a\n".to_owned() )));
+        assert_eq!( io.recv_err_line_partial_timeout( Duration::ZERO ).as_ref().map( Line::as_str ), Some( "b\n" ));
+        assert_eq!( io.recv_err_line_partial_timeout( Duration::ZERO ), None );
+    }
 
-        let mut buf = String::new();
-        io.input().read_line( &mut buf )?;
-        assert_eq!( buf, "abcdefg\n" );
+    #[test]
+    fn alt_command() {
+        let child = AltCommand::new( |io: Altio, args: Vec<OsString>| {
+            echo!( io.out(), "{args:?}" );
+            echo!( io.out(), "{:?} {:?}", io.env().var( "KEY" ), io.env().var( "PATH" ));
+            let mut buf = String::new();
+            while io.input().read_line( &mut buf ).is_ok() && buf != "a\nb\nc\n" {}
+            echo!( -n, io.out(), "{buf}" );
+        })
+            .arg0( "the_tool" )
+            .args([ "--verbose", "-n" ])
+            .env_clear()
+            .env( "KEY", "V" )
+            .stdin_lines([ "a", "b" ])
+            .spawn();
+
+        assert_eq!( child.io().recv_line(), "[\"the_tool\", \"--verbose\", \"-n\"]\n" );
+        assert_eq!( child.io().recv_line(), "Ok(\"V\") Err(NotPresent)\n" );
+        child.io().send_line( "c" );
+        assert_eq!( child.io().recv_lines(3), "a\nb\nc\n" );
+    }
 
-        Ok(())
+    #[test]
+    fn log_session() {
+        let path = std::env::temp_dir().join( format!( "altio-session-{}.log", std::process::id() ));
+        let mut child = AltCommand::new( |io: Altio, _| {
+            let name = io.input().lines().next().unwrap_or_default();
+            echo!( io.err(), "hi {}", name.trim_end() );
+            3
+        }).spawn();
+        child.io().log_session_to( &path ).unwrap();
+        child.io().send_line( "Bob" );
+        assert_eq!( child.wait().unwrap(), 3 );
+
+        let logged = std::fs::read_to_string( &path ).unwrap();
+        std::fs::remove_file( &path ).unwrap();
+        let events = logged.lines().map( |line| line.trim_start().split_once( ' ' ).unwrap().1 ).collect::<Vec<_>>();
+        assert_eq!( events, [ r#"in   "Bob\n""#, r#"err  "hi Bob\n""#, "eof  out", "eof  err", "exit 3" ]);
     }
 
     #[test]
-    fn altin_read_to_string() -> Result<()> {
+    fn cooked_input() -> Result<()> {
         let io = Altio::default();
-
-        io.send( ALPHABET );
+        io.cooked_input( true );
+        io.send( "pasword\x08\x08\x08\x08sword" );
+        assert!( io.input().try_lines().next().is_none() );
+        io.send_line( "" );
+        io.send( "exiy\x7ft" );
+        io.cooked_input( false );
+        io.send( "\x08" );
 
         let mut buf = String::new();
         io.input().read_to_string( &mut buf )?;
-        assert_eq!( buf, ALPHABET );
+        assert_eq!( buf, "password\nexit\x08" );
+        Ok(())
+    }
 
+    #[test]
+    fn send_raw() -> Result<()> {
+        let io = Altio::default();
+        io.invalid_utf8( InvalidUtf8::Lossy );
+        io.send_raw( b"\xffa\x07b\n" );
+        io.send_raw( b"c\x07d\n" );
+        io.send_raw( b"e\x07f\n" );
+
+        let mut line = String::new();
+        io.input().read_line( &mut line )?;
+        assert_eq!( line, "\u{fffd}a\x07b\n" );
+
+        io.control_chars( ControlChars::Strip );
+        line.clear();
+        io.input().lock().read_line( &mut line )?;
+        assert_eq!( line, "cd\n" );
+
+        io.control_chars( ControlChars::Error );
+        assert_eq!( io.input().read_line( &mut line ).unwrap_err().kind(), ErrorKind::InvalidData );
+        assert_eq!( io.input().lock().read_to_string( &mut line )?, 0 );
         Ok(())
     }
 
     #[test]
-    fn altout_lock_write_fmt() -> Result<()> {
+    fn send_invalid_utf8() -> Result<()> {
         let io = Altio::default();
+        io.send_invalid_utf8( b"\xc3(\n" );
+        io.send_line( "valid" );
+        io.send_invalid_utf8( b"\xff" );
+
+        let mut text = String::from( "kept" );
+        assert_eq!( io.input().lock().read_line( &mut text ).unwrap_err().kind(), ErrorKind::InvalidData );
+        assert_eq!( io.input().lock().read_line( &mut text )?, 6 );
+        assert_eq!( io.input().lock().read_to_string( &mut text ).unwrap_err().kind(), ErrorKind::InvalidData );
+        assert_eq!( text, "keptvalid\n" );
+
+        io.send_line( "before" );
+        io.send_invalid_utf8( b"\xff\n" );
+        io.send_line( "after" );
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(), [ "before\n" ]);
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(), [ "after\n" ]);
+        Ok(())
+    }
 
-        {
-            let mut lock = io.out();
-            let contents = ALPHABET;
-            for line in contents.lines() {
-                writeln!( lock, "{}", line )?;
+    #[test]
+    fn input_generator() -> Result<()> {
+        let io = Altio::default();
+        io.set_input_generator( |request| {
+            request.output.lines().last().map( |prompt| format!( "{}:{prompt}", request.count ))
+        });
+        io.send_line( "sent" );
+
+        let tool = std::thread::spawn({
+            let io = io.clone();
+            move || -> Result<String> {
+                let mut lines = String::new();
+                for prompt in [ "first?", "second?", "third?" ] {
+                    echo!( io.out(), "{prompt}" );
+                    io.input().read_line( &mut lines )?;
+                }
+                Ok( lines )
             }
-        }
-
-        assert_eq!( io.recv().trim(), ALPHABET );
-
+        });
+        assert_eq!( tool.join().unwrap()?, "sent\n0:second?\n1:third?\n" );
         Ok(())
     }
 
     #[test]
-    fn altout_write_fmt() -> Result<()> {
+    fn input_generator_output() -> Result<()> {
         let io = Altio::default();
+        io.set_input_generator( |request| Some( request.output.replace( '\n', "|" )));
+        echo!( io.out(), "received" );
+        assert_eq!( io.recv_line(), "received\n" );
+
+        let mut out = io.out();
+        write!( out, "Name: " )?;
+        let mut line = String::new();
+        io.input().read_line( &mut line )?;
+        drop( out );
+        assert_eq!( line, "received|Name: \n" );
+
+        echo!( io.out(), "again" );
+        io.input().read_line( &mut line )?;
+        assert_eq!( line, "received|Name: \nagain|\n" );
+        Ok(())
+    }
 
-        let contents = ALPHABET;
-        for line in contents.lines() {
-            writeln!( io.out(), "{}", line )?;
-        }
-
-        assert_eq!( io.recv().trim(), ALPHABET );
+    #[test]
+    fn send_line_timeout() -> Result<()> {
+        let io = Altio::default();
+        io.send_line( "first" );
+        assert!( !io.send_line_timeout( "second", Duration::from_millis( 10 )));
+        let mut line = String::new();
+        io.input().read_line( &mut line )?;
+        assert!( !io.send_line_timeout( "third", Duration::ZERO ));
+
+        let tool = std::thread::spawn({
+            let io = io.clone();
+            move || -> Result<String> {
+                let mut lines = String::new();
+                for _ in 0..3 {
+                    io.input().read_line( &mut lines )?;
+                }
+                Ok( lines )
+            }
+        });
+        assert!( io.send_line_timeout( "fourth", Duration::from_secs( 10 )));
+        assert_eq!( tool.join().unwrap()?, "second\nthird\nfourth\n" );
 
+        io.0.0.kill();
+        assert!( !io.send_line_timeout( "killed", Duration::from_secs( 10 )));
         Ok(())
     }
+
     #[test]
-    fn alterr_lock_write_fmt() -> Result<()> {
+    fn schedule_input() -> Result<()> {
         let io = Altio::default();
+        let since = Instant::now();
+        let timer = io.schedule_input( &[( Duration::from_millis( 20 ), "b" ), ( Duration::from_millis( 10 ), "a" )]);
+        let mut lines = String::new();
+        io.input().read_line( &mut lines )?;
+        io.input().read_line( &mut lines )?;
+        assert!( since.elapsed() >= Duration::from_millis( 20 ));
+        assert_eq!( lines, "a\nb\n" );
+        timer.join().unwrap();
+
+        let timer = io.schedule_input( &[( Duration::from_secs( 3600 ), "never" )]);
+        drop( io );
+        timer.join().unwrap();
+        Ok(())
+    }
 
-        {
-            let mut lock = io.err();
-            let contents = ALPHABET;
-            for line in contents.lines() {
-                writeln!( lock, "{}", line )?;
-            }
+    #[cfg( feature = "tokio" )]
+    #[tokio::test( start_paused = true )]
+    async fn tokio_paused_time() {
+        let since = Instant::now();
+        let io = Altio::default();
+        let driver = std::thread::spawn({
+            let io = io.clone();
+            move || io.recv_line_timeout( Duration::from_secs( 3600 ))
+        });
+        while !driver.is_finished() {
+            tokio::time::advance( Duration::from_secs( 60 )).await;
         }
+        assert_eq!( driver.join().unwrap(), None );
+        assert!( since.elapsed() < Duration::from_secs( 3600 ));
+    }
 
-        assert_eq!( io.recv_err().trim(), ALPHABET );
-
-        Ok(())
+    #[test]
+    fn send_atomic() {
+        let io = Altio::default();
+        let senders = ( 0..4 ).map( |n| {
+            let io = io.clone();
+            std::thread::spawn( move || for i in 0..100 {
+                io.send_atomic( &[ "<", &n.to_string(), ":", &i.to_string(), ">\n" ]);
+            })
+        }).collect::<Vec<_>>();
+        senders.into_iter().for_each( |sender| sender.join().unwrap() );
+        io.close_input();
+
+        let mut last = [ None; 4 ];
+        for line in io.input().lines() {
+            let ( n, i ) = line.trim_end().strip_prefix( '<' ).and_then( |line| line.strip_suffix( '>' ))
+                .and_then( |line| line.split_once( ':' ))
+                .unwrap_or_else( || panic!( "interleaved: {line:?}" ));
+            let ( n, i ) = ( n.parse::<usize>().unwrap(), i.parse::<usize>().unwrap() );
+            assert_eq!( last[n].map_or( 0, |last| last + 1 ), i );
+            last[n] = Some( i );
+        }
+        assert_eq!( last, [ Some( 99 ); 4 ]);
     }
 
     #[test]
-    fn alterr_write_fmt() -> Result<()> {
+    fn transaction() -> Result<()> {
         let io = Altio::default();
+        let rows = io.transaction( |t| -> Result<usize> {
+            writeln!( t, "+---+" )?;
+            assert_eq!( io.try_recv(), None );
+            writeln!( t, "| 1 |" )?;
+            t.push_str( "+---+\n" );
+            Ok( 3 )
+        })??;
+        assert_eq!( rows, 3 );
+        assert_eq!( io.try_recv().as_deref(), Some( "+---+\n| 1 |\n+---+\n" ));
+
+        io.inject_error( Stream::Err, ErrorKind::BrokenPipe, When::Next );
+        assert_eq!( io.transaction_err( |t| t.push( '!' )).unwrap_err().kind(), ErrorKind::BrokenPipe );
+
+        struct Bad;
+        impl std::fmt::Display for Bad {
+            fn fmt( &self, _: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result { Err( std::fmt::Error )}
+        }
+        assert_eq!( io.transaction( |t| write!( t, "{}", Bad ))?.unwrap_err().to_string(), "formatter error" );
+        Ok(())
+    }
 
-        let contents = ALPHABET;
-        for line in contents.lines() {
-            writeln!( io.err(), "{}", line )?;
+    #[test]
+    fn write_fmt_error() {
+        struct Bad;
+        impl std::fmt::Display for Bad {
+            fn fmt( &self, _: &mut std::fmt::Formatter<'_> ) -> std::fmt::Result { Err( std::fmt::Error )}
         }
+        let io = Altio::default();
+        let err = write!( io.out(), "abc{}", Bad ).unwrap_err();
+        assert_eq!(( err.kind(), err.to_string().as_str() ), ( ErrorKind::Other, "formatter error" ));
+        assert_eq!( io.recv(), "abc" );
+        echo!( io.out(), "def" );
+        assert_eq!( io.recv(), "def\n" );
+    }
 
-        assert_eq!( io.recv_err().trim(), ALPHABET );
+    #[test]
+    fn frame_messages() {
+        let io = Altio::default();
+        echo!( -n, io.out(), "before" );
+        io.frame_messages( true );
+        echo!( io.out(), "{}, {}!", "Hello", "world" );
+        io.out().push_str( "a" );
+        io.out().push_str( "b" );
+        io.transaction( |t| { t.push_str( "c\n" ); t.push_str( "d\n" ); }).unwrap();
+
+        assert_eq!( io.recv_message(), "before" );
+        assert_eq!( io.recv_message(), "Hello, world!\n" );
+        assert_eq!( io.recv_max( 1 ), "a" );
+        assert_eq!( io.recv_message(), "b" );
+        assert_eq!( io.recv_message(), "c\nd\n" );
+        assert_eq!( io.try_recv_message(), None );
+        assert_eq!( io.try_recv_err_message(), None );
+
+        echo!( io.out(), "a" );
+        echo!( io.out(), "b" );
+        io.out().insert_str( 0, "edited " );
+        assert_eq!( io.try_recv_message().as_deref(), Some( "edited a\nb\n" ));
+
+        io.frame_messages( false );
+        echo!( -n, io.out(), "e" );
+        echo!( -n, io.out(), "f" );
+        assert_eq!( io.recv_message(), "ef" );
+    }
 
+    #[test]
+    fn buffering() -> Result<()> {
+        let io = Altio::default();
+        io.buffering( Stream::Out, Buffering::Line );
+        echo!( -n, io.out(), "name? " );
+        assert_eq!( io.try_recv(), None );
+        io.out().flush()?;
+        assert_eq!( io.try_recv().as_deref(), Some( "name? " ));
+
+        io.buffering( Stream::Out, Buffering::Full( 6 ));
+        echo!( io.out(), "one" );
+        assert_eq!( io.try_recv(), None );
+        echo!( io.out(), "two" );
+        assert_eq!( io.try_recv().as_deref(), Some( "one\n" ));
+        io.buffering( Stream::Out, Buffering::Unbuffered );
+        assert_eq!( io.try_recv().as_deref(), Some( "two\n" ));
         Ok(())
     }
 
     #[test]
-    fn nothing_received() {
+    fn recv_until_flush() -> Result<()> {
         let io = Altio::default();
+        echo!( io.out(), "row 1" );
+        echo!( io.out(), "row 2" );
+        io.out().flush()?;
+        echo!( -n, io.out(), "row" );
+        assert_eq!( io.recv_max( 2 ), "ro" );
+        assert_eq!( io.recv_until_flush(), "w 1\nrow 2\n" );
+        assert_eq!( io.try_recv_until_flush(), None );
+
+        let child = AltCommand::new( |io: Altio, _| echo!( -n, io.err(), "bye" )).spawn();
+        assert_eq!( child.io().recv_err_until_flush(), "bye" );
+        Ok(())
+    }
 
-        assert!( io.try_recv().is_none() );
-        assert!( io.try_recv_line().is_none() );
-        assert!( io.try_recv_err().is_none() );
-        assert!( io.try_recv_err_line().is_none() );
+    #[test]
+    fn recv_until_suffix() {
+        let clock = Arc::new( ManualClock::new() );
+        let io = Altio::builder().clock( clock.clone() ).build();
+        echo!( io.out(), "menu" );
+        echo!( -n, io.out(), "> " );
+        assert_eq!( io.recv_until_suffix( "> ", false ), "menu\n" );
+        assert_eq!( io.recv_until_suffix( "> ", true ), "> " );
+
+        echo!( -n, io.out(), "Password: x" );
+        let driver = {
+            let io = io.clone();
+            std::thread::spawn( move || io.wait_for_prompt( "Password: ", Duration::from_secs( 1 )))
+        };
+        std::thread::sleep( Duration::from_millis( 10 ));
+        clock.advance( Duration::from_secs( 1 ));
+        assert_eq!( driver.join().unwrap(), None );
     }
 
     #[test]
-    fn io_print() {
-        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_err().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+    fn unread() -> Result<()> {
+        let io = Altio::default();
+        io.send_line( "yes" );
+        io.send_line( "42" );
+        io.push_front( "no\n" );
+
+        let mut line = String::new();
+        io.input().read_line( &mut line )?;
+        assert_eq!( line, "no\n" );
+        io.input().unread( &line );
+        let mut lock = io.input().lock();
+        line.clear();
+        lock.read_line( &mut line )?;
+        assert_eq!( line, "no\n" );
+        lock.unread( "n" );
+        drop( lock );
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(), [ "nyes\n", "42\n" ]);
+        Ok(())
+    }
 
-        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv().is_some() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_err().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), " " ); assert!( io.try_recv_err_line().is_none() ); }
+    #[test]
+    fn windows_console() -> Result<()> {
+        let io = Altio::default();
+        io.console( Console::Windows );
+        io.send_atomic( &[ "first\r", "\nsecond\n" ]);
+        io.send_raw( b"raw\n" );
+        let mut lines = String::new();
+        io.input().read_to_string( &mut lines )?;
+        assert_eq!( lines, "first\r\nsecond\r\nraw\n" );
+
+        echo!( io.out(), "a\nb" );
+        write!( io.out(), "\r" )?;
+        writeln!( io.out() )?;
+        assert_eq!( io.try_recv_lines( 2 ).as_deref(), Some( "a\r\nb\r\n" ));
+
+        io.console( Console::Unix );
+        echo!( io.err(), "c" );
+        assert_eq!( io.recv_err_line(), "c\n" );
+        Ok(())
+    }
 
-        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv().is_some() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_line().is_some() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_err().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.out(), "\n" ); assert!( io.try_recv_err_line().is_none() ); }
+    #[test]
+    fn send_priority_line() {
+        let io = Altio::default();
+        io.send_line( "answer 1" );
+        io.send_line( "answer 2" );
+        io.send_priority_line( "abort" );
+        io.send_priority_line( "quit" );
+        assert_eq!( io.input().lines().next().as_deref(), Some( "abort\n" ));
+        io.send_priority_line( "exit" );
+        io.send( "answer " );
+        io.send_line( "3" );
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(),
+            [ "quit\n", "exit\n", "answer 1\n", "answer 2\n", "answer 3\n" ]);
+        io.send_priority_line( "again" );
+        io.send_line( "answer 4" );
+        io.send_priority_line( "first" );
+        assert_eq!( io.input().try_lines().collect::<Vec<_>>(), [ "again\n", "first\n", "answer 4\n" ]);
     }
 
     #[test]
-    fn io_println() {
-        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv().is_some() ); }
-        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_line().is_some() ); }
-        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_err().is_none() ); }
-        { let io = Altio::default(); echo!( io.out(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+    fn dump_pending_hex() {
+        let io = Altio::default();
+        io.send_raw( b"\xef\xbb\xbfyes\r\n" );
+        assert_eq!( io.dump_pending_hex( Stream::In ),
+            "00000000  ef bb bf 79 65 73 0d 0a                           |...yes..|\n" );
+        assert_eq!( io.dump_pending_hex( Stream::Out ), "" );
+        assert_eq!( io.input().lines().next().as_deref(), Some( "\u{feff}yes\r\n" ));
     }
 
     #[test]
-    fn io_eprint() {
-        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_err().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "" ); assert!( io.try_recv_err_line().is_none() ); }
+    fn recording() {
+        let io = Altio::default();
+        io.send_line( "before" );
+        io.start_recording();
+        io.send_line( "Alice" );
+        echo!( io.out(), "Hello, Alice!" );
+        echo!( -n, io.err(), "bye" );
+        let transcript = io.stop_recording();
+        io.send_line( "after" );
+        let streams = transcript.entries.iter().map( |entry| entry.stream ).collect::<Vec<_>>();
+        assert_eq!( streams, [ Stream::In, Stream::In, Stream::Out, Stream::Err ]);
+        assert_eq!( transcript.text( Stream::In ), "Alice\n" );
+        assert_eq!( transcript.text( Stream::Out ), "Hello, Alice!\n" );
+        assert_eq!( io.stop_recording(), Default::default() );
+
+        let replay = Altio::default();
+        replay.attach_input( transcript.input_script(), 0 );
+        assert_eq!( replay.input().lines().next().as_deref(), Some( "Alice\n" ));
+    }
 
-        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_err().is_some() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), " " ); assert!( io.try_recv_err_line().is_none() ); }
+    #[test]
+    fn backend() {
+        use crate::backend::{AltBackend, Port};
+        use std::sync::{Arc, Mutex};
+
+        #[derive( Debug )]
+        struct Remote( Arc<Mutex<Vec<u8>>> );
+
+        impl AltBackend for Remote {
+            fn open( &mut self, stream: Stream ) -> Port {
+                match stream {
+                    Stream::In => Port{ source: None, sink: Some( Box::new( SharedSink( self.0.clone() )))},
+                    Stream::Out => Port{ source: Some( Box::new( "remote\n".as_bytes() )), sink: None },
+                    Stream::Err => Port::default(),
+                }
+            }
+        }
 
-        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_err().is_some() ); }
-        { let io = Altio::default(); echo!( -n, io.err(), "\n" ); assert!( io.try_recv_err_line().is_some() ); }
+        struct SharedSink( Arc<Mutex<Vec<u8>>> );
+
+        impl std::io::Write for SharedSink {
+            fn write( &mut self, buf: &[u8] ) -> Result<usize> {
+                self.0.lock().unwrap().extend_from_slice( buf );
+                Ok( buf.len() )
+            }
+            fn flush( &mut self ) -> Result<()> { Ok(() )}
+        }
+
+        let sent = Arc::new( Mutex::new( Vec::new() ));
+        let io = Altio::builder().backend( Remote( sent.clone() )).build();
+        io.send_line( "to remote" );
+        assert_eq!( *sent.lock().unwrap(), b"to remote\n" );
+        assert_eq!( io.input().metrics().bytes, 10 );
+        assert_eq!( io.recv_line(), "remote\n" );
+        echo!( io.err(), "local" );
+        assert_eq!( io.recv_err(), "local\n" );
     }
 
+    #[cfg( all( unix, feature = "os-pipe" ))]
     #[test]
-    fn io_eprintln() {
-        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv().is_none() ); }
-        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_line().is_none() ); }
-        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_err().is_some() ); }
-        { let io = Altio::default(); echo!( io.err(), "" ); assert!( io.try_recv_err_line().is_some() ); }
+    fn inheritable_stdio() {
+        use std::process::Command;
+
+        assert_eq!( Altio::default().inheritable_stdio().unwrap_err().kind(), ErrorKind::Unsupported );
+
+        let io = Altio::builder().backend( crate::backend::OsPipes::new().unwrap() ).build();
+        let ( stdin, stdout, stderr ) = io.inheritable_stdio().unwrap();
+        io.send_line( "grandchild" );
+        Command::new( "sh" ).args([ "-c", "read name; echo \"hello $name\"; echo oops >&2" ])
+            .stdin( stdin ).stdout( stdout ).stderr( stderr )
+            .status().unwrap();
+        assert_eq!( io.recv_line(), "hello grandchild\n" );
+        assert_eq!( io.recv_err_line(), "oops\n" );
     }
 
+    #[cfg( unix )]
     #[test]
-    fn receive_out() {
-        let io = Altio::default();
+    fn adopt_child() {
+        use std::process::{Command, Stdio};
 
-        echo!( -n, io.out(), "" );
-        assert!( io.try_recv().is_none() );
+        let io = Altio::default();
+        let mut child = Command::new( "sh" ).args([ "-c", "echo out; echo err >&2" ])
+            .stdout( Stdio::piped() ).stderr( Stdio::piped() )
+            .spawn().unwrap();
+        io.adopt_child_stdout( child.stdout.take().unwrap(), "[sh] " );
+        io.adopt_child_stderr( child.stderr.take().unwrap(), "" );
+        child.wait().unwrap();
+        assert_eq!( io.recv_line(), "[sh] out\n" );
+        assert_eq!( io.recv_err_line(), "err\n" );
+        echo!( io.out(), "tool" );
+        assert_eq!( io.recv_line(), "tool\n" );
+    }
 
-        echo!( -n, io.out(), " " );
-        assert!( io.try_recv_err().is_none() );
-        assert_eq!( io.try_recv(), Some( " ".to_owned() ));
+    #[test]
+    fn async_api() {
+        use futures::executor::block_on;
 
-        echo!( -n, io.out(), "abcdefg\nhijklmn\nopq rst\nuvw xyz" );
-        assert_eq!( io.try_recv_line(), Some( "abcdefg\n".to_owned() ));
-        assert_eq!( io.recv_line(), "hijklmn\n" );
-        assert_eq!( io.recv(), "opq rst\nuvw xyz" );
+        let io = Altio::default();
+        let tool = io.clone();
+        let handle = std::thread::spawn( move || {
+            let mut name = String::new();
+            tool.input().read_line( &mut name ).unwrap();
+            echo!( tool.out(), "Hello, {}", name.trim_end() );
+            echo!( tool.err(), "bye" );
+        });
+        block_on( async {
+            io.send_line_async( "async" ).await;
+            assert_eq!( io.recv_lines_async( 0 ).await, "" );
+            assert_eq!( io.recv_line_async().await, "Hello, async\n" );
+            assert_eq!( io.recv_err_lines_async( 0 ).await, "" );
+            assert_eq!( io.recv_err_async().await, "bye\n" );
+        });
+        handle.join().unwrap();
     }
 
     #[test]
-    fn receive_err() {
-        let io = Altio::default();
+    fn std_io_traits() {
+        use std::io::{BufRead, Read, Write};
 
-        echo!( -n, io.err(), "" );
-        assert!( io.try_recv_err().is_none() );
+        fn count_lines( input: &mut impl BufRead ) -> usize { input.lines().count() }
 
-        echo!( -n, io.err(), " " );
-        assert!( io.try_recv().is_none() );
-        assert_eq!( io.try_recv_err(), Some( " ".to_owned() ));
+        fn greet( out: &mut impl Write ) {
+            out.write_all( b"hello\n" ).unwrap();
+            out.flush().unwrap();
+        }
 
-        echo!( -n, io.err(), "abcdefg\nhijklmn\nopq rst\nuvw xyz" );
-        assert_eq!( io.try_recv_err_line(), Some( "abcdefg\n".to_owned() ));
-        assert_eq!( io.recv_err_line(), "hijklmn\n" );
-        assert_eq!( io.recv_err(), "opq rst\nuvw xyz" );
+        let io = Altio::default();
+        io.send( "xyz\na\nb\n" );
+        io.close_input();
+        let mut bytes = [0; 2];
+        io.input().lock().read_exact( &mut bytes ).unwrap();
+        assert_eq!( &bytes, b"xy" );
+        assert_eq!( count_lines( &mut io.input().lock() ), 3 );
+        greet( &mut io.out() );
+        assert_eq!( io.recv(), "hello\n" );
     }
 
     #[test]
-    fn receive_lines() {
+    fn pause_capture() {
         let io = Altio::default();
+        echo!( io.out(), "before" );
+        io.pause_capture();
+        echo!( io.out(), "live" );
+        io.send_line( "input" );
+        assert_eq!( io.input().lines().next().as_deref(), Some( "input\n" ));
+        io.resume_capture();
+        echo!( io.out(), "after" );
+        assert_eq!( io.recv(), "before\nafter\n" );
+    }
 
-        echo!( -n, io.out(), "abcd\nefg\nhijk\nlmn\nopq\nrst\nuvw\nxyz" );
-        assert_eq!( io.try_recv_lines(1), Some( "abcd\n".to_owned() ) );
-        assert_eq!( io.try_recv_lines(2), Some( "efg\nhijk\n".to_owned() ));
-        assert_eq!( io.try_recv_lines(3), Some( "lmn\nopq\nrst\n".to_owned() ));
-        assert_eq!( io.try_recv_lines(2), None );
+    #[test]
+    fn pass_through_stdin() {
+        let io = Altio::default();
+        io.pass_through_stdin();
+        io.attach_input( crate::source::Script::new([ "scripted" ]), 0 );
+        io.send_line( "sent" );
+        assert_eq!( io.input().lines().take(2).collect::<Vec<_>>(), [ "sent\n", "scripted\n" ]);
+        io.detach_inputs();
     }
 
     #[test]
-    fn receive_err_lines() {
+    fn attach_to_stdio() {
         let io = Altio::default();
+        io.buffering( Stream::Out, Buffering::Full( 64 ));
+        echo!( io.out(), "captured" );
+        io.attach_to_stdio();
+        assert!( io.is_attached_to_stdio() );
+        echo!( io.err(), "passed through" );
+        assert_eq!( io.try_recv_err(), None );
+        io.detach();
+        assert!( !io.is_attached_to_stdio() );
+        echo!( io.out(), "captured again" );
+        assert_eq!( io.recv(), "captured\n" );
+        assert_eq!( io.try_recv(), None );
+    }
 
-        echo!( -n, io.err(), "abcd\nefg\nhijk\nlmn\nopq\nrst\nuvw\nxyz" );
-        assert_eq!( io.try_recv_err_lines(1), Some( "abcd\n".to_owned() ) );
-        assert_eq!( io.try_recv_err_lines(2), Some( "efg\nhijk\n".to_owned() ));
-        assert_eq!( io.try_recv_err_lines(3), Some( "lmn\nopq\nrst\n".to_owned() ));
-        assert_eq!( io.try_recv_err_lines(2), None );
+    #[test]
+    fn interleave() -> Result<()> {
+        let receive = |seed| {
+            let io = Altio::default();
+            assert_eq!( io.interleave( Some( seed )), seed );
+            assert_eq!( io.interleave_seed(), Some( seed ));
+            let mut received = Vec::new();
+            for i in 0..8 {
+                echo!( io.out(), "line {i}" );
+                received.extend( io.try_recv() );
+            }
+            io.out().flush()?;
+            received.extend( io.try_recv() );
+            Ok::<_,std::io::Error>( received )
+        };
+
+        let lines = (0..8).map( |i| format!( "line {i}\n" )).collect::<Vec<_>>();
+        let received = receive( 42 )?;
+        assert_eq!( received, receive( 42 )? );
+        assert_ne!( received, lines );
+        assert_eq!( received.concat(), lines.concat() );
+
+        Ok(())
     }
 
     #[test]
@@ -777,6 +5377,19 @@ pub mod tests {
         assert_eq!( io.recv_line(),        "efg\n".to_owned()  );
     }
 
+    #[test]
+    fn peek_snapshot() {
+        let io = Altio::default();
+        echo!( io.out(), "abcd\nefg" );
+        assert_eq!( io.peek_lines(2), Some( "abcd\nefg\n".to_owned() ));
+        let locked = io.0.1.output.lock().unwrap();
+        assert_eq!( io.peek_line(), Some( "abcd\n".to_owned() ));
+        assert_eq!( io.peek_lines(3), None );
+        drop( locked );
+        assert_eq!( io.recv_line(), "abcd\n" );
+        assert_eq!( io.peek_line(), Some( "efg\n".to_owned() ));
+    }
+
     #[test]
     fn peek_err_line() {
         let io = Altio::default();
@@ -0,0 +1,213 @@
+//! Logging the traffic of a session into a file as it happens, one line per event
+//! tagged with the time since logging started, e.g. as an artifact of failed CI runs.
+//!
+//! ```text
+//!    0.000152 out  "Name: "
+//!    0.803114 in   "Alice\n"
+//!    0.803290 err  "warning: no profile\n"
+//!    0.803302 out  "Hello, Alice!\n"
+//!    0.803311 eof  out
+//!    0.803315 eof  err
+//!    0.803318 exit 0
+//! ```
+//!
+//! Text is quoted like in transcripts, revealing carriage returns. The text of a line
+//! written or sent in pieces is logged as it comes, at the time of its first piece,
+//! its closing quote following once the line is finished or another event happens.
+//!
+//! With rotation, once the file reaches a size, it is renamed with the suffix ".1",
+//! older files shifting to ".2" and so on, and logging goes on into a new file. The
+//! oldest ones are removed so that a limited amount of files is kept, bounding the disk
+//! space used by always-on logging.
+
+use crate::Stream;
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+/// An event of a session logged.
+#[derive( Clone, Copy, Debug )]
+pub(crate) enum Event<'a> {
+    /// Bytes sent into, written to or read from real stdin by a stream.
+    Text( Stream, &'a [u8] ),
+    /// The stream has been closed.
+    Eof( Stream ),
+    /// The tool has returned the exit code.
+    Exit( i32 ),
+}
+
+/// When to rotate a log file, and how many files to keep.
+#[derive( Clone, Copy, Debug, PartialEq, Eq )]
+pub(crate) struct Rotation {
+    pub(crate) max_bytes : u64,
+    pub(crate) max_files : usize, // including the current one
+}
+
+/// A log file shared by the streams of an `Altio`.
+#[derive( Debug )]
+pub(crate) struct SessionLog {
+    start    : Instant,
+    path     : PathBuf,
+    rotation : Option<Rotation>,
+    file     : Mutex<LogFile>,
+}
+
+/// The file being written.
+#[derive( Debug )]
+struct LogFile {
+    file    : File,
+    written : u64,
+    open    : Option<Stream>, // whose line is not closed yet
+}
+impl SessionLog {
+    /// Creates the log file at `path`, truncating it, rotated if `rotation` says so.
+    pub(crate) fn create( path: &Path, rotation: Option<Rotation> ) -> io::Result<Self> {
+        let file = LogFile{ file: File::create( path )?, written: 0, open: None };
+        Ok( SessionLog{ start: Instant::now(), path: path.to_owned(), rotation, file: Mutex::new( file )})
+    }
+
+    /// Appends the lines of `event` to the file unbuffered, ignoring errors. Rotates
+    /// the file first if it has reached the size of rotation.
+    pub(crate) fn log( &self, event: Event<'_> ) {
+        let at = self.start.elapsed();
+        let at = format!( "{:>4}.{:06}", at.as_secs(), at.subsec_micros() );
+        let mut log = self.file.lock().unwrap_or_else( PoisonError::into_inner );
+        if let Some( rotation ) = self.rotation.filter( |rotation| log.written >= rotation.max_bytes ) {
+            log.close();
+            self.rotate( &mut log, rotation );
+        }
+        let LogFile{ open, .. } = &mut *log;
+        let mut lines = String::new();
+        if let Event::Text( stream, bytes ) = event {
+            for line in String::from_utf8_lossy( bytes ).split_inclusive( '\n' ) {
+                if *open != Some( stream ) {
+                    close( &mut lines, open );
+                    let _ = write!( lines, "{at} {:<4} \"", stream.name() );
+                }
+                let _ = write!( lines, "{}", line.escape_debug() );
+                *open = Some( stream );
+                if line.ends_with( '\n' ) {
+                    close( &mut lines, open );
+                }
+            }
+        } else {
+            close( &mut lines, open );
+            let _ = match event {
+                Event::Eof( stream ) => writeln!( lines, "{at} eof  {}", stream.name() ),
+                Event::Exit( code ) => writeln!( lines, "{at} exit {code}" ),
+                Event::Text(..) => unreachable!(),
+            };
+        }
+        log.write( &lines );
+    }
+
+    /// Shifts the files kept by suffixes, removing the oldest, then writes into a new
+    /// file, or keeps writing into the same one if it can't be renamed.
+    fn rotate( &self, log: &mut LogFile, rotation: Rotation ) {
+        let kept = |n: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push( format!( ".{n}" ));
+            PathBuf::from( path )
+        };
+        if rotation.max_files <= 1 {
+            if log.file.set_len( 0 ).is_ok() && log.file.rewind().is_ok() {
+                log.written = 0;
+            }
+            return;
+        }
+        let _ = fs::remove_file( kept( rotation.max_files-1 ));
+        for n in ( 1..rotation.max_files-1 ).rev() {
+            let _ = fs::rename( kept(n), kept(n+1) );
+        }
+        if fs::rename( &self.path, kept(1) ).is_ok() {
+            if let Ok( file ) = File::create( &self.path ) {
+                *log = LogFile{ file, written: 0, open: None };
+            }
+        }
+    }
+}
+
+impl LogFile {
+    fn write( &mut self, lines: &str ) {
+        if self.file.write_all( lines.as_bytes() ).is_ok() {
+            self.written += lines.len() as u64;
+        }
+    }
+
+    /// Writes the closing quote of the open line, if any.
+    fn close( &mut self ) {
+        let mut lines = String::new();
+        close( &mut lines, &mut self.open );
+        self.write( &lines );
+    }
+}
+
+impl Drop for SessionLog {
+    fn drop( &mut self ) {
+        self.file.get_mut().unwrap_or_else( PoisonError::into_inner ).close();
+    }
+}
+
+/// Appends the closing quote of the open line, if any.
+fn close( lines: &mut String, open: &mut Option<Stream> ) {
+    if open.take().is_some() {
+        lines.push_str( "\"\n" );
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Event, Rotation, SessionLog};
+    use crate::Stream;
+
+    #[test]
+    fn log() {
+        let path = std::env::temp_dir().join( format!( "altio-log-{}.log", std::process::id() ));
+        let log = SessionLog::create( &path, None ).unwrap();
+        log.log( Event::Text( Stream::Out, b"a\r\nb" ));
+        log.log( Event::Text( Stream::Out, b"\"c\"" ));
+        log.log( Event::Text( Stream::In, b"d" ));
+        log.log( Event::Eof( Stream::In ));
+        log.log( Event::Exit( 2 ));
+        log.log( Event::Text( Stream::Err, b"e" ));
+        drop( log );
+        let logged = std::fs::read_to_string( &path ).unwrap();
+        std::fs::remove_file( &path ).unwrap();
+        let untimed = logged.lines().map( |line| {
+            let ( at, event ) = line.trim_start().split_once( ' ' ).unwrap();
+            assert!( at.parse::<f64>().is_ok(), "{line}" );
+            event
+        }).collect::<Vec<_>>();
+        assert_eq!( untimed, [ r#"out  "a\r\n""#, r#"out  "b\"c\"""#, r#"in   "d""#, "eof  in", "exit 2", r#"err  "e""# ]);
+    }
+
+    #[test]
+    fn rotate() {
+        let dir = std::env::temp_dir().join( format!( "altio-rotate-{}", std::process::id() ));
+        std::fs::create_dir_all( &dir ).unwrap();
+        let path = dir.join( "session.log" );
+        let log = SessionLog::create( &path, Some( Rotation{ max_bytes: 30, max_files: 3 })).unwrap();
+        for n in 0..5 {
+            log.log( Event::Text( Stream::Out, format!( "line {n}\n" ).as_bytes() ));
+        }
+        log.log( Event::Text( Stream::Out, b"6" ));
+        log.log( Event::Exit( 0 ));
+        drop( log );
+
+        let read = |name: &str| std::fs::read_to_string( dir.join( name )).ok().map( |logged| logged
+            .lines()
+            .map( |line| line.trim_start().split_once( ' ' ).unwrap().1.to_owned() )
+            .collect::<Vec<_>>() );
+        assert_eq!( read( "session.log.2" ).unwrap(), [ r#"out  "line 2\n""#, r#"out  "line 3\n""# ]);
+        assert_eq!( read( "session.log.1" ).unwrap(), [ r#"out  "line 4\n""#, r#"out  "6""# ]);
+        assert_eq!( read( "session.log" ).unwrap(), [ "exit 0" ]);
+        assert_eq!( read( "session.log.3" ), None );
+        std::fs::remove_dir_all( &dir ).unwrap();
+    }
+}
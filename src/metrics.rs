@@ -0,0 +1,94 @@
+//! Counters of the traffic through altio streams.
+
+use crate::{Stream, trace::trace_io};
+
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Snapshot of the counters of all streams in an `Altio`, returned by
+/// `Altio::metrics()`.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub struct Metrics {
+    /// Counters of the input stream, written by `send()` and read by the tool.
+    pub input : StreamMetrics,
+    /// Counters of the output stream, written by the tool and read by `recv()`.
+    pub out   : StreamMetrics,
+    /// Counters of the error stream, written by the tool and read by `recv_err()`.
+    pub err   : StreamMetrics,
+}
+
+/// Snapshot of the counters of one stream.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq )]
+pub struct StreamMetrics {
+    /// Total bytes written.
+    pub bytes       : u64,
+    /// Total newlines written.
+    pub lines       : u64,
+    /// Total calls of reading/receiving functions.
+    pub receives    : u64,
+    /// Total time spent in blocking reading/receiving functions.
+    pub blocked     : Duration,
+    /// The maximum amount of bytes pending in the stream at the same time.
+    pub max_pending : usize,
+}
+
+/// Live counters of one stream.
+#[derive( Debug )]
+pub(crate) struct Stats {
+    pub(crate) stream : Stream,
+    bytes       : AtomicU64,
+    lines       : AtomicU64,
+    receives    : AtomicU64,
+    blocked     : AtomicU64, // in nanoseconds
+    max_pending : AtomicUsize,
+}
+
+impl Stats {
+    pub(crate) fn new( stream: Stream ) -> Self {
+        Stats {
+            stream,
+            bytes       : AtomicU64::default(),
+            lines       : AtomicU64::default(),
+            receives    : AtomicU64::default(),
+            blocked     : AtomicU64::default(),
+            max_pending : AtomicUsize::default(),
+        }
+    }
+
+    /// Counts `bytes` written into the stream, which now holds `pending` bytes.
+    pub(crate) fn wrote( &self, bytes: &[u8], pending: usize ) {
+        trace_io!( stream = self.stream.name(), bytes = bytes.len(), pending, "write" );
+        let lines = bytes.iter().filter( |&&b| b == b'\n' ).count();
+        self.bytes.fetch_add( bytes.len() as u64, Ordering::Relaxed );
+        self.lines.fetch_add( lines as u64, Ordering::Relaxed );
+        self.max_pending.fetch_max( pending, Ordering::Relaxed );
+    }
+
+    /// Counts a non-blocking receive of `bytes` bytes.
+    #[cfg_attr( not( feature = "trace-io" ), allow( unused_variables ))]
+    pub(crate) fn received( &self, bytes: usize ) {
+        trace_io!( stream = self.stream.name(), bytes, "recv" );
+        self.receives.fetch_add( 1, Ordering::Relaxed );
+    }
+
+    /// Counts a blocking receive of `bytes` bytes which started at `since`.
+    #[cfg_attr( not( feature = "trace-io" ), allow( unused_variables ))]
+    pub(crate) fn received_since( &self, since: Instant, bytes: usize ) {
+        let blocked = since.elapsed();
+        trace_io!( stream = self.stream.name(), bytes, ?blocked, "recv" );
+        self.receives.fetch_add( 1, Ordering::Relaxed );
+        self.blocked.fetch_add( blocked.as_nanos() as u64, Ordering::Relaxed );
+    }
+
+    pub(crate) fn snapshot( &self ) -> StreamMetrics {
+        StreamMetrics {
+            bytes       : self.bytes.load( Ordering::Relaxed ),
+            lines       : self.lines.load( Ordering::Relaxed ),
+            receives    : self.receives.load( Ordering::Relaxed ),
+            blocked     : Duration::from_nanos( self.blocked.load( Ordering::Relaxed )),
+            max_pending : self.max_pending.load( Ordering::Relaxed ),
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! Mirroring the traffic through altio streams to the real stderr, for watching an
+//! embedded tool while debugging.
+//!
+//! Mirroring is off by default. The `ALTIO_MIRROR` environment variable, checked once
+//! when the first stream is created, turns it on for a comma-separated list of
+//! streams, e.g. `ALTIO_MIRROR=out,err`, or `all` of them.
+//!
+//! Mirrored lines can be tagged with a prefix telling sessions apart, and color-coded
+//! telling streams apart if the real stderr is a terminal: output plain, errors red
+//! and input cyan. Traffic of raw bytes can be mirrored as hex+ASCII dumps instead.
+//! A filter can select the lines mirrored, e.g. errors only of a chatty tool. With
+//! the "trace-io" feature, lines can be emitted as `tracing` events instead.
+
+use crate::{Stream, hex, log::{Event, SessionLog}, transcript::Recording};
+
+use std::{
+    fmt::{self, Debug},
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+};
+
+/// Mirrors the bytes written into one stream.
+#[derive( Debug )]
+pub(crate) struct Mirror {
+    stream  : Stream,
+    enabled : AtomicBool,
+    colored : AtomicBool,
+    lines   : Mutex<Lines>,
+    record  : Mutex<Option<Arc<Recording>>>,
+    log     : Mutex<Option<Arc<SessionLog>>>,
+}
+
+/// Where mirrored traffic goes, see `Altio::set_mirror_target()`.
+#[derive( Clone, Debug, Default, PartialEq, Eq )]
+pub enum MirrorTarget {
+    /// The real stderr.
+    #[default]
+    Stderr,
+    /// One `tracing` event of level INFO and target "altio::mirror" per finished
+    /// line, with the line as message, the stream name as field `stream`, and
+    /// `session` as field `session`, landing in the structured logs of the host. The
+    /// prefix, colors and hex dumps of mirroring don't apply. With the "trace-io"
+    /// feature.
+    #[cfg( feature = "trace-io" )]
+    Tracing {
+        /// Tells apart the sessions logged.
+        session : String,
+    },
+}
+
+/// Selects the lines mirrored, given without their line endings.
+#[derive( Clone )]
+pub(crate) struct Filter( pub(crate) Arc<dyn Fn( &str ) -> bool + Send + Sync> );
+
+impl Debug for Filter {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        f.debug_tuple( "Filter" ).finish_non_exhaustive()
+    }
+}
+
+/// Tags each mirrored line with a prefix.
+#[derive( Debug, Default )]
+struct Lines {
+    prefix  : String,
+    inside  : bool,           // the last mirrored line is not finished
+    hex     : Option<u64>,    // the offset of the next byte if mirrored as hex dumps
+    filter  : Option<Filter>,
+    partial : Vec<u8>,        // the unfinished line held until the filter can select it
+    #[cfg_attr( not( feature = "trace-io" ), allow( dead_code ))]
+    target  : MirrorTarget,
+}
+
+impl Lines {
+    /// Whether only finished lines are mirrored, held until then.
+    fn by_line( &self ) -> bool {
+        #[cfg( feature = "trace-io" )]
+        if matches!( self.target, MirrorTarget::Tracing{..} ) {
+            return true;
+        }
+        self.filter.is_some()
+    }
+
+    /// Returns `bytes` as is, or dumped in hex, tagged with the prefix.
+    fn format( &mut self, bytes: &[u8] ) -> Vec<u8> {
+        match self.hex.as_mut() {
+            Some( offset ) => {
+                let dump = hex::dump( bytes, *offset );
+                *offset += bytes.len() as u64;
+                self.tag( dump.as_bytes() )
+            }
+            None => self.tag( bytes ),
+        }
+    }
+
+    /// Returns the lines finished by `bytes`, holding the unfinished one.
+    fn finish( &mut self, bytes: &[u8] ) -> Vec<u8> {
+        self.partial.extend_from_slice( bytes );
+        let end = self.partial.iter().rposition( |&byte| byte == b'\n' ).map_or( 0, |newline| newline+1 );
+        self.partial.drain( ..end ).collect()
+    }
+
+    /// Returns `bytes` with the prefix inserted at the start of each line.
+    fn tag( &mut self, bytes: &[u8] ) -> Vec<u8> {
+        let mut tagged = Vec::with_capacity( bytes.len() );
+        for line in bytes.split_inclusive( |&byte| byte == b'\n' ) {
+            if !self.inside {
+                tagged.extend_from_slice( self.prefix.as_bytes() );
+            }
+            tagged.extend_from_slice( line );
+            self.inside = !line.ends_with( b"\n" );
+        }
+        tagged
+    }
+}
+
+impl Mirror {
+    /// Mirrors `stream` if `ALTIO_MIRROR` says so.
+    pub(crate) fn new( stream: Stream ) -> Self {
+        static STREAMS: OnceLock<Vec<Stream>> = OnceLock::new();
+        let streams = STREAMS.get_or_init( || parse( &std::env::var( "ALTIO_MIRROR" ).unwrap_or_default() ));
+        Mirror {
+            stream,
+            enabled : AtomicBool::new( streams.contains( &stream )),
+            colored : AtomicBool::new( false ),
+            lines   : Mutex::default(),
+            record  : Mutex::default(),
+            log     : Mutex::default(),
+        }
+    }
+    pub(crate) fn set( &self, enabled: bool ) { self.enabled.store( enabled, Ordering::Relaxed ); }
+    pub(crate) fn set_prefix( &self, prefix: &str ) {
+        self.lines.lock().unwrap_or_else( PoisonError::into_inner ).prefix = prefix.to_owned();
+    }
+
+    /// Mirrors bytes as hex dumps, whose offsets count from now on, or as is.
+    pub(crate) fn set_hex( &self, hex: bool ) {
+        self.lines.lock().unwrap_or_else( PoisonError::into_inner ).hex = hex.then_some( 0 );
+    }
+
+    /// Mirrors only the finished lines selected by `filter`, or all the traffic as it
+    /// comes, dropping the unfinished line held.
+    pub(crate) fn set_filter( &self, filter: Option<Filter> ) {
+        let mut lines = self.lines.lock().unwrap_or_else( PoisonError::into_inner );
+        lines.filter = filter;
+        lines.partial.clear();
+    }
+
+    /// Mirrors into `target`, dropping the unfinished line held.
+    pub(crate) fn set_target( &self, target: MirrorTarget ) {
+        let mut lines = self.lines.lock().unwrap_or_else( PoisonError::into_inner );
+        lines.target = target;
+        lines.partial.clear();
+    }
+    pub(crate) fn set_colored( &self, colored: bool ) { self.colored.store( colored, Ordering::Relaxed ); }
+
+    /// Records the bytes into `recording` from now on, or stops recording, returning
+    /// the previous recording.
+    pub(crate) fn set_recording( &self, recording: Option<Arc<Recording>> ) -> Option<Arc<Recording>> {
+        std::mem::replace( &mut *self.record.lock().unwrap_or_else( PoisonError::into_inner ), recording )
+    }
+
+    /// Returns the recording in progress, if any.
+    pub(crate) fn recording( &self ) -> Option<Arc<Recording>> { self.record.lock().unwrap_or_else( PoisonError::into_inner ).clone() }
+
+    /// Logs the traffic into `log` from now on, or stops logging.
+    pub(crate) fn set_log( &self, log: Option<Arc<SessionLog>> ) {
+        *self.log.lock().unwrap_or_else( PoisonError::into_inner ) = log;
+    }
+
+    /// Logs `event` if logging.
+    pub(crate) fn log( &self, event: Event<'_> ) {
+        if let Some( log ) = self.log.lock().unwrap_or_else( PoisonError::into_inner ).as_ref() {
+            log.log( event );
+        }
+    }
+
+    /// Records and logs `bytes` if recording or logging.
+    pub(crate) fn record( &self, bytes: &[u8] ) {
+        if let Some( recording ) = self.record.lock().unwrap_or_else( PoisonError::into_inner ).as_ref() {
+            recording.record( self.stream, bytes );
+        }
+        self.log( Event::Text( self.stream, bytes ));
+    }
+
+    /// Copies `bytes` to the real stderr if enabled, ignoring errors, and records them.
+    ///
+    /// The filter is called without holding the lock of the lines, since it may panic
+    /// or use the streams.
+    pub(crate) fn write( &self, bytes: &[u8] ) {
+        self.record( bytes );
+        if !self.enabled.load( Ordering::Relaxed ) {
+            return;
+        }
+        let ( filter, finished ) = {
+            let mut lines = self.lines.lock().unwrap_or_else( PoisonError::into_inner );
+            ( lines.filter.clone(), lines.by_line().then( || lines.finish( bytes )))
+        };
+        let selected = match ( filter, finished ) {
+            ( Some( filter ), Some( finished )) => select( &filter, &finished ),
+            ( None, Some( finished )) => finished,
+            ( _, None ) => bytes.to_vec(),
+        };
+        if selected.is_empty() {
+            return;
+        }
+        let mut lines = self.lines.lock().unwrap_or_else( PoisonError::into_inner );
+        #[cfg( feature = "trace-io" )]
+        if let MirrorTarget::Tracing{ session } = &lines.target {
+            let session = session.clone();
+            drop( lines );
+            for line in selected.split_inclusive( |&byte| byte == b'\n' ) {
+                tracing::info!( target: "altio::mirror", stream = self.stream.name(), session, "{}", without_ending( line ));
+            }
+            return;
+        }
+        let mut tagged = lines.format( &selected );
+        drop( lines );
+        let stderr = io::stderr();
+        if self.colored.load( Ordering::Relaxed ) && stderr.is_terminal() {
+            tagged = paint( self.stream, tagged );
+        }
+        let _ = stderr.lock().write_all( &tagged );
+    }
+}
+
+/// Returns the lines of `finished` which `filter` selects.
+fn select( filter: &Filter, finished: &[u8] ) -> Vec<u8> {
+    finished
+        .split_inclusive( |&byte| byte == b'\n' )
+        .filter( |line| ( filter.0 )( &without_ending( line )))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// Returns the text of `line` without its line ending.
+fn without_ending( line: &[u8] ) -> String {
+    String::from_utf8_lossy( line ).trim_end_matches( [ '\n', '\r' ]).to_owned()
+}
+
+/// Wraps the mirrored `bytes` of `stream` in the escape codes of its color.
+fn paint( stream: Stream, bytes: Vec<u8> ) -> Vec<u8> {
+    let color: &[u8] = match stream {
+        Stream::In  => b"\x1b[36m",
+        Stream::Out => return bytes,
+        Stream::Err => b"\x1b[31m",
+    };
+    [ color, &bytes, b"\x1b[0m" ].concat()
+}
+
+/// Parses a comma-separated list of stream names, ignoring unknown ones.
+fn parse( spec: &str ) -> Vec<Stream> {
+    spec.split( ',' ).flat_map( |name| match name.trim() {
+        "in"  => &[ Stream::In ][..],
+        "out" => &[ Stream::Out ],
+        "err" => &[ Stream::Err ],
+        "all" => &[ Stream::In, Stream::Out, Stream::Err ],
+        _     => &[],
+    }).copied().collect()
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Filter, Lines, Mirror, paint, parse, select};
+    use crate::Stream;
+    use std::sync::Arc;
+
+    #[test]
+    fn parse_streams() {
+        assert_eq!( parse( "out, err" ), [ Stream::Out, Stream::Err ]);
+        assert_eq!( parse( "all" ), [ Stream::In, Stream::Out, Stream::Err ]);
+        assert_eq!( parse( "" ), [] );
+        assert_eq!( parse( "in,stdout" ), [ Stream::In ]);
+    }
+
+    #[test]
+    fn prefix() {
+        let mut lines = Lines{ prefix: "[w] ".to_owned(), ..Lines::default() };
+        assert_eq!( lines.tag( b"a\nb" ), b"[w] a\n[w] b" );
+        assert_eq!( lines.tag( b"c\n\nd\n" ), b"c\n[w] \n[w] d\n" );
+    }
+
+    #[test]
+    fn hex() {
+        let mut lines = Lines{ hex: Some(0), ..Lines::default() };
+        assert!( lines.format( b"\r\n" ).starts_with( b"00000000  0d 0a  " ));
+        assert!( lines.format( b"\0" ).starts_with( b"00000002  00  " ));
+    }
+
+    #[test]
+    fn filter() {
+        let filter = Filter( Arc::new( |line: &str| line.contains( "ERROR" )));
+        let mut lines = Lines{ filter: Some( filter.clone() ), ..Lines::default() };
+        assert_eq!( lines.finish( b"ok\nERR" ), b"ok\n" );
+        let finished = lines.finish( b"OR: disk\r\nok\n\nERROR\nERROR" );
+        assert_eq!( select( &filter, &finished ), b"ERROR: disk\r\nERROR\n" );
+        assert_eq!( lines.partial, b"ERROR" );
+    }
+
+    #[test]
+    fn filter_unlocked() {
+        let mirror = Arc::new( Mirror::new( Stream::Out ));
+        mirror.set( true );
+        let reentrant = mirror.clone();
+        mirror.set_filter( Some( Filter( Arc::new( move |line: &str| {
+            reentrant.set_prefix( "> " );
+            assert!( !line.contains( "panic" ));
+            false
+        }))));
+        mirror.write( b"ok\n" );
+        assert!( std::panic::catch_unwind( || mirror.write( b"panic\n" )).is_err() );
+        assert!( !mirror.lines.is_poisoned() );
+        mirror.write( b"ok\n" );
+        mirror.set_filter( None );
+    }
+
+    #[test]
+    #[cfg( feature = "trace-io" )]
+    fn tracing() {
+        use super::MirrorTarget;
+        use std::{fmt::{Debug, Write}, sync::Mutex};
+        use tracing::{Event, Metadata, Subscriber, field::Field, span};
+
+        struct Recorder( Arc<Mutex<Vec<String>>> );
+
+        impl Subscriber for Recorder {
+            fn enabled( &self, metadata: &Metadata<'_> ) -> bool { metadata.target() == "altio::mirror" }
+            fn new_span( &self, _: &span::Attributes<'_> ) -> span::Id { span::Id::from_u64(1) }
+            fn record( &self, _: &span::Id, _: &span::Record<'_> ) {}
+            fn record_follows_from( &self, _: &span::Id, _: &span::Id ) {}
+            fn event( &self, event: &Event<'_> ) {
+                let mut fields = String::new();
+                event.record( &mut |field: &Field, value: &dyn Debug| write!( fields, "{}={value:?} ", field.name() ).unwrap() );
+                self.0.lock().unwrap().push( fields );
+            }
+            fn enter( &self, _: &span::Id ) {}
+            fn exit( &self, _: &span::Id ) {}
+        }
+
+        let events = Arc::new( Mutex::new( Vec::new() ));
+        tracing::subscriber::with_default( Recorder( events.clone() ), || {
+            let mirror = Mirror::new( Stream::Err );
+            mirror.set( true );
+            mirror.set_target( MirrorTarget::Tracing{ session: "db".to_owned() });
+            mirror.write( b"a\r\nb" );
+            mirror.write( b"c\n" );
+        });
+        assert_eq!( *events.lock().unwrap(), [
+            r#"message=a stream="err" session="db" "#,
+            r#"message=bc stream="err" session="db" "#,
+        ]);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!( paint( Stream::Out, b"out\n".to_vec() ), b"out\n" );
+        assert_eq!( paint( Stream::Err, b"err\n".to_vec() ), b"\x1b[31merr\n\x1b[0m" );
+        assert_eq!( paint( Stream::In, b"in\n".to_vec() ), b"\x1b[36min\n\x1b[0m" );
+    }
+}
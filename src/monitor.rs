@@ -0,0 +1,135 @@
+//! A live terminal UI of the sessions in `registry`, with the "monitor" feature.
+//!
+//! When an automation script hangs, `Monitor::start()` takes over the terminal and
+//! shows what each registered tool last printed and what input is still queued, with
+//! the counters of its streams, until the monitor is stopped or dropped.
+//!
+//! ```rust,no_run
+//! use altio::{Altio, monitor::Monitor, registry};
+//! use std::time::Duration;
+//!
+//! let io = Altio::default();
+//! registry::register( "worker-1", io.clone() );
+//! let monitor = Monitor::start( Duration::from_millis( 200 ));
+//! // drive the tool ...
+//! monitor.stop().unwrap();
+//! ```
+
+use crate::{Altio, Stream, registry};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    widgets::{Block, Paragraph},
+};
+
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// A running monitor, which restores the terminal when stopped or dropped.
+#[derive( Debug )]
+pub struct Monitor {
+    stop   : Arc<AtomicBool>,
+    thread : Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Monitor {
+    /// Takes over the terminal in a background thread, redrawing the registered
+    /// sessions every `refresh`.
+    pub fn start( refresh: Duration ) -> Self {
+        let stop = Arc::new( AtomicBool::new( false ));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn( move || {
+                let mut terminal = ratatui::init();
+                let mut result = Ok(());
+                while result.is_ok() && !stop.load( Ordering::Relaxed ) {
+                    result = terminal.draw( |frame| draw( frame, &sessions() )).map( drop );
+                    thread::sleep( refresh );
+                }
+                ratatui::restore();
+                result
+            })
+        };
+        Monitor{ stop, thread: Some( thread )}
+    }
+
+    /// Stops monitoring and restores the terminal, returning the error of drawing if any.
+    pub fn stop( mut self ) -> io::Result<()> { self.halt() }
+
+    fn halt( &mut self ) -> io::Result<()> {
+        self.stop.store( true, Ordering::Relaxed );
+        match self.thread.take() {
+            Some( thread ) => thread.join().unwrap_or_else( |_| Err( io::Error::other( "monitor thread panicked" ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Monitor {
+    fn drop( &mut self ) { let _ = self.halt(); }
+}
+
+/// Returns the registered sessions, by name.
+fn sessions() -> Vec<( String, Altio )> {
+    registry::names().into_iter()
+        .filter_map( |name| registry::lookup( &name ).map( |io| ( name, io )))
+        .collect()
+}
+
+/// Draws `sessions` side by side into `frame`, each one as its input, output and error
+/// streams with the end of their pending text, e.g. for embedding into a custom UI.
+pub fn draw( frame: &mut Frame<'_>, sessions: &[( String, Altio )] ) {
+    let columns = Layout::horizontal( vec![ Constraint::Fill(1); sessions.len() ]).split( frame.area() );
+    for (( name, io ), &column ) in sessions.iter().zip( columns.iter() ) {
+        let panes = Layout::vertical([ Constraint::Fill(1); 3 ]).split( column );
+        for ( stream, &pane ) in [ Stream::In, Stream::Out, Stream::Err ].into_iter().zip( panes.iter() ) {
+            draw_stream( frame, pane, name, io, stream );
+        }
+    }
+}
+
+fn draw_stream( frame: &mut Frame<'_>, area: Rect, name: &str, io: &Altio, stream: Stream ) {
+    let metrics = io.metrics();
+    let metrics = match stream {
+        Stream::In  => metrics.input,
+        Stream::Out => metrics.out,
+        Stream::Err => metrics.err,
+    };
+    let pending = io.pending( stream );
+    let title = format!( " {name} {}: {} pending, {} written ", stream.name(), pending.len(), metrics.bytes );
+    let pending = String::from_utf8_lossy( &pending );
+    let height = area.height.saturating_sub(2) as usize;
+    let lines = pending.lines().collect::<Vec<_>>();
+    let tail = lines[ lines.len().saturating_sub( height ).. ].join( "\n" );
+    frame.render_widget( Paragraph::new( tail ).block( Block::bordered().title( title )), area );
+}
+
+#[cfg( test )]
+mod tests {
+    use super::draw;
+    use crate::{Altio, echo};
+    use ratatui::{Terminal, backend::TestBackend};
+
+    #[test]
+    fn draw_sessions() {
+        let io = Altio::default();
+        io.send_line( "queued" );
+        echo!( io.out(), "1\n2\n3\n4\nlast printed" );
+        std::io::Write::write_all( &mut io.err(), b"\xff\xfe" ).unwrap();
+        let mut terminal = Terminal::new( TestBackend::new( 60, 15 )).unwrap();
+        terminal.draw( |frame| draw( frame, &[( "worker".to_owned(), io.clone() )])).unwrap();
+        let screen = terminal.backend().buffer().content().iter().map( |cell| cell.symbol() ).collect::<String>();
+        assert!( screen.contains( "worker in: 7 pending, 7 written" ));
+        assert!( screen.contains( "queued" ));
+        assert!( screen.contains( "last printed" ));
+        assert!( screen.contains( "worker err: 2 pending, 2 written" ));
+    }
+}
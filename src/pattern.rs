@@ -0,0 +1,378 @@
+//! Patterns found in the output of tools by the expect family of functions, in the
+//! flavors of expect(1): exact text, text ignoring case, globs and regular expressions.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    ops::Range,
+};
+
+/// How a `Pattern` matches text.
+#[derive( Clone, Copy, Debug, Default, PartialEq, Eq, Hash )]
+pub enum MatchKind {
+    /// The exact text.
+    #[default]
+    Exact,
+    /// The text ignoring case, comparing the lowercase forms of chars.
+    CaseInsensitive,
+    /// A glob, where `*` matches any text, `?` any char, and `\` escapes the char
+    /// following it. Each `*` matches as little text as possible, so that the match
+    /// doesn't depend on how much text the tool has written so far.
+    Glob,
+    /// A regular expression. With the "regex" feature.
+    Regex,
+}
+
+/// A compiled pattern, found anywhere in a text.
+///
+/// ```rust
+/// use altio::{MatchKind, Pattern};
+///
+/// let pattern = Pattern::new( "listening on port ?*\n", MatchKind::Glob ).unwrap();
+/// assert_eq!( pattern.find( "Server listening on port 8080\nready\n" ), Some( 7..30 ));
+/// let pattern = Pattern::new( "error", MatchKind::CaseInsensitive ).unwrap();
+/// assert_eq!( pattern.find( "FATAL ERROR" ), Some( 6..11 ));
+/// ```
+#[derive( Clone, Debug )]
+pub struct Pattern {
+    source  : String,
+    kind    : MatchKind,
+    matcher : Matcher,
+}
+
+#[derive( Clone, Debug )]
+enum Matcher {
+    Exact,
+    CaseInsensitive( Vec<char> ), // the lowercase chars of the pattern
+    Glob( Vec<Token> ),
+    #[cfg( feature = "regex" )]
+    Regex( regex::Regex ),
+}
+
+/// An element of a glob.
+#[derive( Clone, Copy, Debug, PartialEq, Eq )]
+enum Token {
+    Star,
+    Any,
+    Char( char ),
+}
+
+impl Pattern {
+    /// Compiles `source` as a pattern of `kind`, failing with `InvalidInput` on an
+    /// invalid regular expression, or `Unsupported` on one without the "regex" feature.
+    pub fn new( source: &str, kind: MatchKind ) -> Result<Self> {
+        let matcher = match kind {
+            MatchKind::Exact => Matcher::Exact,
+            MatchKind::CaseInsensitive => Matcher::CaseInsensitive( source.chars().flat_map( char::to_lowercase ).collect() ),
+            MatchKind::Glob => Matcher::Glob( glob( source )),
+            #[cfg( feature = "regex" )]
+            MatchKind::Regex => Matcher::Regex( regex::Regex::new( source ).map_err( |err| Error::new( ErrorKind::InvalidInput, err ))? ),
+            #[cfg( not( feature = "regex" ))]
+            MatchKind::Regex => return Err( Error::new( ErrorKind::Unsupported, "regular expressions require the \"regex\" feature" )),
+        };
+        Ok( Pattern{ source: source.to_owned(), kind, matcher })
+    }
+
+    /// A pattern matching `text` exactly.
+    pub fn exact( text: &str ) -> Self {
+        Pattern{ source: text.to_owned(), kind: MatchKind::Exact, matcher: Matcher::Exact }
+    }
+
+    /// Returns the source of the pattern.
+    pub fn as_str( &self ) -> &str { &self.source }
+
+    /// Returns how the pattern matches text.
+    pub fn kind( &self ) -> MatchKind { self.kind }
+
+    /// Returns the byte range of the first match in `text`.
+    pub fn find( &self, text: &str ) -> Option<Range<usize>> {
+        match &self.matcher {
+            Matcher::Exact => text.find( &self.source ).map( |start| start..start + self.source.len() ),
+            Matcher::CaseInsensitive( lower ) => starts( text ).find_map( |start| nocase_end( lower, text, start ).map( |end| start..end )),
+            Matcher::Glob( tokens ) => starts( text ).find_map( |start| glob_end( tokens, text, start ).map( |end| start..end )),
+            #[cfg( feature = "regex" )]
+            Matcher::Regex( regex ) => regex.find( text ).map( |found| found.range() ),
+        }
+    }
+
+    /// Returns the most bytes of text a match may span, or `None` if unbounded.
+    pub(crate) fn max_len( &self ) -> Option<usize> {
+        match &self.matcher {
+            Matcher::Exact => Some( self.source.len() ),
+            Matcher::CaseInsensitive( lower ) => Some( lower.len() * 4 ),
+            Matcher::Glob( tokens ) => tokens.iter().map( |token| match token {
+                Token::Star => None,
+                Token::Any => Some(4),
+                Token::Char( ch ) => Some( ch.len_utf8() ),
+            }).sum(),
+            #[cfg( feature = "regex" )]
+            Matcher::Regex( _ ) => None,
+        }
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from( text: &str ) -> Self { Pattern::exact( text )}
+}
+
+/// Finds the first of several patterns in text arriving in chunks, e.g. the writes of
+/// a tool, keeping the text not matched yet in a rolling window so that matches split
+/// across chunks are found.
+///
+/// Matches are reported as soon as they are complete, so a pattern ending with a
+/// repetition, e.g. the regular expression `[0-9]+`, may match less than the tool
+/// writes later: end such patterns with a delimiter. With a lookbehind, the window
+/// keeps at most the last `lookbehind` bytes not matched, bounding memory but missing
+/// longer matches.
+///
+/// ```rust
+/// use altio::{MatchKind, Pattern, StreamMatcher};
+///
+/// let mut matcher = StreamMatcher::new( vec![
+///     Pattern::new( "port ?*\n", MatchKind::Glob ).unwrap(),
+///     Pattern::exact( "error" ),
+/// ]).lookbehind( 4096 );
+/// assert_eq!( matcher.push( "starting...\nlistening on po" ), None );
+/// assert_eq!( matcher.push( "rt 80" ), None );
+/// assert_eq!( matcher.push( "80\nready\n" ), Some(( 0, "starting...\nlistening on port 8080\n".to_owned() )));
+/// assert_eq!( matcher.pending(), "ready\n" );
+/// ```
+#[derive( Clone, Debug )]
+pub struct StreamMatcher {
+    patterns   : Vec<Pattern>,
+    window     : String,
+    lookbehind : Option<usize>,
+    discarded  : usize,
+}
+
+impl StreamMatcher {
+    /// Creates a matcher of `patterns`, with an unbounded window.
+    pub fn new( patterns: Vec<Pattern> ) -> Self {
+        StreamMatcher{ patterns, window: String::new(), lookbehind: None, discarded: 0 }
+    }
+
+    /// Keeps at most the last `bytes` of the text not matched, or a little more to
+    /// avoid splitting a char.
+    pub fn lookbehind( mut self, bytes: usize ) -> Self {
+        self.lookbehind = Some( bytes );
+        self.trim();
+        self
+    }
+
+    /// Appends `chunk` to the window, then returns the index of the pattern matching
+    /// earliest in it, the first listed on ties, with the text of the window up to
+    /// and including the match, which is removed from the window. Pushing an empty
+    /// chunk looks for another match in the rest.
+    pub fn push( &mut self, chunk: &str ) -> Option<( usize, String )> {
+        self.window.push_str( chunk );
+        match first_match( &self.patterns, &self.window ) {
+            Some(( index, end )) => Some(( index, self.window.drain( ..end ).collect() )),
+            None => {
+                self.trim();
+                None
+            }
+        }
+    }
+
+    /// Returns the text in the window, not matched yet.
+    pub fn pending( &self ) -> &str { &self.window }
+
+    /// Returns the amount of bytes dropped out of the window by the lookbehind.
+    pub fn discarded( &self ) -> usize { self.discarded }
+
+    fn trim( &mut self ) {
+        if let Some( lookbehind ) = self.lookbehind {
+            let cut = floor_boundary( &self.window, self.window.len().saturating_sub( lookbehind ));
+            self.window.drain( ..cut );
+            self.discarded += cut;
+        }
+    }
+}
+
+/// Returns the index of the pattern matching earliest in `text`, the first listed on
+/// ties, with the end of its match.
+pub(crate) fn first_match( patterns: &[Pattern], text: &str ) -> Option<( usize, usize )> {
+    patterns.iter().enumerate()
+        .filter_map( |( index, pattern )| pattern.find( text ).map( |found| ( found.start, index, found.end )))
+        .min()
+        .map( |( _, index, end )| ( index, end ))
+}
+
+/// Returns the largest char boundary of `text` not greater than `offset`.
+fn floor_boundary( text: &str, mut offset: usize ) -> usize {
+    while !text.is_char_boundary( offset ) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// A match of a regular expression in the output of a tool, received by
+/// `Altio::expect_regex()`. With the "regex" feature.
+#[cfg( feature = "regex" )]
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub struct Match {
+    before : String,
+    groups : Vec<( Option<String>, Option<String> )>, // the name and text of each group
+}
+
+#[cfg( feature = "regex" )]
+impl Match {
+    /// Creates the match of `captures` by `regex`, preceded by `before`.
+    pub(crate) fn new( before: String, regex: &regex::Regex, captures: &regex::Captures<'_> ) -> Self {
+        let groups = regex.capture_names()
+            .zip( captures.iter() )
+            .map( |( name, group )| ( name.map( str::to_owned ), group.map( |group| group.as_str().to_owned() )))
+            .collect();
+        Match{ before, groups }
+    }
+
+    /// Returns the text received before the match.
+    pub fn before( &self ) -> &str { &self.before }
+
+    /// Returns the text matched.
+    pub fn as_str( &self ) -> &str { self.get(0).unwrap_or_default() }
+
+    /// Returns the text of the capture group numbered `index`, 0 for the whole match,
+    /// or `None` if the group didn't participate in the match.
+    pub fn get( &self, index: usize ) -> Option<&str> {
+        self.groups.get( index )?.1.as_deref()
+    }
+
+    /// Returns the text of the capture group named `name`, or `None` if the group
+    /// didn't participate in the match.
+    pub fn name( &self, name: &str ) -> Option<&str> {
+        self.groups.iter().find( |( group, _ )| group.as_deref() == Some( name ))?.1.as_deref()
+    }
+}
+
+/// Returns the source of a regular expression matching like `source` of `kind`.
+#[cfg( feature = "regex" )]
+pub(crate) fn to_regex( source: &str, kind: MatchKind ) -> String {
+    match kind {
+        MatchKind::Exact => regex::escape( source ),
+        MatchKind::CaseInsensitive => format!( "(?i){}", regex::escape( source )),
+        MatchKind::Glob => glob( source ).into_iter().map( |token| match token {
+            Token::Star => "(?s:.*?)".to_owned(),
+            Token::Any => "(?s:.)".to_owned(),
+            Token::Char( ch ) => regex::escape( ch.encode_utf8( &mut [0; 4] )),
+        }).collect(),
+        MatchKind::Regex => source.to_owned(),
+    }
+}
+
+/// Parses a glob, a trailing backslash matching itself.
+fn glob( source: &str ) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars();
+    while let Some( ch ) = chars.next() {
+        let token = match ch {
+            '*' => Token::Star,
+            '?' => Token::Any,
+            '\\' => Token::Char( chars.next().unwrap_or( '\\' )),
+            _ => Token::Char( ch ),
+        };
+        if !( token == Token::Star && tokens.last() == Some( &Token::Star )) {
+            tokens.push( token );
+        }
+    }
+    tokens
+}
+
+/// Returns the offsets where a match may start in `text`, i.e. char boundaries.
+fn starts( text: &str ) -> impl Iterator<Item=usize> + '_ {
+    text.char_indices().map( |( offset, _ )| offset ).chain( Some( text.len() ))
+}
+
+/// Returns the end of the text at `start` of `text` whose lowercase form is `lower`.
+fn nocase_end( lower: &[char], text: &str, start: usize ) -> Option<usize> {
+    let mut rest = lower;
+    for ( offset, ch ) in text[ start.. ].char_indices() {
+        if rest.is_empty() {
+            return Some( start + offset );
+        }
+        for ch in ch.to_lowercase() {
+            rest = rest.strip_prefix( &[ ch ])?;
+        }
+    }
+    rest.is_empty().then_some( text.len() )
+}
+
+/// Returns the end of the match of `tokens` at `start` of `text`, each star matching
+/// as little as possible.
+fn glob_end( tokens: &[Token], text: &str, start: usize ) -> Option<usize> {
+    let Some(( token, rest )) = tokens.split_first() else { return Some( start )};
+    let next = text[ start.. ].chars().next();
+    match token {
+        Token::Star => starts( &text[ start.. ] ).find_map( |offset| glob_end( rest, text, start + offset )),
+        Token::Any => next.and_then( |ch| glob_end( rest, text, start + ch.len_utf8() )),
+        Token::Char( expected ) => next.filter( |ch| ch == expected ).and_then( |ch| glob_end( rest, text, start + ch.len_utf8() )),
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{MatchKind, Pattern, StreamMatcher};
+
+    fn find( source: &str, kind: MatchKind, text: &str ) -> Option<std::ops::Range<usize>> {
+        Pattern::new( source, kind ).unwrap().find( text )
+    }
+
+    #[test]
+    fn find_kinds() {
+        assert_eq!( find( "b", MatchKind::Exact, "abcb" ), Some( 1..2 ));
+        assert_eq!( find( "B", MatchKind::Exact, "abc" ), None );
+
+        assert_eq!( find( "straße", MatchKind::CaseInsensitive, "STRAßE" ), Some( 0..7 ));
+        assert_eq!( find( "İ", MatchKind::CaseInsensitive, "i̇" ), Some( 0..3 ));
+        assert_eq!( find( "", MatchKind::CaseInsensitive, "" ), Some( 0..0 ));
+
+        assert_eq!( find( "a*c", MatchKind::Glob, "xabcbc" ), Some( 1..4 ));
+        assert_eq!( find( "a?c", MatchKind::Glob, "a\nc" ), Some( 0..3 ));
+        assert_eq!( find( "\\*?*", MatchKind::Glob, "a*b" ), Some( 1..3 ));
+        assert_eq!( find( "x*", MatchKind::Glob, "ab" ), None );
+    }
+
+    #[test]
+    fn split_across_chunks() {
+        for kind in [ MatchKind::Exact, MatchKind::CaseInsensitive, MatchKind::Glob ] {
+            let mut matcher = StreamMatcher::new( vec![ Pattern::new( "Done", kind ).unwrap() ]);
+            assert_eq!( matcher.push( "...D" ), None );
+            assert_eq!( matcher.push( "o" ), None );
+            assert_eq!( matcher.push( "ne!" ), Some(( 0, "...Done".to_owned() )), "{kind:?}" );
+            assert_eq!( matcher.pending(), "!" );
+        }
+
+        let mut matcher = StreamMatcher::new( vec![ "abc".into(), "é".into() ]).lookbehind( 2 );
+        assert_eq!( matcher.push( "xxéa" ), Some(( 1, "xxé".to_owned() )));
+        assert_eq!( matcher.push( "b" ), None );
+        assert_eq!( matcher.push( "c" ), Some(( 0, "abc".to_owned() )));
+        assert_eq!( matcher.push( "yyÿz" ), None );
+        assert_eq!(( matcher.pending(), matcher.discarded() ), ( "ÿz", 2 ));
+        assert_eq!( matcher.push( "ab" ), None );
+        assert_eq!( matcher.push( "c" ), Some(( 0, "abc".to_owned() )));
+        assert_eq!( matcher.discarded(), 5 );
+    }
+
+    #[test]
+    #[cfg( feature = "regex" )]
+    fn regex() {
+        assert_eq!( find( "[0-9]+", MatchKind::Regex, "port 80" ), Some( 5..7 ));
+        assert_eq!( Pattern::new( "(", MatchKind::Regex ).unwrap_err().kind(), std::io::ErrorKind::InvalidInput );
+
+        let regex = regex::Regex::new( r"(?<key>\w+)=(\d+)?(?<unit>ms)?" ).unwrap();
+        let captures = regex.captures( "timeout=ms" ).unwrap();
+        let found = super::Match::new( "> ".to_owned(), &regex, &captures );
+        assert_eq!(( found.before(), found.as_str() ), ( "> ", "timeout=ms" ));
+        assert_eq!(( found.get(1), found.get(2), found.get(3), found.get(4) ), ( Some( "timeout" ), None, Some( "ms" ), None ));
+        assert_eq!(( found.name( "key" ), found.name( "unit" ), found.name( "none" )), ( Some( "timeout" ), Some( "ms" ), None ));
+        for ( source, kind ) in [( "a.b", MatchKind::Exact ), ( "A.b", MatchKind::CaseInsensitive ), ( "a*b?", MatchKind::Glob )] {
+            let regex = Pattern::new( &super::to_regex( source, kind ), MatchKind::Regex ).unwrap();
+            assert_eq!( regex.find( "xa.bab\n" ), find( source, kind, "xa.bab\n" ));
+        }
+    }
+
+    #[test]
+    #[cfg( not( feature = "regex" ))]
+    fn regex_unsupported() {
+        assert_eq!( Pattern::new( "a", MatchKind::Regex ).unwrap_err().kind(), std::io::ErrorKind::Unsupported );
+    }
+}
@@ -0,0 +1,62 @@
+//! Snapshots of the lines pending in an output stream, shared by peeking threads.
+
+use crate::buffer::Buffer;
+
+/// The first lines pending in an output stream at a given version of it, so that
+/// peeks read them under a shared lock instead of locking the stream.
+#[derive( Debug, Default )]
+pub(crate) struct Snapshot {
+    version  : u64,
+    lines    : Vec<String>,
+    complete : bool, // no more lines pending
+}
+
+impl Snapshot {
+    /// Copies at most `cnt` lines pending in `buf`, whose version is `version`.
+    pub(crate) fn take( buf: &mut Buffer, version: u64, cnt: usize ) -> Self {
+        let mut end = 0;
+        let mut found = 0;
+        while found < cnt {
+            match buf.find( end, b'\n' ) {
+                Some( offset ) => end = offset + 1,
+                None => break,
+            }
+            found += 1;
+        }
+        let lines = buf.copy_string( end ).split_inclusive( '\n' ).map( str::to_owned ).collect::<Vec<_>>();
+        let complete = found < cnt;
+        Snapshot{ version, lines, complete }
+    }
+
+    /// Returns the first `cnt` lines joined if the snapshot is of `version` and knows
+    /// them, `Some(None)` if it knows there are less of them.
+    pub(crate) fn get( &self, version: u64, cnt: usize ) -> Option<Option<String>> {
+        if self.version != version {
+            None
+        } else if self.lines.len() >= cnt {
+            Some( Some( self.lines[ ..cnt ].concat() ))
+        } else {
+            self.complete.then_some( None )
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::Snapshot;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn snapshot() {
+        let mut buf = Buffer::default();
+        buf.push( b"a\nb\nc" );
+        let snapshot = Snapshot::take( &mut buf, 1, 1 );
+        assert_eq!( snapshot.get( 1, 1 ), Some( Some( "a\n".to_owned() )));
+        assert_eq!( snapshot.get( 1, 2 ), None );
+        assert_eq!( snapshot.get( 2, 1 ), None );
+        let snapshot = Snapshot::take( &mut buf, 1, 3 );
+        assert_eq!( snapshot.get( 1, 2 ), Some( Some( "a\nb\n".to_owned() )));
+        assert_eq!( snapshot.get( 1, 3 ), Some( None ));
+        assert_eq!( buf.len(), 5 );
+    }
+}
@@ -0,0 +1,307 @@
+//! A tiny control protocol for tools willing to tell drivers about their prompts,
+//! instead of drivers scraping the rendered text.
+//!
+//! A tool calls `Altio::emit_prompt()`, which writes a control line into the output
+//! stream: the `CONTROL` char followed by the prompt as a JSON object and a newline,
+//! e.g. `\x1f{"kind":"confirm","question":"Overwrite?","default":false}`. A driver
+//! receives it as a typed `PromptEvent` by `Altio::recv_prompt()`. Attached to real
+//! stdio, or without the "altio" feature, the prompt is rendered as plain text
+//! instead, see `Prompt::render()`.
+//!
+//! ```rust
+//! # #[cfg( feature = "altio" )] {
+//! use altio::{Altio, Prompt, echo};
+//!
+//! let io = Altio::default();
+//! echo!( io.out(), "found old.txt" );
+//! io.emit_prompt( &Prompt::Confirm{ question: "Overwrite?".to_owned(), default: Some( false )}).unwrap();
+//!
+//! let event = io.recv_prompt();
+//! assert_eq!( event.text, "found old.txt\n" );
+//! assert_eq!( event.prompt, Prompt::Confirm{ question: "Overwrite?".to_owned(), default: Some( false )});
+//! # }
+//! ```
+
+use std::fmt::Write;
+
+/// The char beginning a control line.
+pub const CONTROL: char = '\x1f';
+
+/// A prompt of a tool, waiting for an answer line in its input.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub enum Prompt {
+    /// A yes/no question, with the answer taken for an empty line if any.
+    Confirm {
+        /// The question.
+        question : String,
+        /// The answer taken for an empty line.
+        default  : Option<bool>,
+    },
+    /// A line of free text.
+    Input {
+        /// What to enter.
+        label   : String,
+        /// The text taken for an empty line.
+        default : Option<String>,
+    },
+    /// A line of secret text, not echoed.
+    Password {
+        /// What to enter.
+        label : String,
+    },
+    /// One of the options, answered by its number beginning with 1.
+    Select {
+        /// The question.
+        question : String,
+        /// The options.
+        options  : Vec<String>,
+    },
+}
+
+/// A prompt received by the driver, with the plain text written before it.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub struct PromptEvent {
+    /// The text written before the prompt.
+    pub text   : String,
+    /// The prompt.
+    pub prompt : Prompt,
+}
+
+impl Prompt {
+    /// Returns the plain text of the prompt for human users, e.g. `"Overwrite? [y/N] "`.
+    pub fn render( &self ) -> String {
+        match self {
+            Prompt::Confirm{ question, default } => format!( "{question} {} ", match default {
+                Some( true  ) => "[Y/n]",
+                Some( false ) => "[y/N]",
+                None          => "[y/n]",
+            }),
+            Prompt::Input{ label, default: Some( default )} => format!( "{label} [{default}]: " ),
+            Prompt::Input{ label, default: None } | Prompt::Password{ label } => format!( "{label}: " ),
+            Prompt::Select{ question, options } => {
+                let mut text = String::new();
+                for ( no, option ) in options.iter().enumerate() {
+                    let _ = writeln!( text, "{}) {option}", no+1 );
+                }
+                text + question + " "
+            }
+        }
+    }
+
+    /// Returns the control line of the prompt, ending with a newline.
+    pub fn to_control_line( &self ) -> String {
+        let mut line = String::from( CONTROL );
+        match self {
+            Prompt::Confirm{ question, default } => {
+                line.push_str( "{\"kind\":\"confirm\",\"question\":" );
+                quote( &mut line, question );
+                line.push_str( ",\"default\":" );
+                line.push_str( match default { Some( true ) => "true", Some( false ) => "false", None => "null" });
+            }
+            Prompt::Input{ label, default } => {
+                line.push_str( "{\"kind\":\"input\",\"label\":" );
+                quote( &mut line, label );
+                line.push_str( ",\"default\":" );
+                match default {
+                    Some( default ) => quote( &mut line, default ),
+                    None => line.push_str( "null" ),
+                }
+            }
+            Prompt::Password{ label } => {
+                line.push_str( "{\"kind\":\"password\",\"label\":" );
+                quote( &mut line, label );
+            }
+            Prompt::Select{ question, options } => {
+                line.push_str( "{\"kind\":\"select\",\"question\":" );
+                quote( &mut line, question );
+                line.push_str( ",\"options\":[" );
+                for ( i, option ) in options.iter().enumerate() {
+                    if i > 0 {
+                        line.push( ',' );
+                    }
+                    quote( &mut line, option );
+                }
+                line.push( ']' );
+            }
+        }
+        line.push_str( "}\n" );
+        line
+    }
+
+    /// Parses a control line, with or without the trailing newline, returning `None`
+    /// if it is malformed.
+    pub fn from_control_line( line: &str ) -> Option<Self> {
+        let json = line.strip_prefix( CONTROL )?.trim_end_matches( ['\n', '\r'] );
+        let mut parser = Parser( json.chars().peekable() );
+        let fields = parser.object()?;
+        parser.0.next().is_none().then_some(())?;
+        let text = |key: &str| fields.iter().find( |( k, _ )| k == key ).and_then( |( _, value )| match value {
+            Value::Text( text ) => Some( text.clone() ),
+            _ => None,
+        });
+        let value = |key: &str| fields.iter().find( |( k, _ )| k == key ).map( |( _, value )| value );
+        match text( "kind" )?.as_str() {
+            "confirm" => Some( Prompt::Confirm {
+                question : text( "question" )?,
+                default  : match value( "default" ) {
+                    Some( Value::Bool( default )) => Some( *default ),
+                    Some( Value::Null ) | None => None,
+                    _ => return None,
+                },
+            }),
+            "input" => Some( Prompt::Input{ label: text( "label" )?, default: text( "default" )}),
+            "password" => Some( Prompt::Password{ label: text( "label" )? }),
+            "select" => Some( Prompt::Select {
+                question : text( "question" )?,
+                options  : match value( "options" )? {
+                    Value::List( options ) => options.clone(),
+                    _ => return None,
+                },
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Appends `text` as a JSON string.
+fn quote( line: &mut String, text: &str ) {
+    line.push( '"' );
+    for ch in text.chars() {
+        match ch {
+            '"'  => line.push_str( "\\\"" ),
+            '\\' => line.push_str( "\\\\" ),
+            '\n' => line.push_str( "\\n" ),
+            '\r' => line.push_str( "\\r" ),
+            '\t' => line.push_str( "\\t" ),
+            ch if ch.is_control() => { let _ = write!( line, "\\u{:04x}", ch as u32 ); }
+            ch => line.push( ch ),
+        }
+    }
+    line.push( '"' );
+}
+
+/// The values of the fields of a prompt.
+enum Value {
+    Text( String ),
+    Bool( bool ),
+    Null,
+    List( Vec<String> ),
+}
+
+/// Parses the flat JSON objects of control lines, whose values are strings, booleans,
+/// nulls or arrays of strings.
+struct Parser<'a>( std::iter::Peekable<std::str::Chars<'a>> );
+
+impl Parser<'_> {
+    fn skip_space( &mut self ) {
+        while self.0.next_if( |ch| ch.is_ascii_whitespace() ).is_some() {}
+    }
+
+    fn expect( &mut self, expected: char ) -> Option<()> {
+        self.skip_space();
+        ( self.0.next()? == expected ).then_some(())
+    }
+
+    /// Parses the items between `open` and `close`, separated by commas.
+    fn separated<T>( &mut self, open: char, close: char, mut item: impl FnMut( &mut Self ) -> Option<T> ) -> Option<Vec<T>> {
+        self.expect( open )?;
+        let mut items = Vec::new();
+        self.skip_space();
+        if self.0.next_if_eq( &close ).is_some() {
+            return Some( items );
+        }
+        loop {
+            items.push( item( self )? );
+            self.skip_space();
+            match self.0.next()? {
+                ',' => (),
+                ch if ch == close => return Some( items ),
+                _ => return None,
+            }
+        }
+    }
+
+    fn object( &mut self ) -> Option<Vec<( String, Value )>> {
+        self.separated( '{', '}', |parser| {
+            let key = parser.string()?;
+            parser.expect( ':' )?;
+            Some(( key, parser.value()? ))
+        })
+    }
+
+    fn value( &mut self ) -> Option<Value> {
+        self.skip_space();
+        match *self.0.peek()? {
+            '"' => self.string().map( Value::Text ),
+            '[' => self.separated( '[', ']', Self::string ).map( Value::List ),
+            _ => {
+                let mut word = String::new();
+                while let Some( ch ) = self.0.next_if( char::is_ascii_alphabetic ) {
+                    word.push( ch );
+                }
+                match word.as_str() {
+                    "true"  => Some( Value::Bool( true )),
+                    "false" => Some( Value::Bool( false )),
+                    "null"  => Some( Value::Null ),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn string( &mut self ) -> Option<String> {
+        self.expect( '"' )?;
+        let mut text = String::new();
+        loop {
+            match self.0.next()? {
+                '"' => return Some( text ),
+                '\\' => text.push( match self.0.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex = ( 0..4 ).map( |_| self.0.next() ).collect::<Option<String>>()?;
+                        char::from_u32( u32::from_str_radix( &hex, 16 ).ok()? )?
+                    }
+                    ch => ch,
+                }),
+                ch => text.push( ch ),
+            }
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_lines() {
+        let prompts = [
+            Prompt::Confirm{ question: "Go \"on\"?".to_owned(), default: None },
+            Prompt::Input{ label: "Name\t\u{1f}".to_owned(), default: Some( "\u{4e2d}".to_owned() )},
+            Prompt::Password{ label: "Password".to_owned() },
+            Prompt::Select{ question: "Which?".to_owned(), options: vec![ "a\\b".to_owned(), "c\n".to_owned() ]},
+            Prompt::Select{ question: "None?".to_owned(), options: vec![] },
+        ];
+        for prompt in prompts {
+            let line = prompt.to_control_line();
+            assert_eq!( line.matches( CONTROL ).count(), 1 );
+            assert_eq!( line.matches( '\n' ).count(), 1 );
+            assert_eq!( Prompt::from_control_line( &line ), Some( prompt ));
+        }
+        assert_eq!( Prompt::from_control_line( "\u{1f}{ \"label\" : \"x\", \"kind\" : \"password\" }" ),
+            Some( Prompt::Password{ label: "x".to_owned() }));
+        assert_eq!( Prompt::from_control_line( "\u{1f}{\"kind\":\"password\"}" ), None );
+        assert_eq!( Prompt::from_control_line( "\u{1f}{\"kind\":\"confirm\",\"question\":\"q\"}x" ), None );
+        assert_eq!( Prompt::from_control_line( "{\"kind\":\"password\",\"label\":\"x\"}" ), None );
+    }
+
+    #[test]
+    fn render() {
+        assert_eq!( Prompt::Confirm{ question: "Overwrite?".to_owned(), default: Some( false )}.render(), "Overwrite? [y/N] " );
+        assert_eq!( Prompt::Input{ label: "Name".to_owned(), default: Some( "Bob".to_owned() )}.render(), "Name [Bob]: " );
+        assert_eq!( Prompt::Select{ question: "Which?".to_owned(), options: vec![ "a".to_owned(), "b".to_owned() ]}.render(),
+            "1) a\n2) b\nWhich? " );
+    }
+}
@@ -0,0 +1,133 @@
+//! Property-based testing helpers, with the "proptest" feature.
+//!
+//! The strategies generate input of random line lengths and unicode content, sent
+//! in random pieces. `run_tool()` feeds the pieces to a tool running in another
+//! thread, and `check_lines()` verifies that no byte is lost and no line is torn.
+//!
+//! ```rust
+//! use altio::{echo, prop::{check_lines, run_tool, sends}};
+//! use proptest::prelude::*;
+//!
+//! proptest!( |( sends in sends() )| {
+//!     let cnt = sends.lines.len();
+//!     let io = run_tool( &sends.pieces, move |io| {
+//!         for _ in 0..cnt {
+//!             let mut buf = String::new();
+//!             io.input().read_line( &mut buf ).unwrap();
+//!             echo!( -n, io.out(), "{buf}" );
+//!         }
+//!     });
+//!     check_lines( &sends.lines, &io.try_recv().unwrap_or_default() )?;
+//! });
+//! ```
+
+use crate::Altio;
+
+use proptest::{
+    collection::vec,
+    prelude::*,
+    sample::Index,
+    test_runner::TestCaseError,
+};
+
+/// Lines of input and the pieces they are sent in.
+#[derive( Clone, Debug )]
+pub struct Sends {
+    /// The lines, each ending with a newline.
+    pub lines  : Vec<String>,
+    /// The concatenated lines split at random char boundaries, none of them empty.
+    pub pieces : Vec<String>,
+}
+
+/// Generates a line of at most 80 arbitrary chars, ending with a newline.
+pub fn line() -> impl Strategy<Value = String> {
+    "[^\n]{0,80}".prop_map( |line| line + "\n" )
+}
+
+/// Generates at most 32 lines.
+pub fn lines() -> impl Strategy<Value = Vec<String>> {
+    vec( line(), 0..32 )
+}
+
+/// Generates lines and splits them into pieces to send.
+pub fn sends() -> impl Strategy<Value = Sends> {
+    ( lines(), vec( any::<Index>(), 0..16 )).prop_map( |( lines, cuts )| {
+        let text = lines.concat();
+        let mut cuts = cuts.iter()
+            .map( |cut| {
+                let mut offset = cut.index( text.len() + 1 );
+                while !text.is_char_boundary( offset ) {
+                    offset -= 1;
+                }
+                offset
+            })
+            .collect::<Vec<_>>();
+        cuts.push( text.len() );
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let mut start = 0;
+        let mut pieces = Vec::with_capacity( cuts.len() );
+        for end in cuts {
+            if end > start {
+                pieces.push( text[ start..end ].to_owned() );
+                start = end;
+            }
+        }
+        Sends{ lines, pieces }
+    })
+}
+
+/// Runs `tool` in a new thread while sending `pieces` to its input one by one, and
+/// returns the `Altio` after the tool finishes. Panics of the tool are propagated.
+pub fn run_tool( pieces: &[String], tool: impl FnOnce( Altio ) + Send + 'static ) -> Altio {
+    let io = Altio::default();
+    let handle = {
+        let io = io.clone();
+        std::thread::spawn( move || tool( io ))
+    };
+    for piece in pieces {
+        io.send( piece );
+        std::thread::yield_now();
+    }
+    if let Err( panic ) = handle.join() {
+        std::panic::resume_unwind( panic );
+    }
+    io
+}
+
+/// Checks that `received` consists of exactly the `expected` lines, in order.
+pub fn check_lines( expected: &[String], received: &str ) -> Result<(), TestCaseError> {
+    let received = received.split_inclusive( '\n' ).collect::<Vec<_>>();
+    for ( nth, ( expected, received )) in expected.iter().zip( &received ).enumerate() {
+        prop_assert_eq!( expected, received, "line {} is torn", nth );
+    }
+    prop_assert_eq!( expected.len(), received.len(), "lines are lost or added" );
+    Ok(())
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn pieces_cover_lines( sends in sends() ) {
+            prop_assert!( sends.pieces.iter().all( |piece| !piece.is_empty() ));
+            prop_assert_eq!( sends.pieces.concat(), sends.lines.concat() );
+        }
+
+        #[test]
+        fn read_lines( sends in sends() ) {
+            let cnt = sends.lines.len();
+            let io = run_tool( &sends.pieces, move |io| {
+                for _ in 0..cnt {
+                    let mut buf = String::new();
+                    io.input().read_line( &mut buf ).unwrap();
+                    io.out().push_str( &buf );
+                }
+            });
+            check_lines( &sends.lines, &io.try_recv().unwrap_or_default() )?;
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Notifying the readers waiting for a stream, blocked threads as well as pending
+//! futures.
+
+use std::{
+    mem,
+    sync::{Condvar, LockResult, Mutex, MutexGuard, PoisonError, WaitTimeoutResult},
+    task::Waker,
+    time::Duration,
+};
+
+#[derive( Debug, Default )]
+pub(crate) struct Ready {
+    condvar : Condvar,
+    wakers  : Mutex<Vec<Waker>>,
+}
+
+impl Ready {
+    /// Wakes up all blocked threads and pending futures.
+    pub(crate) fn notify_all( &self ) {
+        self.condvar.notify_all();
+        let wakers = mem::take( &mut *self.wakers.lock().unwrap_or_else( PoisonError::into_inner ));
+        wakers.into_iter().for_each( Waker::wake );
+    }
+
+    /// Blocks the current thread until notified or `timeout` elapsed.
+    pub(crate) fn wait_timeout<'a,T>( &self, guard: MutexGuard<'a,T>, timeout: Duration )
+        -> LockResult<( MutexGuard<'a,T>, WaitTimeoutResult )>
+    {
+        self.condvar.wait_timeout( guard, timeout )
+    }
+
+    /// Wakes `waker` on the next notification. Registered before checking the stream,
+    /// so that no notification in between is missed.
+    #[cfg( feature = "altio" )]
+    pub(crate) fn register( &self, waker: &Waker ) {
+        let mut wakers = self.wakers.lock().unwrap_or_else( PoisonError::into_inner );
+        if !wakers.iter().any( |registered| registered.will_wake( waker )) {
+            wakers.push( waker.clone() );
+        }
+    }
+}
+
+#[cfg( all( test, feature = "altio" ))]
+mod tests {
+    use super::*;
+    use std::{sync::{Arc, atomic::{AtomicUsize, Ordering}}, task::Wake};
+
+    struct Counter( AtomicUsize );
+
+    impl Wake for Counter {
+        fn wake( self: Arc<Self> ) { self.0.fetch_add( 1, Ordering::Relaxed ); }
+    }
+
+    #[test]
+    fn wakers() {
+        let counter = Arc::new( Counter( AtomicUsize::new( 0 )));
+        let waker = Waker::from( counter.clone() );
+        let ready = Ready::default();
+        ready.register( &waker );
+        ready.register( &waker );
+        ready.notify_all();
+        ready.notify_all();
+        assert_eq!( counter.0.load( Ordering::Relaxed ), 1 );
+    }
+}
@@ -0,0 +1,68 @@
+//! Named sessions, so that separately initialized parts of an application, e.g.
+//! plugins, test harnesses or admin endpoints, can locate and drive an embedded tool
+//! without threading its `Altio` everywhere.
+//!
+//! ```rust
+//! use altio::{Altio, registry};
+//!
+//! let io = Altio::default();
+//! registry::register( "worker-1", io.clone() );
+//!
+//! let worker = registry::lookup( "worker-1" ).unwrap();
+//! worker.send_line( "status" );
+//! assert_eq!( io.input().lines().next().as_deref(), Some( "status\n" ));
+//! assert!( registry::unregister( "worker-1" ).is_some() );
+//! ```
+
+use crate::Altio;
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+fn sessions() -> MutexGuard<'static, HashMap<String, Altio>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<String, Altio>>> = OnceLock::new();
+    let sessions = SESSIONS.get_or_init( Mutex::default );
+    sessions.lock().unwrap_or_else( PoisonError::into_inner )
+}
+
+/// Registers `io` under `name`, returning the session registered before, if any.
+pub fn register( name: impl Into<String>, io: Altio ) -> Option<Altio> {
+    sessions().insert( name.into(), io )
+}
+
+/// Returns the session registered under `name`.
+pub fn lookup( name: &str ) -> Option<Altio> {
+    sessions().get( name ).cloned()
+}
+
+/// Removes the session registered under `name`, returning it.
+pub fn unregister( name: &str ) -> Option<Altio> {
+    sessions().remove( name )
+}
+
+/// Returns the names of all registered sessions, sorted.
+pub fn names() -> Vec<String> {
+    let mut names = sessions().keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{lookup, names, register, unregister};
+    use crate::Altio;
+
+    #[test]
+    fn registry() {
+        let io = Altio::default();
+        assert!( register( "registry-test", io.clone() ).is_none() );
+        assert!( register( "registry-test", io.clone() ).is_some() );
+        assert!( names().contains( &"registry-test".to_owned() ));
+        lookup( "registry-test" ).unwrap().send_line( "hi" );
+        assert_eq!( io.input().lines().next().as_deref(), Some( "hi\n" ));
+        assert!( unregister( "registry-test" ).is_some() );
+        assert!( lookup( "registry-test" ).is_none() );
+    }
+}
@@ -0,0 +1,121 @@
+//! Automatic replies to prompts written into an output stream, with the "regex"
+//! feature, see `Altio::auto_respond()`.
+
+use regex::Regex;
+
+use std::sync::mpsc::Sender;
+
+/// Keeps at most this many bytes of unmatched output to match rules against.
+const WINDOW: usize = 4096;
+
+/// A prompt and its reply.
+#[derive( Debug )]
+struct Rule {
+    regex     : Regex,
+    reply     : String,
+    remaining : Option<usize>,
+}
+
+/// The rules of an output stream, matched against the text written since the last
+/// match, sending replies through a channel pulled by the input stream.
+#[derive( Debug, Default )]
+pub(crate) struct Responder {
+    rules   : Vec<Rule>,
+    window  : String,
+    replies : Option<Sender<String>>,
+}
+
+impl Responder {
+    /// Returns true if replies are already sent somewhere.
+    pub(crate) fn is_connected( &self ) -> bool { self.replies.is_some() }
+
+    pub(crate) fn connect( &mut self, replies: Sender<String> ) { self.replies = Some( replies ); }
+
+    pub(crate) fn disconnect( &mut self ) { self.replies = None; }
+
+    /// Replies with `reply` to text matching `regex`, at most `max` times if given.
+    pub(crate) fn add( &mut self, regex: Regex, reply: &str, max: Option<usize> ) {
+        self.rules.push( Rule{ regex, reply: reply.to_owned(), remaining: max });
+    }
+
+    pub(crate) fn clear( &mut self ) {
+        self.rules.clear();
+        self.window.clear();
+    }
+
+    /// Matches the rules against the output so far plus `bytes`, replying to each
+    /// match in the order they appear in the output.
+    pub(crate) fn scan( &mut self, bytes: &[u8] ) {
+        if self.rules.is_empty() {
+            return;
+        }
+        self.window.push_str( &String::from_utf8_lossy( bytes ));
+        while let Some(( end, reply )) = self.next_reply() {
+            self.window.drain( ..end );
+            if let Some( replies ) = self.replies.as_ref() {
+                let _ = replies.send( reply );
+            }
+        }
+        if self.window.len() > WINDOW {
+            let mut start = self.window.len() - WINDOW;
+            while !self.window.is_char_boundary( start ) {
+                start += 1;
+            }
+            self.window.drain( ..start );
+        }
+    }
+
+    /// Returns the end of the earliest match of the rules, the first listed on ties,
+    /// and its reply, with capture groups like `$1` substituted. Empty matches are
+    /// skipped, or they would be replied to forever.
+    fn next_reply( &mut self ) -> Option<( usize, String )> {
+        let window = &self.window;
+        let ( _, _, rule, captures ) = self.rules.iter_mut()
+            .enumerate()
+            .filter( |( _, rule )| rule.remaining != Some(0) )
+            .filter_map( |( index, rule )| {
+                let captures = rule.regex.captures_iter( window ).find( |captures| captures.get(0).is_some_and( |found| !found.is_empty() ))?;
+                Some(( captures.get(0)?.start(), index, rule, captures ))
+            })
+            .min_by_key( |&( start, index, .. )| ( start, index ))?;
+        let mut reply = String::new();
+        captures.expand( &rule.reply, &mut reply );
+        if let Some( remaining ) = rule.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        Some(( captures.get(0)?.end(), reply ))
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::Responder;
+    use regex::Regex;
+    use std::sync::mpsc;
+
+    #[test]
+    fn scan() {
+        let ( sender, receiver ) = mpsc::channel();
+        let mut responder = Responder::default();
+        responder.connect( sender );
+        responder.add( Regex::new( r"Continue\? \[y/N\]" ).unwrap(), "y", Some(1) );
+        responder.add( Regex::new( r"Name of (\w+): " ).unwrap(), "${1}-1", None );
+        responder.scan( b"Continue? [y/" );
+        responder.scan( b"N] Name of file: Continue? [y/N] Name of dir: " );
+        assert_eq!( receiver.try_iter().collect::<Vec<_>>(), [ "y", "file-1", "dir-1" ]);
+
+        responder.clear();
+        responder.add( Regex::new( r"Continue\?" ).unwrap(), "y", None );
+        responder.add( Regex::new( r"Name: " ).unwrap(), "bob", None );
+        responder.add( Regex::new( r"Name" ).unwrap(), "alice", None );
+        responder.scan( b"Name: Continue?" );
+        assert_eq!( receiver.try_iter().collect::<Vec<_>>(), [ "bob", "y" ]);
+
+        responder.clear();
+        responder.add( Regex::new( r"y*" ).unwrap(), "y", None );
+        responder.add( Regex::new( r"\b" ).unwrap(), "never", None );
+        responder.scan( b"no? " );
+        responder.scan( b"yy? " );
+        assert_eq!( receiver.try_iter().collect::<Vec<_>>(), [ "y" ]);
+    }
+}
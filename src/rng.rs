@@ -0,0 +1,36 @@
+//! A small deterministic pseudo random number generator (SplitMix64).
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive( Clone, Debug )]
+pub(crate) struct Rng( u64 );
+
+impl Rng {
+    pub(crate) fn seeded( seed: u64 ) -> Self { Rng( seed )}
+
+    /// Returns a generator seeded from the system time.
+    pub(crate) fn from_time() -> Self {
+        static COUNT: AtomicU64 = AtomicU64::new( 0 );
+        let nanos = SystemTime::now().duration_since( UNIX_EPOCH ).map_or( 0, |d| d.as_nanos() as u64 );
+        Rng( nanos ^ COUNT.fetch_add( 1, Ordering::Relaxed ).wrapping_mul( 0x9e37_79b9_7f4a_7c15 ))
+    }
+
+    pub(crate) fn next_u64( &mut self ) -> u64 {
+        self.0 = self.0.wrapping_add( 0x9e37_79b9_7f4a_7c15 );
+        let mut z = self.0;
+        z = ( z ^ ( z >> 30 )).wrapping_mul( 0xbf58_476d_1ce4_e5b9 );
+        z = ( z ^ ( z >> 27 )).wrapping_mul( 0x94d0_49bb_1331_11eb );
+        z ^ ( z >> 31 )
+    }
+
+    /// Returns a number in `0..=max`.
+    pub(crate) fn up_to( &mut self, max: u64 ) -> u64 {
+        match max.checked_add( 1 ) {
+            Some( n ) => self.next_u64() % n,
+            None => self.next_u64(),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Hooks at blocking points, for test harnesses stepping the tool and the driver
+//! deterministically instead of sleeping.
+
+use crate::Stream;
+
+use std::{
+    fmt::{self, Debug},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Hook invoked whenever a blocking read finds nothing to receive, see
+/// `Altio::schedule()`.
+///
+/// A harness can use it to single-step the interaction: run the tool until it
+/// blocks on `Stream::In`, then run the driver until it blocks on `Stream::Out` or
+/// `Stream::Err`, and so on.
+pub trait Scheduler: Send + Sync {
+    /// Called instead of sleeping when a blocking read of `stream` finds nothing to
+    /// receive, without holding any lock of the stream. The read polls the stream
+    /// again after this function returns.
+    fn block( &self, stream: Stream );
+}
+
+/// The scheduler installed on a stream, if any.
+#[derive( Default )]
+pub(crate) struct Hook( Mutex<Option<Arc<dyn Scheduler>>> );
+
+impl Hook {
+    pub(crate) fn set( &self, scheduler: Arc<dyn Scheduler> ) {
+        *self.0.lock().unwrap_or_else( PoisonError::into_inner ) = Some( scheduler );
+    }
+
+    pub(crate) fn clear( &self ) {
+        *self.0.lock().unwrap_or_else( PoisonError::into_inner ) = None;
+    }
+
+    pub(crate) fn get( &self ) -> Option<Arc<dyn Scheduler>> {
+        self.0.lock().unwrap_or_else( PoisonError::into_inner ).clone()
+    }
+}
+
+impl Debug for Hook {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        let installed = self.0.lock().is_ok_and( |scheduler| scheduler.is_some() );
+        f.debug_tuple( "Hook" ).field( &installed ).finish()
+    }
+}
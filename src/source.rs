@@ -0,0 +1,203 @@
+//! Input sources pulled by the input stream, see `Altio::attach_input()`.
+//!
+//! When the tool reads a line and none is pending, the attached sources are asked
+//! for one in order of precedence, so a driver can run mostly from a script while
+//! interjecting answers through a channel of higher precedence.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    io::{self, BufRead},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Mutex, OnceLock, PoisonError,
+    },
+    thread,
+};
+
+/// The answer of a `Source` asked for a line.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub enum Next {
+    /// A line, without the trailing newline.
+    Line( String ),
+    /// No line for now, ask again later.
+    Pending,
+    /// No more lines, the source is detached.
+    Done,
+}
+
+/// A source of input lines.
+pub trait Source: Send {
+    /// Returns the next line if available, without blocking.
+    fn next_line( &mut self ) -> Next;
+}
+
+/// Scripted lines.
+#[derive( Debug, Default )]
+pub struct Script( VecDeque<String> );
+
+impl Script {
+    /// Creates a source of `lines`, without trailing newlines.
+    pub fn new<S: Into<String>>( lines: impl IntoIterator<Item=S> ) -> Self {
+        Script( lines.into_iter().map( Into::into ).collect() )
+    }
+}
+
+impl Source for Script {
+    fn next_line( &mut self ) -> Next {
+        self.0.pop_front().map_or( Next::Done, Next::Line )
+    }
+}
+
+/// Lines sent live through a channel, until all senders are dropped.
+#[derive( Debug )]
+pub struct Channel( pub Receiver<String> );
+
+impl Source for Channel {
+    fn next_line( &mut self ) -> Next {
+        match self.0.try_recv() {
+            Ok( line ) => Next::Line( line ),
+            Err( TryRecvError::Empty ) => Next::Pending,
+            Err( TryRecvError::Disconnected ) => Next::Done,
+        }
+    }
+}
+
+/// Lines read from a reader, e.g. a `BufReader<File>`, until end-of-file or error.
+#[derive( Debug )]
+pub struct Reader<R>( pub R );
+
+impl<R: BufRead + Send> Source for Reader<R> {
+    fn next_line( &mut self ) -> Next {
+        let mut line = String::new();
+        match self.0.read_line( &mut line ) {
+            Ok( 0 ) | Err(_) => Next::Done,
+            Ok(_) => {
+                if line.ends_with( '\n' ) {
+                    line.pop();
+                }
+                Next::Line( line )
+            }
+        }
+    }
+}
+
+/// What the tool has done by the time it asks for a line, passed to the generator set
+/// by `Altio::set_input_generator()`.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub struct InputRequest {
+    /// The text written into the output stream since the previous request, whether
+    /// received or not, e.g. a prompt.
+    pub output : String,
+    /// The number of lines generated before.
+    pub count  : usize,
+}
+
+/// Lines computed on demand by a closure until it returns `None`, see
+/// `Altio::set_input_generator()`.
+#[cfg( feature = "altio" )]
+pub(crate) struct Generator<F>( pub(crate) F );
+
+#[cfg( feature = "altio" )]
+impl<F: FnMut() -> Option<String> + Send> Source for Generator<F> {
+    fn next_line( &mut self ) -> Next {
+        ( self.0 )().map_or( Next::Done, Next::Line )
+    }
+}
+
+/// Lines of the real stdin, read by a background thread shared by all altio streams,
+/// since the process has only one stdin.
+#[derive( Clone, Copy, Debug, Default )]
+pub struct Stdin;
+
+static STDIN_DONE: AtomicBool = AtomicBool::new( false );
+
+impl Source for Stdin {
+    fn next_line( &mut self ) -> Next {
+        static LINES: OnceLock<Mutex<Channel>> = OnceLock::new();
+        let lines = LINES.get_or_init( || {
+            let ( sender, receiver ) = mpsc::channel();
+            thread::spawn( move || {
+                for line in io::stdin().lines().map_while( |line| line.ok() ) {
+                    if sender.send( line ).is_err() {
+                        break;
+                    }
+                }
+            });
+            Mutex::new( Channel( receiver ))
+        });
+        let next = lines.lock().unwrap_or_else( PoisonError::into_inner ).next_line();
+        if next == Next::Done {
+            STDIN_DONE.store( true, Ordering::Relaxed );
+        }
+        next
+    }
+}
+
+/// Returns true if all lines of the real stdin have been read.
+pub(crate) fn stdin_done() -> bool { STDIN_DONE.load( Ordering::Relaxed )}
+
+/// The sources attached to an input stream, in order of precedence.
+#[derive( Default )]
+pub(crate) struct Sources( Vec<( i32, Box<dyn Source> )> );
+
+impl Sources {
+    /// Attaches `source`, which is asked before those of lower `precedence` and after
+    /// those attached before with the same one.
+    pub(crate) fn attach( &mut self, source: Box<dyn Source>, precedence: i32 ) {
+        let at = self.0.partition_point( |&( p, _ )| p >= precedence );
+        self.0.insert( at, ( precedence, source ));
+    }
+
+    pub(crate) fn clear( &mut self ) { self.0.clear(); }
+
+    /// Returns a line of the first source having one, detaching exhausted sources.
+    pub(crate) fn next_line( &mut self ) -> Option<String> {
+        let mut i = 0;
+        while i < self.0.len() {
+            match self.0[i].1.next_line() {
+                Next::Line( line ) => return Some( line ),
+                Next::Pending => i += 1,
+                Next::Done => { self.0.remove( i ); }
+            }
+        }
+        None
+    }
+}
+
+impl Debug for Sources {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        f.debug_list().entries( self.0.iter().map( |( precedence, _ )| precedence )).finish()
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Reader, Script, Sources};
+
+    #[test]
+    fn precedence() {
+        let mut sources = Sources::default();
+        sources.attach( Box::new( Script::new([ "a" ])), 0 );
+        sources.attach( Box::new( Reader( "b\nc".as_bytes() )), 1 );
+        sources.attach( Box::new( Script::new([ "d" ])), 1 );
+        let lines = std::iter::from_fn( || sources.next_line() ).collect::<Vec<_>>();
+        assert_eq!( lines, [ "b", "c", "d", "a" ]);
+        assert!( sources.0.is_empty() );
+    }
+
+    #[cfg( feature = "altio" )]
+    #[test]
+    fn generator() {
+        let mut sources = Sources::default();
+        let mut count = 0;
+        sources.attach( Box::new( super::Generator( move || {
+            count += 1;
+            ( count < 3 ).then( || count.to_string() )
+        })), 0 );
+        let lines = std::iter::from_fn( || sources.next_line() ).collect::<Vec<_>>();
+        assert_eq!( lines, [ "1", "2" ]);
+        assert!( sources.0.is_empty() );
+    }
+}
@@ -0,0 +1,89 @@
+//! Acknowledgements of the lines sent into the input stream.
+
+use crate::Altio;
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    time::Duration,
+};
+
+/// Resolves once the tool has read past a line sent by `Altio::send_line_tracked()`,
+/// for scripting a back-and-forth precisely, with failures telling which answer was
+/// never read.
+///
+/// ```rust
+/// use altio::Altio;
+/// use std::time::Duration;
+///
+/// let io = Altio::default();
+/// let first = io.send_line_tracked( "y" );
+/// let second = io.send_line_tracked( "n" );
+/// let mut answer = String::new();
+/// io.input().read_line( &mut answer ).unwrap();
+/// assert!( first.wait( Duration::ZERO ).is_ok() );
+/// assert_eq!( second.wait( Duration::from_millis( 10 )).unwrap_err().to_string(),
+///     r#"the tool never read answer #2: "n""# );
+/// ```
+#[derive( Clone, Debug )]
+pub struct SendToken {
+    io     : Altio,
+    end    : Option<u64>, // the position past the line, `None` if sent into a sink
+    number : usize,
+    text   : String,
+}
+
+impl SendToken {
+    pub(crate) fn new( io: Altio, end: Option<u64>, number: usize, text: &str ) -> Self {
+        SendToken{ io, end, number, text: text.to_owned() }
+    }
+
+    /// Returns the number of the line among those sent by `send_line_tracked()`,
+    /// beginning with 1.
+    pub fn number( &self ) -> usize { self.number }
+
+    /// Returns true if the tool has read past the line, without blocking.
+    pub fn is_read( &self ) -> bool { self.wait( Duration::ZERO ).is_ok() }
+
+    /// Blocks until the tool has read past the line, at most `timeout` measured by the
+    /// clock of the `Altio`, failing with `ErrorKind::TimedOut` on timeout or if the
+    /// tool is killed. Lines sent into the sink of a backend are taken as read.
+    pub fn wait( &self, timeout: Duration ) -> Result<()> {
+        let altin = &self.io.0.0;
+        match self.end {
+            Some( end ) if !altin.wait_read( end, Some( altin.clock.now() + timeout )) => Err( Error::new(
+                ErrorKind::TimedOut, format!( "the tool never read answer #{}: {:?}", self.number, self.text ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use crate::Altio;
+    use std::time::Duration;
+
+    #[test]
+    fn send_line_tracked() {
+        let io = Altio::default();
+        let tokens = [ "a", "b", "c" ].map( |answer| io.send_line_tracked( answer ));
+        assert_eq!( tokens.each_ref().map( |token| token.number() ), [ 1, 2, 3 ]);
+
+        let tool = std::thread::spawn({
+            let io = io.clone();
+            move || {
+                let mut answers = String::new();
+                io.input().read_line( &mut answers ).unwrap();
+                io.input().read_line( &mut answers ).unwrap();
+                answers
+            }
+        });
+        assert!( tokens[1].wait( Duration::from_secs( 10 )).is_ok() );
+        assert!( tokens[0].is_read() );
+        assert_eq!( tool.join().unwrap(), "a\nb\n" );
+        assert!( !tokens[2].is_read() );
+
+        io.0.0.kill();
+        assert_eq!( tokens[2].wait( Duration::from_secs( 10 )).unwrap_err().to_string(),
+            r#"the tool never read answer #3: "c""# );
+    }
+}
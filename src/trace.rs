@@ -0,0 +1,83 @@
+//! Optional `tracing` instrumentation of io operations, enabled by the "trace-io"
+//! feature.
+
+use crate::Stream;
+
+/// Emits a `tracing` event with target "altio" if the "trace-io" feature is enabled.
+#[cfg( feature = "trace-io" )]
+macro_rules! trace_io {
+    ( $($tt:tt)+ ) => { tracing::trace!( target: "altio", $($tt)+ ) };
+}
+
+/// Emits a `tracing` event with target "altio" if the "trace-io" feature is enabled.
+#[cfg( not( feature = "trace-io" ))]
+macro_rules! trace_io {
+    ( $($tt:tt)+ ) => {};
+}
+
+pub(crate) use trace_io;
+
+/// Span covering a blocking wait on some stream, closed on drop.
+pub(crate) struct WaitSpan(
+    #[cfg( feature = "trace-io" )]
+    #[allow( dead_code )]
+    tracing::span::EnteredSpan
+);
+
+impl WaitSpan {
+    pub(crate) fn enter( stream: Stream ) -> Self {
+        #[cfg( feature = "trace-io" )]
+        return WaitSpan( tracing::trace_span!( target: "altio", "wait", stream = stream.name() ).entered() );
+
+        #[cfg( not( feature = "trace-io" ))]
+        {
+            let _ = stream;
+            WaitSpan()
+        }
+    }
+}
+
+#[cfg( all( test, feature = "altio", feature = "trace-io" ))]
+mod tests {
+    use crate::{Altio, echo};
+
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{Event, Metadata, Subscriber, span};
+
+    #[derive( Clone, Default )]
+    struct Recorder( Arc<Mutex<Vec<String>>> );
+
+    impl Subscriber for Recorder {
+        fn enabled( &self, metadata: &Metadata<'_> ) -> bool { metadata.target() == "altio" }
+        fn new_span( &self, span: &span::Attributes<'_> ) -> span::Id {
+            self.0.lock().unwrap().push( span.metadata().name().to_owned() );
+            span::Id::from_u64(1)
+        }
+        fn record( &self, _: &span::Id, _: &span::Record<'_> ) {}
+        fn record_follows_from( &self, _: &span::Id, _: &span::Id ) {}
+        fn event( &self, event: &Event<'_> ) {
+            self.0.lock().unwrap().push( format!( "{:?}", event.fields().map( |f| f.name() ).collect::<Vec<_>>() ));
+        }
+        fn enter( &self, _: &span::Id ) {}
+        fn exit( &self, _: &span::Id ) {}
+    }
+
+    #[test]
+    fn trace_io() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default( recorder.clone(), || {
+            let io = Altio::default();
+            io.send_line( "abc" );
+            echo!( -n, io.out(), "def" );
+            assert_eq!( io.recv(), "def" );
+        });
+        assert_eq!( *recorder.0.lock().unwrap(), vec![
+            r#"["message", "stream", "bytes", "pending"]"#,
+            r#"["message", "stream", "bytes", "pending"]"#,
+            r#"["message", "stream", "bytes", "pending"]"#,
+            "wait",
+            r#"["message", "stream", "bytes", "blocked"]"#,
+        ]);
+    }
+}
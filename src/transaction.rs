@@ -0,0 +1,37 @@
+//! Output written as a whole.
+
+use std::{
+    fmt::{self, Arguments},
+    io::{Error, Result},
+};
+
+/// Text written in `Altio::transaction()`, becoming visible when the transaction
+/// completes.
+#[derive( Debug, Default )]
+pub struct Transaction {
+    text: String,
+}
+
+impl Transaction {
+    /// Writes a formatted string into the transaction, failing only if a formatting
+    /// trait implementation returns an error.
+    pub fn write_fmt( &mut self, args: Arguments<'_> ) -> Result<()> {
+        fmt::Write::write_fmt( &mut self.text, args ).map_err( |_| Error::other( "formatter error" ))
+    }
+
+    /// Appends a string slice to the transaction.
+    pub fn push_str( &mut self, text: &str ) { self.text.push_str( text )}
+
+    /// Appends a char to the transaction.
+    pub fn push( &mut self, ch: char ) { self.text.push( ch )}
+
+    /// Returns the text written so far.
+    pub fn as_str( &self ) -> &str { &self.text }
+
+    /// Runs `f` in a new transaction, returning its value and the text written.
+    pub(crate) fn run<R>( f: impl FnOnce( &mut Transaction ) -> R ) -> ( R, String ) {
+        let mut transaction = Transaction::default();
+        let value = f( &mut transaction );
+        ( value, transaction.text )
+    }
+}
@@ -0,0 +1,334 @@
+//! Timestamped transcripts of the traffic through altio streams, exportable as
+//! asciinema casts or HTML pages.
+//!
+//! `Altio::start_recording()` records everything sent, written or read from real
+//! stdin until `Altio::stop_recording()`. Recording while attached to real stdio by
+//! `Altio::record_stdio()` captures a human-driven session, which can be saved, loaded
+//! and replayed as an automated test: its input as a `Script`, its output to compare.
+//!
+//! Saved transcripts have one entry per line: the microseconds since recording
+//! started, the stream name and the text as an escaped string literal.
+//!
+//! ```text
+//! 1520 out "Name: "
+//! 803114 in "Alice\n"
+//! 803290 out "Hello, Alice!\n"
+//! ```
+//!
+//! Formatted with `{:#}`, e.g. in failure messages, transcripts are rendered for
+//! humans instead, with the lines of the streams interleaved in order.
+//!
+//! ```text
+//! out: Name:
+//! >>> Alice
+//! out: Hello, Alice!
+//! ```
+
+use crate::{Stream, html, source::Script};
+
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead, Write},
+    path::Path,
+    time::Duration,
+};
+
+#[cfg( feature = "altio" )]
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Instant,
+};
+
+/// A piece of text sent into or written to a stream.
+#[derive( Clone, Debug, PartialEq, Eq )]
+pub struct Entry {
+    /// The time since recording started, in whole microseconds.
+    pub at     : Duration,
+    /// The stream the text went through.
+    pub stream : Stream,
+    /// The text.
+    pub text   : String,
+}
+
+/// Entries of a recorded session in order.
+#[derive( Clone, Debug, Default, PartialEq, Eq )]
+pub struct Transcript {
+    /// The entries in order.
+    pub entries : Vec<Entry>,
+}
+
+impl Transcript {
+    /// Returns the text of all entries of `stream` joined.
+    pub fn text( &self, stream: Stream ) -> String {
+        self.entries.iter().filter( |entry| entry.stream == stream ).map( |entry| entry.text.as_str() ).collect()
+    }
+
+    /// Returns the recorded input as a script, to replay the session with
+    /// `Altio::attach_input()`.
+    pub fn input_script( &self ) -> Script {
+        Script::new( self.text( Stream::In ).lines() )
+    }
+
+    /// Writes this transcript in the format described in the module documentation.
+    pub fn write_to( &self, mut writer: impl Write ) -> io::Result<()> {
+        write!( writer, "{self}" )
+    }
+
+    /// Reads a transcript written by `write_to()`.
+    pub fn read_from( reader: impl BufRead ) -> io::Result<Self> {
+        let invalid = |line: &str| io::Error::new( io::ErrorKind::InvalidData, format!( "invalid transcript entry: {line}" ));
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn( 3, ' ' );
+            let at = fields.next().and_then( |micros| micros.parse().ok() ).ok_or_else( || invalid( &line ))?;
+            let stream = match fields.next() {
+                Some( "in" ) => Stream::In,
+                Some( "out" ) => Stream::Out,
+                Some( "err" ) => Stream::Err,
+                _ => return Err( invalid( &line )),
+            };
+            let text = fields.next().and_then( unescape ).ok_or_else( || invalid( &line ))?;
+            entries.push( Entry{ at: Duration::from_micros( at ), stream, text });
+        }
+        Ok( Transcript{ entries })
+    }
+
+    /// Writes this transcript as an asciinema v2 `.cast` file of an 80x24 terminal,
+    /// for playing back or sharing with asciinema tooling. Output and errors become
+    /// output events, input becomes input events and is echoed in output events, as a
+    /// terminal would do. Newlines are written as a terminal would receive them, "\r\n".
+    pub fn to_asciinema( &self, mut writer: impl Write ) -> io::Result<()> {
+        writeln!( writer, r#"{{"version": 2, "width": 80, "height": 24}}"# )?;
+        for entry in &self.entries {
+            let at = entry.at.as_secs_f64();
+            let text = json_string( &entry.text.replace( '\n', "\r\n" ));
+            if entry.stream == Stream::In {
+                writeln!( writer, r#"[{at:.6}, "i", {}]"#, json_string( &entry.text ))?;
+            }
+            writeln!( writer, r#"[{at:.6}, "o", {text}]"# )?;
+        }
+        Ok(())
+    }
+
+    /// Writes this transcript as a self-contained HTML page, e.g. for attaching to bug
+    /// reports and CI artifacts, showing input, output and errors in different styles
+    /// and honoring ANSI colors.
+    pub fn to_html( &self, mut writer: impl Write ) -> io::Result<()> {
+        writeln!( writer, "<!DOCTYPE html>" )?;
+        writeln!( writer, r#"<html><head><meta charset="utf-8"><title>altio session</title><style>"# )?;
+        writeln!( writer, "pre {{ background: #1e1e1e; color: #e5e5e5; padding: 1em; }}" )?;
+        writeln!( writer, ".in {{ color: #00cdcd; font-style: italic; }}" )?;
+        writeln!( writer, ".err {{ background: #3a1e1e; }}" )?;
+        writeln!( writer, "</style></head><body><pre>" )?;
+        for entry in &self.entries {
+            let ( class, text ) = match entry.stream {
+                Stream::In => {
+                    let mut text = String::new();
+                    html::escape( &entry.text, &mut text );
+                    ( "in", text )
+                }
+                Stream::Out => ( "out", html::render( &entry.text )),
+                Stream::Err => ( "err", html::render( &entry.text )),
+            };
+            write!( writer, r#"<span class="{class}" title="{:.3}s">{text}</span>"#, entry.at.as_secs_f64() )?;
+        }
+        writeln!( writer, "</pre></body></html>" )
+    }
+
+    /// Saves this transcript into the file at `path`.
+    pub fn save( &self, path: impl AsRef<Path> ) -> io::Result<()> {
+        let mut writer = io::BufWriter::new( std::fs::File::create( path )? );
+        self.write_to( &mut writer )?;
+        writer.flush()
+    }
+
+    /// Loads a transcript from the file at `path`.
+    pub fn load( path: impl AsRef<Path> ) -> io::Result<Self> {
+        Self::read_from( io::BufReader::new( std::fs::File::open( path )? ))
+    }
+}
+
+/// Writes the format described in the module documentation, or the rendering for
+/// humans with `{:#}`.
+impl Display for Transcript {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        if f.alternate() {
+            let mut open = None; // the stream of the line written without its newline
+            for entry in &self.entries {
+                for line in entry.text.split_inclusive( '\n' ) {
+                    if open != Some( entry.stream ) {
+                        if open.is_some() {
+                            writeln!( f )?;
+                        }
+                        f.write_str( match entry.stream {
+                            Stream::In  => ">>> ",
+                            Stream::Out => "out: ",
+                            Stream::Err => "err: ",
+                        })?;
+                    }
+                    f.write_str( line )?;
+                    open = ( !line.ends_with( '\n' )).then_some( entry.stream );
+                }
+            }
+            if open.is_some() {
+                writeln!( f )?;
+            }
+            return Ok(());
+        }
+        for entry in &self.entries {
+            writeln!( f, "{} {} {:?}", entry.at.as_micros(), entry.stream.name(), entry.text )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns `text` as a JSON string literal.
+fn json_string( text: &str ) -> String {
+    let mut literal = String::with_capacity( text.len()+2 );
+    literal.push( '"' );
+    for ch in text.chars() {
+        match ch {
+            '"'  => literal.push_str( "\\\"" ),
+            '\\' => literal.push_str( "\\\\" ),
+            '\n' => literal.push_str( "\\n" ),
+            '\r' => literal.push_str( "\\r" ),
+            '\t' => literal.push_str( "\\t" ),
+            ch if ch.is_control() => literal.push_str( &format!( "\\u{:04x}", ch as u32 )),
+            ch => literal.push( ch ),
+        }
+    }
+    literal.push( '"' );
+    literal
+}
+
+/// Parses a string literal escaped by `Debug`.
+fn unescape( literal: &str ) -> Option<String> {
+    let mut chars = literal.strip_prefix( '"' )?.strip_suffix( '"' )?.chars();
+    let mut text = String::new();
+    while let Some( ch ) = chars.next() {
+        if ch != '\\' {
+            text.push( ch );
+            continue;
+        }
+        text.push( match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                let hex = chars.as_str().strip_prefix( '{' )?.split_once( '}' )?.0;
+                let ch = char::from_u32( u32::from_str_radix( hex, 16 ).ok()? )?;
+                chars.nth( hex.len()+1 )?;
+                ch
+            }
+            ch => ch,
+        });
+    }
+    Some( text )
+}
+
+/// A transcript being recorded, shared by the streams of an `Altio`.
+#[cfg( feature = "altio" )]
+#[derive( Debug )]
+pub(crate) struct Recording {
+    start      : Instant,
+    transcript : Mutex<Transcript>,
+}
+
+#[cfg( feature = "altio" )]
+impl Recording {
+    pub(crate) fn new() -> Self {
+        Recording{ start: Instant::now(), transcript: Mutex::default() }
+    }
+
+    pub(crate) fn record( &self, stream: Stream, bytes: &[u8] ) {
+        let at = Duration::from_micros( self.start.elapsed().as_micros() as u64 );
+        let text = String::from_utf8_lossy( bytes ).into_owned();
+        self.transcript.lock().unwrap_or_else( PoisonError::into_inner ).entries.push( Entry{ at, stream, text });
+    }
+
+    pub(crate) fn transcript( &self ) -> Transcript { self.transcript.lock().unwrap_or_else( PoisonError::into_inner ).clone() }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::{Entry, Transcript, unescape};
+    use crate::Stream;
+    use std::time::Duration;
+
+    #[test]
+    fn round_trip() {
+        let entry = |micros, stream, text: &str| Entry{ at: Duration::from_micros( micros ), stream, text: text.to_owned() };
+        let transcript = Transcript{ entries: vec![
+            entry( 1520, Stream::Out, "Name: " ),
+            entry( 803114, Stream::In, "Alice \"A\"\n" ),
+            entry( 803290, Stream::Err, "\u{1b}[1m\t\\\r\n" ),
+        ]};
+        let mut saved = Vec::new();
+        transcript.write_to( &mut saved ).unwrap();
+        assert!( String::from_utf8_lossy( &saved ).starts_with( "1520 out \"Name: \"\n803114 in " ));
+        assert_eq!( Transcript::read_from( saved.as_slice() ).unwrap(), transcript );
+        assert_eq!( transcript.text( Stream::In ), "Alice \"A\"\n" );
+        assert_eq!( unescape( r#""\u{feff}x""# ).as_deref(), Some( "\u{feff}x" ));
+        assert!( Transcript::read_from( "1 out unquoted".as_bytes() ).is_err() );
+    }
+
+    #[test]
+    #[cfg( target_os = "linux" )]
+    fn save_error() {
+        let transcript = Transcript{ entries: vec![ Entry{ at: Duration::ZERO, stream: Stream::Out, text: "abc".to_owned() }]};
+        assert!( transcript.save( "/dev/full" ).is_err() );
+    }
+
+    #[test]
+    fn pretty() {
+        let entry = |stream, text: &str| Entry{ at: Duration::ZERO, stream, text: text.to_owned() };
+        let transcript = Transcript{ entries: vec![
+            entry( Stream::Out, "Name: " ),
+            entry( Stream::In, "Alice\n" ),
+            entry( Stream::Out, "Hello, " ),
+            entry( Stream::Out, "Alice!\nBye" ),
+            entry( Stream::Err, "oops\n\n" ),
+            entry( Stream::Out, "!" ),
+        ]};
+        assert_eq!( format!( "{transcript:#}" ),
+            "out: Name: \n>>> Alice\nout: Hello, Alice!\nout: Bye\nerr: oops\nerr: \nout: !\n" );
+    }
+
+    #[test]
+    fn html() {
+        let transcript = Transcript{ entries: vec![
+            Entry{ at: Duration::from_millis( 1 ), stream: Stream::Out, text: "<name>: ".to_owned() },
+            Entry{ at: Duration::from_secs( 2 ), stream: Stream::In, text: "Al\n".to_owned() },
+            Entry{ at: Duration::from_secs( 3 ), stream: Stream::Err, text: "\u{1b}[31mbye\u{1b}[0m\n".to_owned() },
+        ]};
+        let mut html = Vec::new();
+        transcript.to_html( &mut html ).unwrap();
+        let html = String::from_utf8( html ).unwrap();
+        assert!( html.starts_with( "<!DOCTYPE html>" ));
+        assert!( html.contains( r#"<span class="out" title="0.001s">&lt;name&gt;: </span><span class="in" title="2.000s">Al
+</span><span class="err" title="3.000s"><span style="color:#cd0000;">bye</span>
+</span></pre>"# ));
+    }
+
+    #[test]
+    fn asciinema() {
+        let transcript = Transcript{ entries: vec![
+            Entry{ at: Duration::from_micros( 1500 ), stream: Stream::Out, text: "Name: ".to_owned() },
+            Entry{ at: Duration::from_secs( 2 ), stream: Stream::In, text: "\"Al\"\n".to_owned() },
+            Entry{ at: Duration::from_secs( 3 ), stream: Stream::Err, text: "\u{1b}[31mbye\\\n".to_owned() },
+        ]};
+        let mut cast = Vec::new();
+        transcript.to_asciinema( &mut cast ).unwrap();
+        assert_eq!( String::from_utf8( cast ).unwrap(), r#"{"version": 2, "width": 80, "height": 24}
+[0.001500, "o", "Name: "]
+[2.000000, "i", "\"Al\"\n"]
+[2.000000, "o", "\"Al\"\r\n"]
+[3.000000, "o", "\u001b[31mbye\\\r\n"]
+"# );
+    }
+}
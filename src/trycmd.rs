@@ -0,0 +1,398 @@
+//! Running `trycmd` case files against embedded tools, with the "trycmd" feature.
+//!
+//! Instead of spawning binaries, the commands in case files run the entry functions
+//! registered under their binary names, each with its own `Altio`.
+//!
+//! Supported case files:
+//!
+//! - `*.toml`: `bin.name`, `args` (a string or an array), `env.add`, `env.remove`,
+//!   `env.inherit`, `stdin`, `stdout`, `stderr` and `status` ("success", "failed"
+//!   or `{ code = N }`). The input and the expected text may also be given in the
+//!   sibling files with the extensions `.stdin`, `.stdout` and `.stderr`.
+//!
+//! - `*.trycmd` and `*.md`: commands in lines starting with `$ `, optionally followed
+//!   by a status line like `? failed` or `? 2`, then the expected text written into
+//!   both output and error streams. In markdown files, only code blocks marked as
+//!   `console` or `trycmd` are run.
+//!
+//! In expected text, `[..]` matches anything within a line, and a line of `...`
+//! matches any number of lines.
+//!
+//! ```rust,no_run
+//! use altio::{Altio, trycmd::TestCases};
+//! use std::ffi::OsString;
+//!
+//! fn the_tool( io: Altio, args: Vec<OsString> ) -> i32 { 0 }
+//!
+//! TestCases::new()
+//!     .register_bin( "the-tool", the_tool )
+//!     .case( "tests/cmd" )
+//!     .run();
+//! ```
+
+use crate::{AltCommand, Altio, IntoExitCode};
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt::{self, Debug},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+type Entry = Arc<dyn Fn( Altio, Vec<OsString> ) -> i32 + Send + Sync>;
+
+/// The expected exit status of a command.
+#[derive( Clone, Copy, Debug, PartialEq, Eq )]
+enum Status {
+    Success,
+    Failed,
+    Code( i32 ),
+}
+
+impl Status {
+    fn matches( self, code: i32 ) -> bool {
+        match self {
+            Status::Success    => code == 0,
+            Status::Failed     => code != 0,
+            Status::Code( c )  => code == c,
+        }
+    }
+}
+
+/// One command and its expectations, parsed from a case file.
+#[derive( Debug, Default )]
+struct Step {
+    bin         : String,
+    args        : Vec<String>,
+    env_add     : BTreeMap<String, String>,
+    env_remove  : Vec<String>,
+    env_inherit : bool,
+    stdin       : String,
+    status      : Option<Status>,
+    stdout      : Option<String>,
+    stderr      : Option<String>,
+    merged      : bool,
+}
+
+/// A collection of `trycmd` case files to run against embedded tools, corresponding
+/// to `trycmd::TestCases`.
+#[derive( Default )]
+pub struct TestCases {
+    bins  : BTreeMap<String, Entry>,
+    paths : Vec<PathBuf>,
+}
+
+impl Debug for TestCases {
+    fn fmt( &self, f: &mut fmt::Formatter<'_> ) -> fmt::Result {
+        f.debug_struct( "TestCases" )
+            .field( "bins", &self.bins.keys().collect::<Vec<_>>() )
+            .field( "paths", &self.paths )
+            .finish()
+    }
+}
+
+impl TestCases {
+    /// Creates an empty collection.
+    pub fn new() -> Self { TestCases::default() }
+
+    /// Runs `entry` for the commands of binary `name`.
+    pub fn register_bin<R: IntoExitCode>( &mut self, name: &str,
+                                          entry: impl Fn( Altio, Vec<OsString> ) -> R + Send + Sync + 'static )
+        -> &mut Self
+    {
+        self.bins.insert( name.to_owned(), Arc::new( move |io, args| entry( io, args ).into_exit_code() ));
+        self
+    }
+
+    /// Adds a case file, or all case files in a directory.
+    pub fn case( &mut self, path: impl AsRef<Path> ) -> &mut Self {
+        self.paths.push( path.as_ref().to_owned() );
+        self
+    }
+
+    /// Runs all cases, panicking with a report of every failed one.
+    #[track_caller]
+    pub fn run( &self ) {
+        let mut files = Vec::new();
+        for path in &self.paths {
+            collect_files( path, &mut files );
+        }
+        let failures = files.iter()
+            .filter_map( |file| self.run_file( file ).err().map( |err| format!( "{}: {err}", file.display() )))
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            panic!( "{} of {} cases failed\n\n{}", failures.len(), files.len(), failures.join( "\n\n" ));
+        }
+    }
+
+    fn run_file( &self, file: &Path ) -> Result<(), String> {
+        let text = fs::read_to_string( file ).map_err( |err| err.to_string() )?;
+        let steps = match file.extension().and_then( |ext| ext.to_str() ) {
+            Some( "toml" ) => vec![ parse_toml( file, &text )? ],
+            Some( "md" ) => parse_trycmd( &text, true )?,
+            _ => parse_trycmd( &text, false )?,
+        };
+        for step in &steps {
+            self.run_step( step )?;
+        }
+        Ok(())
+    }
+
+    fn run_step( &self, step: &Step ) -> Result<(), String> {
+        let entry = self.bins.get( &step.bin )
+            .ok_or_else( || format!( "binary `{}` is not registered", step.bin ))?
+            .clone();
+        let mut command = AltCommand::new( move |io, args| entry( io, args ))
+            .arg0( &step.bin )
+            .args( &step.args );
+        if !step.env_inherit {
+            command = command.env_clear();
+        }
+        command = command.envs( &step.env_add );
+        for key in &step.env_remove {
+            command = command.env_remove( key );
+        }
+        let child = command.spawn();
+        child.io().send( &step.stdin );
+        child.io().close_input();
+        let output = child.wait_with_output().map_err( |err| err.to_string() )?;
+
+        let command_line = format!( "$ {} {}", step.bin, step.args.join( " " ));
+        if let Some( status ) = step.status {
+            if !status.matches( output.status ) {
+                return Err( format!( "{command_line}\nexpected status {status:?}, got {}", output.status ));
+            }
+        }
+        let streams = if step.merged {
+            vec![( "output", step.stdout.as_ref(), output.stdout + &output.stderr )]
+        } else {
+            vec![
+                ( "stdout", step.stdout.as_ref(), output.stdout ),
+                ( "stderr", step.stderr.as_ref(), output.stderr ),
+            ]
+        };
+        for ( name, expected, actual ) in streams {
+            if let Some( expected ) = expected {
+                if !matches( expected, &actual ) {
+                    return Err( format!( "{command_line}\n{name} mismatch\nexpected: ```\n{expected}```\nactual: ```\n{actual}```" ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect_files( path: &Path, files: &mut Vec<PathBuf> ) {
+    if path.is_dir() {
+        let mut entries = fs::read_dir( path )
+            .map( |entries| entries.filter_map( |entry| entry.ok().map( |entry| entry.path() )).collect::<Vec<_>>() )
+            .unwrap_or_default();
+        entries.sort();
+        for entry in entries {
+            let is_case = entry.extension().and_then( |ext| ext.to_str() )
+                .is_some_and( |ext| matches!( ext, "toml" | "trycmd" | "md" ));
+            if is_case || entry.is_dir() {
+                collect_files( &entry, files );
+            }
+        }
+    } else {
+        files.push( path.to_owned() );
+    }
+}
+
+fn parse_toml( file: &Path, text: &str ) -> Result<Step, String> {
+    use toml::{Table, Value};
+
+    let table = text.parse::<Table>().map_err( |err| err.to_string() )?;
+    let string = |value: &Value| value.as_str().map( str::to_owned ).ok_or_else( || format!( "expected a string: {value}" ));
+    let sibling = |ext: &str| fs::read_to_string( file.with_extension( ext )).ok();
+
+    let mut step = Step{ env_inherit: true, ..Step::default() };
+    step.bin = table.get( "bin" )
+        .and_then( |bin| bin.get( "name" ))
+        .ok_or( "missing `bin.name`" )
+        .and_then( |name| name.as_str().ok_or( "`bin.name` is not a string" ))?
+        .to_owned();
+    step.args = match table.get( "args" ) {
+        None => Vec::new(),
+        Some( Value::String( args )) => split_args( args )?,
+        Some( Value::Array( args )) => args.iter().map( string ).collect::<Result<_,_>>()?,
+        Some( args ) => return Err( format!( "invalid `args`: {args}" )),
+    };
+    if let Some( env ) = table.get( "env" ) {
+        if let Some( Value::Table( add )) = env.get( "add" ) {
+            for ( key, value ) in add {
+                step.env_add.insert( key.clone(), string( value )? );
+            }
+        }
+        if let Some( Value::Array( remove )) = env.get( "remove" ) {
+            step.env_remove = remove.iter().map( string ).collect::<Result<_,_>>()?;
+        }
+        if let Some( inherit ) = env.get( "inherit" ) {
+            step.env_inherit = inherit.as_bool().ok_or( "`env.inherit` is not a bool" )?;
+        }
+    }
+    step.status = match table.get( "status" ) {
+        None => None,
+        Some( Value::String( status )) if status == "success" => Some( Status::Success ),
+        Some( Value::String( status )) if status == "failed" => Some( Status::Failed ),
+        Some( status ) => match status.get( "code" ).and_then( Value::as_integer ) {
+            Some( code ) => Some( Status::Code( code as i32 )),
+            None => return Err( format!( "invalid `status`: {status}" )),
+        },
+    };
+    step.stdin = match table.get( "stdin" ) {
+        Some( stdin ) => string( stdin )?,
+        None => sibling( "stdin" ).unwrap_or_default(),
+    };
+    step.stdout = table.get( "stdout" ).map( string ).transpose()?.or_else( || sibling( "stdout" ));
+    step.stderr = table.get( "stderr" ).map( string ).transpose()?.or_else( || sibling( "stderr" ));
+    Ok( step )
+}
+
+fn parse_trycmd( text: &str, markdown: bool ) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::<Step>::new();
+    let mut in_block = !markdown;
+    for line in text.lines() {
+        if markdown && line.starts_with( "```" ) {
+            let info = line.trim_start_matches( '`' ).trim();
+            in_block = !in_block && matches!( info, "console" | "trycmd" );
+            continue;
+        }
+        if !in_block {
+            continue;
+        }
+        if let Some( command ) = line.strip_prefix( "$ " ) {
+            let mut args = split_args( command )?.into_iter();
+            let bin = args.next().ok_or( "empty command" )?;
+            steps.push( Step {
+                bin,
+                args        : args.collect(),
+                env_inherit : true,
+                status      : Some( Status::Success ),
+                stdout      : Some( String::new() ),
+                merged      : true,
+                ..Step::default()
+            });
+        } else if let Some( step ) = steps.last_mut() {
+            let expected = step.stdout.as_mut().expect( "commands expect output" );
+            match line.strip_prefix( "? " ) {
+                Some( status ) if expected.is_empty() => {
+                    step.status = match status.trim() {
+                        "success" => Some( Status::Success ),
+                        "failed" => Some( Status::Failed ),
+                        code => Some( Status::Code( code.parse().map_err( |_| format!( "invalid status: {code}" ))? )),
+                    };
+                }
+                _ => {
+                    expected.push_str( line );
+                    expected.push( '\n' );
+                }
+            }
+        }
+    }
+    Ok( steps )
+}
+
+/// Splits a command line into arguments, honoring single and double quotes.
+fn split_args( command: &str ) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg = None::<String>;
+    let mut quote = None;
+    for ch in command.chars() {
+        match ( quote, ch ) {
+            ( None, '\'' | '"' ) => { quote = Some( ch ); arg.get_or_insert_with( String::new ); }
+            ( Some( q ), _ ) if ch == q => quote = None,
+            ( None, _ ) if ch.is_whitespace() => args.extend( arg.take() ),
+            _ => arg.get_or_insert_with( String::new ).push( ch ),
+        }
+    }
+    if quote.is_some() {
+        return Err( format!( "unterminated quote: {command}" ));
+    }
+    args.extend( arg );
+    Ok( args )
+}
+
+/// Matches `actual` text against `expected` text with `[..]` and `...` wildcards.
+fn matches( expected: &str, actual: &str ) -> bool {
+    let expected = expected.replace( "\r\n", "\n" );
+    let actual = actual.replace( "\r\n", "\n" );
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+    match_lines( &expected, &actual )
+}
+
+fn match_lines( expected: &[&str], actual: &[&str] ) -> bool {
+    match expected.split_first() {
+        None => actual.is_empty(),
+        Some(( &"...", rest )) => ( 0..=actual.len() ).any( |skip| match_lines( rest, &actual[ skip.. ])),
+        Some(( line, rest )) => match actual.split_first() {
+            Some(( actual_line, actual_rest )) => match_line( line, actual_line ) && match_lines( rest, actual_rest ),
+            None => false,
+        },
+    }
+}
+
+fn match_line( expected: &str, actual: &str ) -> bool {
+    let mut pieces = expected.split( "[..]" );
+    let first = pieces.next().unwrap_or_default();
+    let Some( mut rest ) = actual.strip_prefix( first ) else { return false };
+    let pieces = pieces.collect::<Vec<_>>();
+    match pieces.split_last() {
+        None => rest.is_empty(),
+        Some(( last, middle )) => {
+            for piece in middle {
+                match rest.find( piece ) {
+                    Some( offset ) => rest = &rest[ offset + piece.len().. ],
+                    None => return false,
+                }
+            }
+            rest.ends_with( last )
+        }
+    }
+}
+
+#[cfg( test )]
+mod tests {
+    use super::*;
+    use crate::echo;
+
+    #[test]
+    fn wildcards() {
+        assert!( matches( "a [..] c\n...\nz\n", "a b c\nx\ny\nz\n" ));
+        assert!( matches( "[..]\n", "anything\n" ));
+        assert!( !matches( "a\n", "a\nb\n" ));
+        assert_eq!( split_args( r#"x "a b" 'c'"# ).unwrap(), [ "x", "a b", "c" ]);
+    }
+
+    #[test]
+    fn run_cases() {
+        let dir = std::env::temp_dir().join( format!( "altio-trycmd-{}", std::process::id() ));
+        fs::create_dir_all( &dir ).unwrap();
+        fs::write( dir.join( "greet.toml" ), r#"
+            bin.name = "greet"
+            args = ["--name", "world"]
+            env.add = { GREETING = "Hi" }
+            stdin = "!"
+            stdout = "Hi, world!\n"
+        "# ).unwrap();
+        fs::write( dir.join( "fail.trycmd" ), "$ greet --fail\n? 2\nfailed at [..]\n" ).unwrap();
+
+        let mut cases = TestCases::new();
+        cases.register_bin( "greet", |io: Altio, args: Vec<OsString>| {
+            if args[1] == "--fail" {
+                echo!( io.err(), "failed at line 1" );
+                return 2;
+            }
+            let mut suffix = String::new();
+            io.input().read_to_string( &mut suffix ).unwrap();
+            echo!( io.out(), "{}, {}{suffix}", io.env().var( "GREETING" ).unwrap(), args[2].to_str().unwrap() );
+            0
+        });
+        cases.case( &dir ).run();
+        fs::remove_dir_all( &dir ).unwrap();
+    }
+}